@@ -4,34 +4,70 @@
 
 //! Main application
 
-use std::ops::Deref;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use bytes::Bytes;
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
 use futures::channel::mpsc::UnboundedSender;
 use futures::lock::Mutex;
-use futures::SinkExt;
-use log::info;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use log::{error, info, warn};
+use semver::Version;
+use sqlx::pool::PoolConnection;
 use sqlx::sqlite::SqlitePoolOptions;
-use sqlx::{Pool, Sqlite};
+use sqlx::{Executor, Pool, Sqlite};
 
-use crate::model::auth::{AuthenticatedUser, RegistryUserToken, RegistryUserTokenWithSecret};
+use crate::model::audit::AuditLogEntry;
+use crate::model::auth::{
+    AuthenticatedUser, OAuthLoginState, PublishNotifications, RegistryUserToken, RegistryUserTokenWithSecret,
+    TokenPublishScope, TokenRestrictions, TokensRevocationCriteria, TokensRevocationResult, UserImportResult,
+    UserNotificationSettings,
+};
 use crate::model::cargo::{
-    CrateUploadData, CrateUploadResult, OwnersQueryResult, RegistryUser, SearchResults, YesNoMsgResult, YesNoResult,
+    CrateUploadData, CrateUploadResult, IndexCrateMetadata, OwnersQueryResult, RegistryUser, SearchCursor, SearchResults,
+    UserCratesReassignment, YesNoMsgResult, YesNoResult, README_CONTENT_TYPE_MARKDOWN,
+};
+use crate::model::config::{Configuration, IndexConfig};
+use crate::model::cratesio::CratesIoCrateResponse;
+use crate::model::deps::{AdvisoryAffectedCrate, AffectedCrateAdvisory, DepsAnalysis, DepsGraph, DepsGraphExport};
+use crate::model::metrics::{MetricKind, Metrics};
+use crate::model::osv::AdvisorySeverityLevel;
+use crate::model::packages::{
+    CrateConsistencyReport, CrateDocFile, CrateDocsBuildSettings, CrateDocsExternal, CrateDocsSettings, CrateDocsStatus,
+    CrateInfo, CrateManagementInfo, CrateOutdatedStatus, CratePublications, CrateReadme, CrateVersionBuild,
+    CrateVersionConsistency, CrateVersionSummary, CratesListing, DocsExport, ReindexFailure, ReindexSummary,
+    SignedDownloadLink, StaleCrateOwner, StorageGcSummary,
 };
-use crate::model::config::Configuration;
-use crate::model::deps::DepsAnalysis;
-use crate::model::packages::CrateInfo;
-use crate::model::stats::{DownloadStats, GlobalStats};
-use crate::model::{CrateAndVersion, JobCrate};
+use crate::model::policy::{DepsPolicy, DepsPolicyReport};
+use crate::model::stats::{
+    CrateDownloadCounts, DownloadStats, DownloadsGranularity, GlobalDownloadsSeries, GlobalStats, DOWNLOADS_SERIES_MAX_DAYS,
+};
+use crate::model::webhooks::{WebhookEvent, WebhookEventKind};
+use crate::model::workers::{HealthStatus, ToolchainStatus, WorkerStatus, WorkersStatus};
+use crate::model::{generate_token, CrateAndVersion, JobCrate};
 use crate::services::database::Database;
 use crate::services::deps::{DepsChecker, DepsCheckerData};
 use crate::services::emails::EmailSender;
-use crate::services::index::Index;
+use crate::services::hooks::HooksExecutor;
+use crate::services::index::{build_package_file_path, Index};
+use crate::services::notifications::notify_publication;
 use crate::services::rustsec::{RustSecChecker, RustSecData};
-use crate::services::storage::Storage;
-use crate::utils::apierror::{error_invalid_request, error_unauthorized, specialize, ApiError};
+use crate::services::storage::{check_crate_archive, extract_readme, get_storage, CrateContent, Storage};
+use crate::services::workers::WorkerState;
+use crate::utils::apierror::{
+    error_backend_failure, error_forbidden, error_invalid_request, error_not_found, error_unauthorized, error_validation,
+    specialize, ApiError, ApiErrorItem,
+};
 use crate::utils::axum::auth::{AuthData, Token};
-use crate::utils::db::{in_transaction, AppTransaction};
+use crate::utils::cidr::IpCidr;
+use crate::utils::db::{acquire_with_retry, in_transaction, AppTransaction};
+use crate::utils::hashes::{hmac_sha256_sign, hmac_sha256_verify, sha256};
+use crate::utils::range::{ByteRangeSpec, RangedContent};
 
 /// The state of this application for axum
 pub struct Application {
@@ -47,12 +83,119 @@ pub struct Application {
     pub rustsec: Arc<Mutex<RustSecData>>,
     /// Sender of documentation generation jobs
     pub docs_worker_sender: UnboundedSender<JobCrate>,
+    /// The activity of the worker for the generation of documentation
+    pub docs_worker_state: Arc<WorkerState>,
+    /// The readiness of the toolchain for the generation of documentation, checked at launch
+    pub docs_toolchain: ToolchainStatus,
+    /// The activity of the worker for the analysis of dependencies
+    pub deps_worker_state: Arc<WorkerState>,
+    /// The number of crate versions published since the launch
+    pub publications: AtomicU64,
+    /// Sender of the notifications to the webhooks
+    pub webhooks_sender: UnboundedSender<WebhookEvent>,
 }
 
 /// The empty database
 const DB_EMPTY: &[u8] = include_bytes!("empty.db");
-/// Maximum number of concurrent connections
-const DB_MAX_CONNECTIONS: u32 = 16;
+/// Number of entries in the audit log fetched at once when exporting
+const AUDIT_EXPORT_PAGE_SIZE: i64 = 500;
+/// Maximum number of crate versions in a bundle
+const BUNDLE_MAX_CRATES: usize = 100;
+/// Maximum number of crates in a bulk check of the outdated status
+const OUTDATED_CHECK_MAX_CRATES: usize = 500;
+/// Maximum size of the crate files in a bundle, in bytes
+const BUNDLE_MAX_SIZE: u64 = 256 * 1024 * 1024;
+/// Size of the end of a tar archive, two zeroed blocks
+const TAR_END_SIZE: usize = 1024;
+/// Number of hours during which a link to verify an email is valid
+const EMAIL_VERIFICATION_VALIDITY: i64 = 48;
+
+/// Adds the metrics for the activity of a worker
+fn add_worker_metrics(metrics: &mut Metrics, worker: &str, status: &WorkerStatus) {
+    let labels = [("worker", worker)];
+    metrics.add_labelled(
+        "cratery_worker_jobs_queued",
+        "Number of jobs waiting to be processed by a worker",
+        MetricKind::Gauge,
+        &labels,
+        status.queued as u64,
+    );
+    metrics.add_labelled(
+        "cratery_worker_jobs_in_flight",
+        "Number of jobs being processed by a worker",
+        MetricKind::Gauge,
+        &labels,
+        status.in_flight as u64,
+    );
+    metrics.add_labelled(
+        "cratery_worker_jobs_completed_total",
+        "Number of jobs completed successfully by a worker since the launch",
+        MetricKind::Counter,
+        &labels,
+        status.completed,
+    );
+    metrics.add_labelled(
+        "cratery_worker_jobs_failed_total",
+        "Number of jobs that failed in a worker since the launch",
+        MetricKind::Counter,
+        &labels,
+        status.failed,
+    );
+    metrics.add_labelled(
+        "cratery_worker_jobs_skipped_total",
+        "Number of jobs skipped by a worker since the launch",
+        MetricKind::Counter,
+        &labels,
+        status.skipped,
+    );
+    metrics.add_labelled(
+        "cratery_worker_jobs_deferred",
+        "Number of jobs that could not be queued and wait for the queue of a worker to be drained",
+        MetricKind::Gauge,
+        &labels,
+        status.deferred as u64,
+    );
+}
+
+/// Appends a file to a tar archive
+fn append_to_tar(builder: &mut tar::Builder<Vec<u8>>, path: &str, content: &[u8]) -> Result<(), ApiError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, content)?;
+    Ok(())
+}
+
+/// Builds the header of a file in a tar archive, for a content that is streamed after it
+fn tar_header(path: &str, size: u64) -> Result<Vec<u8>, ApiError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_cksum();
+    // the builder is only used to write the header, possibly with the extension for long paths
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_data(&mut header, path, std::io::empty())?;
+    Ok(std::mem::take(builder.get_mut()))
+}
+
+/// Gets the size of the padding after the content of a file in a tar archive, to a complete block
+fn tar_padding(size: u64) -> usize {
+    let remainder = (size % 512) as usize;
+    if remainder == 0 {
+        0
+    } else {
+        512 - remainder
+    }
+}
+
+/// Builds the entry of a file in a tar archive, to be streamed before the end of the archive
+fn tar_entry(path: &str, content: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let mut builder = tar::Builder::new(Vec::new());
+    append_to_tar(&mut builder, path, content)?;
+    // take the entry before the end of the archive is written
+    Ok(std::mem::take(builder.get_mut()))
+}
 
 impl Application {
     /// Creates a new application
@@ -69,43 +212,72 @@ impl Application {
             info!("db file is inaccessible => attempt to create an empty one");
             tokio::fs::write(&db_filename, DB_EMPTY).await?;
         }
+        // the pragmas are applied on each new connection, including the first one used for the migrations
+        let db_pragmas = Arc::new(configuration.get_database_pragmas());
         let db_pool = SqlitePoolOptions::new()
-            .max_connections(DB_MAX_CONNECTIONS)
+            .max_connections(configuration.db_max_connections)
+            .acquire_timeout(std::time::Duration::from_millis(configuration.db_acquire_timeout))
+            .after_connect(move |connection, _meta| {
+                let db_pragmas = db_pragmas.clone();
+                Box::pin(async move {
+                    connection.execute(db_pragmas.as_str()).await?;
+                    Ok(())
+                })
+            })
             .connect_lazy(&configuration.get_database_url())?;
         // migrate the database, if appropriate
-        crate::migrations::migrate_to_last(&mut *db_pool.acquire().await?).await?;
+        crate::migrations::migrate_to_last(&mut *db_pool.acquire().await?, configuration.db_migration_lock_timeout).await?;
 
         // prepare the index
         let index = Arc::new(Mutex::new(Index::on_launch(configuration.get_index_git_config()).await?));
 
         // docs worker
-        let docs_worker_sender = crate::services::docs::create_docs_worker(configuration.clone(), db_pool.clone());
+        let docs_toolchain = crate::services::docs::check_docs_toolchain(&configuration).await;
+        let docs_worker_state = Arc::new(WorkerState::default());
+        let docs_worker_sender = crate::services::docs::create_docs_worker(
+            configuration.clone(),
+            db_pool.clone(),
+            docs_worker_state.clone(),
+            &docs_toolchain,
+        );
         // check undocumented packages
-        {
-            let mut docs_worker_sender = docs_worker_sender.clone();
-            let mut connection = db_pool.acquire().await?;
-            in_transaction(&mut connection, |transaction| async move {
-                let app = Database::new(transaction);
-                let jobs = app.get_undocumented_crates().await?;
-                for job in jobs {
-                    docs_worker_sender.send(job).await?;
-                }
-                Ok::<_, ApiError>(())
-            })
+        crate::services::docs::queue_undocumented_crates(&configuration, &db_pool, &docs_worker_state, &docs_worker_sender)
             .await?;
-        }
 
         // deps worker
         let rustsec = Arc::new(Mutex::new(RustSecData::default()));
         let deps_checker = Arc::new(Mutex::new(DepsCheckerData::default()));
+        let deps_worker_state = Arc::new(WorkerState::default());
         crate::services::deps::create_deps_worker(
             configuration.clone(),
             deps_checker.clone(),
             rustsec.clone(),
             index.clone(),
             db_pool.clone(),
+            deps_worker_state.clone(),
+        );
+        // periodic scan of the crates against the advisories
+        crate::services::advisories::create_advisories_worker(
+            configuration.clone(),
+            deps_checker.clone(),
+            rustsec.clone(),
+            index.clone(),
+            db_pool.clone(),
         );
 
+        // audit log retention
+        crate::services::audit::create_audit_worker(configuration.clone(), db_pool.clone());
+        // enrichment from the repositories
+        crate::services::enrich::create_enrich_worker(configuration.clone(), db_pool.clone());
+        // expiry of yanks
+        crate::services::yanks::create_yanks_worker(&configuration, db_pool.clone());
+        // collection of the stored files of deleted crates
+        crate::services::gc::create_gc_worker(configuration.clone(), db_pool.clone(), index.clone());
+        // digests of the publications for the owners of crates
+        crate::services::notifications::create_publish_digest_worker(configuration.clone(), db_pool.clone());
+        // notifications to the webhooks
+        let webhooks_sender = crate::services::webhooks::create_webhooks_worker(configuration.clone());
+
         Ok(Arc::new(Self {
             configuration,
             db_pool,
@@ -113,12 +285,138 @@ impl Application {
             deps_checker,
             rustsec,
             docs_worker_sender,
+            docs_worker_state,
+            docs_toolchain,
+            deps_worker_state,
+            publications: AtomicU64::new(0),
+            webhooks_sender,
         }))
     }
 
+    /// Sends a notification to the webhooks, when configured
+    /// The delivery happens in the background and never fails the operation that triggered it
+    fn send_webhook(&self, event: WebhookEvent) {
+        if self.configuration.webhooks.endpoints.is_empty() {
+            return;
+        }
+        if let Err(e) = self.webhooks_sender.unbounded_send(event) {
+            error!("webhooks: failed to queue the notification: {e}");
+        }
+    }
+
+    /// Sends a job to the worker for the generation of documentation
+    /// This never fails, a job that cannot be queued right now is picked up later
+    fn send_docs_job(&self, job: JobCrate) {
+        crate::services::docs::send_docs_job(&self.configuration, &self.docs_worker_state, &self.docs_worker_sender, job);
+    }
+
+    /// Gets the health of the registry
+    pub fn get_health(&self) -> HealthStatus {
+        HealthStatus {
+            docs_toolchain: self.docs_toolchain.clone(),
+        }
+    }
+
+    /// Gets the effective configuration, with the secrets redacted
+    pub async fn get_configuration(&self, auth_data: &AuthData) -> Result<Configuration, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await
+        })
+        .await?;
+        Ok(self.configuration.sanitized())
+    }
+
+    /// Gets the status of the background workers
+    pub async fn get_workers_status(&self, auth_data: &AuthData) -> Result<WorkersStatus, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await
+        })
+        .await?;
+        Ok(WorkersStatus {
+            docs: self.docs_worker_state.get_status(),
+            deps: self.deps_worker_state.get_status(),
+        })
+    }
+
+    /// Gets the metrics for the registry, formatted in the Prometheus text format
+    pub async fn get_metrics(&self, auth_data: &AuthData) -> Result<String, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let totals = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            if self.configuration.web_metrics_auth {
+                let principal = app.authenticate(auth_data).await?;
+                app.database.check_can_admin(&principal).await?;
+            }
+            app.database.get_registry_totals().await
+        })
+        .await?;
+        let mut metrics = Metrics::default();
+        metrics.add(
+            "cratery_crates",
+            "Number of crates in the registry",
+            MetricKind::Gauge,
+            totals.crates.unsigned_abs(),
+        );
+        metrics.add(
+            "cratery_versions",
+            "Number of crate versions in the registry",
+            MetricKind::Gauge,
+            totals.versions.unsigned_abs(),
+        );
+        metrics.add(
+            "cratery_downloads_total",
+            "Number of downloads of crate versions",
+            MetricKind::Counter,
+            totals.downloads.unsigned_abs(),
+        );
+        metrics.add(
+            "cratery_publications_total",
+            "Number of crate versions published since the launch",
+            MetricKind::Counter,
+            self.publications.load(Ordering::Relaxed),
+        );
+        add_worker_metrics(&mut metrics, "docs", &self.docs_worker_state.get_status());
+        add_worker_metrics(&mut metrics, "deps", &self.deps_worker_state.get_status());
+        // the data is locked while being refreshed, it is then reported as busy instead of waiting for it
+        let rustsec = self.rustsec.try_lock();
+        metrics.add(
+            "cratery_rustsec_busy",
+            "Whether the RustSec advisories are being used or refreshed",
+            MetricKind::Gauge,
+            u64::from(rustsec.is_none()),
+        );
+        if let Some(rustsec) = rustsec {
+            metrics.add(
+                "cratery_rustsec_advisories",
+                "Number of known RustSec advisories",
+                MetricKind::Gauge,
+                rustsec.get_advisories_count() as u64,
+            );
+        }
+        Ok(metrics.to_string())
+    }
+
+    /// Acquires a connection to the database, retrying with a backoff on transient failures
+    async fn acquire_connection(&self) -> Result<PoolConnection<Sqlite>, ApiError> {
+        let connection = acquire_with_retry(
+            &self.db_pool,
+            self.configuration.db_acquire_retries,
+            self.configuration.db_acquire_backoff,
+            self.configuration.db_acquire_max_wait,
+        )
+        .await?;
+        Ok(connection)
+    }
+
     /// Gets the storage service
-    pub fn get_service_storage(&self) -> Storage {
-        crate::services::storage::Storage::from(&self.configuration.deref().clone())
+    pub fn get_service_storage(&self) -> Box<dyn Storage> {
+        get_storage(&self.configuration)
     }
 
     /// Gets the service to check for advisories using `RustSec`
@@ -144,6 +442,11 @@ impl Application {
         EmailSender::new(&self.configuration)
     }
 
+    /// Gets the service to execute hooks
+    pub fn get_service_hooks(&self) -> HooksExecutor<'_> {
+        HooksExecutor::new(&self.configuration)
+    }
+
     /// Creates the application with transaction
     pub fn with_transaction<'a, 'c>(&'a self, transaction: AppTransaction<'c>) -> ApplicationWithTransaction<'a, 'c> {
         ApplicationWithTransaction {
@@ -154,7 +457,7 @@ impl Application {
 
     /// Attempts the authentication of a user
     pub async fn authenticate(&self, auth_data: &AuthData) -> Result<AuthenticatedUser, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             self.with_transaction(transaction).authenticate(auth_data).await
         })
@@ -163,7 +466,7 @@ impl Application {
 
     /// Gets the data about the current user
     pub async fn get_current_user(&self, auth_data: &AuthData) -> Result<RegistryUser, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -172,19 +475,65 @@ impl Application {
         .await
     }
 
+    /// Gets the preferences of the current user for the notifications sent by email
+    pub async fn get_notification_settings(&self, auth_data: &AuthData) -> Result<UserNotificationSettings, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.get_notification_settings(principal.uid).await
+        })
+        .await
+    }
+
+    /// Sets the preferences of the current user for the notifications sent by email
+    pub async fn set_notification_settings(
+        &self,
+        auth_data: &AuthData,
+        settings: &UserNotificationSettings,
+    ) -> Result<UserNotificationSettings, ApiError> {
+        if !self.configuration.publish_notifications && settings.publish != PublishNotifications::None {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("notifications of publications are not enabled on this registry"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.set_notification_settings(principal.uid, settings).await
+        })
+        .await
+    }
+
     /// Attempts to login using an OAuth code
-    pub async fn login_with_oauth_code(&self, code: &str) -> Result<RegistryUser, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// When configured, the state returned by the identity provider must match the one of the login flow
+    pub async fn login_with_oauth_code(
+        &self,
+        code: &str,
+        state: Option<&str>,
+        login_state: Option<&OAuthLoginState>,
+    ) -> Result<RegistryUser, ApiError> {
+        let nonce = if self.configuration.oauth_check_state {
+            let Some(login_state) = login_state.filter(|login_state| Some(login_state.state.as_str()) == state) else {
+                return Err(specialize(error_unauthorized(), String::from("invalid OAuth state")));
+            };
+            Some(login_state.nonce.as_str())
+        } else {
+            None
+        };
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            app.database.login_with_oauth_code(&self.configuration, code).await
+            app.database.login_with_oauth_code(&self.configuration, code, nonce).await
         })
         .await
     }
 
     /// Gets the known users
     pub async fn get_users(&self, auth_data: &AuthData) -> Result<Vec<RegistryUser>, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -193,9 +542,50 @@ impl Application {
         .await
     }
 
+    /// Creates users in bulk, before their first login
+    pub async fn import_users(&self, auth_data: &AuthData, users: &[RegistryUser]) -> Result<Vec<UserImportResult>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.import_users(&principal, users).await
+        })
+        .await
+    }
+
+    /// Sends an email with a link to verify the email address of a user
+    pub async fn send_verification_email(&self, auth_data: &AuthData, target: &str) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let secret = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database
+                .create_email_verification(&principal, target, EMAIL_VERIFICATION_VALIDITY)
+                .await
+        })
+        .await?;
+        let body = format!(
+            "Please confirm that this email address belongs to your account on {} by opening the link below:\n\n{}/api/v1/users/verifyemail?token={secret}\n\nThis link expires in {EMAIL_VERIFICATION_VALIDITY} hours.\n",
+            self.configuration.web_public_uri, self.configuration.web_public_uri
+        );
+        self.get_service_email_sender()
+            .send_email(&[target.to_string()], "Cratery - verify your email address", body)
+            .await
+    }
+
+    /// Marks the email of a user as verified using the secret sent by email
+    pub async fn verify_email(&self, secret: &str) -> Result<String, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.verify_email(secret).await
+        })
+        .await
+    }
+
     /// Updates the information of a user
     pub async fn update_user(&self, auth_data: &AuthData, target: &RegistryUser) -> Result<RegistryUser, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -206,7 +596,7 @@ impl Application {
 
     /// Attempts to deactivate a user
     pub async fn deactivate_user(&self, auth_data: &AuthData, target: &str) -> Result<(), ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -217,7 +607,7 @@ impl Application {
 
     /// Attempts to re-activate a user
     pub async fn reactivate_user(&self, auth_data: &AuthData, target: &str) -> Result<(), ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -228,7 +618,7 @@ impl Application {
 
     /// Attempts to delete a user
     pub async fn delete_user(&self, auth_data: &AuthData, target: &str) -> Result<(), ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -237,9 +627,34 @@ impl Application {
         .await
     }
 
+    /// Reassigns all the crates owned by a user, for example a departing one, to a successor
+    /// Returns the names of the affected crates
+    pub async fn reassign_user_crates(
+        &self,
+        auth_data: &AuthData,
+        former: &str,
+        reassignment: &UserCratesReassignment,
+    ) -> Result<Vec<String>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let (packages, principal) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let packages = app.database.reassign_user_crates(&principal, former, reassignment).await?;
+            Ok::<_, ApiError>((packages, principal.principal))
+        })
+        .await?;
+        for package in &packages {
+            self.send_webhook(
+                WebhookEvent::new(WebhookEventKind::OwnersReassign, package, None, &principal)
+                    .with_users(vec![former.to_string(), reassignment.successor.clone()]),
+            );
+        }
+        Ok(packages)
+    }
+
     /// Gets the tokens for a user
     pub async fn get_tokens(&self, auth_data: &AuthData) -> Result<Vec<RegistryUserToken>, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -248,6 +663,28 @@ impl Application {
         .await
     }
 
+    /// Gets the crates that the current authentication, usually a token, may publish to
+    pub async fn get_token_publish_scope(&self, auth_data: &AuthData) -> Result<TokenPublishScope, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let crates = if principal.can_write {
+                let mut crates = app.database.get_owned_crates(principal.uid).await?;
+                crates.retain(|name| principal.is_crate_in_scope(name));
+                crates
+            } else {
+                Vec::new()
+            };
+            Ok(TokenPublishScope {
+                can_write: principal.can_write,
+                restricted: !principal.crates.is_empty(),
+                crates,
+            })
+        })
+        .await
+    }
+
     /// Creates a token for the current user
     pub async fn create_token(
         &self,
@@ -255,19 +692,115 @@ impl Application {
         name: &str,
         can_write: bool,
         can_admin: bool,
+        expires_at: Option<NaiveDateTime>,
+        restrictions: &TokenRestrictions,
     ) -> Result<RegistryUserTokenWithSecret, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        self.check_token_name(name)?;
+        Self::check_token_crates(&restrictions.crates)?;
+        let restrictions = &TokenRestrictions {
+            crates: restrictions.crates.clone(),
+            allowed_ips: Self::check_token_allowed_ips(&restrictions.allowed_ips)?,
+        };
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
-            app.database.create_token(&principal, name, can_write, can_admin).await
+            if self.configuration.tokens.name_unique {
+                app.database.check_token_name_unused(&principal, name).await?;
+            }
+            // the secret is random, a collision with an existing token is only retried a few times
+            for _ in 0..self.configuration.tokens.create_attempts {
+                if let Some(token) = app
+                    .database
+                    .create_token(
+                        &principal,
+                        generate_token(64),
+                        name,
+                        can_write,
+                        can_admin,
+                        expires_at,
+                        restrictions,
+                    )
+                    .await?
+                {
+                    return Ok(token);
+                }
+            }
+            Err(specialize(
+                error_backend_failure(),
+                String::from("failed to generate a unique secret for the token"),
+            ))
         })
         .await
     }
 
+    /// Checks that the name for a new token is not empty, not too long and only uses allowed characters
+    fn check_token_name(&self, name: &str) -> Result<(), ApiError> {
+        if name.trim().is_empty() {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the name of a token must not be empty"),
+            ));
+        }
+        let max_length = self.configuration.tokens.name_max_length;
+        if name.chars().count() > max_length {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("the name of a token must not exceed {max_length} characters"),
+            ));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !(c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.'))
+        {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("invalid character {c:?} in the name of a token, only letters, digits, spaces, '-', '_' and '.' are allowed"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the patterns for the names of the crates a new token is limited to
+    /// Patterns use the characters allowed in the names of crates, with `*` matching any sequence of characters
+    fn check_token_crates(crates: &[String]) -> Result<(), ApiError> {
+        for pattern in crates {
+            if pattern.is_empty() || pattern.chars().all(|c| c == '*') {
+                return Err(specialize(
+                    error_invalid_request(),
+                    String::from("the crates a token is limited to must not be empty or match all crates"),
+                ));
+            }
+            if let Some(c) = pattern
+                .chars()
+                .find(|&c| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '*'))
+            {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!("invalid character {c:?} in the pattern {pattern} for the crates of a token"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the IP addresses or CIDR blocks a new token is bound to
+    /// Returns the blocks in their normalized form
+    fn check_token_allowed_ips(allowed_ips: &[String]) -> Result<Vec<String>, ApiError> {
+        allowed_ips
+            .iter()
+            .map(|block| {
+                block
+                    .parse::<IpCidr>()
+                    .map(|block| block.to_string())
+                    .map_err(|e| specialize(error_invalid_request(), format!("invalid allowed IP for a token: {e}")))
+            })
+            .collect()
+    }
+
     /// Revoke a previous token
     pub async fn revoke_token(&self, auth_data: &AuthData, token_id: i64) -> Result<(), ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -276,221 +809,1506 @@ impl Application {
         .await
     }
 
-    /// Publish a crate
-    pub async fn publish_crate_version(&self, auth_data: &AuthData, content: &[u8]) -> Result<CrateUploadResult, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// Revokes all the tokens matching the criteria, or only lists them in a dry run
+    pub async fn revoke_tokens_matching(
+        &self,
+        auth_data: &AuthData,
+        criteria: &TokensRevocationCriteria,
+    ) -> Result<TokensRevocationResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
-            // deserialize payload
-            let package = CrateUploadData::new(content)?;
-            let index_data = package.build_index_data();
+            app.database.revoke_tokens_matching(&principal, criteria).await
+        })
+        .await
+    }
+
+    /// Exports the audit log as NDJSON, one entry per line
+    /// The exported entries are marked as such only once the whole log has been produced,
+    /// so that the retention never deletes entries that were not archived when archival is enabled
+    pub async fn export_audit_log(
+        self: &Arc<Self>,
+        auth_data: &AuthData,
+    ) -> Result<BoxStream<'static, Result<Vec<u8>, ApiError>>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let upto = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.get_audit_export_bound(&principal).await
+        })
+        .await?;
+        let application = self.clone();
+        let stream = futures::stream::try_unfold(0, move |after| {
+            let application = application.clone();
+            async move {
+                let entries = application.get_audit_entries(after, upto).await?;
+                let Some(last) = entries.last().map(|entry| entry.id) else {
+                    // the whole log was produced
+                    application.set_audit_entries_exported(upto).await?;
+                    return Ok(None);
+                };
+                let mut buffer = Vec::new();
+                for entry in &entries {
+                    serde_json::to_writer(&mut buffer, entry)?;
+                    buffer.push(b'\n');
+                }
+                Ok(Some((buffer, last)))
+            }
+        });
+        Ok(stream.boxed())
+    }
+
+    /// Gets a page of entries in the audit log for an export
+    async fn get_audit_entries(&self, after: i64, upto: i64) -> Result<Vec<AuditLogEntry>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.get_audit_entries(after, upto, AUDIT_EXPORT_PAGE_SIZE).await
+        })
+        .await
+    }
+
+    /// Marks the entries in the audit log as exported
+    async fn set_audit_entries_exported(&self, upto: i64) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.set_audit_entries_exported(upto).await
+        })
+        .await
+    }
+
+    /// Publish a crate
+    /// `user_agent` is the `User-Agent` of the publishing client, if any, recorded with the version and in the audit log
+    pub async fn publish_crate_version(
+        &self,
+        auth_data: &AuthData,
+        content: &[u8],
+        user_agent: Option<&str>,
+    ) -> Result<CrateUploadResult, ApiError> {
+        // deserialize payload
+        let package = CrateUploadData::new(content)?;
+        self.publish_crate_upload(auth_data, package, user_agent).await
+    }
+
+    /// Publish a crate from already deserialized upload data
+    pub async fn publish_crate_upload(
+        &self,
+        auth_data: &AuthData,
+        package: CrateUploadData,
+        user_agent: Option<&str>,
+    ) -> Result<CrateUploadResult, ApiError> {
+        let principal = self.authenticate(auth_data).await?;
+        // an invalid crate is rejected before the hook and the resolution of its dependencies
+        package.metadata.validate()?;
+        let mut package = package;
+        let sanitization_warnings = package
+            .metadata
+            .sanitize_description(self.configuration.publish_description_max_length);
+        let index_data = package.build_index_data(
+            self.configuration.index.duplicate_deps,
+            self.configuration.index.strip_dev_deps,
+        )?;
+        // the hook is an external process, it runs before opening the transaction so that it does not block the other writers
+        self.get_service_hooks()
+            .run_pre_publish(&principal.principal, &package.metadata)
+            .await?;
+        let mut connection = self.acquire_connection().await?;
+        if self.configuration.publish_deny_advisories.is_some() || self.configuration.publish_policy.is_some() {
+            let name = &package.metadata.name;
+            let targets = in_transaction(&mut connection, |transaction| async move {
+                let app = self.with_transaction(transaction);
+                // a new crate has no targets yet, the host is used
+                Ok::<_, ApiError>(app.database.get_crate_effective_deps_targets(name).await.unwrap_or_default())
+            })
+            .await?;
+            // the dependencies are resolved over the network, outside the write transaction so that the other writers are not blocked
+            self.check_publish_deps(&index_data, &targets).await?;
+        }
+        // the index is locked until the transaction is committed, so that the collection of the stored files of deleted crates is serialized with the publication
+        let index_guard = self.index.lock().await;
+        let index = &index_guard;
+        let (r, principal, metadata) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
             // publish
-            let index = self.index.lock().await;
-            let r = app.database.publish_crate_version(&principal, &package).await?;
+            let replaced = app
+                .database
+                .check_crate_exists(&package.metadata.name, &package.metadata.vers)
+                .await
+                .is_ok();
+            let mut r = app
+                .database
+                .publish_crate_version(&principal, &package, self.configuration.version_mutable_window, user_agent)
+                .await?;
+            r.warnings.other.extend(sanitization_warnings);
+            if self.configuration.search_readme {
+                let readme = extract_readme(&package.content)?;
+                app.database
+                    .set_crate_readme_search(&package.metadata.name, &String::from_utf8_lossy(&readme))
+                    .await?;
+            }
             self.get_service_storage()
                 .store_crate(&package.metadata, package.content)
                 .await?;
-            index.publish_crate_version(&index_data).await?;
+            if replaced {
+                r.warnings.other.push(format!(
+                    "Version {} replaced the previously published one",
+                    package.metadata.vers
+                ));
+                index.replace_crate_version(&index_data).await?;
+            } else {
+                index.publish_crate_version(&index_data).await?;
+            }
             let targets = app.database.get_crate_targets(&package.metadata.name).await?;
             // generate the doc
-            self.docs_worker_sender
-                .clone()
-                .send(JobCrate {
-                    name: package.metadata.name.clone(),
-                    version: package.metadata.vers.clone(),
-                    targets,
-                })
-                .await?;
-            Ok(r)
+            self.send_docs_job(JobCrate {
+                name: package.metadata.name.clone(),
+                version: package.metadata.vers.clone(),
+                targets,
+            });
+            Ok::<_, ApiError>((r, principal.principal, package.metadata))
         })
-        .await
+        .await?;
+        drop(index_guard);
+        self.publications.fetch_add(1, Ordering::Relaxed);
+        self.send_webhook(WebhookEvent::new(
+            WebhookEventKind::Publish,
+            &metadata.name,
+            Some(&metadata.vers),
+            &principal,
+        ));
+        // the post-publish hook and the notifications do not hold up the response
+        let configuration = self.configuration.clone();
+        let pool = self.db_pool.clone();
+        tokio::spawn(async move {
+            HooksExecutor::new(&configuration)
+                .run_post_publish(&principal, &metadata)
+                .await;
+            if let Err(e) = notify_publication(&configuration, &pool, &metadata.name, &metadata.vers, &principal).await {
+                error!("failed to notify the publication of {} {}: {e}", metadata.name, metadata.vers);
+            }
+        });
+        Ok(r)
+    }
+
+    /// Checks the dependencies of a crate about to be published
+    /// The dependencies are resolved once to check them against the denied level of advisories and the enforced policy, when configured
+    async fn check_publish_deps(&self, index_data: &IndexCrateMetadata, targets: &[String]) -> Result<(), ApiError> {
+        let (graph, analysis) = self
+            .get_service_deps_checker()
+            .check_dependencies(&index_data.deps, targets)
+            .await?;
+        if let Some(level) = self.configuration.publish_deny_advisories {
+            Self::check_publish_advisories(&analysis, level)?;
+        }
+        if let Some(name) = &self.configuration.publish_policy {
+            let policy = self.load_deps_policy(name).await?;
+            let licenses = if policy.denied_licenses.is_empty() {
+                HashMap::new()
+            } else {
+                self.get_deps_local_licenses(&graph).await
+            };
+            let report = policy.check(&graph, &analysis, &licenses);
+            if !report.passed {
+                let violations = report
+                    .violations
+                    .iter()
+                    .map(|violation| {
+                        ApiErrorItem::new(
+                            violation.rule.code(),
+                            format!(
+                                "{} {} violates the policy {name}: {}",
+                                violation.package, violation.version, violation.message
+                            ),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                return Err(error_validation(violations));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the dependencies of a crate about to be published are not affected by advisories of at least a level of severity
+    /// Each offending advisory is reported as an error item, with the identifier of the advisory in its detail
+    fn check_publish_advisories(analysis: &DepsAnalysis, level: AdvisorySeverityLevel) -> Result<(), ApiError> {
+        let offending = analysis
+            .advisories
+            .iter()
+            .filter(|advisory| level.is_reached_by(&advisory.content))
+            .map(|advisory| {
+                let score = advisory
+                    .content
+                    .score
+                    .map_or_else(|| String::from("unscored"), |score| score.to_string());
+                ApiErrorItem::new(
+                    "denied-advisory",
+                    format!(
+                        "{} {} is affected by {} ({score}): {}",
+                        advisory.package, advisory.version, advisory.content.id, advisory.content.summary
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        if offending.is_empty() {
+            Ok(())
+        } else {
+            Err(error_validation(offending))
+        }
     }
 
     /// Gets all the data about a crate
     pub async fn get_crate_info(&self, auth_data: &AuthData, package: &str) -> Result<CrateInfo, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_not_deleted(package, None).await?;
+            // resolve the crate as it was published when the case differs
+            let canonical = app.database.get_crate_canonical_name(package).await?;
+            let package = canonical.as_deref().unwrap_or(package);
             let versions = app
                 .database
                 .get_crate_versions(package, self.index.lock().await.get_crate_data(package).await?)
                 .await?;
-            let metadata = self
+            let (metadata, metadata_unavailable) = match self
                 .get_service_storage()
                 .download_crate_metadata(package, &versions.last().unwrap().index.vers)
-                .await?;
+                .await
+            {
+                Ok(metadata) => (metadata, false),
+                Err(e) if self.configuration.storage_degraded_metadata => {
+                    // serve what is in the database only
+                    warn!("failed to load the metadata for {package} from the storage: {e}");
+                    (None, true)
+                }
+                Err(e) => return Err(e),
+            };
             let targets = app.database.get_crate_targets(package).await?;
+            let builds = app
+                .database
+                .get_crate_version_builds(package, &versions.last().unwrap().index.vers)
+                .await?;
+            let docs = app
+                .database
+                .get_crate_version_docs(package, &versions.last().unwrap().index.vers)
+                .await?;
+            let docs_enabled = app.database.get_crate_docs_enabled(package).await?;
+            let docs_external_url = app.database.get_crate_docs_external_url(package).await?;
+            let deps_targets = app.database.get_crate_deps_targets(package).await?;
+            let repository = app.database.get_crate_repository_info(package).await?;
             Ok(CrateInfo {
                 metadata,
+                metadata_unavailable,
                 versions,
                 targets,
+                deps_targets,
+                builds,
+                docs,
+                docs_enabled,
+                docs_external_url,
+                repository,
             })
         })
         .await
     }
 
-    /// Downloads the last README for a crate
-    pub async fn get_crate_last_readme(&self, auth_data: &AuthData, package: &str) -> Result<Vec<u8>, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// Gets the name of a crate as it was published, looking it up without regard to the case
+    /// `None` is returned when the crate does not exist or when the requester cannot read it
+    pub async fn get_crate_canonical_name(&self, auth_data: &AuthData, package: &str) -> Option<String> {
+        let mut connection = self.acquire_connection().await.ok()?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
-            let version = app.database.get_crate_last_version(package).await?;
-            let readme = self.get_service_storage().download_crate_readme(package, &version).await?;
-            Ok(readme)
+            app.database.get_crate_canonical_name(package).await
         })
         .await
+        .ok()
+        .flatten()
     }
 
-    /// Downloads the README for a crate
-    pub async fn get_crate_readme(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<Vec<u8>, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
-        in_transaction(&mut connection, |transaction| async move {
+    /// Gets the data about a crate in the shape of the crates.io API
+    pub async fn get_crate_info_cratesio(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+    ) -> Result<CratesIoCrateResponse, ApiError> {
+        let info = self.get_crate_info(auth_data, package).await?;
+        CratesIoCrateResponse::from_info(&info).ok_or_else(error_not_found)
+    }
+
+    /// Downloads the last README for a crate
+    pub async fn get_crate_last_readme(&self, auth_data: &AuthData, package: &str) -> Result<CrateReadme, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let version = in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
-            let readme = self.get_service_storage().download_crate_readme(package, version).await?;
-            Ok(readme)
+            app.database.get_crate_last_version(package).await
         })
-        .await
+        .await?;
+        self.download_crate_readme(package, &version).await
     }
 
-    /// Downloads the content for a crate
-    pub async fn get_crate_content(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<Vec<u8>, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// Gets the changelog for a crate version
+    pub async fn get_crate_changelog(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
-            app.database.check_crate_exists(package, version).await?;
-            app.database.increment_crate_version_dl_count(package, version).await?;
-            let content = self.get_service_storage().download_crate(package, version).await?;
-            Ok(content)
+            app.database.check_crate_exists(package, version).await
         })
-        .await
+        .await?;
+        self.get_service_storage()
+            .download_crate_changelog(package, version)
+            .await?
+            .ok_or_else(|| specialize(error_not_found(), format!("no changelog in {package} {version}")))
     }
 
-    /// Yank a crate version
-    pub async fn yank_crate_version(
-        &self,
-        auth_data: &AuthData,
-        package: &str,
-        version: &str,
-    ) -> Result<YesNoResult, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// Gets the `Cargo.lock` shipped with a crate version
+    pub async fn get_crate_lockfile(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let principal = app.authenticate(auth_data).await?;
-            app.database.yank_crate_version(&principal, package, version).await
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await
         })
-        .await
+        .await?;
+        self.get_service_storage()
+            .download_crate_lockfile(package, version)
+            .await?
+            .ok_or_else(|| specialize(error_not_found(), format!("no Cargo.lock in {package} {version}")))
     }
 
-    /// Unyank a crate version
-    pub async fn unyank_crate_version(
-        &self,
-        auth_data: &AuthData,
-        package: &str,
-        version: &str,
-    ) -> Result<YesNoResult, ApiError> {
-        let mut connection = self.db_pool.acquire().await?;
+    /// Downloads the README for a crate
+    pub async fn get_crate_readme(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<CrateReadme, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let principal = app.authenticate(auth_data).await?;
-            app.database.unyank_crate_version(&principal, package, version).await
+            app.authenticate(auth_data).await
         })
-        .await
+        .await?;
+        self.download_crate_readme(package, version).await
+    }
+
+    /// Downloads the README for a crate version along with its content type
+    /// The content type is deduced from the manifest, markdown is assumed when it is not available
+    async fn download_crate_readme(&self, package: &str, version: &str) -> Result<CrateReadme, ApiError> {
+        let storage = self.get_service_storage();
+        let content = storage.download_crate_readme(package, version).await?;
+        let content_type = match storage.download_crate_metadata(package, version).await {
+            Ok(Some(metadata)) => metadata.readme_content_type(),
+            Ok(None) => README_CONTENT_TYPE_MARKDOWN,
+            Err(e) => {
+                warn!("failed to load the metadata for {package} {version} from the storage: {e}");
+                README_CONTENT_TYPE_MARKDOWN
+            }
+        };
+        Ok(CrateReadme { content_type, content })
+    }
+
+    /// Downloads the content for a crate
+    /// The content is streamed from the storage, except for crates proxied from the upstream registry that are checked first
+    pub async fn get_crate_content(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<CrateContent, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let result = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            app.database.increment_crate_version_dl_count(package, version).await?;
+            let content = self.get_service_storage().download_crate_content(package, version).await?;
+            Ok::<_, ApiError>(content)
+        })
+        .await;
+        match result {
+            // the user is authenticated at this point
+            Err(e) if e.http == 404 && self.configuration.proxy.enabled => {
+                drop(connection);
+                let content = self.get_proxied_crate_content(package, version).await?;
+                Ok(CrateContent::from_bytes(content))
+            }
+            result => result,
+        }
+    }
+
+    /// Gets the checksum of the content of a crate version, used to validate the copies cached by clients
+    /// For a crate proxied from the upstream registry, this is `None` until the crate version is cached
+    pub async fn get_crate_checksum(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<Option<String>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let result = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            let checksum = self.get_service_storage().get_crate_checksum(package, version).await?;
+            Ok::<_, ApiError>(Some(checksum))
+        })
+        .await;
+        match result {
+            // the user is authenticated at this point
+            Err(e) if e.http == 404 && self.configuration.proxy.enabled => {
+                in_transaction(&mut connection, |transaction| async move {
+                    let app = self.with_transaction(transaction);
+                    if app.database.is_crate_local(package).await? {
+                        return Err(error_not_found());
+                    }
+                    app.database.get_proxied_crate_version_checksum(package, version).await
+                })
+                .await
+            }
+            result => result,
+        }
+    }
+
+    /// Gets a range of the bytes of the content of a crate version
+    /// Only the ranges starting at the beginning of the content are counted as downloads, so that resumed downloads are counted once
+    pub async fn get_crate_content_range(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        spec: ByteRangeSpec,
+    ) -> Result<RangedContent, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let result = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            let content = self
+                .get_service_storage()
+                .download_crate_range(package, version, spec)
+                .await?;
+            if matches!(&content, RangedContent::Partial { range, .. } if range.start == 0) {
+                app.database.increment_crate_version_dl_count(package, version).await?;
+            }
+            Ok::<_, ApiError>(content)
+        })
+        .await;
+        match result {
+            // the user is authenticated at this point
+            Err(e) if e.http == 404 && self.configuration.proxy.enabled => {
+                drop(connection);
+                // proxied crates are checked against their checksum, so they are read completely
+                let content = self.get_proxied_crate_content(package, version).await?;
+                Ok(RangedContent::from_content(&content, spec))
+            }
+            result => result,
+        }
+    }
+
+    /// Gets the content of a crate version proxied from the upstream registry
+    /// The crate version is fetched on first access and cached, after checking its checksum against the upstream index
+    async fn get_proxied_crate_content(&self, package: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        if !crate::services::proxy::is_valid_crate_name(package) {
+            return Err(error_not_found());
+        }
+        let mut connection = self.acquire_connection().await?;
+        let cached = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            if app.database.is_crate_local(package).await? {
+                // never mix the versions of a local crate with the upstream ones
+                return Err(error_not_found());
+            }
+            app.database.get_proxied_crate_version_checksum(package, version).await
+        })
+        .await?;
+        let storage = self.get_service_storage();
+        let content = if let Some(checksum) = cached.as_ref() {
+            let content = storage.download_proxied_crate(package, version).await?;
+            if &sha256(&content) != checksum {
+                return Err(specialize(
+                    error_backend_failure(),
+                    format!("checksum mismatch for the cached {package} {version}"),
+                ));
+            }
+            content
+        } else {
+            let metadata = crate::services::proxy::get_crate_metadata(&self.configuration, package, version).await?;
+            let content = crate::services::proxy::fetch_crate(&self.configuration, &metadata).await?;
+            storage.store_proxied_crate(package, version, content.clone()).await?;
+            content
+        };
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            if cached.is_none() {
+                app.database
+                    .add_proxied_crate_version(package, version, &sha256(&content))
+                    .await?;
+            }
+            app.database
+                .increment_proxied_crate_version_dl_count(package, version)
+                .await?;
+            Ok(content)
+        })
+        .await
+    }
+
+    /// Gets the file of the upstream index for a crate that is not hosted by this registry, when the proxy is enabled
+    /// The file must be requested at its path in the layout of the sparse index
+    pub async fn get_proxied_index_file(&self, path: &str) -> Result<Option<Vec<u8>>, ApiError> {
+        if !self.configuration.proxy.enabled {
+            return Ok(None);
+        }
+        let package = path.rsplit('/').next().unwrap_or_default();
+        if !crate::services::proxy::is_valid_crate_name(package)
+            || std::path::Path::new(path) != crate::services::index::build_package_file_path(PathBuf::from("/"), package)
+        {
+            return Ok(None);
+        }
+        let mut connection = self.acquire_connection().await?;
+        let is_local = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.is_crate_local(package).await
+        })
+        .await?;
+        if is_local {
+            return Ok(None);
+        }
+        crate::services::proxy::get_index_file(&self.configuration, package).await
+    }
+
+    /// Creates a signed link to download a crate version without authentication, for sharing with external systems
+    /// The link is valid for `ttl` seconds, or the maximum allowed validity when not specified
+    pub async fn create_download_token(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        ttl: Option<u64>,
+    ) -> Result<SignedDownloadLink, ApiError> {
+        let Some(key) = self.configuration.web_download_links_key.as_ref() else {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("signed download links are deactivated"),
+            ));
+        };
+        let max_ttl = self.configuration.web_download_links_max_ttl;
+        let ttl = ttl.unwrap_or(max_ttl);
+        if ttl == 0 || ttl > max_ttl {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("the validity of a download link must be between 1 and {max_ttl} seconds"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            app.database
+                .add_audit_entry(
+                    &principal,
+                    "download-link",
+                    Some(package),
+                    Some(version),
+                    &format!("ttl={ttl}s"),
+                )
+                .await
+        })
+        .await?;
+        let expires = Local::now() + Duration::seconds(i64::try_from(ttl)?);
+        let timestamp = expires.timestamp();
+        let signature = hmac_sha256_sign(key.as_bytes(), download_link_message(package, version, timestamp).as_bytes());
+        Ok(SignedDownloadLink {
+            url: format!(
+                "{}/api/v1/crates/{package}/{version}/download/signed?expires={timestamp}&signature={signature}",
+                self.configuration.web_public_uri
+            ),
+            expires: expires.naive_local(),
+        })
+    }
+
+    /// Downloads the content for a crate with a signed link instead of the normal authentication
+    pub async fn get_crate_content_signed(
+        &self,
+        package: &str,
+        version: &str,
+        expires: i64,
+        signature: &str,
+    ) -> Result<CrateContent, ApiError> {
+        let valid = self.configuration.web_download_links_key.as_ref().is_some_and(|key| {
+            hmac_sha256_verify(
+                key.as_bytes(),
+                download_link_message(package, version, expires).as_bytes(),
+                signature,
+            )
+        });
+        if !valid || expires < Local::now().timestamp() {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("the download link is invalid or expired"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.check_crate_exists(package, version).await?;
+            app.database.increment_crate_version_dl_count(package, version).await?;
+            self.get_service_storage().download_crate_content(package, version).await
+        })
+        .await
+    }
+
+    /// Downloads a set of crate versions as a tar archive in the layout of a local registry
+    /// The archive contains the `.crate` files at the root and their entries in the index under `index/`
+    /// The crates are resolved against the database and the index first, the archive is then streamed from the storage.
+    pub async fn download_crates_bundle(
+        &self,
+        auth_data: &AuthData,
+        crates: &[CrateAndVersion],
+    ) -> Result<BoxStream<'static, Result<Bytes, ApiError>>, ApiError> {
+        if crates.is_empty() {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the bundle must contain at least one crate"),
+            ));
+        }
+        if crates.len() > BUNDLE_MAX_CRATES {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("the bundle must not contain more than {BUNDLE_MAX_CRATES} crates"),
+            ));
+        }
+        // the distinct versions for each crate, in the order of the request
+        let mut versions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut bundled = Vec::new();
+        for CrateAndVersion { name, version } in crates {
+            let entries = versions.entry(name).or_default();
+            if !entries.contains(&version.as_str()) {
+                entries.push(version);
+                bundled.push((name.clone(), version.clone()));
+            }
+        }
+        let mut connection = self.acquire_connection().await?;
+        let bundled_ref = &bundled;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            for (name, version) in bundled_ref {
+                app.database.check_crate_exists(name, version).await?;
+            }
+            Ok::<_, ApiError>(())
+        })
+        .await?;
+        // the files in the index, with the lines of the bundled versions
+        let mut index_files = Vec::new();
+        {
+            let index = self.index.lock().await;
+            for (name, versions) in versions {
+                let data = index.get_crate_data(name).await?;
+                let mut lines = String::new();
+                for version in versions {
+                    let index_entry = data
+                        .iter()
+                        .find(|metadata| metadata.vers == version)
+                        .ok_or_else(error_not_found)?;
+                    lines.push_str(&serde_json::to_string(index_entry)?);
+                    lines.push('\n');
+                }
+                let path = build_package_file_path(PathBuf::from("index"), name);
+                index_files.push((path.to_string_lossy().into_owned(), lines.into_bytes()));
+            }
+        }
+        let storage: Arc<dyn Storage> = Arc::from(self.get_service_storage());
+        let mut total_size = 0;
+        for (name, version) in &bundled {
+            total_size += storage.get_crate_size(name, version).await?;
+            if total_size > BUNDLE_MAX_SIZE {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!("the bundle must not exceed {BUNDLE_MAX_SIZE} bytes"),
+                ));
+            }
+        }
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            for (name, version) in bundled_ref {
+                app.database.increment_crate_version_dl_count(name, version).await?;
+            }
+            Ok::<_, ApiError>(())
+        })
+        .await?;
+        // the crates are streamed from the storage between their header and their padding
+        let crate_entries = futures::stream::iter(bundled)
+            .then(move |(name, version)| {
+                let storage = storage.clone();
+                async move {
+                    let size = storage.get_crate_size(&name, &version).await?;
+                    let header = tar_header(&format!("{name}-{version}.crate"), size)?;
+                    let content = storage.download_crate_stream(&name, &version).await?;
+                    let padding = vec![0; tar_padding(size)];
+                    Ok::<_, ApiError>(
+                        futures::stream::once(async move { Ok(Bytes::from(header)) })
+                            .chain(content)
+                            .chain(futures::stream::once(async move { Ok(Bytes::from(padding)) })),
+                    )
+                }
+            })
+            .try_flatten();
+        let index_entries =
+            futures::stream::iter(index_files).map(|(path, content)| tar_entry(&path, &content).map(Bytes::from));
+        let end = futures::stream::once(async { Ok(Bytes::from(vec![0; TAR_END_SIZE])) });
+        Ok(crate_entries.chain(index_entries).chain(end).boxed())
+    }
+
+    /// Yank a crate version
+    /// When `with_expiry` is set, the version is automatically unyanked after the configured duration
+    pub async fn yank_crate_version(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        with_expiry: bool,
+    ) -> Result<YesNoResult, ApiError> {
+        let expiry = if with_expiry {
+            let hours = self.configuration.yank_expiry_hours;
+            let expiry = Duration::try_hours(hours)
+                .filter(|_| hours > 0)
+                .and_then(|delta| Local::now().naive_local().checked_add_signed(delta))
+                .ok_or_else(|| {
+                    specialize(
+                        error_invalid_request(),
+                        String::from("yanks with an expiry are deactivated on this registry"),
+                    )
+                })?;
+            Some(expiry)
+        } else {
+            None
+        };
+        let mut connection = self.acquire_connection().await?;
+        let (r, principal) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let r = app.database.yank_crate_version(&principal, package, version, expiry).await?;
+            Ok::<_, ApiError>((r, principal.principal))
+        })
+        .await?;
+        self.send_webhook(WebhookEvent::new(WebhookEventKind::Yank, package, Some(version), &principal));
+        Ok(r)
+    }
+
+    /// Confirms the yank of a crate version so that it is no longer automatically unyanked
+    pub async fn confirm_crate_version_yank(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<YesNoResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.confirm_crate_version_yank(&principal, package, version).await
+        })
+        .await
+    }
+
+    /// Unyank a crate version
+    pub async fn unyank_crate_version(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<YesNoResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let (r, principal) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let r = app.database.unyank_crate_version(&principal, package, version).await?;
+            Ok::<_, ApiError>((r, principal.principal))
+        })
+        .await?;
+        self.send_webhook(WebhookEvent::new(
+            WebhookEventKind::Unyank,
+            package,
+            Some(version),
+            &principal,
+        ));
+        Ok(r)
     }
 
     /// Force the re-generation for the documentation of a package
     pub async fn regen_crate_version_doc(&self, auth_data: &AuthData, package: &str, version: &str) -> Result<(), ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.regen_crate_version_doc(&principal, package, version).await?;
+            let targets = app.database.get_crate_targets(package).await?;
+            self.send_docs_job(JobCrate {
+                name: package.to_string(),
+                version: version.to_string(),
+                targets,
+            });
+            Ok(())
+        })
+        .await
+    }
+
+    /// Gets the status of the generation of the documentation for a crate version
+    pub async fn get_crate_doc_status(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<CrateDocsStatus, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_not_deleted(package, Some(version)).await?;
+            app.database.get_crate_docs_status(package, version).await
+        })
+        .await
+    }
+
+    /// Gets the log of the last generation of the documentation of a crate version for a target, or a variant of it
+    pub async fn get_crate_doc_log(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        target: &str,
+        variant: Option<&str>,
+    ) -> Result<Vec<u8>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_not_deleted(package, Some(version)).await
+        })
+        .await?;
+        self.get_service_storage()
+            .download_doc_log(package, version, target, variant)
+            .await?
+            .ok_or_else(|| {
+                specialize(
+                    error_not_found(),
+                    format!("no documentation log for {package} {version} on {target}"),
+                )
+            })
+    }
+
+    /// Gets whether the documentation is generated and served for a crate
+    pub async fn get_crate_docs_enabled(&self, package: &str) -> Result<bool, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.get_crate_docs_enabled(package).await
+        })
+        .await
+    }
+
+    /// Sets whether the documentation is generated and served for a crate
+    pub async fn set_crate_docs_settings(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        settings: &CrateDocsSettings,
+    ) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database
+                .set_crate_docs_enabled(&principal, package, settings.enabled, settings.purge)
+                .await?;
+            if settings.purge {
+                self.get_service_storage().delete_doc_files(package).await?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Gets the URL of the documentation of a crate hosted elsewhere, if any
+    pub async fn get_crate_docs_external_url(&self, package: &str) -> Result<Option<String>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.get_crate_docs_external_url(package).await
+        })
+        .await
+    }
+
+    /// Sets the URL of the documentation of a crate hosted elsewhere
+    pub async fn set_crate_docs_external(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        external: &CrateDocsExternal,
+    ) -> Result<(), ApiError> {
+        external.validate()?;
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database
+                .set_crate_docs_external_url(&principal, package, external.url.as_deref())
+                .await
+        })
+        .await
+    }
+
+    /// Lists the files in the stored documentation of a crate version
+    pub async fn get_crate_doc_files(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<Vec<CrateDocFile>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await?;
+            app.database.check_crate_exists(package, version).await?;
+            self.get_service_storage().list_doc_files(package, version).await
+        })
+        .await
+    }
+
+    /// Exports the stored documentation of a crate version to a folder, for publication on a static host
+    /// The documentation is written in `{target_dir}/{package}/{version}` within the configured export folder.
+    /// Links to the documentation of other crates in this registry are made relative,
+    /// so that they work when the exports of these crates are published side by side.
+    pub async fn export_docs_to_dir(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        target_dir: &str,
+    ) -> Result<DocsExport, ApiError> {
+        let Some(export_root) = self.configuration.docs_export_dir.as_ref() else {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("the export of documentation is deactivated"),
+            ));
+        };
+        if !std::path::Path::new(target_dir)
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("invalid target folder {target_dir}, it must be relative to the export folder"),
+            ));
+        }
+        let files = self.get_crate_doc_files(auth_data, package, version).await?;
+        if files.is_empty() {
+            return Err(specialize(
+                error_not_found(),
+                format!("no documentation for {package} {version}"),
+            ));
+        }
+        let storage = self.get_service_storage();
+        let root = std::path::Path::new(export_root).join(target_dir).join(package).join(version);
+        let registry_docs = format!("{}/docs/", self.configuration.web_public_uri);
+        let mut rewritten = 0;
+        for file in &files {
+            let mut content = storage
+                .download_doc_file(&format!("{package}/{version}/{}", file.path))
+                .await?;
+            if let Some(relocated) = relocate_doc_links(&content, &file.path, &registry_docs) {
+                content = relocated;
+                rewritten += 1;
+            }
+            let path = root.join(&file.path);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, content).await?;
+        }
+        Ok(DocsExport {
+            path: root.to_string_lossy().to_string(),
+            files: files.len(),
+            rewritten,
+        })
+    }
+
+    /// Gets all the packages that are outdated while also being the latest version
+    pub async fn get_crates_outdated_heads(&self, auth_data: &AuthData) -> Result<Vec<CrateAndVersion>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crates_outdated_heads().await
+        })
+        .await
+    }
+
+    /// Gets whether the latest version of each of the given crates is outdated
+    /// Duplicate names are only reported once, in the order of their first occurrence
+    pub async fn get_outdated_for_crates(
+        &self,
+        auth_data: &AuthData,
+        mut packages: Vec<String>,
+    ) -> Result<Vec<CrateOutdatedStatus>, ApiError> {
+        let mut seen = HashSet::new();
+        packages.retain(|package| seen.insert(package.clone()));
+        if packages.len() > OUTDATED_CHECK_MAX_CRATES {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("the outdated status can be checked for at most {OUTDATED_CHECK_MAX_CRATES} crates at once"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_outdated_for_crates(&packages).await
+        })
+        .await
+    }
+
+    /// Gets the download statistics for a crate
+    pub async fn get_crate_dl_stats(&self, auth_data: &AuthData, package: &str) -> Result<DownloadStats, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crate_dl_stats(package).await
+        })
+        .await
+    }
+
+    /// Gets the download counts for all the versions of a crate
+    pub async fn get_crate_dl_counts(&self, auth_data: &AuthData, package: &str) -> Result<CrateDownloadCounts, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crate_dl_counts(package).await
+        })
+        .await
+    }
+
+    /// Gets the list of owners for a package
+    pub async fn get_crate_owners(&self, auth_data: &AuthData, package: &str) -> Result<OwnersQueryResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crate_owners(package).await
+        })
+        .await
+    }
+
+    /// Add owners to a package
+    pub async fn add_crate_owners(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        new_users: &[String],
+    ) -> Result<YesNoMsgResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let (r, principal) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let r = app.database.add_crate_owners(&principal, package, new_users).await?;
+            Ok::<_, ApiError>((r, principal.principal))
+        })
+        .await?;
+        self.send_webhook(
+            WebhookEvent::new(WebhookEventKind::OwnersAdd, package, None, &principal).with_users(new_users.to_vec()),
+        );
+        Ok(r)
+    }
+
+    /// Remove owners from a package
+    pub async fn remove_crate_owners(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        old_users: &[String],
+    ) -> Result<YesNoResult, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let (r, principal) = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let r = app.database.remove_crate_owners(&principal, package, old_users).await?;
+            Ok::<_, ApiError>((r, principal.principal))
+        })
+        .await?;
+        self.send_webhook(
+            WebhookEvent::new(WebhookEventKind::OwnersRemove, package, None, &principal).with_users(old_users.to_vec()),
+        );
+        Ok(r)
+    }
+
+    /// Deletes a crate with all its versions, removing it from the index
+    /// `confirm` must be the name of the crate, as a safeguard
+    pub async fn delete_crate(&self, auth_data: &AuthData, package: &str, confirm: &str) -> Result<(), ApiError> {
+        if confirm != package {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the deletion must be confirmed with the name of the crate"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        // the index is locked before the transaction is opened, as for publications, so that they never wait on each other
+        let index_guard = self.index.lock().await;
+        let index = &index_guard;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
-            app.database.regen_crate_version_doc(&principal, package, version).await?;
-            let targets = app.database.get_crate_targets(package).await?;
-            self.docs_worker_sender
-                .clone()
-                .send(JobCrate {
-                    name: package.to_string(),
-                    version: version.to_string(),
-                    targets,
-                })
-                .await?;
-            Ok(())
+            app.database.delete_crate(&principal, package).await?;
+            // the database changes are rolled back if the crate cannot be removed from the index
+            index.remove_crate(package).await
+        })
+        .await?;
+        drop(index_guard);
+        if let Err(e) = self.get_service_storage().delete_crate_files(package).await {
+            error!("failed to delete the stored files for deleted crate {package}: {e}");
+        }
+        Ok(())
+    }
+
+    /// Deletes a version of a crate, removing it from the index
+    /// The crate itself is deleted when this is its only version.
+    /// `confirm` must be the version, as a safeguard
+    pub async fn delete_crate_version(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        confirm: &str,
+    ) -> Result<(), ApiError> {
+        if confirm != version {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the deletion must be confirmed with the version"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        // the index is locked before the transaction is opened, as for publications, so that they never wait on each other
+        // holding the lock on the index ensures that concurrent reads see the index either before or after the change
+        let index_guard = self.index.lock().await;
+        let index = &index_guard;
+        // the entries of the crate in the index before the removal, kept to restore them if the transaction cannot be committed
+        let removed = Mutex::new(None);
+        let removed_ref = &removed;
+        let result = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            let whole_crate = app.database.delete_crate_version(&principal, package, version).await?;
+            // the database changes are rolled back if the version cannot be removed from the index
+            let previous = index.get_crate_data(package).await?;
+            if whole_crate {
+                index.remove_crate(package).await?;
+            } else {
+                index.remove_crate_version(package, version).await?;
+            }
+            *removed_ref.lock().await = Some(previous);
+            Ok::<_, ApiError>(whole_crate)
+        })
+        .await;
+        let whole_crate = match result {
+            Ok(whole_crate) => whole_crate,
+            Err(e) => {
+                if let Some(previous) = removed.into_inner() {
+                    // the version is already removed from the index but the transaction could not be committed
+                    let restored = async {
+                        index.rewrite_crate(package, &previous).await?;
+                        index.commit_changes(&format!("Restore {package}:{version}")).await
+                    }
+                    .await;
+                    if let Err(restore_error) = restored {
+                        error!("failed to restore {package} {version} in the index: {restore_error}");
+                    }
+                }
+                return Err(e);
+            }
+        };
+        drop(index_guard);
+        let storage = self.get_service_storage();
+        let result = if whole_crate {
+            storage.delete_crate_files(package).await
+        } else {
+            storage.delete_crate_version_files(package, version).await
+        };
+        if let Err(e) = result {
+            error!("failed to delete the stored files for deleted crate {package} {version}: {e}");
+        }
+        Ok(())
+    }
+
+    /// Checks that a crate was not deliberately deleted, yielding a `410 Gone` error when it was
+    pub async fn check_crate_not_deleted(&self, package: &str) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            app.database.check_crate_not_deleted(package, None).await
         })
         .await
     }
 
-    /// Gets all the packages that are outdated while also being the latest version
-    pub async fn get_crates_outdated_heads(&self, auth_data: &AuthData) -> Result<Vec<CrateAndVersion>, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+    /// Collects the stored files of the crates and versions that were deleted
+    /// In a dry run, the files that would be collected are only listed
+    pub async fn collect_storage_garbage(&self, auth_data: &AuthData, dry_run: bool) -> Result<StorageGcSummary, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let _principal = app.authenticate(auth_data).await?;
-            app.database.get_crates_outdated_heads().await
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await?;
+            Ok::<_, ApiError>(())
+        })
+        .await?;
+        crate::services::gc::collect_storage_garbage(&self.configuration, &self.db_pool, &self.index, dry_run).await
+    }
+
+    /// Rebuilds the index for all crates from the content in the storage
+    /// A crate with a version that cannot be re-indexed is left untouched in the index and reported.
+    /// Depending on the configuration, the operation then either continues with the other crates or stops.
+    pub async fn reindex_from_storage(&self, auth_data: &AuthData) -> Result<ReindexSummary, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let versions = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await?;
+            app.database.get_all_crate_versions().await
+        })
+        .await?;
+        let storage = self.get_service_storage();
+        let index = self.index.lock().await;
+        let mut summary = ReindexSummary::default();
+        for versions in versions.chunk_by(|(a, _), (b, _)| a.name == b.name) {
+            let package = &versions[0].0.name;
+            let mut entries = Vec::with_capacity(versions.len());
+            let mut failures = Vec::new();
+            for (version, yanked) in versions {
+                match Self::build_index_entry(storage.as_ref(), version, *yanked, &self.configuration.index).await {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => {
+                        warn!("failed to re-index {} {}: {e}", version.name, version.version);
+                        failures.push(ReindexFailure {
+                            name: version.name.clone(),
+                            version: version.version.clone(),
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+            if failures.is_empty() {
+                index.rewrite_crate(package, &entries).await?;
+                summary.crates += 1;
+                summary.versions += entries.len();
+            } else {
+                summary.failures.append(&mut failures);
+                if self.configuration.reindex_halt_on_failure {
+                    summary.halted = true;
+                    break;
+                }
+            }
+        }
+        if summary.crates > 0 {
+            index.commit_changes("Re-index from storage").await?;
+        }
+        info!(
+            "re-indexed {} crates ({} versions), {} failures",
+            summary.crates,
+            summary.versions,
+            summary.failures.len()
+        );
+        Ok(summary)
+    }
+
+    /// Checks that the database, the storage and the index agree on the versions of a crate
+    /// Each known version must be in the database, have an archive in the storage and a line in the index, with the same yank flag.
+    pub async fn verify_crate_index(&self, auth_data: &AuthData, package: &str) -> Result<CrateConsistencyReport, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let in_database = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_ownership(&principal, package).await?;
+            app.database.get_crate_versions_sorted(package).await
+        })
+        .await?;
+        let in_storage = self.get_service_storage().list_crate_versions(package).await?;
+        let in_index = match self.index.lock().await.get_crate_data(package).await {
+            Ok(entries) => entries,
+            Err(e) if e.http == 404 => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut versions = in_database
+            .iter()
+            .map(|v| v.version.clone())
+            .chain(in_storage.iter().cloned())
+            .chain(in_index.iter().map(|entry| entry.vers.clone()))
+            .collect::<Vec<_>>();
+        versions.sort_by(|a, b| match (a.parse::<Version>(), b.parse::<Version>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        });
+        versions.dedup();
+        let versions = versions
+            .into_iter()
+            .map(|version| {
+                let yanked_in_database = in_database.iter().find(|v| v.version == version).map(|v| v.yanked);
+                let yanked_in_index = in_index.iter().find(|entry| entry.vers == version).map(|entry| entry.yanked);
+                let in_storage = in_storage.contains(&version);
+                let mut problems = Vec::new();
+                if yanked_in_database.is_none() {
+                    problems.push(String::from("missing in the database"));
+                }
+                if !in_storage {
+                    problems.push(String::from("missing archive in the storage"));
+                }
+                if yanked_in_index.is_none() {
+                    problems.push(String::from("missing line in the index"));
+                }
+                if let (Some(database), Some(index)) = (yanked_in_database, yanked_in_index) {
+                    if database != index {
+                        problems.push(format!("yanked is {database} in the database but {index} in the index"));
+                    }
+                }
+                CrateVersionConsistency {
+                    version,
+                    in_database: yanked_in_database.is_some(),
+                    in_storage,
+                    in_index: yanked_in_index.is_some(),
+                    yanked_in_database,
+                    yanked_in_index,
+                    problems,
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(CrateConsistencyReport {
+            package: package.to_string(),
+            consistent: versions.iter().all(|v| v.problems.is_empty()),
+            versions,
+        })
+    }
+
+    /// Builds the index entry for a crate version from its content in the storage
+    async fn build_index_entry(
+        storage: &dyn Storage,
+        version: &CrateAndVersion,
+        yanked: bool,
+        config: &IndexConfig,
+    ) -> Result<IndexCrateMetadata, ApiError> {
+        let content = storage.download_crate(&version.name, &version.version).await?;
+        check_crate_archive(&content)?;
+        let metadata = storage
+            .download_crate_metadata(&version.name, &version.version)
+            .await?
+            .ok_or_else(|| specialize(error_not_found(), String::from("missing metadata in the storage")))?;
+        let mut entry = CrateUploadData { metadata, content }.build_index_data(config.duplicate_deps, config.strip_dev_deps)?;
+        entry.yanked = yanked;
+        Ok(entry)
+    }
+
+    /// Gets the crates that have no active owner
+    pub async fn get_orphaned_crates(&self, auth_data: &AuthData) -> Result<Vec<String>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.get_orphaned_crates(&principal).await
         })
         .await
     }
 
-    /// Gets the download statistics for a crate
-    pub async fn get_crate_dl_stats(&self, auth_data: &AuthData, package: &str) -> Result<DownloadStats, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+    /// Claims the ownership of a crate that has no active owner
+    pub async fn claim_orphaned_crate(&self, auth_data: &AuthData, package: &str) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let _principal = app.authenticate(auth_data).await?;
-            app.database.get_crate_dl_stats(package).await
+            let principal = app.authenticate(auth_data).await?;
+            app.database.claim_orphaned_crate(&principal, package).await
         })
         .await
     }
 
-    /// Gets the list of owners for a package
-    pub async fn get_crate_owners(&self, auth_data: &AuthData, package: &str) -> Result<OwnersQueryResult, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+    /// Re-attests the ownership of a crate by the current user
+    pub async fn attest_crate_ownership(&self, auth_data: &AuthData, package: &str) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let _principal = app.authenticate(auth_data).await?;
-            app.database.get_crate_owners(package).await
+            let principal = app.authenticate(auth_data).await?;
+            app.database.attest_crate_ownership(&principal, package).await
         })
         .await
     }
 
-    /// Add owners to a package
-    pub async fn add_crate_owners(
+    /// Gets the owners that did not re-attest their ownership within the configured interval
+    pub async fn get_stale_crate_owners(&self, auth_data: &AuthData) -> Result<Vec<StaleCrateOwner>, ApiError> {
+        let days = self.configuration.owners_attestation_days;
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            if days <= 0 {
+                // attestation is deactivated, no owner is ever flagged
+                app.database.check_can_admin(&principal).await?;
+                return Ok(Vec::new());
+            }
+            let before = Duration::try_days(days)
+                .and_then(|interval| Local::now().naive_local().checked_sub_signed(interval))
+                .unwrap_or(NaiveDateTime::MIN);
+            app.database.get_stale_crate_owners(&principal, before).await
+        })
+        .await
+    }
+
+    /// Gets the information required to manage a crate, with the permissions of the current user on it
+    pub async fn get_crate_management_info(
         &self,
         auth_data: &AuthData,
         package: &str,
-        new_users: &[String],
-    ) -> Result<YesNoMsgResult, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+    ) -> Result<CrateManagementInfo, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
-            app.database.add_crate_owners(&principal, package, new_users).await
+            app.database.check_crate_not_deleted(package, None).await?;
+            // fails when the crate does not exist
+            let targets = app.database.get_crate_targets(package).await?;
+            Ok(CrateManagementInfo {
+                owners: app.database.get_crate_owners(package).await?.users,
+                targets,
+                deps_targets: app.database.get_crate_deps_targets(package).await?,
+                docs_enabled: app.database.get_crate_docs_enabled(package).await?,
+                docs_external_url: app.database.get_crate_docs_external_url(package).await?,
+                docs_build: app.database.get_crate_docs_build_settings(package).await?,
+                permissions: app.database.get_crate_permissions(&principal, package).await?,
+            })
         })
         .await
     }
 
-    /// Remove owners from a package
-    pub async fn remove_crate_owners(
+    /// Gets the versions of a crate, sorted according to semver from the greatest
+    pub async fn get_crate_versions_sorted(
         &self,
         auth_data: &AuthData,
         package: &str,
-        old_users: &[String],
-    ) -> Result<YesNoResult, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+    ) -> Result<Vec<CrateVersionSummary>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
-            let principal = app.authenticate(auth_data).await?;
-            app.database.remove_crate_owners(&principal, package, old_users).await
+            let _principal = app.authenticate(auth_data).await?;
+            let versions = app.database.get_crate_versions_sorted(package).await?;
+            if versions.is_empty() {
+                app.database.check_crate_not_deleted(package, None).await?;
+                return Err(error_not_found());
+            }
+            Ok(versions)
         })
         .await
     }
 
     /// Gets the targets for a crate
     pub async fn get_crate_targets(&self, auth_data: &AuthData, package: &str) -> Result<Vec<String>, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
@@ -501,7 +2319,7 @@ impl Application {
 
     /// Sets the targets for a crate
     pub async fn set_crate_targets(&self, auth_data: &AuthData, package: &str, targets: &[String]) -> Result<(), ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let principal = app.authenticate(auth_data).await?;
@@ -515,9 +2333,149 @@ impl Application {
         .await
     }
 
+    /// Gets the settings for the generation of the documentation of a crate
+    pub async fn get_crate_docs_build_settings(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+    ) -> Result<CrateDocsBuildSettings, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crate_docs_build_settings(package).await
+        })
+        .await
+    }
+
+    /// Sets the settings for the generation of the documentation of a crate
+    pub async fn set_crate_docs_build_settings(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        settings: &CrateDocsBuildSettings,
+    ) -> Result<(), ApiError> {
+        settings.validate()?;
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database
+                .set_crate_docs_build_settings(&principal, package, settings)
+                .await
+        })
+        .await
+    }
+
+    /// Gets the targets specifically configured for checking the dependencies of a crate, if any
+    pub async fn get_crate_deps_targets(&self, auth_data: &AuthData, package: &str) -> Result<Option<Vec<String>>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crate_deps_targets(package).await
+        })
+        .await
+    }
+
+    /// Sets the targets specifically used for checking the dependencies of a crate
+    /// `None` resets to using the build targets
+    pub async fn set_crate_deps_targets(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        targets: Option<&[String]>,
+    ) -> Result<(), ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            for target in targets.unwrap_or_default() {
+                if !self.configuration.self_builtin_targets.contains(target) {
+                    return Err(specialize(error_invalid_request(), format!("Unknown target: {target}")));
+                }
+            }
+            app.database.set_crate_deps_targets(&principal, package, targets).await
+        })
+        .await
+    }
+
+    /// Gets the results of the verification of the last version of a crate on each target
+    pub async fn get_crate_builds(&self, auth_data: &AuthData, package: &str) -> Result<Vec<CrateVersionBuild>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            let version = app.database.get_crate_last_version(package).await?;
+            app.database.get_crate_version_builds(package, &version).await
+        })
+        .await
+    }
+
+    /// Gets a page in the listing of all crates with their latest version
+    pub async fn get_crates_listing(
+        &self,
+        auth_data: &AuthData,
+        page: Option<i64>,
+        per_page: Option<i64>,
+    ) -> Result<CratesListing, ApiError> {
+        let page = page.unwrap_or(1).max(1);
+        let per_page = per_page.unwrap_or(100).clamp(1, 1000);
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crates_listing(page, per_page).await
+        })
+        .await
+    }
+
+    /// Gets a page of the crate versions published between two dates, both inclusive
+    pub async fn get_crates_published_between(
+        &self,
+        auth_data: &AuthData,
+        from: NaiveDate,
+        to: NaiveDate,
+        page: Option<i64>,
+        per_page: Option<i64>,
+    ) -> Result<CratePublications, ApiError> {
+        if from > to {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the start of the range must not be after its end"),
+            ));
+        }
+        let from = from.and_time(NaiveTime::MIN);
+        let to = to.succ_opt().map_or(NaiveDateTime::MAX, |to| to.and_time(NaiveTime::MIN));
+        let page = page.unwrap_or(1).max(1);
+        let per_page = per_page.unwrap_or(100).clamp(1, 1000);
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.list_crates_published_between(from, to, page, per_page).await
+        })
+        .await
+    }
+
+    /// Gets the sitemap for all the crates, when enabled
+    pub async fn get_sitemap(&self, auth_data: &AuthData) -> Result<String, ApiError> {
+        if !self.configuration.web_sitemap {
+            return Err(error_not_found());
+        }
+        let mut connection = self.acquire_connection().await?;
+        let listing = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_crates_listing(1, i64::MAX).await
+        })
+        .await?;
+        Ok(listing.to_sitemap(&self.configuration.web_public_uri))
+    }
+
     /// Gets the global statistics for the registry
     pub async fn get_crates_stats(&self, auth_data: &AuthData) -> Result<GlobalStats, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
@@ -526,18 +2484,61 @@ impl Application {
         .await
     }
 
+    /// Gets the total downloads across all crates between two days (inclusive), bucketed by day or week
+    pub async fn get_global_dl_timeseries(
+        &self,
+        auth_data: &AuthData,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: DownloadsGranularity,
+    ) -> Result<GlobalDownloadsSeries, ApiError> {
+        if from > to {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the start of the range must not be after its end"),
+            ));
+        }
+        if (to - from).num_days() >= DOWNLOADS_SERIES_MAX_DAYS {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("the range must not exceed {DOWNLOADS_SERIES_MAX_DAYS} days"),
+            ));
+        }
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_global_dl_timeseries(from, to, granularity).await
+        })
+        .await
+    }
+
     /// Search for crates
     pub async fn search_crates(
         &self,
         auth_data: &AuthData,
         query: &str,
+        page: Option<usize>,
+        cursor: Option<&str>,
         per_page: Option<usize>,
+        search_readme: bool,
     ) -> Result<SearchResults, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let search_readme = search_readme && self.configuration.search_readme;
+        let cursor = cursor.map(SearchCursor::decode).transpose()?;
+        let mut connection = self.acquire_connection().await?;
         in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
-            app.database.search_crates(query, per_page).await
+            app.database
+                .search_crates(
+                    query,
+                    page,
+                    cursor.as_ref(),
+                    per_page,
+                    search_readme,
+                    self.configuration.search_popularity_half_life,
+                )
+                .await
         })
         .await
     }
@@ -549,15 +2550,159 @@ impl Application {
         package: &str,
         version: &str,
     ) -> Result<DepsAnalysis, ApiError> {
-        let mut connection: sqlx::pool::PoolConnection<Sqlite> = self.db_pool.acquire().await?;
+        let mut connection = self.acquire_connection().await?;
         let targets = in_transaction(&mut connection, |transaction| async move {
             let app = self.with_transaction(transaction);
             let _principal = app.authenticate(auth_data).await?;
             app.database.check_crate_exists(package, version).await?;
-            app.database.get_crate_targets(package).await
+            app.database.get_crate_effective_deps_targets(package).await
+        })
+        .await?;
+        let (graph, mut analysis) = self
+            .get_service_deps_checker()
+            .check_crate_graph(package, version, &targets)
+            .await?;
+        let allowed = &self.configuration.deps_allowed_licenses;
+        let denied = &self.configuration.deps_denied_licenses;
+        if !allowed.is_empty() || !denied.is_empty() {
+            let licenses = self.get_deps_local_licenses(&graph).await;
+            analysis.check_licenses(&graph, &licenses, allowed, denied);
+        }
+        Ok(analysis)
+    }
+
+    /// Gets the complete dependency graph of a local crate, as nodes and edges
+    pub async fn get_crate_dependency_graph(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+    ) -> Result<DepsGraphExport, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let targets = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            app.database.get_crate_effective_deps_targets(package).await
+        })
+        .await?;
+        self.get_service_deps_checker()
+            .export_crate_graph(package, version, &targets)
+            .await
+    }
+
+    /// Gets the license expression of the resolved dependencies hosted by this registry, by name and version
+    async fn get_deps_local_licenses(&self, graph: &DepsGraph) -> HashMap<(String, String), Option<String>> {
+        let mut licenses = HashMap::new();
+        let storage = self.get_service_storage();
+        for dep in graph.crates.iter().filter(|dep| dep.registry.is_none()) {
+            for resolution in &dep.resolutions {
+                let version = dep.versions[resolution.version_index].semver.to_string();
+                if let Ok(Some(metadata)) = storage.download_crate_metadata(&dep.name, &version).await {
+                    licenses.insert((dep.name.clone(), version), metadata.license);
+                }
+            }
+        }
+        licenses
+    }
+
+    /// Gets the crates affected by an advisory through their dependencies, with the path of dependencies that introduces it
+    /// Only the last version of the crates flagged with advisories by their last dependency analysis are checked again
+    pub async fn get_crates_affected_by_advisory(
+        &self,
+        auth_data: &AuthData,
+        advisory_id: &str,
+    ) -> Result<Vec<AdvisoryAffectedCrate>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let heads = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let principal = app.authenticate(auth_data).await?;
+            app.database.check_can_admin(&principal).await?;
+            let mut heads = Vec::new();
+            for head in app.database.get_crates_vulnerable_heads().await? {
+                let targets = app.database.get_crate_effective_deps_targets(&head.name).await?;
+                heads.push((head, targets));
+            }
+            Ok::<_, ApiError>(heads)
+        })
+        .await?;
+        let checker = self.get_service_deps_checker();
+        let mut results = Vec::new();
+        for (head, targets) in heads {
+            let (graph, analysis) = checker.check_crate_graph(&head.name, &head.version, &targets).await?;
+            for advisory in analysis.advisories {
+                if advisory.content.id == advisory_id {
+                    let path = graph.find_path(&advisory.package, &advisory.version).unwrap_or_default();
+                    results.push(AdvisoryAffectedCrate {
+                        package: head.name.clone(),
+                        version: head.version.clone(),
+                        advisory,
+                        path,
+                    });
+                }
+            }
+        }
+        results.sort_unstable_by(|a, b| a.package.cmp(&b.package));
+        Ok(results)
+    }
+
+    /// Gets the advisories found by the periodic scan against the last version of the crates
+    pub async fn get_affected_crates_advisories(&self, auth_data: &AuthData) -> Result<Vec<AffectedCrateAdvisory>, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.get_affected_crates_advisories().await
+        })
+        .await
+    }
+
+    /// Gets a named policy for the dependencies of crates
+    /// Named policies are JSON files in the `policies` folder of the data directory
+    pub async fn get_deps_policy(&self, auth_data: &AuthData, name: &str) -> Result<DepsPolicy, ApiError> {
+        let _principal = self.authenticate(auth_data).await?;
+        self.load_deps_policy(name).await
+    }
+
+    /// Loads a named policy for the dependencies of crates from the `policies` folder of the data directory
+    async fn load_deps_policy(&self, name: &str) -> Result<DepsPolicy, ApiError> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(specialize(error_invalid_request(), format!("invalid policy name {name}")));
+        }
+        let path = format!("{}/policies/{name}.json", self.configuration.data_dir);
+        let content = tokio::fs::read(&path)
+            .await
+            .map_err(|_| specialize(error_not_found(), format!("unknown policy {name}")))?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Checks the dependencies of a crate version against a policy
+    /// Licenses can only be checked for the dependencies hosted by this registry
+    pub async fn check_crate_against_policy(
+        &self,
+        auth_data: &AuthData,
+        package: &str,
+        version: &str,
+        policy: &DepsPolicy,
+    ) -> Result<DepsPolicyReport, ApiError> {
+        let mut connection = self.acquire_connection().await?;
+        let targets = in_transaction(&mut connection, |transaction| async move {
+            let app = self.with_transaction(transaction);
+            let _principal = app.authenticate(auth_data).await?;
+            app.database.check_crate_exists(package, version).await?;
+            app.database.get_crate_effective_deps_targets(package).await
         })
         .await?;
-        self.get_service_deps_checker().check_crate(package, version, &targets).await
+        let (graph, analysis) = self
+            .get_service_deps_checker()
+            .check_crate_graph(package, version, &targets)
+            .await?;
+        let licenses = if policy.denied_licenses.is_empty() {
+            HashMap::new()
+        } else {
+            self.get_deps_local_licenses(&graph).await
+        };
+        Ok(policy.check(&graph, &analysis, &licenses))
     }
 }
 
@@ -571,9 +2716,10 @@ pub struct ApplicationWithTransaction<'a, 'c> {
 
 impl<'a, 'c> ApplicationWithTransaction<'a, 'c> {
     /// Attempts the authentication of a user
+    /// Deactivated users are rejected, whether they use a token or a session cookie
     pub async fn authenticate(&self, auth_data: &AuthData) -> Result<AuthenticatedUser, ApiError> {
         if let Some(token) = &auth_data.token {
-            self.authenticate_token(token).await
+            self.authenticate_token(token, auth_data.client_ip).await
         } else {
             let authenticated_user = auth_data.try_authenticate_cookie()?.ok_or_else(error_unauthorized)?;
             self.database.check_is_user(&authenticated_user.principal).await?;
@@ -582,7 +2728,8 @@ impl<'a, 'c> ApplicationWithTransaction<'a, 'c> {
     }
 
     /// Tries to authenticate using a token
-    pub async fn authenticate_token(&self, token: &Token) -> Result<AuthenticatedUser, ApiError> {
+    /// The IP address of the client is checked against the addresses the token is bound to, if any
+    pub async fn authenticate_token(&self, token: &Token, client_ip: Option<IpAddr>) -> Result<AuthenticatedUser, ApiError> {
         if token.id == self.application.configuration.self_service_login
             && token.secret == self.application.configuration.self_service_token
         {
@@ -592,9 +2739,28 @@ impl<'a, 'c> ApplicationWithTransaction<'a, 'c> {
                 principal: self.application.configuration.self_service_login.clone(),
                 can_write: false,
                 can_admin: false,
+                token_id: None,
+                crates: Vec::new(),
             });
         }
-        let user = self.database.check_token(&token.id, &token.secret).await?;
+        let user = self.database.check_token(&token.id, &token.secret, client_ip).await?;
         Ok(user)
     }
 }
+
+/// Builds the message signed for a download link
+fn download_link_message(package: &str, version: &str, expires: i64) -> String {
+    format!("{package}/{version}/{expires}")
+}
+
+/// Rewrites the absolute links to the documentation hosted by the registry into relative ones
+/// `path` is the path of the file relative to the documentation of its crate version, which is two levels deep.
+/// Returns `None` when the file contains no such link.
+fn relocate_doc_links(content: &[u8], path: &str, registry_docs: &str) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(content).ok()?;
+    if !text.contains(registry_docs) {
+        return None;
+    }
+    let depth = 2 + path.matches('/').count();
+    Some(text.replace(registry_docs, &"../".repeat(depth)).into_bytes())
+}
@@ -8,19 +8,23 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::pin::pin;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::extract::DefaultBodyLimit;
 use axum::routing::{delete, get, patch, post, put};
-use axum::Router;
+use axum::{middleware, Router};
 use cookie::Key;
+use futures::lock::Mutex;
 use log::info;
 
 use crate::application::Application;
 use crate::routes::AxumState;
+use crate::utils::ratelimit::RateLimiter;
 use crate::utils::sigterm::waiting_sigterm;
 
 mod application;
@@ -42,15 +46,28 @@ pub const GIT_TAG: &str = env!("GIT_TAG");
 async fn main_serve_app(application: Arc<Application>, cookie_key: Key) -> Result<(), std::io::Error> {
     // web application
     let webapp_resources = webapp::get_resources();
+    let webapp_not_found = match &application.configuration.web_not_found_page {
+        Some(path) => Some(tokio::fs::read(path).await?),
+        None => None,
+    };
     let body_limit = application.configuration.web_body_limit;
     let socket_addr = SocketAddr::new(
         application.configuration.web_listenon_ip,
         application.configuration.web_listenon_port,
     );
+    let rate_limit_window = Duration::from_secs(application.configuration.web_rate_limit_window);
+    let read_limiter = RateLimiter::new(application.configuration.web_rate_limit_reads, rate_limit_window);
+    let publish_limiter = RateLimiter::new(application.configuration.web_rate_limit_publishes, rate_limit_window);
+    let index_limiter = RateLimiter::new(application.configuration.web_rate_limit_index, rate_limit_window);
     let state = Arc::new(AxumState {
         application,
         cookie_key,
         webapp_resources,
+        webapp_not_found,
+        download_semaphores: Mutex::new(HashMap::new()),
+        read_limiter,
+        publish_limiter,
+        index_limiter,
     });
     let app = Router::new()
         .route("/", get(routes::get_root))
@@ -59,64 +76,140 @@ async fn main_serve_app(application: Arc<Application>, cookie_key: Key) -> Resul
         .route("/git-upload-pack", post(routes::index_serve_git_upload_pack))
         // web resources
         .route("/favicon.png", get(routes::get_favicon))
+        .route("/sitemap.xml", get(routes::get_sitemap))
         .route("/crates/:package/:version", get(routes::get_redirection_crate_version))
         .route("/crates/:package", get(routes::get_redirection_crate))
         .route("/webapp/*path", get(routes::get_webapp_resource))
         // api version
         .route("/version", get(routes::get_version))
+        .route("/health", get(routes::get_health))
+        .route("/metrics", get(routes::get_metrics))
         // special handling for cargo login
         .route("/me", get(routes::webapp_me))
         // serve the documentation
         .route("/docs/*path", get(routes::get_docs_resource))
+        // API compatible with crates.io for ecosystem tools
+        .route(
+            "/cratesio/api/v1/crates/:package",
+            get(routes::cratesio_api_v1_get_crate_info),
+        )
         // API
         .nest(
             "/api/v1",
             Router::new()
                 .route("/me", get(routes::api_v1_get_current_user))
+                .route("/me/notifications", get(routes::api_v1_get_notification_settings))
+                .route("/me/notifications", put(routes::api_v1_set_notification_settings))
+                .route("/oauth/login", get(routes::api_v1_oauth_login))
                 .route("/oauth/code", post(routes::api_v1_login_with_oauth_code))
+                .route("/index/protocols", get(routes::api_v1_get_index_protocols))
                 .route("/logout", post(routes::api_v1_logout))
                 .nest(
                     "/tokens",
                     Router::new()
                         .route("/", get(routes::api_v1_get_tokens))
                         .route("/", put(routes::api_v1_create_token))
+                        .route("/revoke", post(routes::api_v1_revoke_tokens_matching))
+                        .route("/scope", get(routes::api_v1_get_token_publish_scope))
                         .route("/:token_id", delete(routes::api_v1_revoke_token)),
                 )
                 .nest(
                     "/users",
                     Router::new()
                         .route("/", get(routes::api_v1_get_users))
+                        .route("/import", post(routes::api_v1_import_users))
+                        .route("/verifyemail", get(routes::api_v1_verify_email))
                         .route("/:target", patch(routes::api_v1_update_user))
                         .route("/:target", delete(routes::api_v1_delete_user))
                         .route("/:target/deactivate", post(routes::api_v1_deactivate_user))
-                        .route("/:target/reactivate", post(routes::api_v1_reactivate_user)),
+                        .route("/:target/reactivate", post(routes::api_v1_reactivate_user))
+                        .route("/:target/reassign", post(routes::api_v1_reassign_user_crates))
+                        .route("/:target/verifyemail", post(routes::api_v1_send_verification_email)),
+                )
+                .nest("/audit", Router::new().route("/export", get(routes::api_v1_export_audit_log)))
+                .route("/advisories/affected", get(routes::api_v1_get_affected_crates_advisories))
+                .route(
+                    "/advisories/:advisory/crates",
+                    get(routes::api_v1_get_crates_affected_by_advisory),
                 )
+                .route("/workers", get(routes::api_v1_get_workers_status))
+                .route("/config", get(routes::api_v1_get_configuration))
+                .route("/reindex", post(routes::api_v1_reindex_from_storage))
+                .route("/storage/gc", post(routes::api_v1_collect_storage_garbage))
                 .nest(
                     "/crates",
                     Router::new()
                         .route("/", get(routes::api_v1_cargo_search))
                         .route("/stats", get(routes::api_v1_get_crates_stats))
+                        .route("/dlstats", get(routes::api_v1_get_crates_dl_stats))
+                        .route("/listing", get(routes::api_v1_get_crates_listing))
+                        .route("/published", get(routes::api_v1_get_crates_published))
                         .route("/outdated", get(routes::api_v1_get_crates_outdated_heads))
+                        .route("/outdated", post(routes::api_v1_get_outdated_for_crates))
+                        .route("/orphans", get(routes::api_v1_get_orphaned_crates))
+                        .route("/staleowners", get(routes::api_v1_get_stale_crate_owners))
                         .route("/new", put(routes::api_v1_cargo_publish_crate_version))
+                        .route("/new", post(routes::api_v1_publish_crate_version_multipart))
+                        .route("/bundle", post(routes::api_v1_download_crates_bundle))
                         .route("/:package", get(routes::api_v1_get_crate_info))
+                        .route("/:package", delete(routes::api_v1_delete_crate))
+                        .route("/:package/:version", delete(routes::api_v1_delete_crate_version))
                         .route("/:package/readme", get(routes::api_v1_get_crate_last_readme))
+                        .route("/:package/versions", get(routes::api_v1_get_crate_versions))
                         .route("/:package/:version/readme", get(routes::api_v1_get_crate_readme))
+                        .route("/:package/:version/changelog", get(routes::api_v1_get_crate_changelog))
+                        .route("/:package/:version/lockfile", get(routes::api_v1_get_crate_lockfile))
                         .route("/:package/:version/download", get(routes::api_v1_download_crate))
+                        .route(
+                            "/:package/:version/download/signed",
+                            get(routes::api_v1_download_crate_signed),
+                        )
+                        .route(
+                            "/:package/:version/downloadlink",
+                            post(routes::api_v1_create_crate_download_link),
+                        )
                         .route("/:package/:version/yank", delete(routes::api_v1_cargo_yank))
+                        .route(
+                            "/:package/:version/yankconfirm",
+                            post(routes::api_v1_confirm_crate_version_yank),
+                        )
                         .route("/:package/:version/unyank", put(routes::api_v1_cargo_unyank))
                         .route("/:package/:version/docsregen", post(routes::api_v1_regen_crate_version_doc))
+                        .route("/:package/:version/docsfiles", get(routes::api_v1_get_crate_doc_files))
+                        .route("/:package/:version/docsstatus", get(routes::api_v1_get_crate_doc_status))
+                        .route("/:package/:version/docslogs/:target", get(routes::api_v1_get_crate_doc_log))
+                        .route("/:package/:version/docsexport", post(routes::api_v1_export_crate_docs))
                         .route("/:package/:version/checkdeps", get(routes::api_v1_check_crate_version))
+                        .route("/:package/:version/depsgraph", get(routes::api_v1_get_crate_dep_graph))
+                        .route("/:package/:version/policy", post(routes::api_v1_check_crate_version_policy))
+                        .route(
+                            "/:package/:version/policy/:policy",
+                            get(routes::api_v1_check_crate_version_named_policy),
+                        )
                         .route("/:package/dlstats", get(routes::api_v1_get_crate_dl_stats))
+                        .route("/:package/dlcounts", get(routes::api_v1_get_crate_dl_counts))
+                        .route("/:package/management", get(routes::api_v1_get_crate_management_info))
+                        .route("/:package/consistency", get(routes::api_v1_verify_crate_index))
                         .route("/:package/owners", get(routes::api_v1_cargo_get_crate_owners))
                         .route("/:package/owners", put(routes::api_v1_cargo_add_crate_owners))
                         .route("/:package/owners", delete(routes::api_v1_cargo_remove_crate_owners))
+                        .route("/:package/claim", post(routes::api_v1_claim_orphaned_crate))
+                        .route("/:package/attest", post(routes::api_v1_attest_crate_ownership))
                         .route("/:package/targets", get(routes::api_v1_get_crate_targets))
-                        .route("/:package/targets", patch(routes::api_v1_set_crate_targets)),
+                        .route("/:package/targets", patch(routes::api_v1_set_crate_targets))
+                        .route("/:package/docsbuild", get(routes::api_v1_get_crate_docs_build_settings))
+                        .route("/:package/docsbuild", patch(routes::api_v1_set_crate_docs_build_settings))
+                        .route("/:package/depstargets", get(routes::api_v1_get_crate_deps_targets))
+                        .route("/:package/depstargets", patch(routes::api_v1_set_crate_deps_targets))
+                        .route("/:package/builds", get(routes::api_v1_get_crate_builds))
+                        .route("/:package/docs", patch(routes::api_v1_set_crate_docs_settings))
+                        .route("/:package/docsexternal", patch(routes::api_v1_set_crate_docs_external)),
                 ),
         )
         // fall back to serving the index
         .fallback(routes::index_serve)
         .layer(DefaultBodyLimit::max(body_limit))
+        .layer(middleware::from_fn_with_state(state.clone(), routes::rate_limit))
         .with_state(state);
     axum::serve(
         tokio::net::TcpListener::bind(socket_addr)
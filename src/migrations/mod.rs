@@ -5,8 +5,10 @@
 //! Module for the migrations of the platform database
 
 use std::ops::DerefMut;
+use std::time::Duration;
 
-use log::info;
+use chrono::Utc;
+use log::{info, warn};
 use sqlx::{Executor, SqliteConnection};
 
 use crate::utils::apierror::ApiError;
@@ -34,6 +36,118 @@ const MIGRATIONS: &[Migration<'static>] = &[
         target: "1.5.0",
         content: MigrationContent::Sql(include_bytes!("v1.5.0.sql")),
     },
+    Migration {
+        target: "1.6.0",
+        content: MigrationContent::Sql(include_bytes!("v1.6.0.sql")),
+    },
+    Migration {
+        target: "1.7.0",
+        content: MigrationContent::Sql(include_bytes!("v1.7.0.sql")),
+    },
+    Migration {
+        target: "1.8.0",
+        content: MigrationContent::Sql(include_bytes!("v1.8.0.sql")),
+    },
+    Migration {
+        target: "1.9.0",
+        content: MigrationContent::Sql(include_bytes!("v1.9.0.sql")),
+    },
+    Migration {
+        target: "1.10.0",
+        content: MigrationContent::Sql(include_bytes!("v1.10.0.sql")),
+    },
+    Migration {
+        target: "1.11.0",
+        content: MigrationContent::Sql(include_bytes!("v1.11.0.sql")),
+    },
+    Migration {
+        target: "1.12.0",
+        content: MigrationContent::Sql(include_bytes!("v1.12.0.sql")),
+    },
+    Migration {
+        target: "1.13.0",
+        content: MigrationContent::Sql(include_bytes!("v1.13.0.sql")),
+    },
+    Migration {
+        target: "1.14.0",
+        content: MigrationContent::Sql(include_bytes!("v1.14.0.sql")),
+    },
+    Migration {
+        target: "1.15.0",
+        content: MigrationContent::Sql(include_bytes!("v1.15.0.sql")),
+    },
+    Migration {
+        target: "1.16.0",
+        content: MigrationContent::Sql(include_bytes!("v1.16.0.sql")),
+    },
+    Migration {
+        target: "1.17.0",
+        content: MigrationContent::Sql(include_bytes!("v1.17.0.sql")),
+    },
+    Migration {
+        target: "1.18.0",
+        content: MigrationContent::Sql(include_bytes!("v1.18.0.sql")),
+    },
+    Migration {
+        target: "1.19.0",
+        content: MigrationContent::Sql(include_bytes!("v1.19.0.sql")),
+    },
+    Migration {
+        target: "1.20.0",
+        content: MigrationContent::Sql(include_bytes!("v1.20.0.sql")),
+    },
+    Migration {
+        target: "1.21.0",
+        content: MigrationContent::Sql(include_bytes!("v1.21.0.sql")),
+    },
+    Migration {
+        target: "1.22.0",
+        content: MigrationContent::Sql(include_bytes!("v1.22.0.sql")),
+    },
+    Migration {
+        target: "1.23.0",
+        content: MigrationContent::Sql(include_bytes!("v1.23.0.sql")),
+    },
+    Migration {
+        target: "1.24.0",
+        content: MigrationContent::Sql(include_bytes!("v1.24.0.sql")),
+    },
+    Migration {
+        target: "1.25.0",
+        content: MigrationContent::Sql(include_bytes!("v1.25.0.sql")),
+    },
+    Migration {
+        target: "1.26.0",
+        content: MigrationContent::Sql(include_bytes!("v1.26.0.sql")),
+    },
+    Migration {
+        target: "1.27.0",
+        content: MigrationContent::Sql(include_bytes!("v1.27.0.sql")),
+    },
+    Migration {
+        target: "1.28.0",
+        content: MigrationContent::Sql(include_bytes!("v1.28.0.sql")),
+    },
+    Migration {
+        target: "1.29.0",
+        content: MigrationContent::Sql(include_bytes!("v1.29.0.sql")),
+    },
+    Migration {
+        target: "1.30.0",
+        content: MigrationContent::Sql(include_bytes!("v1.30.0.sql")),
+    },
+    Migration {
+        target: "1.31.0",
+        content: MigrationContent::Sql(include_bytes!("v1.31.0.sql")),
+    },
+    Migration {
+        target: "1.32.0",
+        content: MigrationContent::Sql(include_bytes!("v1.32.0.sql")),
+    },
+    Migration {
+        target: "1.33.0",
+        content: MigrationContent::Sql(include_bytes!("v1.33.0.sql")),
+    },
 ];
 
 /// Gets the value for the metadata item
@@ -141,8 +255,73 @@ async fn migrate_db(connection: &mut SqliteConnection, migrations: &[Migration<'
     Ok(())
 }
 
+/// The name of the metadata item used as a lock so that a single instance migrates the database at a time
+const SCHEMA_METADATA_MIGRATION_LOCK: &str = "migration-lock";
+
+/// The interval between two attempts at acquiring the migration lock
+const MIGRATION_LOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Acquires the lock for migrating the database, waiting for another instance holding it to release it
+/// A lock held for more than `timeout` seconds is deemed abandoned by a crashed instance and taken over
+///
+/// # Errors
+///
+/// Return a `MigrationError` when the connection fail
+async fn acquire_migration_lock(connection: &mut SqliteConnection, timeout: u64) -> Result<String, MigrationError> {
+    let owner = uuid::Uuid::new_v4();
+    loop {
+        let now = Utc::now().timestamp();
+        let value = format!("{owner}@{now}");
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO SchemaMetadata (name, value) VALUES ($1, $2)",
+            SCHEMA_METADATA_MIGRATION_LOCK,
+            value
+        )
+        .execute(&mut *connection)
+        .await?;
+        if result.rows_affected() == 1 {
+            return Ok(value);
+        }
+        if let Some(current) = get_schema_metadata(connection, SCHEMA_METADATA_MIGRATION_LOCK).await? {
+            let acquired_at = current
+                .rsplit_once('@')
+                .and_then(|(_, timestamp)| timestamp.parse::<i64>().ok())
+                .unwrap_or_default();
+            if u64::try_from(now - acquired_at).is_ok_and(|elapsed| elapsed > timeout) {
+                warn!("Taking over the abandoned migration lock {current}");
+                release_migration_lock(connection, &current).await?;
+                continue;
+            }
+        }
+        info!("Waiting for another instance to migrate the database ...");
+        tokio::time::sleep(MIGRATION_LOCK_POLL_INTERVAL).await;
+    }
+}
+
+/// Releases the lock for migrating the database
+///
+/// # Errors
+///
+/// Return a `sqlx::Error` when the connection fail
+async fn release_migration_lock(connection: &mut SqliteConnection, lock: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM SchemaMetadata WHERE name = $1 AND value = $2",
+        SCHEMA_METADATA_MIGRATION_LOCK,
+        lock
+    )
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
 /// Migrate to the last version
-pub async fn migrate_to_last(connection: &mut SqliteConnection) -> Result<i32, ApiError> {
-    migrate_db(connection, MIGRATIONS).await?;
+/// When several instances start at the same time, only one migrates the database while the others wait for it.
+/// `lock_timeout` is the number of seconds after which the lock of another instance is deemed abandoned.
+pub async fn migrate_to_last(connection: &mut SqliteConnection, lock_timeout: u64) -> Result<i32, ApiError> {
+    connection.execute(CREATE_METADATA_TABLE_SQL).await?;
+    let lock = acquire_migration_lock(connection, lock_timeout).await?;
+    let result = migrate_db(connection, MIGRATIONS).await;
+    release_migration_lock(connection, &lock).await?;
+    result?;
     Ok(0)
 }
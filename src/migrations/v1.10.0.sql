@@ -0,0 +1,2 @@
+ALTER TABLE Package
+    ADD COLUMN depsTargets TEXT DEFAULT NULL;
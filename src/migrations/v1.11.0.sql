@@ -0,0 +1,5 @@
+ALTER TABLE PackageOwner
+    ADD COLUMN lastAttestation TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+-- existing owners start with a fresh attestation
+UPDATE PackageOwner SET lastAttestation = datetime('now', 'localtime');
@@ -0,0 +1,4 @@
+CREATE TABLE DownloadDay (
+    day DATE NOT NULL PRIMARY KEY,
+    downloadCount INTEGER NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE Package ADD COLUMN repoStars INTEGER;
+ALTER TABLE Package ADD COLUMN repoLastActivity TIMESTAMP;
+ALTER TABLE Package ADD COLUMN repoLastCheck TIMESTAMP;
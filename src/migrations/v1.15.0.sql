@@ -0,0 +1,3 @@
+ALTER TABLE RegistryUser ADD COLUMN emailVerified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE RegistryUser ADD COLUMN emailVerificationToken TEXT;
+ALTER TABLE RegistryUser ADD COLUMN emailVerificationExpires TIMESTAMP;
@@ -0,0 +1 @@
+CREATE VIRTUAL TABLE PackageReadmeSearch USING fts5(package UNINDEXED, content);
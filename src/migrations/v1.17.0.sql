@@ -0,0 +1,8 @@
+CREATE TABLE PackageTombstone (
+    id INTEGER PRIMARY KEY,
+    package TEXT NOT NULL,
+    version TEXT,
+    deletedOn TIMESTAMP NOT NULL
+);
+
+CREATE INDEX PackageTombstoneIndex ON PackageTombstone(package);
@@ -0,0 +1 @@
+ALTER TABLE RegistryUserToken ADD COLUMN expiresAt TIMESTAMP;
@@ -0,0 +1,7 @@
+CREATE TABLE RegistryUserTokenScope (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    token INTEGER NOT NULL REFERENCES RegistryUserToken(id),
+    pattern TEXT NOT NULL
+);
+
+CREATE INDEX IndexRegistryUserTokenScope ON RegistryUserTokenScope (token);
@@ -0,0 +1 @@
+CREATE INDEX IndexRegistryUserTokenLastUsed ON RegistryUserToken (lastUsed);
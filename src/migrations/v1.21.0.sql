@@ -0,0 +1,10 @@
+CREATE TABLE PackageVersionDocs (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    target TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    lastUpdate TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IndexPackageVersionDocs ON PackageVersionDocs(package, version);
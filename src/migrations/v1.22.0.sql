@@ -0,0 +1,2 @@
+ALTER TABLE RegistryUser ADD COLUMN publishNotifications TEXT NOT NULL DEFAULT 'none';
+ALTER TABLE RegistryUser ADD COLUMN lastPublishDigest TIMESTAMP;
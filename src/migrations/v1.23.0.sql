@@ -0,0 +1,5 @@
+ALTER TABLE PackageVersion ADD COLUMN docsStatus TEXT NOT NULL DEFAULT 'queued';
+ALTER TABLE PackageVersion ADD COLUMN docsStatusUpdate TIMESTAMP;
+UPDATE PackageVersion SET docsStatus = 'success' WHERE hasDocs = TRUE;
+UPDATE PackageVersion SET docsStatus = 'failed' WHERE hasDocs = FALSE AND docGenAttempted = TRUE;
+UPDATE PackageVersion SET docsStatusUpdate = upload;
@@ -0,0 +1,3 @@
+ALTER TABLE Package ADD COLUMN docsFeatures TEXT;
+ALTER TABLE Package ADD COLUMN docsNoDefaultFeatures BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE Package ADD COLUMN docsRustdocFlags TEXT NOT NULL DEFAULT '';
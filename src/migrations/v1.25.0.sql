@@ -0,0 +1,20 @@
+CREATE TABLE PackageAdvisoryScan (
+    package TEXT NOT NULL PRIMARY KEY REFERENCES Package(name),
+    version TEXT NOT NULL,
+    targets TEXT NOT NULL,
+    dependencies TEXT NOT NULL,
+    lastScan TIMESTAMP NOT NULL
+);
+
+CREATE TABLE PackageAdvisory (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    advisory TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    dependency TEXT NOT NULL,
+    dependencyVersion TEXT NOT NULL,
+    detectedOn TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IndexPackageAdvisory ON PackageAdvisory(package);
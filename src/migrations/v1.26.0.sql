@@ -0,0 +1,2 @@
+ALTER TABLE Package ADD COLUMN docsVariants TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE PackageVersionDocs ADD COLUMN variant TEXT;
@@ -0,0 +1,10 @@
+ALTER TABLE RegistryUser ADD COLUMN advisoryNotifications BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE PackageAdvisoryNotification (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    advisory TEXT NOT NULL,
+    sentOn TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX IndexPackageAdvisoryNotification ON PackageAdvisoryNotification(package, advisory);
@@ -0,0 +1 @@
+ALTER TABLE PackageVersion ADD COLUMN uploadUserAgent TEXT;
@@ -0,0 +1 @@
+ALTER TABLE Package ADD COLUMN docsExternalUrl TEXT;
@@ -0,0 +1,7 @@
+CREATE VIRTUAL TABLE PackageSearch USING fts5(package UNINDEXED, name, description, keywords);
+
+INSERT INTO PackageSearch (package, name, description, keywords)
+    SELECT Package.name, Package.name, PackageVersion.description, ''
+    FROM Package INNER JOIN PackageVersion ON PackageVersion.id = (
+        SELECT id FROM PackageVersion WHERE package = Package.name ORDER BY id DESC LIMIT 1
+    );
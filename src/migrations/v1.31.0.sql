@@ -0,0 +1,10 @@
+CREATE TABLE ProxiedCrateVersion (
+    id INTEGER PRIMARY KEY,
+    package TEXT NOT NULL,
+    version TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    cachedOn TIMESTAMP NOT NULL,
+    downloadCount INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE UNIQUE INDEX ProxiedCrateVersionIndex ON ProxiedCrateVersion(package, version);
@@ -0,0 +1 @@
+CREATE UNIQUE INDEX IndexRegistryUserTokenSecret ON RegistryUserToken(token);
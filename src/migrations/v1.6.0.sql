@@ -0,0 +1,12 @@
+CREATE TABLE AuditLog (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    principal TEXT NOT NULL,
+    action TEXT NOT NULL,
+    package TEXT,
+    version TEXT,
+    details TEXT NOT NULL,
+    exported BOOLEAN NOT NULL
+);
+
+CREATE INDEX IndexAuditLogByTimestamp ON AuditLog(timestamp);
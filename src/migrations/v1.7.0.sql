@@ -0,0 +1,11 @@
+CREATE TABLE PackageVersionBuild (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    target TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    message TEXT NOT NULL,
+    lastCheck TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IndexPackageVersionBuild ON PackageVersionBuild(package, version);
@@ -0,0 +1,2 @@
+ALTER TABLE Package
+    ADD COLUMN docsEnabled BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,5 @@
+ALTER TABLE RegistryUserToken
+    ADD COLUMN created TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+-- the creation date of existing tokens is unknown, approximate it with their last use
+UPDATE RegistryUserToken SET created = lastUsed;
@@ -0,0 +1,27 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for the audit log
+
+use chrono::NaiveDateTime;
+use serde_derive::{Deserialize, Serialize};
+
+/// An entry in the audit log
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditLogEntry {
+    /// The unique identifier of the entry
+    pub id: i64,
+    /// The timestamp for the action
+    pub timestamp: NaiveDateTime,
+    /// The principal (email of the user) that performed the action
+    pub principal: String,
+    /// The kind of action
+    pub action: String,
+    /// The name of the impacted crate, if any
+    pub package: Option<String>,
+    /// The impacted version of the crate, if any
+    pub version: Option<String>,
+    /// Additional details about the action
+    pub details: String,
+}
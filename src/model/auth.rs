@@ -4,9 +4,18 @@
 
 //! Objects related to authentication
 
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
+use data_encoding::BASE64URL_NOPAD;
 use serde_derive::{Deserialize, Serialize};
 
+use super::cargo::RegistryUser;
+use crate::utils::apierror::{error_forbidden, specialize, ApiError};
+
+/// The principal recorded in the audit log for the actions performed by the registry itself
+pub const SYSTEM_PRINCIPAL: &str = "registry";
+
 /// Represents the possible access for an authenticated user
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AuthenticatedUser {
@@ -20,6 +29,59 @@ pub struct AuthenticatedUser {
     /// Whether administration can be done
     #[serde(rename = "canAdmin")]
     pub can_admin: bool,
+    /// The identifier of the token used for the authentication, if any
+    #[serde(rename = "tokenId", default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<i64>,
+    /// The patterns for the names of the crates that the token used for the authentication is limited to
+    /// An empty list means all crates
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crates: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    /// Gets whether this authentication is allowed to act on a crate
+    #[must_use]
+    pub fn is_crate_in_scope(&self, package: &str) -> bool {
+        self.crates.is_empty() || self.crates.iter().any(|pattern| crate_pattern_matches(pattern, package))
+    }
+
+    /// Checks that this authentication is allowed to act on a crate
+    ///
+    /// # Errors
+    ///
+    /// Returns a 403 error when the token is limited to other crates
+    pub fn check_crate_in_scope(&self, package: &str) -> Result<(), ApiError> {
+        if self.is_crate_in_scope(package) {
+            Ok(())
+        } else {
+            Err(specialize(
+                error_forbidden(),
+                format!("this token is not allowed to act on crate {package}"),
+            ))
+        }
+    }
+}
+
+/// Gets whether the name of a crate matches a pattern, where `*` matches any sequence of characters
+/// The comparison is case-insensitive, as for the names of crates
+fn crate_pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    let Some(mut remaining) = name.strip_prefix(first) else {
+        return false;
+    };
+    for middle in &parts[1..parts.len() - 1] {
+        match remaining.find(middle) {
+            Some(index) => remaining = &remaining[index + middle.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
 }
 
 /// A token for a registry user
@@ -38,6 +100,19 @@ pub struct RegistryUserToken {
     /// Whether administration can be done using this token through the API
     #[serde(rename = "canAdmin")]
     pub can_admin: bool,
+    /// The creation date time
+    pub created: NaiveDateTime,
+    /// The date time after which the token is no longer accepted, if any
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<NaiveDateTime>,
+    /// Whether the token has expired
+    pub expired: bool,
+    /// The patterns for the names of the crates the token is limited to, empty for all crates
+    #[serde(default)]
+    pub crates: Vec<String>,
+    /// The IP addresses or CIDR blocks the token can be used from, empty for all
+    #[serde(rename = "allowedIps", default)]
+    pub allowed_ips: Vec<String>,
 }
 
 /// A token for a registry user
@@ -58,6 +133,191 @@ pub struct RegistryUserTokenWithSecret {
     /// Whether administration can be done using this token through the API
     #[serde(rename = "canAdmin")]
     pub can_admin: bool,
+    /// The date time after which the token is no longer accepted, if any
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<NaiveDateTime>,
+    /// The patterns for the names of the crates the token is limited to, empty for all crates
+    #[serde(default)]
+    pub crates: Vec<String>,
+    /// The IP addresses or CIDR blocks the token can be used from, empty for all
+    #[serde(rename = "allowedIps", default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// The restrictions on the use of a new token
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TokenRestrictions {
+    /// The patterns for the names of the crates the token is limited to, empty for all crates
+    #[serde(default)]
+    pub crates: Vec<String>,
+    /// The IP addresses or CIDR blocks the token can be used from, empty for all
+    #[serde(rename = "allowedIps", default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// The criteria to select the tokens to revoke in bulk, a token must match all the specified criteria
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokensRevocationCriteria {
+    /// Selects the tokens last used before this date time
+    #[serde(rename = "lastUsedBefore")]
+    pub last_used_before: Option<NaiveDateTime>,
+    /// Selects the tokens created before this date time
+    #[serde(rename = "createdBefore")]
+    pub created_before: Option<NaiveDateTime>,
+    /// Selects the tokens that can, or cannot, upload crates
+    #[serde(rename = "canWrite")]
+    pub can_write: Option<bool>,
+    /// Selects the tokens that can, or cannot, be used for administration
+    #[serde(rename = "canAdmin")]
+    pub can_admin: Option<bool>,
+    /// Whether to only list the matching tokens without revoking them
+    #[serde(rename = "dryRun", default)]
+    pub dry_run: bool,
+}
+
+impl TokensRevocationCriteria {
+    /// Gets whether no criteria is specified, in which case all tokens would match
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.last_used_before.is_none() && self.created_before.is_none() && self.can_write.is_none() && self.can_admin.is_none()
+    }
+}
+
+/// A token matched by a bulk revocation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevokedToken {
+    /// The login of the user owning the token
+    pub owner: String,
+    /// The token
+    #[serde(flatten)]
+    pub token: RegistryUserToken,
+}
+
+/// The result of a bulk revocation of tokens
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokensRevocationResult {
+    /// Whether this was a dry run, in which case the tokens were not revoked
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    /// The number of matching tokens
+    pub count: usize,
+    /// The matching tokens
+    pub tokens: Vec<RevokedToken>,
+}
+
+/// The crates that the current authentication, usually a token, may publish to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenPublishScope {
+    /// Whether the authentication allows publishing at all
+    #[serde(rename = "canWrite")]
+    pub can_write: bool,
+    /// Whether the authentication is restricted to crates matching the patterns of the token
+    /// When not restricted, new crates can also be published
+    pub restricted: bool,
+    /// The existing crates that new versions can be published for
+    pub crates: Vec<String>,
+}
+
+/// How a user is notified of the publication of new versions for the crates it owns
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishNotifications {
+    /// No notification
+    #[default]
+    None,
+    /// An email for each publication
+    Immediate,
+    /// A daily email summarizing the publications
+    Digest,
+}
+
+impl PublishNotifications {
+    /// Gets the name of this option, as stored in the database
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Immediate => "immediate",
+            Self::Digest => "digest",
+        }
+    }
+}
+
+impl FromStr for PublishNotifications {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "immediate" => Ok(Self::Immediate),
+            "digest" => Ok(Self::Digest),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The preferences of a user for the notifications sent by email
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct UserNotificationSettings {
+    /// How the user is notified of publications for the crates it owns
+    pub publish: PublishNotifications,
+    /// Whether the user is notified when the crates it owns are affected by new advisories
+    #[serde(default = "UserNotificationSettings::default_advisories")]
+    pub advisories: bool,
+}
+
+impl UserNotificationSettings {
+    /// Users are notified of advisories unless they opt out
+    fn default_advisories() -> bool {
+        true
+    }
+}
+
+impl Default for UserNotificationSettings {
+    fn default() -> Self {
+        Self {
+            publish: PublishNotifications::default(),
+            advisories: Self::default_advisories(),
+        }
+    }
+}
+
+/// A user due for a digest of the publications for the crates it owns
+#[derive(Debug, Clone)]
+pub struct PublishDigestRecipient {
+    /// The identifier of the user
+    pub uid: i64,
+    /// The email of the user
+    pub email: String,
+    /// The time of the last digest, the new one starts there
+    pub since: NaiveDateTime,
+}
+
+/// The outcome of the import of a user
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserImportStatus {
+    /// The user was created
+    Created,
+    /// A user with the same email already exists, it was left untouched
+    Duplicate,
+    /// The data for the user are invalid
+    Invalid,
+}
+
+/// The result of the import of a user
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserImportResult {
+    /// The email of the imported user
+    pub email: String,
+    /// The outcome of the import
+    pub status: UserImportStatus,
+    /// The created user, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<RegistryUser>,
+    /// The reason why the user was not created, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 /// An OAuth access token
@@ -73,6 +333,28 @@ pub struct OAuthToken {
     pub refresh_token: Option<String>,
     /// The grant scope
     pub scope: Option<String>,
+    /// The identity token, if any
+    pub id_token: Option<String>,
+}
+
+impl OAuthToken {
+    /// Gets the nonce claim within the identity token, if any
+    #[must_use]
+    pub fn get_id_token_nonce(&self) -> Option<String> {
+        let payload = self.id_token.as_ref()?.split('.').nth(1)?;
+        let payload = BASE64URL_NOPAD.decode(payload.trim_end_matches('=').as_bytes()).ok()?;
+        let claims = serde_json::from_slice::<serde_json::Value>(&payload).ok()?;
+        claims.get("nonce")?.as_str().map(str::to_string)
+    }
+}
+
+/// The state of an OAuth login flow, kept by the client in a private cookie until the callback
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OAuthLoginState {
+    /// The state passed to the identity provider, expected back on the callback
+    pub state: String,
+    /// The nonce passed to the identity provider, expected in the identity token
+    pub nonce: String,
 }
 
 /// Finds a field in a JSON blob
@@ -8,11 +8,14 @@ use std::collections::HashMap;
 use std::io::Cursor;
 use std::str::FromStr;
 
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
 use byteorder::{LittleEndian, ReadBytesExt};
 use serde_derive::{Deserialize, Serialize};
 
-use crate::utils::apierror::{error_invalid_request, specialize, ApiError};
+use crate::utils::apierror::{error_invalid_request, error_validation, specialize, ApiError, ApiErrorItem};
 use crate::utils::hashes::sha256;
+use crate::utils::push_if_not_present;
 
 /// A crate to appear in search results
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,6 +33,68 @@ pub struct SearchResultCrate {
 pub struct SearchResultsMeta {
     /// Total number of results available on the server
     pub total: usize,
+    /// The index of the returned page of results, starting at 1
+    #[serde(default)]
+    pub page: usize,
+    /// The maximum number of results per page
+    #[serde(default)]
+    pub per_page: usize,
+    /// The index of the next page of results, if there are more results than returned
+    #[serde(default)]
+    pub next_page: Option<usize>,
+    /// The opaque cursor to get the next results, if there are more results than returned
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// The position after the last crate in a page of search results, exchanged as an opaque cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchCursor {
+    /// The number of results before the position
+    pub offset: usize,
+    /// The name of the last crate before the position
+    pub last: String,
+}
+
+impl SearchCursor {
+    /// Encodes this cursor as an opaque token
+    #[must_use]
+    pub fn encode(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(format!("{}:{}", self.offset, self.last))
+    }
+
+    /// Decodes a cursor from an opaque token
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the token is not a valid cursor
+    pub fn decode(token: &str) -> Result<Self, ApiError> {
+        BASE64_URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|value| {
+                let (offset, last) = value.split_once(':')?;
+                Some(Self {
+                    offset: offset.parse().ok()?,
+                    last: last.to_string(),
+                })
+            })
+            .ok_or_else(|| specialize(error_invalid_request(), String::from("invalid cursor for the search results")))
+    }
+
+    /// Gets the number of results to skip in the current results in order to resume after the position
+    /// The position follows the last crate when results were added or removed before it in the meantime.
+    #[must_use]
+    pub fn resolve(&self, names: &[String]) -> usize {
+        if self.offset > 0 && names.get(self.offset - 1) == Some(&self.last) {
+            return self.offset;
+        }
+        names
+            .iter()
+            .position(|name| name == &self.last)
+            .map_or(self.offset, |index| index + 1)
+    }
 }
 
 /// The search results for crates
@@ -107,6 +172,16 @@ pub struct OwnersChangeQuery {
     pub users: Vec<String>,
 }
 
+/// The query for reassigning all the crates owned by a user to a successor
+#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+pub struct UserCratesReassignment {
+    /// The login of the successor
+    pub successor: String,
+    /// Whether to remove the former owner from the crates
+    #[serde(rename = "removeFormer", default)]
+    pub remove_former: bool,
+}
+
 /// A user for the registry
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RegistryUser {
@@ -187,39 +262,162 @@ pub struct CrateMetadata {
 
 impl CrateMetadata {
     /// Validate the crate's metadata
+    /// All the violations are aggregated into a single error
     pub fn validate(&self) -> Result<CrateUploadResult, ApiError> {
-        self.validate_name()?;
-        Ok(CrateUploadResult::default())
+        let mut errors = Vec::new();
+        self.validate_name(&mut errors);
+        self.validate_version(&mut errors);
+        self.validate_deps(&mut errors);
+        self.validate_keywords(&mut errors);
+        self.validate_categories(&mut errors);
+        if errors.is_empty() {
+            Ok(CrateUploadResult::default())
+        } else {
+            Err(error_validation(errors))
+        }
     }
 
     /// Validates the package name
-    fn validate_name(&self) -> Result<(), ApiError> {
+    fn validate_name(&self, errors: &mut Vec<ApiErrorItem>) {
         if self.name.is_empty() {
-            return validation_error("Name must not be empty");
+            errors.push(ApiErrorItem::new("name-empty", String::from("Name must not be empty")));
+            return;
         }
         if self.name.len() > 64 {
-            return validation_error("Name must not exceed 64 characters");
+            errors.push(ApiErrorItem::new(
+                "name-too-long",
+                String::from("Name must not exceed 64 characters"),
+            ));
         }
-        for (i, c) in self.name.chars().enumerate() {
-            match (i, c) {
-                (0, c) if !c.is_ascii_alphabetic() => {
-                    return validation_error("Name must start with an ASCII letter");
-                }
-                (_, c) if !c.is_ascii_alphanumeric() && c != '-' && c != '_' => {
-                    return validation_error("Name must only contain alphanumeric, -, _");
-                }
-                _ => { /* this is ok */ }
+        if !self.name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            errors.push(ApiErrorItem::new(
+                "name-invalid-start",
+                String::from("Name must start with an ASCII letter"),
+            ));
+        }
+        if self.name.chars().any(|c| !c.is_ascii_alphanumeric() && c != '-' && c != '_') {
+            errors.push(ApiErrorItem::new(
+                "name-invalid-chars",
+                String::from("Name must only contain alphanumeric, -, _"),
+            ));
+        }
+    }
+
+    /// Validates the package version
+    fn validate_version(&self, errors: &mut Vec<ApiErrorItem>) {
+        if let Err(e) = self.vers.parse::<semver::Version>() {
+            errors.push(ApiErrorItem::new(
+                "version-invalid",
+                format!("Version {} is not a valid semver version: {e}", self.vers),
+            ));
+        }
+    }
+
+    /// Validates the requirements on dependencies
+    fn validate_deps(&self, errors: &mut Vec<ApiErrorItem>) {
+        for dep in &self.deps {
+            if let Err(e) = dep.version_req.parse::<semver::VersionReq>() {
+                errors.push(ApiErrorItem::new(
+                    "dependency-invalid-requirement",
+                    format!("Requirement {} for dependency {} is invalid: {e}", dep.version_req, dep.name),
+                ));
             }
         }
-        Ok(())
     }
-}
 
-/// Creates a validation error
-pub fn validation_error(details: &str) -> Result<(), ApiError> {
-    Err(specialize(error_invalid_request(), details.to_string()))
+    /// Validates the keywords, using the same bounds as cargo
+    fn validate_keywords(&self, errors: &mut Vec<ApiErrorItem>) {
+        if self.keywords.len() > MAX_KEYWORDS {
+            errors.push(ApiErrorItem::new(
+                "keywords-too-many",
+                format!("There must be at most {MAX_KEYWORDS} keywords"),
+            ));
+        }
+        for keyword in &self.keywords {
+            if keyword.is_empty()
+                || keyword.chars().count() > MAX_KEYWORD_LENGTH
+                || !keyword.starts_with(|c: char| c.is_ascii_alphanumeric())
+                || keyword
+                    .chars()
+                    .any(|c| !c.is_ascii_alphanumeric() && c != '-' && c != '_' && c != '+')
+            {
+                errors.push(ApiErrorItem::new(
+                    "keyword-invalid",
+                    format!("Keyword {keyword} must be at most {MAX_KEYWORD_LENGTH} alphanumeric, -, _ or + characters"),
+                ));
+            }
+        }
+    }
+
+    /// Validates the categories, using the same bounds as cargo
+    fn validate_categories(&self, errors: &mut Vec<ApiErrorItem>) {
+        if self.categories.len() > MAX_CATEGORIES {
+            errors.push(ApiErrorItem::new(
+                "categories-too-many",
+                format!("There must be at most {MAX_CATEGORIES} categories"),
+            ));
+        }
+    }
+
+    /// Sanitizes the description by removing control characters and excessive whitespace,
+    /// then truncating it to a maximum number of characters
+    /// Returns the warnings to report to the user
+    pub fn sanitize_description(&mut self, max_length: usize) -> Vec<String> {
+        let Some(description) = self.description.as_mut() else {
+            return Vec::new();
+        };
+        let mut warnings = Vec::new();
+        if description.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+            warnings.push(String::from("Control characters have been removed from the description"));
+        }
+        let cleaned = description
+            .chars()
+            .filter(|c| !c.is_control() || c.is_whitespace())
+            .collect::<String>();
+        let mut cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+        if let Some((index, _)) = cleaned.char_indices().nth(max_length) {
+            cleaned.truncate(index);
+            warnings.push(format!("The description has been truncated to {max_length} characters"));
+        }
+        *description = cleaned;
+        warnings
+    }
+
+    /// Gets the content type of the README file, deduced from the extension of its path in the manifest
+    /// Defaults to markdown when the path is not specified, as for cargo
+    #[must_use]
+    pub fn readme_content_type(&self) -> &'static str {
+        let extension = self
+            .readme_file
+            .as_deref()
+            .and_then(|path| std::path::Path::new(path).extension())
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match (self.readme_file.as_ref(), extension.as_deref()) {
+            (_, Some("rst")) => README_CONTENT_TYPE_RST,
+            (_, Some("adoc" | "asciidoc")) => README_CONTENT_TYPE_ASCIIDOC,
+            (_, Some("txt")) | (Some(_), None) => README_CONTENT_TYPE_TEXT,
+            _ => README_CONTENT_TYPE_MARKDOWN,
+        }
+    }
 }
 
+/// The content type for a README in markdown
+pub const README_CONTENT_TYPE_MARKDOWN: &str = "text/markdown";
+/// The content type for a README in reStructuredText
+pub const README_CONTENT_TYPE_RST: &str = "text/x-rst";
+/// The content type for a README in `AsciiDoc`
+pub const README_CONTENT_TYPE_ASCIIDOC: &str = "text/asciidoc";
+/// The content type for a README in plain text
+pub const README_CONTENT_TYPE_TEXT: &str = "text/plain";
+
+/// The maximum number of keywords for a crate, as in cargo
+const MAX_KEYWORDS: usize = 5;
+/// The maximum length of a keyword, as in cargo
+const MAX_KEYWORD_LENGTH: usize = 20;
+/// The maximum number of categories for a crate, as in cargo
+const MAX_CATEGORIES: usize = 5;
+
 /// The kind of dependency
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DependencyKind {
@@ -324,24 +522,125 @@ impl CrateUploadData {
         Ok(CrateUploadData { metadata, content })
     }
 
+    /// Builds the upload data from the metadata as JSON and the content of the .crate package, received separately
+    pub fn from_parts(metadata: &[u8], content: Vec<u8>) -> Result<CrateUploadData, ApiError> {
+        let metadata = serde_json::from_slice(metadata)
+            .map_err(|e| specialize(error_invalid_request(), format!("invalid crate metadata: {e}")))?;
+        Ok(CrateUploadData { metadata, content })
+    }
+
     /// Builds the metadata to be index for this version
-    pub fn build_index_data(&self) -> IndexCrateMetadata {
+    /// Features using the extended syntax (`dep:` and `pkg?/feat`) go into `features2` so that older versions of cargo ignore them,
+    /// in which case the entry is marked with the version 2 of the schema.
+    /// Duplicate dependencies are handled according to the policy, see `build_index_deps`.
+    /// The dev-dependencies are left out when `strip_dev_deps` is set, the build-dependencies are always kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the dependencies contain duplicates that cannot be emitted with the policy
+    pub fn build_index_data(
+        &self,
+        duplicates: DuplicateDepsPolicy,
+        strip_dev_deps: bool,
+    ) -> Result<IndexCrateMetadata, ApiError> {
         let cksum = sha256(&self.content);
-        IndexCrateMetadata {
+        let (features2, features): (HashMap<_, _>, HashMap<_, _>) = self
+            .metadata
+            .features
+            .iter()
+            .map(|(name, values)| (name.clone(), values.clone()))
+            .partition(|(_, values)| values.iter().any(|value| is_extended_feature_syntax(value)));
+        let (v, features2) = if features2.is_empty() {
+            (None, None)
+        } else {
+            (Some(2), Some(features2))
+        };
+        Ok(IndexCrateMetadata {
             name: self.metadata.name.clone(),
             vers: self.metadata.vers.clone(),
-            deps: self.metadata.deps.iter().map(IndexCrateDependency::from).collect(),
+            deps: build_index_deps(&self.metadata.deps, duplicates, strip_dev_deps)?,
             cksum,
-            features: HashMap::new(),
+            features,
             yanked: false,
             links: self.metadata.links.clone(),
-            v: Some(2),
-            features2: Some(self.metadata.features.clone()),
+            v,
+            features2,
             rust_version: self.metadata.rust_version.clone(),
+        })
+    }
+}
+
+/// How the dependencies of a crate version that are declared more than once are emitted in the index
+/// Dependencies are duplicates when they have the same name, target and kind,
+/// so that the same dependency for different targets or kinds always gives distinct entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateDepsPolicy {
+    /// Duplicates are merged into a single entry with the union of their features,
+    /// provided they agree on the version requirement, the package and the registry
+    #[default]
+    Merge,
+    /// Duplicates are emitted as they are
+    Keep,
+    /// A crate version with duplicates is rejected
+    Reject,
+}
+
+impl FromStr for DuplicateDepsPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(DuplicateDepsPolicy::Merge),
+            "keep" => Ok(DuplicateDepsPolicy::Keep),
+            "reject" => Ok(DuplicateDepsPolicy::Reject),
+            _ => Err(()),
         }
     }
 }
 
+/// Builds the dependencies in the index from the ones in the metadata of a crate version
+/// Cargo expects a single entry for a dependency with a name, target and kind.
+/// When merged, a duplicate is optional only if all declarations are and uses the default features if any does.
+fn build_index_deps(
+    deps: &[CrateMetadataDependency],
+    duplicates: DuplicateDepsPolicy,
+    strip_dev_deps: bool,
+) -> Result<Vec<IndexCrateDependency>, ApiError> {
+    let deps = deps.iter().filter(|dep| !strip_dev_deps || dep.kind != DependencyKind::Dev);
+    if duplicates == DuplicateDepsPolicy::Keep {
+        return Ok(deps.map(IndexCrateDependency::from).collect());
+    }
+    let mut results: Vec<IndexCrateDependency> = Vec::new();
+    for dep in deps {
+        let dep = IndexCrateDependency::from(dep);
+        let Some(existing) = results.iter_mut().find(|existing| existing.is_same_declaration(&dep)) else {
+            results.push(dep);
+            continue;
+        };
+        if duplicates == DuplicateDepsPolicy::Reject
+            || existing.req != dep.req
+            || existing.package != dep.package
+            || existing.registry != dep.registry
+        {
+            return Err(specialize(
+                error_invalid_request(),
+                format!(
+                    "dependency {} is declared more than once for {}",
+                    dep.name,
+                    dep.target.as_deref().unwrap_or("all targets")
+                ),
+            ));
+        }
+        for feature in dep.features {
+            push_if_not_present(&mut existing.features, feature);
+        }
+        existing.optional &= dep.optional;
+        existing.default_features |= dep.default_features;
+    }
+    Ok(results)
+}
+
 /// The metadata for a crate inside the index
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct IndexCrateMetadata {
@@ -413,6 +712,22 @@ impl IndexCrateMetadata {
             .or_else(|| self.features.get(feature))
             .map(Vec::as_slice)
     }
+
+    /// Gets all the features, whether they use the extended syntax or not
+    #[must_use]
+    pub fn get_all_features(&self) -> HashMap<String, Vec<String>> {
+        let mut features = self.features.clone();
+        if let Some(features2) = &self.features2 {
+            features.extend(features2.iter().map(|(name, values)| (name.clone(), values.clone())));
+        }
+        features
+    }
+}
+
+/// Gets whether the value of a feature uses the syntax introduced in Rust 1.60,
+/// that is namespaced dependencies (`dep:name`) or weak dependency features (`name?/feature`)
+fn is_extended_feature_syntax(value: &str) -> bool {
+    value.starts_with("dep:") || value.contains("?/")
 }
 
 /// A dependency for a crate in the index
@@ -456,6 +771,17 @@ impl IndexCrateDependency {
         self.package.as_deref().unwrap_or(&self.name)
     }
 
+    /// Gets whether this is a declaration of the same dependency, i.e. with the same name, target and kind
+    /// Targets are compared regardless of whitespaces, so that `cfg(unix)` and `cfg( unix )` are the same
+    fn is_same_declaration(&self, other: &IndexCrateDependency) -> bool {
+        let normalize = |target: &Option<String>| {
+            target
+                .as_ref()
+                .map(|target| target.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        };
+        self.name == other.name && self.kind == other.kind && normalize(&self.target) == normalize(&other.target)
+    }
+
     /// Gets whether this dependency is active, for the specified targets and features
     pub fn is_active_for(&self, active_targets: &[String], active_features: &[&str]) -> bool {
         let is_in_targets = match self.target.as_ref() {
@@ -505,3 +831,149 @@ impl From<&CrateMetadataDependency> for IndexCrateDependency {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        build_index_deps, CrateMetadata, CrateMetadataDependency, CrateUploadData, DependencyKind, DuplicateDepsPolicy,
+    };
+
+    /// Builds a dependency, for a target if any
+    fn dep(name: &str, req: &str, target: Option<&str>, kind: DependencyKind, features: &[&str]) -> CrateMetadataDependency {
+        CrateMetadataDependency {
+            name: name.to_string(),
+            version_req: req.to_string(),
+            features: features.iter().map(|f| (*f).to_string()).collect(),
+            default_features: true,
+            target: target.map(str::to_string),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    /// Builds the upload data for a crate version with features
+    fn upload_with_features(features: &[(&str, &[&str])]) -> CrateUploadData {
+        CrateUploadData {
+            metadata: CrateMetadata {
+                name: String::from("test"),
+                vers: String::from("1.0.0"),
+                features: features
+                    .iter()
+                    .map(|(name, values)| ((*name).to_string(), values.iter().map(|v| (*v).to_string()).collect()))
+                    .collect(),
+                ..Default::default()
+            },
+            content: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn extended_features_go_into_features2() {
+        let upload = upload_with_features(&[
+            ("default", &["std"]),
+            ("std", &[]),
+            ("serde", &["dep:serde"]),
+            ("weak", &["log?/std"]),
+        ]);
+        let index_data = upload.build_index_data(DuplicateDepsPolicy::Merge, false).unwrap();
+        assert_eq!(index_data.v, Some(2));
+        let features2 = index_data.features2.unwrap();
+        assert_eq!(features2.len(), 2);
+        assert_eq!(features2.get("serde"), Some(&vec![String::from("dep:serde")]));
+        assert_eq!(features2.get("weak"), Some(&vec![String::from("log?/std")]));
+        let expected = HashMap::from([
+            (String::from("default"), vec![String::from("std")]),
+            (String::from("std"), Vec::new()),
+        ]);
+        assert_eq!(index_data.features, expected);
+    }
+
+    #[test]
+    fn plain_features_do_not_need_features2() {
+        let upload = upload_with_features(&[("default", &["std"]), ("std", &["log/std"])]);
+        let index_data = upload.build_index_data(DuplicateDepsPolicy::Merge, false).unwrap();
+        assert_eq!(index_data.v, None);
+        assert!(index_data.features2.is_none());
+        assert_eq!(index_data.features.len(), 2);
+    }
+
+    #[test]
+    fn duplicate_target_deps_are_merged() {
+        let deps = [
+            dep("libc", "0.2", Some("cfg(unix)"), DependencyKind::Normal, &["std"]),
+            dep("libc", "0.2", Some("cfg( unix )"), DependencyKind::Normal, &["extra_traits"]),
+        ];
+        let index_deps = build_index_deps(&deps, DuplicateDepsPolicy::Merge, false).unwrap();
+        assert_eq!(index_deps.len(), 1);
+        assert_eq!(index_deps[0].target.as_deref(), Some("cfg(unix)"));
+        assert_eq!(
+            index_deps[0].features,
+            vec![String::from("std"), String::from("extra_traits")]
+        );
+    }
+
+    #[test]
+    fn same_dep_for_different_targets_is_kept_separate() {
+        let deps = [
+            dep("libc", "0.2", Some("cfg(unix)"), DependencyKind::Normal, &[]),
+            dep("libc", "0.2", Some("cfg(windows)"), DependencyKind::Normal, &[]),
+            dep("libc", "0.2", None, DependencyKind::Normal, &[]),
+        ];
+        let index_deps = build_index_deps(&deps, DuplicateDepsPolicy::Merge, false).unwrap();
+        assert_eq!(index_deps.len(), 3);
+    }
+
+    #[test]
+    fn duplicate_deps_with_different_requirements_are_rejected() {
+        let deps = [
+            dep("libc", "0.2", Some("cfg(unix)"), DependencyKind::Normal, &[]),
+            dep("libc", "0.3", Some("cfg(unix)"), DependencyKind::Normal, &[]),
+        ];
+        let error = build_index_deps(&deps, DuplicateDepsPolicy::Merge, false).unwrap_err();
+        assert_eq!(error.http, 400);
+    }
+
+    #[test]
+    fn duplicate_deps_follow_the_policy() {
+        let deps = [
+            dep("libc", "0.2", Some("cfg(unix)"), DependencyKind::Normal, &["std"]),
+            dep("libc", "0.2", Some("cfg(unix)"), DependencyKind::Normal, &["extra_traits"]),
+        ];
+        let kept = build_index_deps(&deps, DuplicateDepsPolicy::Keep, false).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].features, vec![String::from("std")]);
+        assert_eq!(kept[1].features, vec![String::from("extra_traits")]);
+        let error = build_index_deps(&deps, DuplicateDepsPolicy::Reject, false).unwrap_err();
+        assert_eq!(error.http, 400);
+        // distinct dependencies are accepted by the strict policy
+        let distinct = [deps[0].clone(), dep("log", "0.4", None, DependencyKind::Normal, &[])];
+        assert_eq!(
+            build_index_deps(&distinct, DuplicateDepsPolicy::Reject, false).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn dev_deps_are_kept_unless_stripped() {
+        let deps = [
+            dep("serde", "1", None, DependencyKind::Normal, &[]),
+            dep("proptest", "1", None, DependencyKind::Dev, &[]),
+            dep("cc", "1", None, DependencyKind::Build, &[]),
+        ];
+        let kept = build_index_deps(&deps, DuplicateDepsPolicy::Merge, false).unwrap();
+        let kinds = kept.iter().map(|dep| (dep.name.as_str(), dep.kind)).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("serde", DependencyKind::Normal),
+                ("proptest", DependencyKind::Dev),
+                ("cc", DependencyKind::Build)
+            ]
+        );
+        let stripped = build_index_deps(&deps, DuplicateDepsPolicy::Merge, true).unwrap();
+        let kinds = stripped.iter().map(|dep| (dep.name.as_str(), dep.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![("serde", DependencyKind::Normal), ("cc", DependencyKind::Build)]);
+    }
+}
@@ -8,7 +8,7 @@ use std::net::{IpAddr, Ipv4Addr};
 use std::process::Stdio;
 use std::str::FromStr;
 
-use axum::http::Uri;
+use axum::http::{HeaderValue, Uri};
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use serde_derive::{Deserialize, Serialize};
@@ -16,8 +16,11 @@ use tokio::fs::File;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::process::Command;
 
+use crate::model::cargo::DuplicateDepsPolicy;
 use crate::model::errors::MissingEnvVar;
+use crate::model::osv::AdvisorySeverityLevel;
 use crate::utils::apierror::ApiError;
+use crate::utils::cidr::{parse_cidr_list, IpCidr};
 
 /// Gets the value for an environment variable
 pub fn get_var<T: AsRef<str>>(name: T) -> Result<String, MissingEnvVar> {
@@ -28,6 +31,59 @@ pub fn get_var<T: AsRef<str>>(name: T) -> Result<String, MissingEnvVar> {
     })
 }
 
+/// Gets the value of a `Cache-Control` header from an environment variable, with a default value
+fn get_cache_control_var(name: &str, default: &str) -> String {
+    get_var(name).map_or_else(
+        |_| default.to_string(),
+        |value| {
+            assert!(HeaderValue::from_str(&value).is_ok(), "invalid {name}");
+            value
+        },
+    )
+}
+
+/// The reverse proxies trusted by default: only the loopback, other networks must be trusted explicitly
+const DEFAULT_TRUSTED_PROXIES: &str = "127.0.0.0/8,::1";
+
+/// The largest allowed size of the pool of connections to the database
+const DB_MAX_CONNECTIONS_LIMIT: u32 = 1024;
+
+/// Gets the size of the pool of connections to the database, with a default value
+fn get_db_max_connections_var(name: &str, default: u32) -> u32 {
+    let value = get_var(name).map_or(default, |s| s.parse().unwrap_or_else(|_| panic!("invalid {name}")));
+    assert!(
+        (1..=DB_MAX_CONNECTIONS_LIMIT).contains(&value),
+        "invalid {name}: expected a number of connections between 1 and {DB_MAX_CONNECTIONS_LIMIT}, got {value}"
+    );
+    value
+}
+
+/// Gets the value of a `SQLite` pragma from an environment variable, with a default value
+/// The value must be one of the allowed ones, regardless of the case, because it is written as is in the pragma
+fn get_pragma_var(name: &str, default: &str, allowed: &[&str]) -> String {
+    let value = get_var(name).map_or_else(|_| default.to_string(), |value| value.to_ascii_uppercase());
+    assert!(
+        allowed.contains(&value.as_str()),
+        "invalid {name}: expected one of {}, got {value}",
+        allowed.join(", ")
+    );
+    value
+}
+
+/// Gets a comma-separated list of SPDX license identifiers from an environment variable
+fn get_licenses_var(name: &str) -> Vec<String> {
+    get_var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|license| !license.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 /// The protocol to use for an external registry
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 pub enum ExternalRegistryProtocol {
@@ -81,6 +137,19 @@ impl ExternalRegistry {
             Ok(None)
         }
     }
+
+    /// Redacts the secrets in this configuration
+    fn redact_secrets(&mut self) {
+        let ExternalRegistry {
+            name: _,
+            index: _,
+            protocol: _,
+            docs_root: _,
+            login: _,
+            token,
+        } = self;
+        redact(token);
+    }
 }
 
 /// The specification of the storage system to use
@@ -116,6 +185,19 @@ impl StorageConfig {
             _ => panic!("invalid REGISTRY_STORAGE"),
         })
     }
+
+    /// Redacts the secrets in this configuration
+    fn redact_secrets(&mut self) {
+        if let StorageConfig::S3 { params, bucket: _ } = self {
+            let S3Params {
+                uri: _,
+                region: _,
+                access_key: _,
+                secret_key,
+            } = params;
+            redact(secret_key);
+        }
+    }
 }
 
 /// The S3 parameters
@@ -135,6 +217,7 @@ pub struct S3Params {
 
 /// The configuration in the index
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct IndexConfig {
     /// The location in the file system
     pub location: String,
@@ -144,6 +227,16 @@ pub struct IndexConfig {
     /// Whether to allow the sparse protocol for clients fetching the index
     #[serde(rename = "allowProtocolSparse")]
     pub allow_protocol_sparse: bool,
+    /// The number of seconds clients may cache `config.json` for the sparse protocol, 0 to always revalidate
+    /// The files for the crates are never cached
+    #[serde(rename = "configCacheMaxAge")]
+    pub config_cache_max_age: u64,
+    /// How the dependencies declared more than once by a crate version are emitted in the index
+    #[serde(rename = "duplicateDeps")]
+    pub duplicate_deps: DuplicateDepsPolicy,
+    /// Whether the dev-dependencies are left out of the entries in the index
+    #[serde(rename = "stripDevDeps")]
+    pub strip_dev_deps: bool,
     /// URI for the origin git remote to sync with
     #[serde(rename = "remoteOrigin")]
     pub remote_origin: Option<String>,
@@ -170,6 +263,13 @@ impl IndexConfig {
             location: format!("{data_dir}/index"),
             allow_protocol_git: get_var("REGISTRY_INDEX_PROTOCOL_GIT").map(|v| v == "true").unwrap_or(true),
             allow_protocol_sparse: get_var("REGISTRY_INDEX_PROTOCOL_SPARSE").map(|v| v == "true").unwrap_or(true),
+            config_cache_max_age: get_var("REGISTRY_INDEX_CONFIG_CACHE_MAX_AGE")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_INDEX_CONFIG_CACHE_MAX_AGE")),
+            duplicate_deps: get_var("REGISTRY_INDEX_DUPLICATE_DEPS").map_or(DuplicateDepsPolicy::Merge, |s| {
+                s.parse().expect("invalid REGISTRY_INDEX_DUPLICATE_DEPS")
+            }),
+            strip_dev_deps: get_var("REGISTRY_INDEX_STRIP_DEV_DEPS")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
             remote_origin: get_var("REGISTRY_GIT_REMOTE").ok(),
             remote_ssh_key_file_name: get_var("REGISTRY_GIT_REMOTE_SSH_KEY_FILENAME").ok(),
             remote_push_changes: get_var("REGISTRY_GIT_REMOTE_PUSH_CHANGES")
@@ -243,10 +343,240 @@ impl EmailConfig {
             cc: get_var("REGISTRY_EMAIL_CC").unwrap_or_default(),
         })
     }
+
+    /// Redacts the secrets in this configuration
+    fn redact_secrets(&mut self) {
+        let EmailConfig {
+            smtp:
+                SmtpConfig {
+                    host: _,
+                    port: _,
+                    login: _,
+                    password,
+                },
+            sender: _,
+            cc: _,
+        } = self;
+        redact(password);
+    }
+}
+
+/// The configuration for the hooks executed around the publication of crates
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct HooksConfig {
+    /// Path to the command to execute before a crate is published, if any
+    /// A non-zero exit code rejects the publication
+    #[serde(rename = "prePublish")]
+    pub pre_publish: Option<String>,
+    /// Path to the command to execute after a crate is published, if any
+    #[serde(rename = "postPublish")]
+    pub post_publish: Option<String>,
+    /// Timeout (in milli-seconds) for the execution of a hook
+    pub timeout: u64,
+}
+
+impl HooksConfig {
+    /// Loads the configuration for the hooks from the environment
+    fn from_env() -> Self {
+        Self {
+            pre_publish: get_var("REGISTRY_HOOKS_PRE_PUBLISH").ok().filter(|v| !v.is_empty()),
+            post_publish: get_var("REGISTRY_HOOKS_POST_PUBLISH").ok().filter(|v| !v.is_empty()),
+            timeout: get_var("REGISTRY_HOOKS_TIMEOUT")
+                .map_or(10 * 1000, |s| s.parse().expect("invalid REGISTRY_HOOKS_TIMEOUT")),
+        }
+    }
+}
+
+/// The configuration for the audit log
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct AuditConfig {
+    /// Number of days after which entries in the audit log are deleted
+    /// Zero deactivates the retention and keeps all entries
+    #[serde(rename = "retentionDays")]
+    pub retention_days: u64,
+    /// Whether entries must be archived through an export before they can be deleted
+    pub archive: bool,
+}
+
+impl AuditConfig {
+    /// Loads the configuration for the audit log from the environment
+    fn from_env() -> Self {
+        Self {
+            retention_days: get_var("REGISTRY_AUDIT_RETENTION_DAYS")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_AUDIT_RETENTION_DAYS")),
+            archive: get_var("REGISTRY_AUDIT_ARCHIVE").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+        }
+    }
+}
+
+/// The configuration for the enrichment of crates with metadata from their repository
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct EnrichmentConfig {
+    /// Whether to fetch metadata about the repository of crates, for the recognized hosts
+    pub enabled: bool,
+    /// Number of hours after which the metadata for a repository are fetched again
+    #[serde(rename = "staleHours")]
+    pub stale_hours: i64,
+    /// The token to use for the GitHub API, if any
+    #[serde(rename = "githubToken")]
+    pub github_token: Option<String>,
+    /// The token to use for the GitLab API, if any
+    #[serde(rename = "gitlabToken")]
+    pub gitlab_token: Option<String>,
+}
+
+impl EnrichmentConfig {
+    /// Loads the configuration for the enrichment from the environment
+    fn from_env() -> Self {
+        Self {
+            enabled: get_var("REGISTRY_ENRICH_REPOSITORY")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            stale_hours: get_var("REGISTRY_ENRICH_STALE_HOURS")
+                .map_or(24, |s| s.parse().expect("invalid REGISTRY_ENRICH_STALE_HOURS")),
+            github_token: get_var("REGISTRY_ENRICH_GITHUB_TOKEN").ok(),
+            gitlab_token: get_var("REGISTRY_ENRICH_GITLAB_TOKEN").ok(),
+        }
+    }
+
+    /// Redacts the secrets in this configuration
+    fn redact_secrets(&mut self) {
+        let EnrichmentConfig {
+            enabled: _,
+            stale_hours: _,
+            github_token,
+            gitlab_token,
+        } = self;
+        github_token.iter_mut().for_each(redact);
+        gitlab_token.iter_mut().for_each(redact);
+    }
+}
+
+/// The configuration for the webhooks notified of the changes to crates
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct WebhooksConfig {
+    /// The URLs of the endpoints to notify
+    pub endpoints: Vec<String>,
+    /// The shared secret used to sign the payloads
+    pub secret: String,
+    /// The number of attempts to deliver a notification to an endpoint before giving up
+    pub attempts: u32,
+    /// Timeout (in milli-seconds) for each attempt
+    pub timeout: u64,
+}
+
+impl WebhooksConfig {
+    /// Loads the configuration for the webhooks from the environment
+    fn from_env() -> Result<Self, MissingEnvVar> {
+        let endpoints = get_var("REGISTRY_WEBHOOKS_URLS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let secret = if endpoints.is_empty() {
+            String::new()
+        } else {
+            get_var("REGISTRY_WEBHOOKS_SECRET")?
+        };
+        Ok(Self {
+            endpoints,
+            secret,
+            attempts: get_var("REGISTRY_WEBHOOKS_ATTEMPTS")
+                .map_or(5, |s| s.parse().expect("invalid REGISTRY_WEBHOOKS_ATTEMPTS")),
+            timeout: get_var("REGISTRY_WEBHOOKS_TIMEOUT")
+                .map_or(10 * 1000, |s| s.parse().expect("invalid REGISTRY_WEBHOOKS_TIMEOUT")),
+        })
+    }
+
+    /// Redacts the secrets in this configuration
+    fn redact_secrets(&mut self) {
+        let WebhooksConfig {
+            endpoints: _,
+            secret,
+            attempts: _,
+            timeout: _,
+        } = self;
+        redact(secret);
+    }
+}
+
+/// The configuration for the proxy to an upstream registry, crates.io by default, for the crates not hosted by this registry
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ProxyConfig {
+    /// Whether the crates not hosted by this registry are fetched from the upstream registry
+    pub enabled: bool,
+    /// The URI of the sparse index of the upstream registry
+    #[serde(rename = "upstreamIndex")]
+    pub upstream_index: String,
+    /// The URI for the downloads of the upstream registry
+    #[serde(rename = "upstreamDownloads")]
+    pub upstream_dl: String,
+    /// The number of seconds a cached file of the upstream index is used before being fetched again
+    #[serde(rename = "indexMaxAge")]
+    pub index_max_age: u64,
+    /// Timeout (in milli-seconds) for the requests to the upstream registry
+    pub timeout: u64,
+}
+
+impl ProxyConfig {
+    /// Loads the configuration for the proxy from the environment
+    fn from_env() -> Self {
+        Self {
+            enabled: get_var("REGISTRY_PROXY_CRATESIO").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            upstream_index: get_var("REGISTRY_PROXY_UPSTREAM_INDEX").map_or_else(
+                |_| String::from("https://index.crates.io"),
+                |value| value.trim_end_matches('/').to_string(),
+            ),
+            upstream_dl: get_var("REGISTRY_PROXY_UPSTREAM_DL").map_or_else(
+                |_| String::from("https://static.crates.io/crates"),
+                |value| value.trim_end_matches('/').to_string(),
+            ),
+            index_max_age: get_var("REGISTRY_PROXY_INDEX_MAX_AGE")
+                .map_or(300, |s| s.parse().expect("invalid REGISTRY_PROXY_INDEX_MAX_AGE")),
+            timeout: get_var("REGISTRY_PROXY_TIMEOUT")
+                .map_or(30 * 1000, |s| s.parse().expect("invalid REGISTRY_PROXY_TIMEOUT")),
+        }
+    }
+}
+
+/// The configuration for the tokens of users
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct TokensConfig {
+    /// The maximum number of characters in the name of a token
+    #[serde(rename = "nameMaxLength")]
+    pub name_max_length: usize,
+    /// Whether the names of the tokens of a user must be unique
+    #[serde(rename = "nameUnique")]
+    pub name_unique: bool,
+    /// The maximum number of attempts to generate a new token when the generated secret collides with an existing one
+    #[serde(rename = "createAttempts")]
+    pub create_attempts: u32,
+}
+
+impl TokensConfig {
+    /// Loads the configuration for the tokens from the environment
+    fn from_env() -> Self {
+        Self {
+            name_max_length: get_var("REGISTRY_TOKEN_NAME_MAX_LENGTH")
+                .map_or(64, |s| s.parse().expect("invalid REGISTRY_TOKEN_NAME_MAX_LENGTH")),
+            name_unique: get_var("REGISTRY_TOKEN_NAME_UNIQUE")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            create_attempts: get_var("REGISTRY_TOKEN_CREATE_ATTEMPTS").map_or(3, |s| {
+                let value = s.parse().expect("invalid REGISTRY_TOKEN_CREATE_ATTEMPTS");
+                assert!(value > 0, "REGISTRY_TOKEN_CREATE_ATTEMPTS must be at least 1");
+                value
+            }),
+        }
+    }
 }
 
 /// A configuration for the registry
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Configuration {
     /// The log level to use
     #[serde(rename = "logLevel")]
@@ -269,9 +599,89 @@ pub struct Configuration {
     /// The maximum size for the body of incoming requests
     #[serde(rename = "webBodyLimit")]
     pub web_body_limit: usize,
+    /// The maximum number of concurrent downloads of crates for a single token
+    /// Zero means no limit
+    #[serde(rename = "webDownloadsMaxPerToken")]
+    pub web_downloads_max_per_token: usize,
+    /// Time (in milli-seconds) a download waits for a slot when the limit for its token is reached
+    #[serde(rename = "webDownloadsQueueTimeout")]
+    pub web_downloads_queue_timeout: u64,
+    /// The duration (in seconds) of the sliding window for the rate limits
+    #[serde(rename = "webRateLimitWindow")]
+    pub web_rate_limit_window: u64,
+    /// The maximum number of requests, other than publications, within the window for a single token or client IP
+    /// Zero means no limit
+    #[serde(rename = "webRateLimitReads")]
+    pub web_rate_limit_reads: usize,
+    /// The maximum number of publications within the window for a single token or client IP
+    /// Zero means no limit
+    #[serde(rename = "webRateLimitPublishes")]
+    pub web_rate_limit_publishes: usize,
+    /// The maximum number of requests to the index within the window for a single token or client IP
+    /// Zero means no limit
+    #[serde(rename = "webRateLimitIndex")]
+    pub web_rate_limit_index: usize,
+    /// The secret key to sign download links for a single crate version, links are deactivated when not set
+    #[serde(rename = "webDownloadLinksKey")]
+    pub web_download_links_key: Option<String>,
+    /// The maximum validity (in seconds) of a signed download link
+    #[serde(rename = "webDownloadLinksMaxTtl")]
+    pub web_download_links_max_ttl: u64,
+    /// Whether to serve a sitemap of the crates at `/sitemap.xml`
+    #[serde(rename = "webSitemap")]
+    pub web_sitemap: bool,
+    /// Whether the shortcuts to the pages of crates redirect to the canonical case of the crate's name
+    #[serde(rename = "webCratesCanonicalRedirect")]
+    pub web_crates_canonical_redirect: bool,
+    /// The target of the redirection for browsers on the root path, defaults to the web application
+    #[serde(rename = "webRootRedirect")]
+    pub web_root_redirect: String,
+    /// Whether the metrics at `/metrics` require the authentication of an administrator
+    #[serde(rename = "webMetricsAuth")]
+    pub web_metrics_auth: bool,
+    /// The value of the `Cache-Control` header for the downloads of crate versions
+    /// The downloads require authentication, so that only private caches keep them by default
+    #[serde(rename = "webCratesCacheControl")]
+    pub web_crates_cache_control: String,
+    /// The value of the `Cache-Control` header for the content that may change for a crate version, like the readme and documentation
+    #[serde(rename = "webContentCacheControl")]
+    pub web_content_cache_control: String,
+    /// Path to a custom HTML page served for the unknown pages of the web application, the embedded one is used when not set
+    #[serde(rename = "webNotFoundPage")]
+    pub web_not_found_page: Option<String>,
+    /// The blocks of IP addresses of the reverse proxies trusted for the `X-Forwarded-For` header
+    #[serde(rename = "webTrustedProxies")]
+    pub web_trusted_proxies: Vec<IpCidr>,
     /// The data directory
     #[serde(rename = "dataDir")]
     pub data_dir: String,
+    /// Number of seconds after which the lock on the database migration held by another instance is deemed abandoned
+    #[serde(rename = "dbMigrationLockTimeout")]
+    pub db_migration_lock_timeout: u64,
+    /// The maximum number of retries when acquiring a connection to the database fails
+    #[serde(rename = "dbAcquireRetries")]
+    pub db_acquire_retries: u32,
+    /// The number of milliseconds to wait before the first retry, doubled for each subsequent retry
+    #[serde(rename = "dbAcquireBackoff")]
+    pub db_acquire_backoff: u64,
+    /// The maximum total number of milliseconds to wait for retries
+    #[serde(rename = "dbAcquireMaxWait")]
+    pub db_acquire_max_wait: u64,
+    /// The maximum number of connections in the pool to the database
+    #[serde(rename = "dbMaxConnections")]
+    pub db_max_connections: u32,
+    /// The maximum number of milliseconds to wait for a connection of the pool to become available
+    #[serde(rename = "dbAcquireTimeout")]
+    pub db_acquire_timeout: u64,
+    /// The journal mode of `SQLite` for the database (`journal_mode` pragma)
+    #[serde(rename = "dbJournalMode")]
+    pub db_journal_mode: String,
+    /// The synchronous mode of `SQLite` for the database (`synchronous` pragma)
+    #[serde(rename = "dbSynchronous")]
+    pub db_synchronous: String,
+    /// The number of milliseconds a connection waits for a lock on the database held by another connection (`busy_timeout` pragma)
+    #[serde(rename = "dbBusyTimeout")]
+    pub db_busy_timeout: u64,
     /// The configuration for the index
     #[serde(rename = "indexConfig")]
     pub index: IndexConfig,
@@ -280,6 +690,15 @@ pub struct Configuration {
     /// Timeout (in milli-seconds) to use when interacting with the storage
     #[serde(rename = "storageTimeout")]
     pub storage_timeout: u64,
+    /// Whether identical READMEs are stored once and shared across versions of a crate
+    #[serde(rename = "storageDedupReadme")]
+    pub storage_dedup_readme: bool,
+    /// Whether the information about a crate is still served, without its metadata, when they cannot be loaded from the storage
+    #[serde(rename = "storageDegradedMetadata")]
+    pub storage_degraded_metadata: bool,
+    /// The interval (in hours) between two collections of the stored files of deleted crates, 0 to deactivate
+    #[serde(rename = "storageGcIntervalHours")]
+    pub storage_gc_interval_hours: u64,
     /// The uri of the OAuth login page
     #[serde(rename = "oauthLoginUri")]
     pub oauth_login_uri: String,
@@ -307,6 +726,23 @@ pub struct Configuration {
     /// The secret for the client to use
     #[serde(rename = "oauthClientScope")]
     pub oauth_client_scope: String,
+    /// Whether to check the state and nonce of the OAuth login flow on the callback
+    #[serde(rename = "oauthCheckState")]
+    pub oauth_check_state: bool,
+    /// Whether to check at startup that the toolchain for the generation of documentation is available
+    /// When the check fails, documentation jobs are skipped instead of failing
+    #[serde(rename = "docsCheckToolchain")]
+    pub docs_check_toolchain: bool,
+    /// The root folder into which the documentation of crates can be exported, deactivated when not set
+    #[serde(rename = "docsExportDir")]
+    pub docs_export_dir: Option<String>,
+    /// The maximum number of targets for which the documentation of a crate is generated at the same time
+    #[serde(rename = "docsGenConcurrency")]
+    pub docs_gen_concurrency: usize,
+    /// The maximum number of jobs waiting for the generation of documentation, 0 for no limit
+    /// The jobs beyond are deferred until the queue is drained
+    #[serde(rename = "docsQueueCapacity")]
+    pub docs_queue_capacity: usize,
     /// The known external registries that require authentication
     #[serde(rename = "externalRegistries")]
     pub external_registries: Vec<ExternalRegistry>,
@@ -317,14 +753,79 @@ pub struct Configuration {
     /// A negative number deactivates background analysis of crates
     #[serde(rename = "depsStaleAnalysis")]
     pub deps_stale_analysis: i64,
+    /// The maximum depth of the dependency graph to resolve for an analysis, 0 for no limit
+    /// Deeper dependencies are not resolved and the analysis is flagged as truncated
+    #[serde(rename = "depsMaxDepth")]
+    pub deps_max_depth: usize,
+    /// The maximum number of crates in the dependency graph for an analysis, 0 for no limit
+    /// Past this number, other dependencies are not resolved and the analysis is flagged as truncated
+    #[serde(rename = "depsMaxNodes")]
+    pub deps_max_nodes: usize,
+    /// Number of minutes between two scans of the last version of all crates against the `RustSec` advisories, 0 to deactivate
+    #[serde(rename = "depsAdvisoriesScanInterval")]
+    pub deps_advisories_scan_interval: u64,
+    /// The SPDX identifiers of the licenses that dependencies are allowed to require, empty to allow all
+    #[serde(rename = "depsAllowedLicenses")]
+    pub deps_allowed_licenses: Vec<String>,
+    /// The SPDX identifiers of the licenses that dependencies must not require
+    #[serde(rename = "depsDeniedLicenses")]
+    pub deps_denied_licenses: Vec<String>,
     /// Whether to send a notification by email to the owners of a crate when some of its dependencies become outdated
     #[serde(rename = "depsNotifyOutdated")]
     pub deps_notify_outdated: bool,
     /// Whether to send a notification by email to the owners of a crate when CVEs are discovered in its dependencies
     #[serde(rename = "depsNotifyCVEs")]
     pub deps_notify_cves: bool,
+    /// Number of days after which owners that did not re-attest their ownership of a crate are flagged
+    /// 0 deactivates the attestation of ownership
+    #[serde(rename = "ownersAttestationDays")]
+    pub owners_attestation_days: i64,
+    /// Number of hours after which a version yanked with an expiry is automatically unyanked, unless an admin confirms the yank
+    /// 0 deactivates yanks with an expiry
+    #[serde(rename = "yankExpiryHours")]
+    pub yank_expiry_hours: i64,
+    /// Number of minutes after its first publication during which a version can be replaced by publishing it again
+    /// 0 makes versions immutable
+    #[serde(rename = "versionMutableWindow")]
+    pub version_mutable_window: i64,
+    /// Whether re-indexing crates from the storage stops at the first crate that fails, instead of skipping it
+    #[serde(rename = "reindexHaltOnFailure")]
+    pub reindex_halt_on_failure: bool,
     /// The configuration for sending emails
     pub email: EmailConfig,
+    /// The configuration for the tokens of users
+    pub tokens: TokensConfig,
+    /// The maximum number of characters in the description of a published crate, longer descriptions are truncated
+    #[serde(rename = "publishDescriptionMaxLength")]
+    pub publish_description_max_length: usize,
+    /// The minimum severity of advisories against the dependencies of a crate that gets its publication rejected
+    /// When set, the dependencies are checked synchronously at publication
+    #[serde(rename = "publishDenyAdvisories")]
+    pub publish_deny_advisories: Option<AdvisorySeverityLevel>,
+    /// The name of the policy for the dependencies that crates must comply with to be published, if any
+    /// When set, the dependencies are checked synchronously at publication
+    #[serde(rename = "publishPolicy")]
+    pub publish_policy: Option<String>,
+    /// Whether the owners of crates can be notified by email of the publication of new versions, immediately or in a daily digest
+    #[serde(rename = "publishNotifications")]
+    pub publish_notifications: bool,
+    /// Whether to index the README of published crates so that the search can look into them
+    #[serde(rename = "searchReadme")]
+    pub search_readme: bool,
+    /// The number of days after which the weight of downloads is halved in the popularity of crates used to rank the search results
+    /// Zero deactivates the decay so that all downloads weigh the same
+    #[serde(rename = "searchPopularityHalfLife")]
+    pub search_popularity_half_life: u64,
+    /// The hooks executed around the publication of crates
+    pub hooks: HooksConfig,
+    /// The configuration for the audit log
+    pub audit: AuditConfig,
+    /// The configuration for the enrichment of crates with metadata from their repository
+    pub enrichment: EnrichmentConfig,
+    /// The webhooks notified of the changes to crates
+    pub webhooks: WebhooksConfig,
+    /// The proxy to an upstream registry for the crates not hosted by this registry
+    pub proxy: ProxyConfig,
     /// The name to use for the local registry in cargo and git config
     #[serde(rename = "selfLocalName")]
     pub self_local_name: String,
@@ -351,6 +852,7 @@ impl Configuration {
     /// # Errors
     ///
     /// Return a `VarError` when an expected environment variable is not present
+    #[allow(clippy::too_many_lines)]
     pub async fn from_env() -> Result<Self, MissingEnvVar> {
         let data_dir = get_var("REGISTRY_DATA_DIR")?;
         let web_public_uri = get_var("REGISTRY_WEB_PUBLIC_URI")?;
@@ -367,10 +869,16 @@ impl Configuration {
             },
         };
         let index = IndexConfig::from_env(&data_dir, &web_public_uri)?;
+        let web_root_redirect = get_var("REGISTRY_WEB_ROOT_REDIRECT")
+            .ok()
+            .filter(|target| !target.is_empty())
+            .unwrap_or_else(|| format!("{web_public_uri}/webapp/index.html"));
         let storage = StorageConfig::from_env()?;
         let deps_notify_outdated = get_var("REGISTRY_DEPS_NOTIFY_OUTDATED").map(|v| v == "true").unwrap_or(false);
         let deps_notify_cves = get_var("REGISTRY_DEPS_NOTIFY_CVES").map(|v| v == "true").unwrap_or(false);
-        let email = if deps_notify_outdated || deps_notify_cves {
+        let publish_notifications =
+            get_var("REGISTRY_PUBLISH_NOTIFICATIONS").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+        let email = if deps_notify_outdated || deps_notify_cves || publish_notifications {
             EmailConfig::from_env()?
         } else {
             EmailConfig::default()
@@ -397,12 +905,74 @@ impl Configuration {
             web_body_limit: get_var("REGISTRY_WEB_BODY_LIMIT")
                 .map(|s| s.parse().expect("invalid REGISTRY_WEB_BODY_LIMIT"))
                 .unwrap_or(10 * 1024 * 1024),
+            web_downloads_max_per_token: get_var("REGISTRY_WEB_DOWNLOADS_MAX_PER_TOKEN")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_WEB_DOWNLOADS_MAX_PER_TOKEN")),
+            web_downloads_queue_timeout: get_var("REGISTRY_WEB_DOWNLOADS_QUEUE_TIMEOUT")
+                .map_or(2000, |s| s.parse().expect("invalid REGISTRY_WEB_DOWNLOADS_QUEUE_TIMEOUT")),
+            web_rate_limit_window: get_var("REGISTRY_WEB_RATE_LIMIT_WINDOW")
+                .map_or(60, |s| s.parse().expect("invalid REGISTRY_WEB_RATE_LIMIT_WINDOW")),
+            web_rate_limit_reads: get_var("REGISTRY_WEB_RATE_LIMIT_READS")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_WEB_RATE_LIMIT_READS")),
+            web_rate_limit_publishes: get_var("REGISTRY_WEB_RATE_LIMIT_PUBLISHES")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_WEB_RATE_LIMIT_PUBLISHES")),
+            web_rate_limit_index: get_var("REGISTRY_WEB_RATE_LIMIT_INDEX")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_WEB_RATE_LIMIT_INDEX")),
+            web_download_links_key: get_var("REGISTRY_WEB_DOWNLOAD_LINKS_KEY").ok().filter(|key| !key.is_empty()),
+            web_download_links_max_ttl: get_var("REGISTRY_WEB_DOWNLOAD_LINKS_MAX_TTL").map_or(24 * 60 * 60, |s| {
+                s.parse().expect("invalid REGISTRY_WEB_DOWNLOAD_LINKS_MAX_TTL")
+            }),
+            web_sitemap: get_var("REGISTRY_WEB_SITEMAP").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            web_crates_canonical_redirect: get_var("REGISTRY_WEB_CRATES_CANONICAL_REDIRECT")
+                .map_or(true, |value| value == "1" || value.eq_ignore_ascii_case("true")),
+            web_root_redirect,
+            web_metrics_auth: get_var("REGISTRY_WEB_METRICS_AUTH")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            web_crates_cache_control: get_cache_control_var("REGISTRY_WEB_CRATES_CACHE_CONTROL", "private, max-age=31536000"),
+            web_content_cache_control: get_cache_control_var("REGISTRY_WEB_CONTENT_CACHE_CONTROL", "max-age=3600"),
+            web_not_found_page: get_var("REGISTRY_WEB_NOT_FOUND_PAGE").ok(),
+            web_trusted_proxies: parse_cidr_list(
+                get_var("REGISTRY_WEB_TRUSTED_PROXIES")
+                    .as_deref()
+                    .unwrap_or(DEFAULT_TRUSTED_PROXIES),
+            )
+            .expect("invalid REGISTRY_WEB_TRUSTED_PROXIES"),
             data_dir,
+            db_migration_lock_timeout: get_var("REGISTRY_DB_MIGRATION_LOCK_TIMEOUT")
+                .map_or(600, |s| s.parse().expect("invalid REGISTRY_DB_MIGRATION_LOCK_TIMEOUT")),
+            db_acquire_retries: get_var("REGISTRY_DB_ACQUIRE_RETRIES")
+                .map_or(3, |s| s.parse().expect("invalid REGISTRY_DB_ACQUIRE_RETRIES")),
+            db_acquire_backoff: get_var("REGISTRY_DB_ACQUIRE_BACKOFF")
+                .map_or(100, |s| s.parse().expect("invalid REGISTRY_DB_ACQUIRE_BACKOFF")),
+            db_acquire_max_wait: get_var("REGISTRY_DB_ACQUIRE_MAX_WAIT")
+                .map_or(2000, |s| s.parse().expect("invalid REGISTRY_DB_ACQUIRE_MAX_WAIT")),
+            db_max_connections: get_db_max_connections_var("REGISTRY_DB_MAX_CONNECTIONS", 16),
+            db_acquire_timeout: get_var("REGISTRY_DB_ACQUIRE_TIMEOUT").map_or(30 * 1000, |s| {
+                let value = s.parse().expect("invalid REGISTRY_DB_ACQUIRE_TIMEOUT");
+                assert!(
+                    value > 0,
+                    "invalid REGISTRY_DB_ACQUIRE_TIMEOUT: expected a positive number of milliseconds"
+                );
+                value
+            }),
+            db_journal_mode: get_pragma_var(
+                "REGISTRY_DB_JOURNAL_MODE",
+                "WAL",
+                &["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"],
+            ),
+            db_synchronous: get_pragma_var("REGISTRY_DB_SYNCHRONOUS", "NORMAL", &["OFF", "NORMAL", "FULL", "EXTRA"]),
+            db_busy_timeout: get_var("REGISTRY_DB_BUSY_TIMEOUT")
+                .map_or(5000, |s| s.parse().expect("invalid REGISTRY_DB_BUSY_TIMEOUT")),
             index,
             storage,
             storage_timeout: get_var("REGISTRY_STORAGE_TIMEOUT")
                 .map(|s| s.parse().expect("invalid REGISTRY_STORAGE_TIMEOUT"))
                 .unwrap_or(3000),
+            storage_dedup_readme: get_var("REGISTRY_STORAGE_DEDUP_README")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            storage_degraded_metadata: get_var("REGISTRY_STORAGE_DEGRADED_METADATA")
+                .map_or(true, |value| value == "1" || value.eq_ignore_ascii_case("true")),
+            storage_gc_interval_hours: get_var("REGISTRY_STORAGE_GC_INTERVAL_HOURS")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_STORAGE_GC_INTERVAL_HOURS")),
             oauth_login_uri: get_var("REGISTRY_OAUTH_LOGIN_URI")?,
             oauth_token_uri: get_var("REGISTRY_OAUTH_TOKEN_URI")?,
             oauth_callback_uri: get_var("REGISTRY_OAUTH_CALLBACK_URI")?,
@@ -413,15 +983,58 @@ impl Configuration {
             oauth_client_id: get_var("REGISTRY_OAUTH_CLIENT_ID")?,
             oauth_client_secret: get_var("REGISTRY_OAUTH_CLIENT_SECRET")?,
             oauth_client_scope: get_var("REGISTRY_OAUTH_CLIENT_SCOPE")?,
+            oauth_check_state: get_var("REGISTRY_OAUTH_CHECK_STATE")
+                .map_or(true, |value| value == "1" || value.eq_ignore_ascii_case("true")),
+            docs_check_toolchain: get_var("REGISTRY_DOCS_CHECK_TOOLCHAIN")
+                .map_or(true, |value| value == "1" || value.eq_ignore_ascii_case("true")),
+            docs_export_dir: get_var("REGISTRY_DOCS_EXPORT_DIR").ok(),
+            docs_gen_concurrency: get_var("REGISTRY_DOCS_GEN_CONCURRENCY")
+                .map_or(1, |s| s.parse().expect("invalid REGISTRY_DOCS_GEN_CONCURRENCY"))
+                .max(1),
+            docs_queue_capacity: get_var("REGISTRY_DOCS_QUEUE_CAPACITY")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_DOCS_QUEUE_CAPACITY")),
             deps_stale_registry: get_var("REGISTRY_DEPS_STALE_REGISTRY")
                 .map(|s| s.parse().expect("invalid REGISTRY_DEPS_STALE_REGISTRY"))
                 .unwrap_or(60 * 1000), // 1 minute
             deps_stale_analysis: get_var("REGISTRY_DEPS_STALE_ANALYSIS")
                 .map(|s| s.parse().expect("invalid REGISTRY_DEPS_STALE_ANALYSIS"))
                 .unwrap_or(24 * 60), // 24 hours
+            deps_max_depth: get_var("REGISTRY_DEPS_MAX_DEPTH")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_DEPS_MAX_DEPTH")),
+            deps_max_nodes: get_var("REGISTRY_DEPS_MAX_NODES")
+                .map_or(5000, |s| s.parse().expect("invalid REGISTRY_DEPS_MAX_NODES")),
+            deps_advisories_scan_interval: get_var("REGISTRY_DEPS_ADVISORIES_SCAN_INTERVAL")
+                .map_or(6 * 60, |s| s.parse().expect("invalid REGISTRY_DEPS_ADVISORIES_SCAN_INTERVAL")),
+            deps_allowed_licenses: get_licenses_var("REGISTRY_DEPS_LICENSES_ALLOWED"),
+            deps_denied_licenses: get_licenses_var("REGISTRY_DEPS_LICENSES_DENIED"),
             deps_notify_outdated,
             deps_notify_cves,
+            owners_attestation_days: get_var("REGISTRY_OWNERS_ATTESTATION_DAYS")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_OWNERS_ATTESTATION_DAYS")),
+            yank_expiry_hours: get_var("REGISTRY_YANK_EXPIRY_HOURS")
+                .map_or(168, |s| s.parse().expect("invalid REGISTRY_YANK_EXPIRY_HOURS")),
+            version_mutable_window: get_var("REGISTRY_VERSION_MUTABLE_WINDOW")
+                .map_or(0, |s| s.parse().expect("invalid REGISTRY_VERSION_MUTABLE_WINDOW")),
+            reindex_halt_on_failure: get_var("REGISTRY_REINDEX_HALT_ON_FAILURE")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
             email,
+            tokens: TokensConfig::from_env(),
+            publish_description_max_length: get_var("REGISTRY_PUBLISH_DESCRIPTION_MAX_LENGTH")
+                .map_or(1000, |s| s.parse().expect("invalid REGISTRY_PUBLISH_DESCRIPTION_MAX_LENGTH")),
+            publish_deny_advisories: get_var("REGISTRY_PUBLISH_DENY_ADVISORIES")
+                .ok()
+                .map(|value| AdvisorySeverityLevel::parse(&value).expect("invalid REGISTRY_PUBLISH_DENY_ADVISORIES")),
+            publish_policy: get_var("REGISTRY_PUBLISH_POLICY").ok().filter(|name| !name.is_empty()),
+            publish_notifications,
+            search_readme: get_var("REGISTRY_SEARCH_README")
+                .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+            search_popularity_half_life: get_var("REGISTRY_SEARCH_POPULARITY_HALF_LIFE")
+                .map_or(30, |s| s.parse().expect("invalid REGISTRY_SEARCH_POPULARITY_HALF_LIFE")),
+            hooks: HooksConfig::from_env(),
+            audit: AuditConfig::from_env(),
+            enrichment: EnrichmentConfig::from_env(),
+            webhooks: WebhooksConfig::from_env()?,
+            proxy: ProxyConfig::from_env(),
             self_local_name,
             self_service_login: super::generate_token(16),
             self_service_token: super::generate_token(64),
@@ -432,6 +1045,110 @@ impl Configuration {
         })
     }
 
+    /// Gets a copy of this configuration with all the secrets redacted, suitable to be shown to administrators
+    /// All fields are explicitly destructured so that adding a field requires deciding whether it is a secret.
+    #[must_use]
+    pub fn sanitized(&self) -> Configuration {
+        let mut result = self.clone();
+        let Configuration {
+            log_level: _,
+            log_datetime_format: _,
+            web_listenon_ip: _,
+            web_listenon_port: _,
+            web_public_uri: _,
+            web_domain: _,
+            web_body_limit: _,
+            web_downloads_max_per_token: _,
+            web_downloads_queue_timeout: _,
+            web_rate_limit_window: _,
+            web_rate_limit_reads: _,
+            web_rate_limit_publishes: _,
+            web_rate_limit_index: _,
+            web_download_links_key,
+            web_download_links_max_ttl: _,
+            web_sitemap: _,
+            web_crates_canonical_redirect: _,
+            web_root_redirect: _,
+            web_metrics_auth: _,
+            web_crates_cache_control: _,
+            web_content_cache_control: _,
+            web_not_found_page: _,
+            web_trusted_proxies: _,
+            data_dir: _,
+            db_migration_lock_timeout: _,
+            db_acquire_retries: _,
+            db_acquire_backoff: _,
+            db_acquire_max_wait: _,
+            db_max_connections: _,
+            db_acquire_timeout: _,
+            db_journal_mode: _,
+            db_synchronous: _,
+            db_busy_timeout: _,
+            index: _,
+            storage,
+            storage_timeout: _,
+            storage_dedup_readme: _,
+            storage_degraded_metadata: _,
+            storage_gc_interval_hours: _,
+            oauth_login_uri: _,
+            oauth_token_uri: _,
+            oauth_callback_uri: _,
+            oauth_userinfo_uri: _,
+            oauth_userinfo_path_email: _,
+            oauth_userinfo_path_fullname: _,
+            oauth_client_id: _,
+            oauth_client_secret,
+            oauth_client_scope: _,
+            oauth_check_state: _,
+            docs_check_toolchain: _,
+            docs_export_dir: _,
+            docs_gen_concurrency: _,
+            docs_queue_capacity: _,
+            external_registries,
+            deps_stale_registry: _,
+            deps_stale_analysis: _,
+            deps_max_depth: _,
+            deps_max_nodes: _,
+            deps_advisories_scan_interval: _,
+            deps_allowed_licenses: _,
+            deps_denied_licenses: _,
+            deps_notify_outdated: _,
+            deps_notify_cves: _,
+            owners_attestation_days: _,
+            yank_expiry_hours: _,
+            version_mutable_window: _,
+            reindex_halt_on_failure: _,
+            email,
+            tokens: _,
+            publish_description_max_length: _,
+            publish_deny_advisories: _,
+            publish_policy: _,
+            publish_notifications: _,
+            search_readme: _,
+            search_popularity_half_life: _,
+            hooks: _,
+            audit: _,
+            enrichment,
+            webhooks,
+            proxy: _,
+            self_local_name: _,
+            self_service_login: _,
+            self_service_token,
+            self_toolchain_version: _,
+            self_toolchain_host: _,
+            self_builtin_targets: _,
+        } = &mut result;
+        storage.redact_secrets();
+        web_download_links_key.iter_mut().for_each(redact);
+        redact(oauth_client_secret);
+        external_registries.iter_mut().for_each(ExternalRegistry::redact_secrets);
+        email.redact_secrets();
+        enrichment.redact_secrets();
+        webhooks.redact_secrets();
+        redact(self_service_token);
+        result
+    }
+
     /// Gets the name of the file for the database
     pub fn get_database_filename(&self) -> String {
         format!("{}/registry.db", self.data_dir)
@@ -442,6 +1159,15 @@ impl Configuration {
         format!("sqlite://{}/registry.db", self.data_dir)
     }
 
+    /// Gets the pragmas to apply on each new connection to the database
+    #[must_use]
+    pub fn get_database_pragmas(&self) -> String {
+        format!(
+            "PRAGMA journal_mode = {}; PRAGMA synchronous = {}; PRAGMA busy_timeout = {};",
+            self.db_journal_mode, self.db_synchronous, self.db_busy_timeout
+        )
+    }
+
     /// Gets the corresponding index git config
     pub fn get_index_git_config(&self) -> IndexConfig {
         self.index.clone()
@@ -546,6 +1272,16 @@ impl Configuration {
     }
 }
 
+/// The value replacing secrets in a sanitized configuration
+const REDACTED: &str = "<redacted>";
+
+/// Redacts a secret, keeping empty values as is to show that they are not set
+fn redact(secret: &mut String) {
+    if !secret.is_empty() {
+        *secret = REDACTED.to_string();
+    }
+}
+
 /// Gets the rustc version
 async fn get_rustc_version() -> String {
     let child = Command::new("rustc")
@@ -0,0 +1,229 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for crate metadata in the shape of the crates.io API, for interoperability with tools built against it
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use semver::Version;
+use serde_derive::{Deserialize, Serialize};
+
+use super::packages::CrateInfo;
+
+/// The response for a crate, as in `GET /api/v1/crates/{name}` on crates.io
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoCrateResponse {
+    /// The crate itself
+    #[serde(rename = "crate")]
+    pub krate: CratesIoCrate,
+    /// The versions of the crate, newest first
+    pub versions: Vec<CratesIoVersion>,
+    /// The keywords of the crate
+    pub keywords: Vec<CratesIoKeyword>,
+    /// The categories of the crate
+    pub categories: Vec<CratesIoCategory>,
+}
+
+/// A crate in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoCrate {
+    /// The identifier, this is the name of the crate
+    pub id: String,
+    /// The name of the crate
+    pub name: String,
+    /// The description of the last version
+    pub description: Option<String>,
+    /// The URL to the home page
+    pub homepage: Option<String>,
+    /// The URL to the documentation
+    pub documentation: Option<String>,
+    /// The URL to the source repository
+    pub repository: Option<String>,
+    /// The keywords of the last version
+    pub keywords: Vec<String>,
+    /// The categories of the last version
+    pub categories: Vec<String>,
+    /// The upload date time of the first version
+    pub created_at: NaiveDateTime,
+    /// The upload date time of the last version
+    pub updated_at: NaiveDateTime,
+    /// The total number of downloads for all versions
+    pub downloads: i64,
+    /// The highest version that is not yanked
+    pub max_version: String,
+    /// The highest version that is not yanked and not a pre-release, if any
+    pub max_stable_version: Option<String>,
+    /// The last uploaded version
+    pub newest_version: String,
+    /// The identifiers of the versions
+    pub versions: Vec<i64>,
+    /// The links to related resources
+    pub links: CratesIoCrateLinks,
+}
+
+/// The links to the resources related to a crate in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoCrateLinks {
+    /// The path to the owners of the crate
+    pub owners: String,
+    /// The path to the download statistics of the crate
+    pub version_downloads: String,
+}
+
+/// A crate version in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoVersion {
+    /// The identifier of the version, its position within the versions of the crate, starting at 1
+    pub id: i64,
+    /// The name of the crate
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// The version number
+    pub num: String,
+    /// The path to download the version
+    pub dl_path: String,
+    /// The path to the README of the version
+    pub readme_path: String,
+    /// The upload date time
+    pub created_at: NaiveDateTime,
+    /// The upload date time
+    pub updated_at: NaiveDateTime,
+    /// The number of downloads
+    pub downloads: i64,
+    /// The features of the version
+    pub features: HashMap<String, Vec<String>>,
+    /// Whether the version is yanked
+    pub yanked: bool,
+    /// The license, only known for the last version
+    pub license: Option<String>,
+    /// The minimum supported Rust version, only known for the last version
+    pub rust_version: Option<String>,
+    /// The SHA256 checksum of the `.crate` file
+    pub checksum: String,
+    /// The user that published the version
+    pub published_by: CratesIoUser,
+}
+
+/// A user in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoUser {
+    /// The identifier of the user
+    pub id: i64,
+    /// The login of the user
+    pub login: String,
+    /// The name of the user
+    pub name: String,
+}
+
+/// A keyword in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoKeyword {
+    /// The identifier, this is the keyword itself
+    pub id: String,
+    /// The keyword
+    pub keyword: String,
+}
+
+/// A category in the crates.io API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesIoCategory {
+    /// The identifier, this is the slug of the category
+    pub id: String,
+    /// The name of the category
+    pub category: String,
+    /// The slug of the category
+    pub slug: String,
+}
+
+impl CratesIoCrateResponse {
+    /// Maps the information about a crate into the shape of the crates.io API
+    /// Returns `None` when the crate has no version
+    #[must_use]
+    pub fn from_info(info: &CrateInfo) -> Option<Self> {
+        let first = info.versions.first()?;
+        let last = info.versions.last()?;
+        let name = &last.index.name;
+        let metadata = info.metadata.as_ref();
+        let max_version = |stable_only: bool| {
+            info.versions
+                .iter()
+                .filter(|version| !version.index.yanked)
+                .filter_map(|version| version.index.vers.parse::<Version>().ok())
+                .filter(|version| !stable_only || version.pre.is_empty())
+                .max()
+                .map(|version| version.to_string())
+        };
+        let versions = info
+            .versions
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, version)| {
+                let is_last = index + 1 == info.versions.len();
+                CratesIoVersion {
+                    id: i64::try_from(index + 1).unwrap_or(i64::MAX),
+                    krate: name.clone(),
+                    num: version.index.vers.clone(),
+                    dl_path: format!("/api/v1/crates/{name}/{}/download", version.index.vers),
+                    readme_path: format!("/api/v1/crates/{name}/{}/readme", version.index.vers),
+                    created_at: version.upload,
+                    updated_at: version.upload,
+                    downloads: version.download_count,
+                    features: version.index.get_all_features(),
+                    yanked: version.index.yanked,
+                    license: metadata.filter(|_| is_last).and_then(|m| m.license.clone()),
+                    rust_version: metadata.filter(|_| is_last).and_then(|m| m.rust_version.clone()),
+                    checksum: version.index.cksum.clone(),
+                    published_by: CratesIoUser {
+                        id: version.uploaded_by.id,
+                        login: version.uploaded_by.login.clone(),
+                        name: version.uploaded_by.name.clone(),
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        let keywords = metadata.map(|m| m.keywords.clone()).unwrap_or_default();
+        let categories = metadata.map(|m| m.categories.clone()).unwrap_or_default();
+        Some(Self {
+            krate: CratesIoCrate {
+                id: name.clone(),
+                name: name.clone(),
+                description: metadata.and_then(|m| m.description.clone()),
+                homepage: metadata.and_then(|m| m.homepage.clone()),
+                documentation: metadata.and_then(|m| m.documentation.clone()),
+                repository: metadata.and_then(|m| m.repository.clone()),
+                keywords: keywords.clone(),
+                categories: categories.clone(),
+                created_at: first.upload,
+                updated_at: last.upload,
+                downloads: info.versions.iter().map(|version| version.download_count).sum(),
+                max_version: max_version(false).unwrap_or_else(|| last.index.vers.clone()),
+                max_stable_version: max_version(true),
+                newest_version: last.index.vers.clone(),
+                versions: versions.iter().map(|version| version.id).collect(),
+                links: CratesIoCrateLinks {
+                    owners: format!("/api/v1/crates/{name}/owners"),
+                    version_downloads: format!("/api/v1/crates/{name}/dlstats"),
+                },
+            },
+            versions,
+            keywords: keywords
+                .into_iter()
+                .map(|keyword| CratesIoKeyword {
+                    id: keyword.clone(),
+                    keyword,
+                })
+                .collect(),
+            categories: categories
+                .into_iter()
+                .map(|category| CratesIoCategory {
+                    id: category.clone(),
+                    slug: category.clone(),
+                    category,
+                })
+                .collect(),
+        })
+    }
+}
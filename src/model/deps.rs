@@ -4,35 +4,57 @@
 
 //! Data types around dependency analysis
 
+use std::collections::{HashMap, VecDeque};
+
+use chrono::NaiveDateTime;
 use log::error;
 use serde_derive::{Deserialize, Serialize};
 
 use super::cargo::{DependencyKind, IndexCrateDependency, IndexCrateMetadata};
 use super::osv::SimpleAdvisory;
+use super::policy::{is_license_denied, is_license_not_allowed};
 use super::semver::{SemverVersion, SemverVersionReq};
+use super::CrateAndVersion;
 use crate::utils::apierror::ApiError;
 use crate::utils::push_if_not_present;
 
 /// The URI of the fake registry for built-in crates
 pub const BUILTIN_CRATES_REGISTRY_URI: &str = "<builtin>";
 
+/// The URI identifying crates.io as the registry for a dependency
+pub const CRATES_IO_REGISTRY_URI: &str = "https://github.com/rust-lang/crates.io-index";
+
 /// The list of built-in crates
 pub const BUILTIN_CRATES_LIST: &[&str] = &["core", "alloc", "std"];
 
 /// The complete dependency analysis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DepsAnalysis {
+    /// The targets for which the dependencies were resolved
+    /// These are the targets configured for dependency checks, which default to the build targets of the crate
+    pub targets: Vec<String>,
     /// The direct dependencies
     #[serde(rename = "directDependencies")]
     pub direct_dependencies: Vec<DirectDepInfo>,
     /// The advisories against dependencies
     pub advisories: Vec<DepAdvisory>,
+    /// Whether the dependency graph was truncated because it exceeded the configured limits
+    /// In this case, the advisories only cover the part of the graph that was resolved
+    #[serde(default)]
+    pub truncated: bool,
+    /// The dependencies whose license is not compliant with the configured allowed and denied licenses
+    #[serde(default)]
+    pub licenses: Vec<DepLicenseViolation>,
+    /// The dependencies whose license could not be checked because they are not hosted by this registry
+    #[serde(rename = "uncheckedLicenses", default)]
+    pub unchecked_licenses: Vec<String>,
 }
 
 impl DepsAnalysis {
     /// Creates the analysis
     pub fn new(graph: &DepsGraph, deps: &[IndexCrateDependency], advisories: Vec<DepAdvisory>) -> Self {
         Self {
+            targets: graph.targets.clone(),
             direct_dependencies: deps
                 .iter()
                 .filter(|d| d.kind != DependencyKind::Dev)
@@ -54,10 +76,65 @@ impl DepsAnalysis {
                 })
                 .collect(),
             advisories,
+            truncated: graph.truncated,
+            licenses: Vec::new(),
+            unchecked_licenses: Vec::new(),
+        }
+    }
+
+    /// Checks the licenses of the resolved dependencies against the allowed and denied licenses
+    /// `licenses` gives the license expression, if any, for the dependencies hosted by this registry, by name and version
+    pub fn check_licenses(
+        &mut self,
+        graph: &DepsGraph,
+        licenses: &HashMap<(String, String), Option<String>>,
+        allowed: &[String],
+        denied: &[String],
+    ) {
+        if allowed.is_empty() && denied.is_empty() {
+            return;
+        }
+        for dep in &graph.crates {
+            for resolution in &dep.resolutions {
+                let version = dep.versions[resolution.version_index].semver.to_string();
+                let Some(license) = licenses.get(&(dep.name.clone(), version.clone())) else {
+                    self.unchecked_licenses.push(format!("{}@{version}", dep.name));
+                    continue;
+                };
+                let message = match license {
+                    None if !allowed.is_empty() => Some(String::from("no license is specified")),
+                    Some(license) if is_license_denied(license, denied) => Some(format!("license {license} is denied")),
+                    Some(license) if !allowed.is_empty() && is_license_not_allowed(license, allowed) => {
+                        Some(format!("license {license} is not allowed"))
+                    }
+                    _ => None,
+                };
+                if let Some(message) = message {
+                    self.licenses.push(DepLicenseViolation {
+                        package: dep.name.clone(),
+                        version,
+                        license: license.clone(),
+                        message,
+                    });
+                }
+            }
         }
     }
 }
 
+/// A dependency with a license that is not compliant with the configured allowed and denied licenses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepLicenseViolation {
+    /// The name of the dependency
+    pub package: String,
+    /// The resolved version of the dependency
+    pub version: String,
+    /// The license expression of the dependency, if any
+    pub license: Option<String>,
+    /// A description of the violation
+    pub message: String,
+}
+
 /// The information about a direct dependency, resulting from an analysis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectDepInfo {
@@ -88,6 +165,51 @@ pub struct DepAdvisory {
     pub content: SimpleAdvisory,
 }
 
+/// A crate affected by an advisory through its dependencies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdvisoryAffectedCrate {
+    /// The name of the affected crate
+    pub package: String,
+    /// The affected version, the last one for the crate
+    pub version: String,
+    /// The advisory against the dependency
+    pub advisory: DepAdvisory,
+    /// The path of dependencies introducing the vulnerable one, from a direct dependency to the vulnerable one
+    pub path: Vec<CrateAndVersion>,
+}
+
+/// The state of the last scan of a crate against the advisories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateAdvisoryScan {
+    /// The scanned version, the last one for the crate
+    pub version: String,
+    /// The targets used to resolve the dependencies
+    pub targets: Vec<String>,
+    /// The resolved dependencies, direct or indirect
+    pub dependencies: Vec<CrateAndVersion>,
+}
+
+/// An advisory found by the periodic scan against the dependencies of the last version of a crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AffectedCrateAdvisory {
+    /// The name of the affected crate
+    pub package: String,
+    /// The affected version, the last one for the crate
+    pub version: String,
+    /// The identifier of the advisory
+    pub advisory: String,
+    /// The summary of the advisory
+    pub summary: String,
+    /// The name of the vulnerable dependency
+    pub dependency: String,
+    /// The resolved version of the vulnerable dependency
+    #[serde(rename = "dependencyVersion")]
+    pub dependency_version: String,
+    /// The timestamp of the scan that first found the advisory
+    #[serde(rename = "detectedOn")]
+    pub detected_on: NaiveDateTime,
+}
+
 impl IndexCrateMetadata {
     /// Assumes this is the metadata for a crate in an external registry, including crates.io
     /// Find and rewrite the registry for built-in crates
@@ -105,6 +227,85 @@ impl IndexCrateMetadata {
     }
 }
 
+/// The source of a crate in an exported dependency graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepsGraphExportSource {
+    /// The crate is hosted by this registry
+    #[serde(rename = "local")]
+    Local,
+    /// The crate is hosted on crates.io
+    #[serde(rename = "crates.io")]
+    CratesIo,
+    /// The crate is built into the toolchain (core, alloc, std)
+    #[serde(rename = "builtin")]
+    Builtin,
+    /// The crate is hosted on another registry
+    #[serde(rename = "external")]
+    External,
+}
+
+impl DepsGraphExportSource {
+    /// Gets the source for the URI of a registry, `None` for the local one
+    #[must_use]
+    pub fn from_registry(registry: Option<&str>) -> Self {
+        match registry {
+            None => Self::Local,
+            Some(BUILTIN_CRATES_REGISTRY_URI) => Self::Builtin,
+            Some(CRATES_IO_REGISTRY_URI) => Self::CratesIo,
+            Some(_) => Self::External,
+        }
+    }
+}
+
+/// A node in an exported dependency graph, i.e. a resolved crate version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsGraphExportNode {
+    /// The identifier of the node within the graph, 0 for the root crate
+    pub id: usize,
+    /// The name of the crate
+    #[serde(rename = "crate")]
+    pub package: String,
+    /// The resolved version
+    pub version: String,
+    /// The source of the crate
+    pub source: DepsGraphExportSource,
+    /// URI for the owning registry, `None` for the local one
+    pub registry: Option<String>,
+}
+
+/// An edge in an exported dependency graph, from a dependent to a dependency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsGraphExportEdge {
+    /// The identifier of the dependent node
+    pub from: usize,
+    /// The identifier of the dependency node
+    pub to: usize,
+    /// The kind of dependency
+    pub kind: DependencyKind,
+    /// Whether the dependency is optional
+    pub optional: bool,
+    /// The semver requirement for this dependency
+    pub req: String,
+}
+
+/// The complete dependency graph of a crate version, as nodes and edges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsGraphExport {
+    /// The name of the root crate
+    #[serde(rename = "crate")]
+    pub package: String,
+    /// The version of the root crate
+    pub version: String,
+    /// The targets for which the dependencies were resolved
+    pub targets: Vec<String>,
+    /// The resolved crate versions, the root crate being the first
+    pub nodes: Vec<DepsGraphExportNode>,
+    /// The dependencies between the nodes
+    pub edges: Vec<DepsGraphExportEdge>,
+    /// Whether the dependency graph was truncated because it exceeded the configured limits
+    pub truncated: bool,
+}
+
 /// A complete dependency graphs
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DepsGraph {
@@ -116,6 +317,14 @@ pub struct DepsGraph {
     pub unknowns: Vec<(Option<String>, String)>,
     /// The crate and resolution to analyse
     pub dirty: Vec<(usize, usize)>,
+    /// The maximum depth of the dependencies to resolve, 0 for no limit
+    #[serde(rename = "maxDepth")]
+    pub max_depth: usize,
+    /// The maximum number of crates in the graph, 0 for no limit
+    #[serde(rename = "maxNodes")]
+    pub max_nodes: usize,
+    /// Whether some dependencies were not resolved because of the limits
+    pub truncated: bool,
 }
 
 impl DepsGraph {
@@ -127,6 +336,16 @@ impl DepsGraph {
         }
     }
 
+    /// Sets the limits for the resolution of this graph, 0 for no limit
+    #[must_use]
+    pub fn with_limits(self, max_depth: usize, max_nodes: usize) -> Self {
+        Self {
+            max_depth,
+            max_nodes,
+            ..self
+        }
+    }
+
     /// Gets the crate in the graph, if it exists
     pub fn get_crate(&mut self, registry: Option<&str>, name: &str) -> Option<(usize, &mut DepsGraphCrate)> {
         self.crates
@@ -135,17 +354,188 @@ impl DepsGraph {
             .find(|(_, c)| c.registry.as_deref() == registry && c.name == name)
     }
 
+    /// Finds a shortest path of dependencies leading to a resolved version of a crate
+    /// The path starts with a direct dependency and ends with the crate itself
+    pub fn find_path(&self, name: &str, version: &SemverVersion) -> Option<Vec<CrateAndVersion>> {
+        // the resolution that led to each visited one, none for the direct dependencies
+        let mut previous = HashMap::<(usize, usize), Option<(usize, usize)>>::new();
+        let mut queue = VecDeque::new();
+        for (crate_index, c) in self.crates.iter().enumerate() {
+            for (resolution_index, resolution) in c.resolutions.iter().enumerate() {
+                if resolution
+                    .origins
+                    .iter()
+                    .any(|origin| matches!(origin, DepsGraphCrateOrigin::Direct(_)))
+                {
+                    previous.insert((crate_index, resolution_index), None);
+                    queue.push_back((crate_index, resolution_index));
+                }
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            let (crate_index, resolution_index) = node;
+            let c = &self.crates[crate_index];
+            if c.name == name && &c.versions[c.resolutions[resolution_index].version_index].semver == version {
+                let mut path = Vec::new();
+                let mut current = Some(node);
+                while let Some((crate_index, resolution_index)) = current {
+                    let c = &self.crates[crate_index];
+                    path.push(CrateAndVersion {
+                        name: c.name.clone(),
+                        version: c.versions[c.resolutions[resolution_index].version_index].semver.to_string(),
+                    });
+                    current = previous[&(crate_index, resolution_index)];
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (dep, _) in c.get_active_deps_in(resolution_index, &self.targets) {
+                if dep.kind == DependencyKind::Dev {
+                    // dev-dependencies of dependencies are not resolved
+                    continue;
+                }
+                let Ok(req) = dep.req.parse::<semver::VersionReq>() else {
+                    continue;
+                };
+                let Some((child_index, child)) = self
+                    .crates
+                    .iter()
+                    .enumerate()
+                    .find(|(_, child)| child.registry == dep.registry && child.name == dep.get_name())
+                else {
+                    continue;
+                };
+                for (child_resolution_index, resolution) in child.resolutions.iter().enumerate() {
+                    let next = (child_index, child_resolution_index);
+                    if !previous.contains_key(&next) && req.matches(&child.versions[resolution.version_index].semver.0) {
+                        previous.insert(next, Some(node));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Gets all the resolved crate versions in this graph
+    pub fn get_resolved(&self) -> Vec<CrateAndVersion> {
+        self.crates
+            .iter()
+            .flat_map(|c| {
+                c.resolutions.iter().map(|resolution| CrateAndVersion {
+                    name: c.name.clone(),
+                    version: c.versions[resolution.version_index].semver.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Exports this graph as nodes and edges, rooted at a crate version with the specified direct dependencies
+    /// Each resolved crate version is visited only once, so that cycles between dependencies terminate
+    #[must_use]
+    pub fn export(&self, package: &str, version: &str, deps: &[IndexCrateDependency]) -> DepsGraphExport {
+        let mut export = DepsGraphExport {
+            package: package.to_string(),
+            version: version.to_string(),
+            targets: self.targets.clone(),
+            nodes: vec![DepsGraphExportNode {
+                id: 0,
+                package: package.to_string(),
+                version: version.to_string(),
+                source: DepsGraphExportSource::Local,
+                registry: None,
+            }],
+            edges: Vec::new(),
+            truncated: self.truncated,
+        };
+        // the node for each visited crate and resolution
+        let mut visited = HashMap::<(usize, usize), usize>::new();
+        let mut queue = VecDeque::new();
+        let directs = deps.iter().filter(|dep| dep.is_active_for(&self.targets, &[]));
+        self.export_edges(0, directs, &mut export, &mut visited, &mut queue);
+        while let Some((crate_index, resolution_index)) = queue.pop_front() {
+            let from = visited[&(crate_index, resolution_index)];
+            // dev-dependencies of dependencies are not resolved
+            let deps = self.crates[crate_index]
+                .get_active_deps_in(resolution_index, &self.targets)
+                .map(|(dep, _)| dep)
+                .filter(|dep| dep.kind != DependencyKind::Dev);
+            self.export_edges(from, deps, &mut export, &mut visited, &mut queue);
+        }
+        export
+    }
+
+    /// Adds the edges from a node to the resolutions of its dependencies, queueing the newly visited ones
+    fn export_edges<'a>(
+        &self,
+        from: usize,
+        deps: impl Iterator<Item = &'a IndexCrateDependency>,
+        export: &mut DepsGraphExport,
+        visited: &mut HashMap<(usize, usize), usize>,
+        queue: &mut VecDeque<(usize, usize)>,
+    ) {
+        for dep in deps {
+            let Ok(req) = dep.req.parse::<semver::VersionReq>() else {
+                continue;
+            };
+            let Some((child_index, child)) = self
+                .crates
+                .iter()
+                .enumerate()
+                .find(|(_, child)| child.registry == dep.registry && child.name == dep.get_name())
+            else {
+                continue;
+            };
+            // the greatest resolved version that matches the requirement
+            let Some((resolution_index, resolution)) = child
+                .resolutions
+                .iter()
+                .enumerate()
+                .filter(|(_, resolution)| req.matches(&child.versions[resolution.version_index].semver.0))
+                .max_by(|(_, r1), (_, r2)| {
+                    child.versions[r1.version_index]
+                        .semver
+                        .0
+                        .cmp(&child.versions[r2.version_index].semver.0)
+                })
+            else {
+                continue;
+            };
+            let to = *visited.entry((child_index, resolution_index)).or_insert_with(|| {
+                let id = export.nodes.len();
+                export.nodes.push(DepsGraphExportNode {
+                    id,
+                    package: child.name.clone(),
+                    version: child.versions[resolution.version_index].semver.to_string(),
+                    source: DepsGraphExportSource::from_registry(child.registry.as_deref()),
+                    registry: child.registry.clone(),
+                });
+                queue.push_back((child_index, resolution_index));
+                id
+            });
+            export.edges.push(DepsGraphExportEdge {
+                from,
+                to,
+                kind: dep.kind,
+                optional: dep.optional,
+                req: dep.req.clone(),
+            });
+        }
+    }
+
     /// Gets whether this is a known failing crate
     pub fn is_unknown(&self, registry: Option<&str>, name: &str) -> bool {
         self.unknowns.iter().any(|(r, n)| r.as_deref() == registry && n == name)
     }
 
     /// Resolves a dependency within this graph
+    /// The depth is 1 for the direct dependencies
     pub async fn resolve<F, FUT>(
         &mut self,
         dep: &IndexCrateDependency,
         features: &[String],
         origins: &[DepsGraphCrateOrigin],
+        depth: usize,
         get_versions: &F,
     ) -> Result<(), ApiError>
     where
@@ -153,9 +543,12 @@ impl DepsGraph {
         FUT: std::future::Future<Output = Result<Vec<IndexCrateMetadata>, ApiError>>,
     {
         if let Some((crate_index, c)) = self.get_crate(dep.registry.as_deref(), dep.get_name()) {
+            c.depth = c.depth.min(depth);
             if let Some(resolution_index) = c.resolve(dep, features, origins) {
                 push_if_not_present(&mut self.dirty, (crate_index, resolution_index));
             }
+        } else if self.max_nodes > 0 && self.crates.len() >= self.max_nodes {
+            self.truncated = true;
         } else if !self.is_unknown(dep.registry.as_deref(), dep.get_name()) {
             let all_versions = match get_versions(dep.registry.clone(), dep.get_name().to_string()).await {
                 Ok(d) => d,
@@ -165,7 +558,7 @@ impl DepsGraph {
                     return Ok(());
                 }
             };
-            self.crates.push(DepsGraphCrate::new(dep, all_versions)?);
+            self.crates.push(DepsGraphCrate::new(dep, all_versions, depth)?);
             let crate_index = self.crates.len() - 1;
             if let Some(resolution_index) = self.crates.last_mut().unwrap().resolve(dep, features, origins) {
                 push_if_not_present(&mut self.dirty, (crate_index, resolution_index));
@@ -178,12 +571,19 @@ impl DepsGraph {
     ///
     /// Closes over the direct dependencies already in the graph.
     /// The direct dependencies include normal, dev and build dependencies
+    /// The dependencies of crates at the maximum depth are not resolved
     pub async fn close<F, FUT>(&mut self, get_versions: &F) -> Result<(), ApiError>
     where
         F: Fn(Option<String>, String) -> FUT,
         FUT: std::future::Future<Output = Result<Vec<IndexCrateMetadata>, ApiError>>,
     {
         while let Some((crate_index, resolution_index)) = self.dirty.pop() {
+            let depth = self.crates[crate_index].depth + 1;
+            if self.max_depth > 0 && depth > self.max_depth {
+                // too deep, do not go further
+                self.truncated = true;
+                continue;
+            }
             // new selected version/origin
             let dependencies = self.crates[crate_index]
                 .get_active_deps_in(resolution_index, &self.targets)
@@ -196,7 +596,7 @@ impl DepsGraph {
                     .filter_map(|&origin| origin.child_of_kind(dep.kind))
                     .collect::<Vec<_>>();
                 if !origins.is_empty() {
-                    self.resolve(&dep, &features, &origins, get_versions).await?;
+                    self.resolve(&dep, &features, &origins, depth, get_versions).await?;
                 }
             }
         }
@@ -286,11 +686,13 @@ pub struct DepsGraphCrate {
     pub resolutions: Vec<DepsGraphCrateResolution>,
     /// The list of unresolved requirements for this crate
     pub unresolved: Vec<SemverVersionReq>,
+    /// The smallest depth at which this crate appears in the graph, 1 for direct dependencies
+    pub depth: usize,
 }
 
 impl DepsGraphCrate {
     /// Creates the data for this crate
-    pub fn new(package: &IndexCrateDependency, versions: Vec<IndexCrateMetadata>) -> Result<Self, semver::Error> {
+    pub fn new(package: &IndexCrateDependency, versions: Vec<IndexCrateMetadata>, depth: usize) -> Result<Self, semver::Error> {
         let semvers = versions
             .iter()
             .map(|v| v.vers.parse::<SemverVersion>())
@@ -312,6 +714,7 @@ impl DepsGraphCrate {
             last_version,
             resolutions: Vec::new(),
             unresolved: Vec::new(),
+            depth,
         })
     }
 
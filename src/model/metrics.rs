@@ -0,0 +1,110 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for exposing metrics in the Prometheus text format
+
+use std::fmt::{Display, Formatter, Write};
+
+/// The type of a metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A value that only goes up, except on restart
+    Counter,
+    /// A value that can go up and down
+    Gauge,
+}
+
+impl MetricKind {
+    /// Gets the name of the type in the text format
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+        }
+    }
+}
+
+/// A family of samples sharing the same name
+#[derive(Debug, Clone)]
+struct MetricFamily {
+    /// The name of the metric
+    name: &'static str,
+    /// The help text
+    help: &'static str,
+    /// The type of metric
+    kind: MetricKind,
+    /// The samples, with their labels
+    samples: Vec<(Vec<(&'static str, String)>, u64)>,
+}
+
+/// A lightweight registry of metrics, built on demand and formatted in the Prometheus text format
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// The families of metrics
+    families: Vec<MetricFamily>,
+}
+
+impl Metrics {
+    /// Adds a metric with a single sample without labels
+    pub fn add(&mut self, name: &'static str, help: &'static str, kind: MetricKind, value: u64) {
+        self.families.push(MetricFamily {
+            name,
+            help,
+            kind,
+            samples: vec![(Vec::new(), value)],
+        });
+    }
+
+    /// Adds a sample with labels to a metric, registering the metric when it is new
+    pub fn add_labelled(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        kind: MetricKind,
+        labels: &[(&'static str, &str)],
+        value: u64,
+    ) {
+        let labels = labels.iter().map(|(key, value)| (*key, (*value).to_string())).collect();
+        if let Some(family) = self.families.iter_mut().find(|family| family.name == name) {
+            family.samples.push((labels, value));
+        } else {
+            self.families.push(MetricFamily {
+                name,
+                help,
+                kind,
+                samples: vec![(labels, value)],
+            });
+        }
+    }
+}
+
+/// Escapes the value of a label
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for family in &self.families {
+            writeln!(f, "# HELP {} {}", family.name, family.help)?;
+            writeln!(f, "# TYPE {} {}", family.name, family.kind.as_str())?;
+            for (labels, value) in &family.samples {
+                f.write_str(family.name)?;
+                if !labels.is_empty() {
+                    f.write_char('{')?;
+                    for (index, (key, value)) in labels.iter().enumerate() {
+                        if index > 0 {
+                            f.write_char(',')?;
+                        }
+                        write!(f, "{key}=\"{}\"", escape_label_value(value))?;
+                    }
+                    f.write_char('}')?;
+                }
+                writeln!(f, " {value}")?;
+            }
+        }
+        Ok(())
+    }
+}
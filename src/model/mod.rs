@@ -4,21 +4,29 @@
 
 //! Data model
 
+pub mod audit;
 pub mod auth;
 pub mod cargo;
 pub mod config;
+pub mod cratesio;
 pub mod deps;
 pub mod errors;
+pub mod metrics;
 pub mod namegen;
 pub mod osv;
 pub mod packages;
+pub mod policy;
 pub mod semver;
 pub mod stats;
+pub mod webhooks;
+pub mod workers;
 
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde_derive::{Deserialize, Serialize};
 
+use self::config::Configuration;
+
 /// The object representing the application version
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AppVersion {
@@ -28,6 +36,85 @@ pub struct AppVersion {
     pub tag: String,
 }
 
+/// The description of the registry and its endpoints for API clients
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegistryDescriptor {
+    /// The name of the registry, as used in the configuration of cargo
+    pub name: String,
+    /// The version of the application
+    pub version: AppVersion,
+    /// The URI of the web application
+    pub webapp: String,
+    /// The URI of the API
+    pub api: String,
+    /// The URI of the index for cargo's sparse protocol, if allowed
+    #[serde(rename = "indexSparse")]
+    pub index_sparse: Option<String>,
+    /// The URI of the index for cargo's git protocol, if allowed
+    #[serde(rename = "indexGit")]
+    pub index_git: Option<String>,
+    /// The URI of the documentation of crates
+    pub docs: String,
+}
+
+/// The status of a protocol for the index
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexProtocolStatus {
+    /// The name of the protocol, `sparse` or `git`
+    pub protocol: String,
+    /// Whether the protocol is enabled
+    pub enabled: bool,
+    /// The URI of the index for this protocol, as used in the configuration of cargo
+    pub index: String,
+}
+
+/// The protocols offered for the index, to configure cargo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexProtocols {
+    /// The name of the registry, as used in the configuration of cargo
+    pub name: String,
+    /// The status of each protocol
+    pub protocols: Vec<IndexProtocolStatus>,
+    /// The protocol to use by default, sparse when enabled, if any is enabled
+    pub preferred: Option<String>,
+    /// The content to add to `.cargo/config.toml` to use the registry with the preferred protocol, if any is enabled
+    #[serde(rename = "cargoConfig")]
+    pub cargo_config: Option<String>,
+}
+
+impl IndexProtocols {
+    /// Gets the protocols offered for the index in a configuration
+    #[must_use]
+    pub fn new(configuration: &Configuration) -> Self {
+        let uri = &configuration.web_public_uri;
+        let protocols = vec![
+            IndexProtocolStatus {
+                protocol: String::from("sparse"),
+                enabled: configuration.index.allow_protocol_sparse,
+                index: format!("sparse+{uri}/"),
+            },
+            IndexProtocolStatus {
+                protocol: String::from("git"),
+                enabled: configuration.index.allow_protocol_git,
+                index: uri.clone(),
+            },
+        ];
+        let preferred = protocols.iter().find(|status| status.enabled);
+        let cargo_config = preferred.map(|status| {
+            format!(
+                "[registry]\nglobal-credential-providers = [\"cargo:token\"]\n\n[registries]\n{} = {{ index = \"{}\" }}\n",
+                configuration.self_local_name, status.index
+            )
+        });
+        Self {
+            name: configuration.self_local_name.clone(),
+            preferred: preferred.map(|status| status.protocol.clone()),
+            cargo_config,
+            protocols,
+        }
+    }
+}
+
 /// Generates a token
 pub fn generate_token(length: usize) -> String {
     let rng = thread_rng();
@@ -134,6 +134,9 @@ pub struct SimpleAdvisory {
     pub ranges: Vec<SimpleAdvisoryRange>,
     /// The affected versions
     pub versions: Vec<SemverVersion>,
+    /// The CVSS v3 base score, if known
+    #[serde(default)]
+    pub score: Option<f32>,
 }
 
 impl SimpleAdvisory {
@@ -179,6 +182,12 @@ impl TryFrom<Advisory> for SimpleAdvisory {
             .map(|v| v.parse())
             .collect::<Result<Vec<_>, _>>()
             .map_err(|_| ())?;
+        let score = advisory
+            .severity
+            .iter()
+            .chain(&affected.severity)
+            .filter(|severity| severity.type_value == "CVSS_V3")
+            .find_map(|severity| cvss3_base_score(&severity.score));
         Ok(Self {
             package: affected.package.name,
             id: advisory.id,
@@ -187,6 +196,93 @@ impl TryFrom<Advisory> for SimpleAdvisory {
             summary: advisory.summary,
             ranges,
             versions,
+            score,
         })
     }
 }
+
+/// A level of severity for advisories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdvisorySeverityLevel {
+    /// Any advisory, including those without a score
+    Any,
+    /// A CVSS score of at least 0.1
+    Low,
+    /// A CVSS score of at least 4.0
+    Medium,
+    /// A CVSS score of at least 7.0
+    High,
+    /// A CVSS score of at least 9.0
+    Critical,
+}
+
+impl AdvisorySeverityLevel {
+    /// Parses a level from its name
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "any" => Some(Self::Any),
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+
+    /// Gets whether an advisory is at or above this level
+    pub fn is_reached_by(self, advisory: &SimpleAdvisory) -> bool {
+        let minimum = match self {
+            Self::Any => return true,
+            Self::Low => 0.1,
+            Self::Medium => 4.0,
+            Self::High => 7.0,
+            Self::Critical => 9.0,
+        };
+        advisory.score.is_some_and(|score| score >= minimum)
+    }
+}
+
+/// Computes the base score of a CVSS v3 vector, such as `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+fn cvss3_base_score(vector: &str) -> Option<f32> {
+    let metrics = vector.split('/').skip(1).filter_map(|metric| metric.split_once(':'));
+    let get = |name: &str| metrics.clone().find(|(n, _)| *n == name).map(|(_, value)| value);
+    let changed = get("S")? == "C";
+    let attack_vector = match get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        _ => 0.2,
+    };
+    let attack_complexity = if get("AC")? == "L" { 0.77 } else { 0.44 };
+    let privileges = match (get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        (_, false) => 0.27,
+        (_, true) => 0.5,
+    };
+    let user_interaction = if get("UI")? == "N" { 0.85 } else { 0.62 };
+    let impact_of = |value: &str| match value {
+        "H" => 0.56,
+        "L" => 0.22,
+        _ => 0.0,
+    };
+    let iss = 1.0 - (1.0 - impact_of(get("C")?)) * (1.0 - impact_of(get("I")?)) * (1.0 - impact_of(get("A")?));
+    let impact = if changed {
+        7.52 * (iss - 0.029) - 3.25 * f64::powi(iss - 0.02, 15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges * user_interaction;
+    let score = if changed {
+        f64::min(1.08 * (impact + exploitability), 10.0)
+    } else {
+        f64::min(impact + exploitability, 10.0)
+    };
+    // round up to one decimal
+    #[allow(clippy::cast_possible_truncation)]
+    Some(((score * 10.0).ceil() / 10.0) as f32)
+}
@@ -4,20 +4,105 @@
 
 //! Data types for crate information and description, in addition to Cargo types
 
+use std::fmt::Write;
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
 use serde_derive::{Deserialize, Serialize};
 
 use super::cargo::{CrateMetadata, IndexCrateMetadata, RegistryUser};
+use crate::utils::apierror::{error_invalid_request, specialize, ApiError};
 
 /// Gets the last info for a crate
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrateInfo {
     /// The last metadata, if any
     pub metadata: Option<CrateMetadata>,
+    /// Whether the metadata could not be loaded from the storage, in which case they are missing
+    #[serde(rename = "metadataUnavailable")]
+    pub metadata_unavailable: bool,
     /// Gets the versions in the index
     pub versions: Vec<CrateInfoVersion>,
     /// The build targets to use (for docs generation and deps analysis)
     pub targets: Vec<String>,
+    /// The targets specifically used for the deps analysis, if they differ from the build targets
+    #[serde(rename = "depsTargets")]
+    pub deps_targets: Option<Vec<String>>,
+    /// The results of the verification of the last version on each target
+    pub builds: Vec<CrateVersionBuild>,
+    /// The results of the generation of the documentation of the last version on each target
+    pub docs: Vec<CrateVersionDocs>,
+    /// Whether the documentation is generated and served for this crate
+    #[serde(rename = "docsEnabled")]
+    pub docs_enabled: bool,
+    /// The URL of the documentation hosted elsewhere, if any, in place of the generated documentation
+    #[serde(rename = "docsExternalUrl")]
+    pub docs_external_url: Option<String>,
+    /// The metadata fetched from the repository of the crate, if any
+    pub repository: Option<CrateRepositoryInfo>,
+}
+
+/// The metadata about the repository of a crate, as fetched from its host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateRepositoryInfo {
+    /// The number of stars of the repository, if known
+    pub stars: Option<i64>,
+    /// The last activity (push) on the repository, if known
+    #[serde(rename = "lastActivity")]
+    pub last_activity: Option<NaiveDateTime>,
+    /// The last time the metadata were fetched
+    #[serde(rename = "lastCheck")]
+    pub last_check: NaiveDateTime,
+}
+
+impl CrateInfo {
+    /// Builds the `OpenGraph` meta tags (see <https://ogp.me/>) for the page of a crate, used by messaging platforms to render previews
+    /// When no version is given, the last one is used
+    #[must_use]
+    pub fn to_open_graph_tags(&self, web_public_uri: &str, version: Option<&str>) -> Option<String> {
+        let last = self.versions.last()?;
+        let version = version
+            .and_then(|version| self.versions.iter().find(|v| v.index.vers == version))
+            .unwrap_or(last);
+        let name = &version.index.name;
+        let vers = &version.index.vers;
+        let description = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.as_deref())
+            .unwrap_or_default();
+        Some(open_graph_tags(
+            &format!("{name} {vers}"),
+            description,
+            &format!("{web_public_uri}/crates/{name}/{vers}"),
+        ))
+    }
+}
+
+/// Builds the `OpenGraph` meta tags for a page
+#[must_use]
+pub fn open_graph_tags(title: &str, description: &str, url: &str) -> String {
+    let title = escape_html(title);
+    let description = escape_html(description);
+    let url = escape_html(url);
+    format!(
+        "<meta name=\"description\" content=\"{description}\">
+  <meta property=\"og:type\" content=\"website\">
+  <meta property=\"og:site_name\" content=\"Cratery\">
+  <meta property=\"og:title\" content=\"{title}\">
+  <meta property=\"og:description\" content=\"{description}\">
+  <meta property=\"og:url\" content=\"{url}\">"
+    )
+}
+
+/// Escapes a text for its inclusion in HTML content or attribute values
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
 /// The data for a crate version
@@ -31,6 +116,9 @@ pub struct CrateInfoVersion {
     /// The user that uploaded the version
     #[serde(rename = "uploadedBy")]
     pub uploaded_by: RegistryUser,
+    /// The user agent of the client that uploaded the version, if known
+    #[serde(rename = "uploadUserAgent")]
+    pub upload_user_agent: Option<String>,
     /// Whether documentation was generated for this version
     #[serde(rename = "hasDocs")]
     pub has_docs: bool,
@@ -49,4 +137,540 @@ pub struct CrateInfoVersion {
     /// Flag whether CVEs have been filed against dependencies of this crate
     #[serde(rename = "depsHasCVEs")]
     pub deps_has_cves: bool,
+    /// When the version is yanked with an expiry, the time at which it is automatically unyanked
+    #[serde(rename = "yankExpiry")]
+    pub yank_expiry: Option<NaiveDateTime>,
+}
+
+/// A version of a crate in a list sorted according to semver
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateVersionSummary {
+    /// The version number
+    pub version: String,
+    /// Whether the version is yanked
+    pub yanked: bool,
+    /// Whether this is a pre-release version
+    pub prerelease: bool,
+    /// Whether this is the latest stable version, i.e. the greatest version neither yanked nor a pre-release
+    #[serde(rename = "latestStable")]
+    pub latest_stable: bool,
+}
+
+/// The result of the verification of a crate version for a build target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateVersionBuild {
+    /// The build target
+    pub target: String,
+    /// Whether the crate version builds for this target
+    pub success: bool,
+    /// The error message when the verification failed
+    pub message: String,
+    /// The last time this verification was performed
+    #[serde(rename = "lastCheck")]
+    pub last_check: NaiveDateTime,
+}
+
+/// The result of the generation of the documentation of a crate version for a build target, or a variant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateVersionDocs {
+    /// The build target
+    pub target: String,
+    /// The variant of the documentation, `None` for the main documentation
+    #[serde(default)]
+    pub variant: Option<String>,
+    /// Whether the documentation could be generated for this target
+    pub success: bool,
+    /// The last time the documentation was generated
+    #[serde(rename = "lastUpdate")]
+    pub last_update: NaiveDateTime,
+}
+
+/// The effective permissions of the current authentication on a crate
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct CratePermissions {
+    /// Whether the user is an owner of the crate
+    #[serde(rename = "isOwner")]
+    pub is_owner: bool,
+    /// Whether the user is an administrator of the registry, who can act on all crates
+    #[serde(rename = "isAdmin")]
+    pub is_admin: bool,
+    /// Whether new versions of the crate can be published
+    #[serde(rename = "canPublish")]
+    pub can_publish: bool,
+    /// Whether versions of the crate can be yanked and unyanked
+    #[serde(rename = "canYank")]
+    pub can_yank: bool,
+    /// Whether the owners, targets and documentation settings of the crate can be changed
+    #[serde(rename = "canManage")]
+    pub can_manage: bool,
+}
+
+/// The information required to manage a crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateManagementInfo {
+    /// The owners of the crate
+    pub owners: Vec<RegistryUser>,
+    /// The build targets to use (for docs generation and deps analysis)
+    pub targets: Vec<String>,
+    /// The targets for the resolution of dependencies, when different from the build targets
+    #[serde(rename = "depsTargets")]
+    pub deps_targets: Option<Vec<String>>,
+    /// Whether the documentation is generated and served for this crate
+    #[serde(rename = "docsEnabled")]
+    pub docs_enabled: bool,
+    /// The URL of the documentation hosted elsewhere, if any, in place of the generated documentation
+    #[serde(rename = "docsExternalUrl")]
+    pub docs_external_url: Option<String>,
+    /// The settings for the generation of the documentation
+    #[serde(rename = "docsBuild")]
+    pub docs_build: CrateDocsBuildSettings,
+    /// The effective permissions of the current authentication on the crate
+    pub permissions: CratePermissions,
+}
+
+/// A request to change whether the documentation is generated and served for a crate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateDocsSettings {
+    /// Whether the documentation is generated and served
+    pub enabled: bool,
+    /// Whether to delete the documentation that was already generated
+    #[serde(default)]
+    pub purge: bool,
+}
+
+/// The maximum length of the URL of the documentation hosted elsewhere
+pub const DOCS_EXTERNAL_URL_MAX_LENGTH: usize = 1024;
+
+/// A request to set the URL of the documentation of a crate hosted elsewhere, e.g. a book
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrateDocsExternal {
+    /// The URL of the documentation, `None` to serve the generated documentation again
+    pub url: Option<String>,
+}
+
+impl CrateDocsExternal {
+    /// Validates the URL
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the URL is not an absolute HTTP(S) URL
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let Some(url) = &self.url else {
+            return Ok(());
+        };
+        let is_valid = (url.starts_with("https://") || url.starts_with("http://"))
+            && url.len() <= DOCS_EXTERNAL_URL_MAX_LENGTH
+            && !url.chars().any(|c| c.is_whitespace() || c.is_control());
+        if is_valid {
+            Ok(())
+        } else {
+            Err(specialize(
+                error_invalid_request(),
+                format!("invalid URL for the documentation: {url}"),
+            ))
+        }
+    }
+}
+
+/// The status of the generation of the documentation for a crate version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocsGenerationStatus {
+    /// The generation is waiting for the worker
+    Queued,
+    /// The documentation is being generated
+    Building,
+    /// The documentation was generated for the default target
+    Success,
+    /// The generation failed for the default target
+    Failed,
+    /// The generation was skipped because the documentation is disabled for the crate
+    Disabled,
+}
+
+impl DocsGenerationStatus {
+    /// Gets the name of this status, as stored in the database
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Building => "building",
+            Self::Success => "success",
+            Self::Failed => "failed",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+impl FromStr for DocsGenerationStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(Self::Queued),
+            "building" => Ok(Self::Building),
+            "success" => Ok(Self::Success),
+            "failed" => Ok(Self::Failed),
+            "disabled" => Ok(Self::Disabled),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The status of the generation of the documentation for a crate version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateDocsStatus {
+    /// The current status
+    pub status: DocsGenerationStatus,
+    /// The last time the status changed
+    #[serde(rename = "lastUpdate")]
+    pub last_update: NaiveDateTime,
+    /// The results of the last generation for each target and variant
+    pub docs: Vec<CrateVersionDocs>,
+}
+
+/// The settings for the generation of the documentation of a crate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrateDocsBuildSettings {
+    /// The features to activate, all the features when not set
+    #[serde(default)]
+    pub features: Option<Vec<String>>,
+    /// Whether to deactivate the default features
+    #[serde(rename = "noDefaultFeatures", default)]
+    pub no_default_features: bool,
+    /// The additional flags for rustdoc, for example `--cfg docsrs`
+    #[serde(rename = "rustdocFlags", default)]
+    pub rustdoc_flags: Vec<String>,
+    /// The additional variants of the documentation, generated for the default target only
+    #[serde(default)]
+    pub variants: Vec<CrateDocsVariant>,
+}
+
+/// The maximum number of variants of the documentation for a crate
+pub const DOCS_VARIANTS_MAX: usize = 8;
+
+/// The prefix of the folder of a variant of the documentation, within the folder of the crate version
+/// A variant `full` of the documentation of `mycrate` 1.0.0 is served at `/docs/mycrate/1.0.0/features-full/mycrate/index.html`
+pub const DOCS_VARIANT_FOLDER_PREFIX: &str = "features-";
+
+/// A variant of the documentation of a crate, generated with a specific set of features
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateDocsVariant {
+    /// The name of the variant, used in the path of the documentation
+    pub name: String,
+    /// The features to activate
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Whether to deactivate the default features
+    #[serde(rename = "noDefaultFeatures", default)]
+    pub no_default_features: bool,
+}
+
+impl CrateDocsBuildSettings {
+    /// Validates the settings
+    /// A feature is either the name of a feature of the crate, or of a feature of a dependency in the form `dep/feature`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a feature name is malformed, a flag is empty or contains whitespaces, or the variants are invalid
+    pub fn validate(&self) -> Result<(), ApiError> {
+        for feature in self.features.iter().flatten() {
+            if !is_valid_feature_spec(feature) {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!("Invalid feature name: {feature}"),
+                ));
+            }
+        }
+        for flag in &self.rustdoc_flags {
+            if flag.is_empty() || flag.contains(char::is_whitespace) {
+                return Err(specialize(error_invalid_request(), format!("Invalid rustdoc flag: {flag:?}")));
+            }
+        }
+        if self.variants.len() > DOCS_VARIANTS_MAX {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("At most {DOCS_VARIANTS_MAX} variants of the documentation are allowed"),
+            ));
+        }
+        for (index, variant) in self.variants.iter().enumerate() {
+            let is_valid_name = !variant.name.is_empty()
+                && variant
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !is_valid_name {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!("Invalid variant name: {:?}", variant.name),
+                ));
+            }
+            if self.variants[..index].iter().any(|other| other.name == variant.name) {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!("Duplicate variant name: {}", variant.name),
+                ));
+            }
+            for feature in &variant.features {
+                if !is_valid_feature_spec(feature) {
+                    return Err(specialize(
+                        error_invalid_request(),
+                        format!("Invalid feature name: {feature}"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the settings for generating a variant of the documentation
+    #[must_use]
+    pub fn for_variant(&self, variant: &CrateDocsVariant) -> CrateDocsBuildSettings {
+        CrateDocsBuildSettings {
+            features: Some(variant.features.clone()),
+            no_default_features: variant.no_default_features,
+            rustdoc_flags: self.rustdoc_flags.clone(),
+            variants: Vec::new(),
+        }
+    }
+}
+
+/// Gets whether a feature is well-formed, either a feature of the crate or of a dependency in the form `dep/feature`
+fn is_valid_feature_spec(feature: &str) -> bool {
+    match feature.split_once('/') {
+        Some((dependency, feature)) => is_valid_feature_name(dependency) && is_valid_feature_name(feature),
+        None => is_valid_feature_name(feature),
+    }
+}
+
+/// Gets whether a feature name is well-formed, following the rules of cargo
+/// It must start with a letter, a digit or `_`, followed by letters, digits, `_`, `-`, `+` or `.`.
+fn is_valid_feature_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+' || c == '.')
+}
+
+/// A crate in the listing of all crates, with its latest version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateListingItem {
+    /// The name of the crate
+    pub name: String,
+    /// The latest version that is not yanked
+    pub version: String,
+    /// The description of the latest version
+    pub description: String,
+    /// The upload date time of the latest version
+    pub upload: NaiveDateTime,
+}
+
+/// A page in the listing of all crates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratesListing {
+    /// The crates in this page
+    pub crates: Vec<CrateListingItem>,
+    /// The total number of crates
+    pub total: i64,
+    /// The index of this page, starting at 1
+    pub page: i64,
+    /// The maximum number of crates per page
+    #[serde(rename = "perPage")]
+    pub per_page: i64,
+}
+
+impl CratesListing {
+    /// Builds a sitemap (see <https://www.sitemaps.org/protocol.html>) pointing to the pages of the crates
+    pub fn to_sitemap(&self, web_public_uri: &str) -> String {
+        let mut result = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+        for item in &self.crates {
+            let location = format!("{web_public_uri}/crates/{}/{}", item.name, item.version)
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            let _ = writeln!(
+                result,
+                "  <url><loc>{location}</loc><lastmod>{}</lastmod></url>",
+                item.upload.format("%Y-%m-%d")
+            );
+        }
+        result.push_str("</urlset>\n");
+        result
+    }
+}
+
+/// A version of a crate published within a date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratePublication {
+    /// The name of the crate
+    pub package: String,
+    /// The published version
+    pub version: String,
+    /// The upload date time
+    pub upload: NaiveDateTime,
+    /// The login of the user that published the version, if the user still exists
+    #[serde(rename = "publisherLogin")]
+    pub publisher_login: Option<String>,
+    /// The name of the user that published the version, if the user still exists
+    #[serde(rename = "publisherName")]
+    pub publisher_name: Option<String>,
+}
+
+/// A page in the versions published within a date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CratePublications {
+    /// The publications in this page, ordered by upload date
+    pub publications: Vec<CratePublication>,
+    /// The total number of publications within the range
+    pub total: i64,
+    /// The index of this page, starting at 1
+    pub page: i64,
+    /// The maximum number of publications per page
+    #[serde(rename = "perPage")]
+    pub per_page: i64,
+}
+
+/// An owner of a crate that did not re-attest its ownership in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaleCrateOwner {
+    /// The name of the crate
+    pub package: String,
+    /// The owner
+    pub owner: RegistryUser,
+    /// The last time the owner attested its ownership
+    #[serde(rename = "lastAttestation")]
+    pub last_attestation: NaiveDateTime,
+}
+
+/// Whether the latest version of a crate has outdated dependencies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateOutdatedStatus {
+    /// The name of the crate
+    pub name: String,
+    /// The latest version of the crate, `None` when the crate is unknown
+    pub version: Option<String>,
+    /// Whether the last analysis of the latest version found outdated dependencies
+    pub outdated: bool,
+}
+
+/// A crate version that could not be re-indexed from the storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexFailure {
+    /// The name of the crate
+    pub name: String,
+    /// The version that failed
+    pub version: String,
+    /// The reason for the failure
+    pub error: String,
+}
+
+/// The summary of the re-indexing of all crates from the storage
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ReindexSummary {
+    /// The number of crates that were re-indexed
+    pub crates: usize,
+    /// The number of versions that were re-indexed
+    pub versions: usize,
+    /// The versions that failed, the index of their crate is left untouched
+    pub failures: Vec<ReindexFailure>,
+    /// Whether the re-indexing stopped at the first failing crate
+    pub halted: bool,
+}
+
+/// The consistency of a crate version across the database, the storage and the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateVersionConsistency {
+    /// The version
+    pub version: String,
+    /// Whether the version is in the database
+    #[serde(rename = "inDatabase")]
+    pub in_database: bool,
+    /// Whether the archive for the version is in the storage
+    #[serde(rename = "inStorage")]
+    pub in_storage: bool,
+    /// Whether the version has a line in the index
+    #[serde(rename = "inIndex")]
+    pub in_index: bool,
+    /// Whether the version is yanked according to the database, if it is there
+    #[serde(rename = "yankedInDatabase")]
+    pub yanked_in_database: Option<bool>,
+    /// Whether the version is yanked according to the index, if it is there
+    #[serde(rename = "yankedInIndex")]
+    pub yanked_in_index: Option<bool>,
+    /// The found inconsistencies, empty when the version is consistent
+    pub problems: Vec<String>,
+}
+
+/// The report of the consistency of a crate across the database, the storage and the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateConsistencyReport {
+    /// The name of the crate
+    pub package: String,
+    /// Whether all the versions are consistent
+    pub consistent: bool,
+    /// The consistency for each known version, in semver order
+    pub versions: Vec<CrateVersionConsistency>,
+}
+
+/// A file in the storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredFile {
+    /// The path of the file in the storage
+    pub path: String,
+    /// The size of the file in bytes
+    pub size: u64,
+}
+
+/// The summary of the collection of the stored files of deleted crates and versions
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StorageGcSummary {
+    /// Whether this was a dry run, in which case the files were not deleted
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    /// The number of collected files
+    pub count: usize,
+    /// The total size in bytes of the collected files
+    pub size: u64,
+    /// The collected files
+    pub files: Vec<StoredFile>,
+}
+
+/// The README of a crate version, with its content type
+#[derive(Debug, Clone)]
+pub struct CrateReadme {
+    /// The content type, depending on the format of the README
+    pub content_type: &'static str,
+    /// The raw content
+    pub content: Vec<u8>,
+}
+
+/// A signed link to download a crate version without authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedDownloadLink {
+    /// The URL to download the crate version
+    pub url: String,
+    /// The date time after which the link is no longer valid
+    pub expires: NaiveDateTime,
+}
+
+/// The result of exporting the documentation of a crate version to a folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocsExport {
+    /// The folder into which the documentation was exported
+    pub path: String,
+    /// The number of exported files
+    pub files: usize,
+    /// The number of files in which links to the registry were made relative
+    pub rewritten: usize,
+}
+
+/// A file in the stored documentation of a crate version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateDocFile {
+    /// The path of the file, relative to the documentation of the crate version
+    pub path: String,
+    /// The size of the file, in bytes
+    pub size: u64,
 }
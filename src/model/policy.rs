@@ -0,0 +1,236 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for the policies on the dependencies of crates
+
+use std::collections::HashMap;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::deps::{DepsAnalysis, DepsGraph, DepsGraphCrateOrigin};
+
+/// A policy for the dependencies of a crate
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepsPolicy {
+    /// The crates that must not appear in the dependency graph
+    #[serde(rename = "deniedCrates", default)]
+    pub denied_crates: Vec<String>,
+    /// The SPDX identifiers of the licenses that dependencies must not require
+    #[serde(rename = "deniedLicenses", default)]
+    pub denied_licenses: Vec<String>,
+    /// The maximum number of newer versions that may exist for a direct dependency
+    #[serde(rename = "maxVersionsBehind", default)]
+    pub max_versions_behind: Option<usize>,
+    /// The identifiers of the advisories that must not affect any dependency
+    #[serde(rename = "bannedAdvisories", default)]
+    pub banned_advisories: Vec<String>,
+    /// Whether any advisory against a dependency is a violation
+    #[serde(rename = "denyAllAdvisories", default)]
+    pub deny_all_advisories: bool,
+}
+
+/// The rule of a policy that is violated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepsPolicyRule {
+    /// A denied crate is in the dependency graph
+    DeniedCrate,
+    /// A dependency requires a denied license
+    DeniedLicense,
+    /// A direct dependency is too far behind its latest version
+    VersionsBehind,
+    /// A dependency is affected by an advisory
+    Advisory,
+}
+
+impl DepsPolicyRule {
+    /// Gets the stable code for violations of this rule, as reported to cargo when a publication is rejected
+    #[must_use]
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::DeniedCrate => "policy-denied-crate",
+            Self::DeniedLicense => "policy-denied-license",
+            Self::VersionsBehind => "policy-versions-behind",
+            Self::Advisory => "policy-advisory",
+        }
+    }
+}
+
+/// A violation of a policy by a dependency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsPolicyViolation {
+    /// The violated rule
+    pub rule: DepsPolicyRule,
+    /// The name of the dependency
+    pub package: String,
+    /// The resolved version of the dependency
+    pub version: String,
+    /// A description of the violation
+    pub message: String,
+}
+
+/// The result of checking the dependencies of a crate against a policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsPolicyReport {
+    /// Whether the crate complies with the policy
+    pub passed: bool,
+    /// Whether the dependency graph was truncated, in which case some dependencies were not checked
+    pub truncated: bool,
+    /// The violations of the policy
+    pub violations: Vec<DepsPolicyViolation>,
+    /// The dependencies whose license could not be checked because they are not hosted by this registry
+    #[serde(rename = "uncheckedLicenses")]
+    pub unchecked_licenses: Vec<String>,
+}
+
+impl DepsPolicy {
+    /// Checks a resolved dependency graph against this policy
+    /// `licenses` gives the license expression, if any, for the dependencies hosted by this registry, by name and version
+    #[must_use]
+    pub fn check(
+        &self,
+        graph: &DepsGraph,
+        analysis: &DepsAnalysis,
+        licenses: &HashMap<(String, String), Option<String>>,
+    ) -> DepsPolicyReport {
+        let mut violations = Vec::new();
+        let mut unchecked_licenses = Vec::new();
+        for dep in &graph.crates {
+            for resolution in &dep.resolutions {
+                let resolved = &dep.versions[resolution.version_index];
+                let version = resolved.semver.to_string();
+                if self.denied_crates.contains(&dep.name) {
+                    violations.push(DepsPolicyViolation {
+                        rule: DepsPolicyRule::DeniedCrate,
+                        package: dep.name.clone(),
+                        version: version.clone(),
+                        message: format!("crate {} is denied", dep.name),
+                    });
+                }
+                if !self.denied_licenses.is_empty() {
+                    match licenses.get(&(dep.name.clone(), version.clone())) {
+                        Some(Some(license)) if is_license_denied(license, &self.denied_licenses) => {
+                            violations.push(DepsPolicyViolation {
+                                rule: DepsPolicyRule::DeniedLicense,
+                                package: dep.name.clone(),
+                                version: version.clone(),
+                                message: format!("license {license} is denied"),
+                            });
+                        }
+                        Some(_) => {}
+                        None => unchecked_licenses.push(format!("{}@{version}", dep.name)),
+                    }
+                }
+                let is_direct = resolution
+                    .origins
+                    .iter()
+                    .any(|origin| matches!(origin, DepsGraphCrateOrigin::Direct(_)));
+                if let Some(max) = self.max_versions_behind.filter(|_| is_direct) {
+                    let behind = dep
+                        .versions
+                        .iter()
+                        .filter(|v| !v.metadata.yanked && v.semver.0.pre.is_empty() && v.semver > resolved.semver)
+                        .count();
+                    if behind > max {
+                        violations.push(DepsPolicyViolation {
+                            rule: DepsPolicyRule::VersionsBehind,
+                            package: dep.name.clone(),
+                            version: version.clone(),
+                            message: format!("{behind} newer versions exist, at most {max} are allowed"),
+                        });
+                    }
+                }
+            }
+        }
+        for advisory in &analysis.advisories {
+            if self.deny_all_advisories || self.banned_advisories.contains(&advisory.content.id) {
+                violations.push(DepsPolicyViolation {
+                    rule: DepsPolicyRule::Advisory,
+                    package: advisory.package.clone(),
+                    version: advisory.version.to_string(),
+                    message: format!("affected by {}: {}", advisory.content.id, advisory.content.summary),
+                });
+            }
+        }
+        DepsPolicyReport {
+            passed: violations.is_empty(),
+            truncated: analysis.truncated,
+            violations,
+            unchecked_licenses,
+        }
+    }
+}
+
+/// Gets whether a SPDX license expression requires one of the denied licenses
+/// An expression with an `OR` alternative is acceptable when at least one alternative is,
+/// while all the parts of an `AND` must be acceptable.
+/// The legacy `/` separator is understood as `OR`.
+#[must_use]
+pub fn is_license_denied(expression: &str, denied: &[String]) -> bool {
+    is_license_expression_denied(expression, &|license| is_license_in(license, denied))
+}
+
+/// Gets whether a SPDX license expression requires a license that is not in the allowed ones
+/// An expression with an `OR` alternative is acceptable when at least one alternative only uses allowed licenses.
+#[must_use]
+pub fn is_license_not_allowed(expression: &str, allowed: &[String]) -> bool {
+    is_license_expression_denied(expression, &|license| !is_license_in(license, allowed))
+}
+
+/// Gets whether a license, possibly with the `+` suffix, is in a list of SPDX identifiers
+fn is_license_in(license: &str, licenses: &[String]) -> bool {
+    let base = license.trim_end_matches('+');
+    licenses
+        .iter()
+        .any(|l| l.eq_ignore_ascii_case(base) || l.eq_ignore_ascii_case(license))
+}
+
+/// Gets whether a SPDX license expression is denied, given whether single licenses are denied
+fn is_license_expression_denied(expression: &str, is_denied: &dyn Fn(&str) -> bool) -> bool {
+    let expression = expression.replace('(', " ( ").replace(')', " ) ").replace('/', " OR ");
+    let tokens = expression.split_whitespace().collect::<Vec<_>>();
+    let mut position = 0;
+    parse_license_or(&tokens, &mut position, is_denied)
+}
+
+/// Parses a disjunction of license terms, returning whether it is denied
+fn parse_license_or(tokens: &[&str], position: &mut usize, is_denied: &dyn Fn(&str) -> bool) -> bool {
+    let mut result = parse_license_and(tokens, position, is_denied);
+    while tokens.get(*position).is_some_and(|token| token.eq_ignore_ascii_case("OR")) {
+        *position += 1;
+        let other = parse_license_and(tokens, position, is_denied);
+        result = result && other;
+    }
+    result
+}
+
+/// Parses a conjunction of license terms, returning whether it is denied
+fn parse_license_and(tokens: &[&str], position: &mut usize, is_denied: &dyn Fn(&str) -> bool) -> bool {
+    let mut result = parse_license_term(tokens, position, is_denied);
+    while tokens.get(*position).is_some_and(|token| token.eq_ignore_ascii_case("AND")) {
+        *position += 1;
+        let other = parse_license_term(tokens, position, is_denied);
+        result = result || other;
+    }
+    result
+}
+
+/// Parses a single license, possibly with an exception, or a parenthesized expression, returning whether it is denied
+fn parse_license_term(tokens: &[&str], position: &mut usize, is_denied: &dyn Fn(&str) -> bool) -> bool {
+    let Some(&token) = tokens.get(*position) else {
+        return false;
+    };
+    *position += 1;
+    if token == "(" {
+        let result = parse_license_or(tokens, position, is_denied);
+        if tokens.get(*position) == Some(&")") {
+            *position += 1;
+        }
+        return result;
+    }
+    if tokens.get(*position).is_some_and(|token| token.eq_ignore_ascii_case("WITH")) {
+        // skip the exception, the license itself decides
+        *position += 2;
+    }
+    is_denied(token)
+}
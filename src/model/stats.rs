@@ -4,6 +4,8 @@
 
 //! Data types for global statistics
 
+use std::collections::BTreeMap;
+
 use byteorder::ByteOrder;
 use chrono::{Datelike, Days, Local, NaiveDate};
 use semver::Version;
@@ -11,6 +13,17 @@ use serde_derive::{Deserialize, Serialize};
 
 use super::CrateAndVersion;
 
+/// The totals for the content of the registry
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct RegistryTotals {
+    /// Total number of crates
+    pub crates: i64,
+    /// Total number of crate versions
+    pub versions: i64,
+    /// Total number of downloads
+    pub downloads: i64,
+}
+
 /// The global stats for the registry
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GlobalStats {
@@ -78,18 +91,8 @@ impl DownloadStats {
 
     /// Adds the data for a version
     pub fn add_version(&mut self, version: String, data: Option<&[u8]>) {
-        let mut counts = vec![0; SERIES_LENGTH];
-        let mut total = 0;
-        if let Some(data) = data {
-            let today = Local::now().naive_local().date();
-            let mut index = ((today.ordinal0() + 1) as usize % SERIES_LENGTH) * size_of::<u32>();
-            for count in &mut counts {
-                let v = byteorder::NativeEndian::read_u32(&data[index..]);
-                total += v;
-                *count = v;
-                index = (index + size_of::<u32>()) % data.len();
-            }
-        }
+        let counts = read_downloads_series(data);
+        let total = counts.iter().sum();
         self.versions.push(DownloadStatsForVersion {
             version_semver: version.parse().unwrap(),
             version,
@@ -115,3 +118,107 @@ impl DownloadStats {
         }
     }
 }
+
+/// Reads the download counts for each day in the series stored for a version, from the oldest day to today
+/// The counts are stored in a ring buffer indexed by the day in the year.
+fn read_downloads_series(data: Option<&[u8]>) -> Vec<u32> {
+    let mut counts = vec![0; SERIES_LENGTH];
+    if let Some(data) = data {
+        let today = Local::now().naive_local().date();
+        let mut index = ((today.ordinal0() + 1) as usize % SERIES_LENGTH) * size_of::<u32>();
+        for count in &mut counts {
+            *count = byteorder::NativeEndian::read_u32(&data[index..]);
+            index = (index + size_of::<u32>()) % data.len();
+        }
+    }
+    counts
+}
+
+/// Computes the popularity of a crate version from its downloads, the recent downloads weighing more than the old ones
+/// The weight of the downloads of a day is halved every `half_life` days, the downloads older than the series count as the oldest day.
+/// A `half_life` of 0 deactivates the decay so that the popularity is the total number of downloads.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn get_downloads_popularity(download_count: i64, data: Option<&[u8]>, half_life: u64) -> f64 {
+    if half_life == 0 {
+        return download_count.max(0) as f64;
+    }
+    let weight = |age: usize| 0.5_f64.powf(age as f64 / half_life as f64);
+    let counts = read_downloads_series(data);
+    let recent: i64 = counts.iter().map(|count| i64::from(*count)).sum();
+    let older = (download_count - recent).max(0) as f64 * weight(SERIES_LENGTH);
+    counts
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(age, count)| f64::from(*count) * weight(age))
+        .sum::<f64>()
+        + older
+}
+
+/// The download counts for a version of a crate
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct VersionDownloadCounts {
+    /// The total number of downloads since the publication
+    pub total: i64,
+    /// The number of downloads in the last `SERIES_LENGTH` days
+    pub recent: u32,
+}
+
+/// The download counts for all versions of a crate, keyed by version
+/// This is a stable format intended for external tools such as mirrors and dashboards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrateDownloadCounts {
+    /// The name of the crate
+    pub name: String,
+    /// The number of days for the recent downloads
+    #[serde(rename = "recentDays")]
+    pub recent_days: usize,
+    /// The counts for each version
+    pub versions: BTreeMap<String, VersionDownloadCounts>,
+}
+
+/// The maximum number of days in a time series of downloads across all crates
+pub const DOWNLOADS_SERIES_MAX_DAYS: i64 = 3660;
+
+/// The granularity of the buckets in a time series of downloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadsGranularity {
+    /// One bucket per day
+    #[default]
+    Day,
+    /// One bucket per week, starting on Monday
+    Week,
+}
+
+impl DownloadsGranularity {
+    /// Gets the first day of the bucket that contains a day
+    #[must_use]
+    pub fn bucket_start(self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => day,
+            Self::Week => day - Days::new(u64::from(day.weekday().num_days_from_monday())),
+        }
+    }
+}
+
+/// A bucket in a time series of downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadsBucket {
+    /// The first day in the bucket
+    pub start: NaiveDate,
+    /// The total number of downloads within the bucket
+    pub count: i64,
+}
+
+/// The downloads across all crates over time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalDownloadsSeries {
+    /// The granularity of the buckets
+    pub granularity: DownloadsGranularity,
+    /// The buckets, ordered by date
+    pub buckets: Vec<DownloadsBucket>,
+    /// The total number of downloads for the series
+    pub total: i64,
+}
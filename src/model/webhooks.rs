@@ -0,0 +1,87 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for the notifications sent to webhooks
+
+use chrono::{Local, NaiveDateTime};
+use serde_derive::{Deserialize, Serialize};
+
+/// The kind of event notified to webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEventKind {
+    /// A crate version was published
+    #[serde(rename = "publish")]
+    Publish,
+    /// A crate version was yanked
+    #[serde(rename = "yank")]
+    Yank,
+    /// A crate version was unyanked
+    #[serde(rename = "unyank")]
+    Unyank,
+    /// Owners were added to a crate
+    #[serde(rename = "owners-add")]
+    OwnersAdd,
+    /// Owners were removed from a crate
+    #[serde(rename = "owners-remove")]
+    OwnersRemove,
+    /// The crates of a user were reassigned to a successor
+    #[serde(rename = "owners-reassign")]
+    OwnersReassign,
+}
+
+impl WebhookEventKind {
+    /// Gets the name of the event, as sent in the payload
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Publish => "publish",
+            Self::Yank => "yank",
+            Self::Unyank => "unyank",
+            Self::OwnersAdd => "owners-add",
+            Self::OwnersRemove => "owners-remove",
+            Self::OwnersReassign => "owners-reassign",
+        }
+    }
+}
+
+/// The payload of a notification sent to webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// The kind of event
+    pub event: WebhookEventKind,
+    /// The name of the crate
+    #[serde(rename = "crate")]
+    pub package: String,
+    /// The version of the crate, for the events on a single version
+    pub version: Option<String>,
+    /// The principal (email of the user) that triggered the event
+    pub actor: String,
+    /// The users affected by a change of the owners
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    /// The timestamp of the event
+    pub timestamp: NaiveDateTime,
+}
+
+impl WebhookEvent {
+    /// Creates an event happening now
+    #[must_use]
+    pub fn new(event: WebhookEventKind, package: &str, version: Option<&str>, actor: &str) -> Self {
+        Self {
+            event,
+            package: package.to_string(),
+            version: version.map(str::to_string),
+            actor: actor.to_string(),
+            users: Vec::new(),
+            timestamp: Local::now().naive_local(),
+        }
+    }
+
+    /// Sets the users affected by a change of the owners
+    #[must_use]
+    pub fn with_users(mut self, users: Vec<String>) -> Self {
+        self.users = users;
+        self
+    }
+}
@@ -0,0 +1,62 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Data types for the status of the background workers
+
+use chrono::NaiveDateTime;
+use serde_derive::{Deserialize, Serialize};
+
+/// The status of a background worker
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    /// The number of jobs waiting to be processed
+    pub queued: usize,
+    /// The number of jobs being processed
+    #[serde(rename = "inFlight")]
+    pub in_flight: usize,
+    /// The number of jobs that completed successfully since the launch
+    pub completed: u64,
+    /// The number of jobs that failed since the launch
+    pub failed: u64,
+    /// The number of jobs that were skipped since the launch, for example because the required tools are missing
+    pub skipped: u64,
+    /// The number of jobs that could not be queued and are deferred until the queue is drained
+    #[serde(default)]
+    pub deferred: usize,
+    /// The last time a job finished, successfully or not
+    #[serde(rename = "lastFinished")]
+    pub last_finished: Option<NaiveDateTime>,
+}
+
+/// The status of all the background workers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkersStatus {
+    /// The worker for the generation of documentation
+    pub docs: WorkerStatus,
+    /// The worker for the analysis of dependencies
+    pub deps: WorkerStatus,
+}
+
+/// The readiness of the toolchain used for the generation of documentation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolchainStatus {
+    /// Whether the toolchain was checked, this is deactivated in the configuration
+    pub checked: bool,
+    /// Whether documentation can be generated
+    pub ready: bool,
+    /// The version of cargo, when available
+    pub cargo: Option<String>,
+    /// The version of rustdoc, when available
+    pub rustdoc: Option<String>,
+    /// The problems found with the toolchain
+    pub problems: Vec<String>,
+}
+
+/// The health of the registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthStatus {
+    /// The readiness of the toolchain used for the generation of documentation
+    #[serde(rename = "docsToolchain")]
+    pub docs_toolchain: ToolchainStatus,
+}
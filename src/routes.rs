@@ -8,33 +8,56 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::body::{Body, Bytes};
-use axum::extract::{Path, Query, State};
+use axum::extract::{Multipart, Path, Query, State};
 use axum::http::header::{HeaderName, SET_COOKIE};
-use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, IntoResponseParts, Response};
+use axum::Extension;
 use axum::{BoxError, Json};
+use chrono::{NaiveDate, NaiveDateTime};
 use cookie::Key;
-use futures::Stream;
+use futures::lock::Mutex;
+use futures::{Stream, TryStreamExt};
 use serde::Deserialize;
 use tokio::fs::File;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio_util::io::ReaderStream;
 
 use crate::application::Application;
-use crate::model::auth::{AuthenticatedUser, RegistryUserToken, RegistryUserTokenWithSecret};
+use crate::model::auth::{
+    AuthenticatedUser, OAuthLoginState, RegistryUserToken, RegistryUserTokenWithSecret, TokenPublishScope, TokenRestrictions,
+    TokensRevocationCriteria, TokensRevocationResult, UserImportResult, UserNotificationSettings,
+};
 use crate::model::cargo::{
-    CrateUploadResult, OwnersChangeQuery, OwnersQueryResult, RegistryUser, SearchResults, YesNoMsgResult, YesNoResult,
+    CrateUploadData, CrateUploadResult, OwnersChangeQuery, OwnersQueryResult, RegistryUser, SearchResults,
+    UserCratesReassignment, YesNoMsgResult, YesNoResult,
+};
+use crate::model::config::Configuration;
+use crate::model::cratesio::CratesIoCrateResponse;
+use crate::model::deps::{AdvisoryAffectedCrate, AffectedCrateAdvisory, DepsAnalysis, DepsGraphExport};
+use crate::model::packages::{
+    open_graph_tags, CrateConsistencyReport, CrateDocFile, CrateDocsBuildSettings, CrateDocsExternal, CrateDocsSettings,
+    CrateDocsStatus, CrateInfo, CrateManagementInfo, CrateOutdatedStatus, CratePublications, CrateVersionBuild,
+    CrateVersionSummary, CratesListing, DocsExport, ReindexSummary, SignedDownloadLink, StaleCrateOwner, StorageGcSummary,
 };
-use crate::model::deps::DepsAnalysis;
-use crate::model::packages::CrateInfo;
-use crate::model::stats::{DownloadStats, GlobalStats};
-use crate::model::{generate_token, AppVersion, CrateAndVersion};
+use crate::model::policy::{DepsPolicy, DepsPolicyReport};
+use crate::model::stats::{CrateDownloadCounts, DownloadStats, DownloadsGranularity, GlobalDownloadsSeries, GlobalStats};
+use crate::model::workers::{HealthStatus, WorkersStatus};
+use crate::model::{generate_token, AppVersion, CrateAndVersion, IndexProtocols, RegistryDescriptor};
 use crate::services::index::Index;
-use crate::utils::apierror::{error_invalid_request, error_not_found, specialize, ApiError};
+use crate::services::storage::CrateContent;
+use crate::utils::apierror::{error_invalid_request, error_not_found, error_too_many_requests, specialize, ApiError};
 use crate::utils::axum::auth::{AuthData, AxumStateForCookies};
 use crate::utils::axum::embedded::Resources;
-use crate::utils::axum::extractors::Base64;
+use crate::utils::axum::extractors::{Base64, ClientIp};
 use crate::utils::axum::{response, response_error, ApiResult};
+use crate::utils::cidr::IpCidr;
+use crate::utils::range::{ByteRangeSpec, RangedContent};
+use crate::utils::ratelimit::RateLimiter;
 
 /// The state of this application for axum
 pub struct AxumState {
@@ -44,6 +67,108 @@ pub struct AxumState {
     pub cookie_key: Key,
     /// The static resources for the web app
     pub webapp_resources: Resources,
+    /// The content of the custom page for the unknown pages of the web application, if any
+    pub webapp_not_found: Option<Vec<u8>>,
+    /// The semaphores limiting the concurrent downloads, for each token
+    pub download_semaphores: Mutex<HashMap<i64, Arc<Semaphore>>>,
+    /// The rate limiter for the requests other than publications, for each token or client IP
+    pub read_limiter: RateLimiter,
+    /// The rate limiter for the publications, for each token or client IP
+    pub publish_limiter: RateLimiter,
+    /// The rate limiter for the requests to the index, for each token or client IP
+    pub index_limiter: RateLimiter,
+}
+
+impl AxumState {
+    /// Acquires a slot to download a crate when the authentication uses a token and downloads are limited
+    /// The user is the one already authenticated for the request, if any.
+    async fn acquire_download_permit(
+        &self,
+        user: Option<&AuthenticatedUser>,
+    ) -> Result<Option<OwnedSemaphorePermit>, ApiError> {
+        let max = self.application.configuration.web_downloads_max_per_token;
+        let Some(token_id) = user.and_then(|user| user.token_id).filter(|_| max > 0) else {
+            return Ok(None);
+        };
+        let semaphore = {
+            let mut semaphores = self.download_semaphores.lock().await;
+            // forget the semaphores without outstanding permits or waiting downloads, only the map still refers to them
+            semaphores.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+            semaphores
+                .entry(token_id)
+                .or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone()
+        };
+        let timeout = Duration::from_millis(self.application.configuration.web_downloads_queue_timeout);
+        match tokio::time::timeout(timeout, semaphore.acquire_owned()).await {
+            Ok(permit) => Ok(Some(permit?)),
+            Err(_) => Err(specialize(
+                error_too_many_requests(),
+                String::from("too many concurrent downloads for this token"),
+            )),
+        }
+    }
+}
+
+/// Gets whether a path is served by the index, through the sparse or the git protocol
+/// Files in the sparse index are at `/1/{name}`, `/2/{name}`, `/3/{c}/{name}` or `/{ab}/{cd}/{name}`.
+fn is_index_path(path: &str) -> bool {
+    if matches!(path, "/config.json" | "/info/refs" | "/git-upload-pack") {
+        return true;
+    }
+    let segments = path[1..].split('/').collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["1" | "2", name] => !name.is_empty(),
+        ["3", first, name] => first.len() == 1 && !name.is_empty(),
+        [first, second, name] => first.len() == 2 && second.len() == 2 && !name.is_empty(),
+        _ => false,
+    }
+}
+
+/// Middleware applying the rate limits, for each valid token or for each client IP for the requests without a valid token
+/// The requests to the index have their own limit, so that the resolution of dependencies by cargo does not consume the limit for the other requests.
+/// The resources of the web application are not limited
+/// The user authenticated by a token is passed to the handlers as an extension of the request.
+pub async fn rate_limit(
+    State(state): State<Arc<AxumState>>,
+    auth_data: AuthData,
+    client_ip: ClientIp,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let limiter = if path == "/api/v1/crates/new" {
+        &state.publish_limiter
+    } else if is_index_path(path) {
+        &state.index_limiter
+    } else if path.starts_with("/webapp/") {
+        return next.run(request).await;
+    } else {
+        &state.read_limiter
+    };
+    // only the tokens that authenticate get their own limit, otherwise random tokens would escape the limit for the client IP
+    let key = match &auth_data.token {
+        Some(_) => match state.application.authenticate(&auth_data).await {
+            Ok(user) => {
+                let key = match user.token_id {
+                    Some(token_id) => format!("token:{token_id}"),
+                    None => format!("user:{}", user.principal),
+                };
+                request.extensions_mut().insert(user);
+                key
+            }
+            Err(_) => format!("ip:{client_ip}"),
+        },
+        None => format!("ip:{client_ip}"),
+    };
+    match limiter.check(&key) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let (status, body) = response_error(error_too_many_requests());
+            (status, [(header::RETRY_AFTER, HeaderValue::from(seconds))], body).into_response()
+        }
+    }
 }
 
 impl AxumStateForCookies for AxumState {
@@ -58,6 +183,10 @@ impl AxumStateForCookies for AxumState {
     fn get_cookie_key(&self) -> &Key {
         &self.cookie_key
     }
+
+    fn get_trusted_proxies(&self) -> &[IpCidr] {
+        &self.application.configuration.web_trusted_proxies
+    }
 }
 
 #[derive(Deserialize)]
@@ -72,16 +201,44 @@ pub struct PathInfoCrateVersion {
 }
 
 /// Response for a GET on the root
-/// Redirect to the web app
-pub async fn get_root(State(state): State<Arc<AxumState>>) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
-    let target = format!("{}/webapp/index.html", state.application.configuration.web_public_uri);
+/// API clients that only accept JSON get a description of the registry, other clients are redirected, to the web app by default
+pub async fn get_root(State(state): State<Arc<AxumState>>, headers: HeaderMap) -> Response {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let configuration = &state.application.configuration;
+    if accept.contains("application/json") && !accept.contains("text/html") {
+        let uri = &configuration.web_public_uri;
+        let descriptor = RegistryDescriptor {
+            name: configuration.self_local_name.clone(),
+            version: AppVersion {
+                commit: crate::GIT_HASH.to_string(),
+                tag: crate::GIT_TAG.to_string(),
+            },
+            webapp: format!("{uri}/webapp/index.html"),
+            api: format!("{uri}/api/v1"),
+            index_sparse: configuration.index.allow_protocol_sparse.then(|| format!("sparse+{uri}/")),
+            index_git: configuration.index.allow_protocol_git.then(|| uri.clone()),
+            docs: format!("{uri}/docs"),
+        };
+        return (
+            [(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
+            Json(descriptor),
+        )
+            .into_response();
+    }
     (
         StatusCode::FOUND,
         [
-            (header::LOCATION, HeaderValue::from_str(&target).unwrap()),
+            (
+                header::LOCATION,
+                HeaderValue::from_str(&configuration.web_root_redirect).unwrap(),
+            ),
             (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
         ],
     )
+        .into_response()
 }
 
 /// Gets the favicon
@@ -97,11 +254,32 @@ pub async fn get_favicon(State(state): State<Arc<AxumState>>) -> (StatusCode, [(
     )
 }
 
+/// The name of the private cookie holding the state of an OAuth login flow
+const OAUTH_STATE_COOKIE: &str = "cratery-oauth-state";
+
 /// Gets the redirection response when not authenticated
 fn get_auth_redirect(state: &AxumState) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
     // redirect to login
-    let nonce = generate_token(64);
-    let oauth_state = generate_token(32);
+    let target = format!("{}/api/v1/oauth/login", state.application.configuration.web_public_uri);
+    (
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, HeaderValue::from_str(&target).unwrap()),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+    )
+}
+
+/// Starts an OAuth login flow by redirecting to the identity provider
+/// The state and nonce of the flow are kept in a private cookie so that they can be checked on the callback
+pub async fn api_v1_oauth_login(
+    mut auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> (StatusCode, [(HeaderName, HeaderValue); 3]) {
+    let login_state = OAuthLoginState {
+        state: generate_token(32),
+        nonce: generate_token(64),
+    };
     let target = format!(
         "{}?response_type={}&redirect_uri={}&client_id={}&scope={}&nonce={}&state={}",
         state.application.configuration.oauth_login_uri,
@@ -112,22 +290,29 @@ fn get_auth_redirect(state: &AxumState) -> (StatusCode, [(HeaderName, HeaderValu
         )),
         urlencoding::encode(&state.application.configuration.oauth_client_id),
         urlencoding::encode(&state.application.configuration.oauth_client_scope),
-        nonce,
-        oauth_state
+        login_state.nonce,
+        login_state.state
     );
+    let cookie = auth_data.create_cookie(OAUTH_STATE_COOKIE, &serde_json::to_string(&login_state).unwrap(), true);
     (
         StatusCode::FOUND,
         [
             (header::LOCATION, HeaderValue::from_str(&target).unwrap()),
             (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            (SET_COOKIE, HeaderValue::from_str(&cookie.to_string()).unwrap()),
         ],
     )
 }
 
 /// Gets the redirection for a crates shortcut
 pub async fn get_redirection_crate(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
     Path(PathInfoCrate { package }): Path<PathInfoCrate>,
 ) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
+    if let Some(canonical) = get_redirection_canonical_crate(&state, &auth_data, &package).await {
+        return redirection_permanent(&format!("/crates/{canonical}"));
+    }
     let target = format!("/webapp/crate.html?crate={package}");
     (
         StatusCode::FOUND,
@@ -140,8 +325,13 @@ pub async fn get_redirection_crate(
 
 /// Gets the redirection for a crates shortcut
 pub async fn get_redirection_crate_version(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
     Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
 ) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
+    if let Some(canonical) = get_redirection_canonical_crate(&state, &auth_data, &package).await {
+        return redirection_permanent(&format!("/crates/{canonical}/{version}"));
+    }
     let target = format!("/webapp/crate.html?crate={package}&version={version}");
     (
         StatusCode::FOUND,
@@ -152,14 +342,72 @@ pub async fn get_redirection_crate_version(
     )
 }
 
+/// Gets the canonical name of a crate in a shortcut, when it differs from the requested one and the redirection is activated
+async fn get_redirection_canonical_crate(state: &AxumState, auth_data: &AuthData, package: &str) -> Option<String> {
+    if !state.application.configuration.web_crates_canonical_redirect {
+        return None;
+    }
+    state
+        .application
+        .get_crate_canonical_name(auth_data, package)
+        .await
+        .filter(|canonical| canonical != package)
+}
+
+/// Builds a permanent redirection to a target
+fn redirection_permanent(target: &str) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [
+            (header::LOCATION, HeaderValue::from_str(target).unwrap()),
+            (header::CACHE_CONTROL, HeaderValue::from_static("max-age=3600")),
+        ],
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CratePageQuery {
+    #[serde(rename = "crate")]
+    package: Option<String>,
+    version: Option<String>,
+}
+
+/// The placeholder meta tag in the page for a crate, replaced by the `OpenGraph` meta tags
+const CRATE_PAGE_META_PLACEHOLDER: &str = "<meta name=\"description\" content=\"\">";
+
+/// Gets the page for a crate, with the `OpenGraph` meta tags for previews
+/// The description of the crate is only given when the requester can read it
+async fn get_webapp_crate_page(state: &AxumState, auth_data: &AuthData, query: &CratePageQuery, content: &[u8]) -> Vec<u8> {
+    let Some(package) = query.package.as_deref() else {
+        return content.to_vec();
+    };
+    let web_public_uri = &state.application.configuration.web_public_uri;
+    let tags = match state.application.get_crate_info(auth_data, package).await {
+        Ok(info) => info.to_open_graph_tags(web_public_uri, query.version.as_deref()),
+        Err(_) => None,
+    };
+    let tags = tags.unwrap_or_else(|| {
+        // not readable by the requester, do not disclose anything but the name in the link
+        let url = match query.version.as_deref() {
+            Some(version) => format!("{web_public_uri}/crates/{package}/{version}"),
+            None => format!("{web_public_uri}/crates/{package}"),
+        };
+        open_graph_tags(package, "", &url)
+    });
+    String::from_utf8_lossy(content)
+        .replacen(CRATE_PAGE_META_PLACEHOLDER, &tags, 1)
+        .into_bytes()
+}
+
 /// Gets the favicon
 pub async fn get_webapp_resource(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
     request: Request<Body>,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], &'static [u8]), StatusCode> {
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Cow<'static, [u8]>), StatusCode> {
     let path = request.uri().path();
     let path = &path["/webapp/".len()..];
+    let path = if path.is_empty() { "index.html" } else { path };
 
     if let Some(crate_name) = path.strip_prefix("crates/") {
         // URL shortcut for crates
@@ -170,7 +418,7 @@ pub async fn get_webapp_resource(
                 (header::LOCATION, HeaderValue::from_str(&target).unwrap()),
                 (header::CACHE_CONTROL, HeaderValue::from_static("max-age=3600")),
             ],
-            &[],
+            Cow::Borrowed(&[]),
         ));
     }
 
@@ -178,24 +426,53 @@ pub async fn get_webapp_resource(
         let is_authenticated = state.application.authenticate(&auth_data).await.is_ok();
         if !is_authenticated {
             let (code, headers) = get_auth_redirect(&state);
-            return Ok((code, headers, &[]));
+            return Ok((code, headers, Cow::Borrowed(&[])));
         }
     }
 
-    let resource = state.webapp_resources.get(path);
+    let resource = state.webapp_resources.data.get(path);
     match resource {
+        Some(resource) if path == "crate.html" => {
+            let query = Query::<CratePageQuery>::try_from_uri(request.uri()).map_err(|_| StatusCode::BAD_REQUEST)?;
+            let content = get_webapp_crate_page(&state, &auth_data, &query, resource.content).await;
+            Ok((
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_static(resource.content_type)),
+                    (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+                ],
+                Cow::Owned(content),
+            ))
+        }
         Some(resource) => Ok((
             StatusCode::OK,
             [
                 (header::CONTENT_TYPE, HeaderValue::from_static(resource.content_type)),
                 (header::CACHE_CONTROL, HeaderValue::from_static("max-age=3600")),
             ],
-            resource.content,
+            Cow::Borrowed(resource.content),
         )),
-        None => Err(StatusCode::NOT_FOUND),
+        None => Ok(get_webapp_not_found(&state)),
     }
 }
 
+/// Gets the page for an unknown page of the web application
+/// This is the custom page when configured, or the embedded one
+fn get_webapp_not_found(state: &AxumState) -> (StatusCode, [(HeaderName, HeaderValue); 2], Cow<'static, [u8]>) {
+    let content = match &state.webapp_not_found {
+        Some(content) => Cow::Owned(content.clone()),
+        None => Cow::Borrowed(state.webapp_resources.get("404.html").unwrap().content),
+    };
+    (
+        StatusCode::NOT_FOUND,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("text/html")),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+        content,
+    )
+}
+
 /// Redirects to the login page
 pub async fn webapp_me(State(state): State<Arc<AxumState>>) -> (StatusCode, [(HeaderName, HeaderValue); 2]) {
     let target = format!("{}/webapp/index.html", state.application.configuration.web_public_uri);
@@ -209,38 +486,130 @@ pub async fn webapp_me(State(state): State<Arc<AxumState>>) -> (StatusCode, [(He
 }
 
 /// Gets a file from the documentation
-pub async fn get_docs_resource(
-    auth_data: AuthData,
-    State(state): State<Arc<AxumState>>,
-    request: Request<Body>,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Body), (StatusCode, [(HeaderName, HeaderValue); 1], Body)> {
+/// A single range of bytes can be requested with the `Range` header
+pub async fn get_docs_resource(auth_data: AuthData, State(state): State<Arc<AxumState>>, request: Request<Body>) -> Response {
     let is_authenticated = state.application.authenticate(&auth_data).await.is_ok();
     if !is_authenticated {
-        let (code, headers) = get_auth_redirect(&state);
-        return Ok((code, headers, Body::empty()));
+        return get_auth_redirect(&state).into_response();
     }
 
     let path = &request.uri().path()[1..]; // strip leading /
     assert!(path.starts_with("docs/"));
-    let extension = get_content_type(path);
-    match state.application.get_service_storage().download_doc_file(&path[5..]).await {
-        Ok(content) => Ok((
-            StatusCode::OK,
-            [
-                (header::CONTENT_TYPE, HeaderValue::from_str(extension).unwrap()),
-                (header::CACHE_CONTROL, HeaderValue::from_static("max-age=3600")),
-            ],
-            Body::from(content),
-        )),
-        Err(e) => {
-            let message = e.to_string();
-            Err((
-                StatusCode::NOT_FOUND,
-                [(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
-                Body::from(message),
-            ))
+    let package = path[5..].split('/').next().unwrap_or_default();
+    if let Ok(Some(url)) = state.application.get_crate_docs_external_url(package).await {
+        if let Ok(location) = HeaderValue::from_str(&url) {
+            return (
+                StatusCode::FOUND,
+                [
+                    (header::LOCATION, location),
+                    (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+                ],
+            )
+                .into_response();
+        }
+    }
+    if !state.application.get_crate_docs_enabled(package).await.unwrap_or(false) {
+        return (
+            StatusCode::NOT_FOUND,
+            [(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
+            Body::from("documentation is not available for this crate"),
+        )
+            .into_response();
+    }
+    let storage = state.application.get_service_storage();
+    let cache_control = HeaderValue::from_str(&state.application.configuration.web_content_cache_control).unwrap();
+    // the tag is only missing when the file does not exist, which is handled below
+    let etag = storage
+        .get_doc_file_etag(&path[5..])
+        .await
+        .ok()
+        .and_then(|etag| HeaderValue::from_str(&format!("\"{etag}\"")).ok());
+    if let Some(etag) = &etag {
+        if is_not_modified(request.headers(), etag) {
+            return not_modified_response(etag.clone(), cache_control);
         }
     }
+    let headers = (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_str(get_content_type(path)).unwrap()),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        etag.map(|etag| [(header::ETAG, etag)]),
+    );
+    let result = match get_range_spec(request.headers()) {
+        None => storage
+            .download_doc_file(&path[5..])
+            .await
+            .map(|content| (StatusCode::OK, headers, [accept_ranges_header()], content).into_response()),
+        Some(spec) => storage
+            .download_doc_file_range(&path[5..], spec)
+            .await
+            .map(|content| ranged_content_response(content, headers)),
+    };
+    result.unwrap_or_else(|e| {
+        (
+            StatusCode::NOT_FOUND,
+            [(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
+            Body::from(e.to_string()),
+        )
+            .into_response()
+    })
+}
+
+/// Gets whether the copy cached by the client is still valid, i.e. whether the entity tag matches the `If-None-Match` header
+/// Weak tags from the client are compared by their value
+fn is_not_modified(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Builds the `304` response for a copy cached by the client that is still valid
+fn not_modified_response(etag: HeaderValue, cache_control: HeaderValue) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
+        [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+    )
+        .into_response()
+}
+
+/// Gets the single range of bytes requested with the `Range` header, if any
+fn get_range_spec(headers: &HeaderMap) -> Option<ByteRangeSpec> {
+    headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(ByteRangeSpec::parse)
+}
+
+/// Gets the header telling that ranges of bytes can be requested
+fn accept_ranges_header() -> (HeaderName, HeaderValue) {
+    (header::ACCEPT_RANGES, HeaderValue::from_static("bytes"))
+}
+
+/// Builds the response for a range of a content
+/// This is a `206` with the part of the content, or a `416` when the range is beyond the end of the content
+fn ranged_content_response(content: RangedContent, headers: impl IntoResponseParts) -> Response {
+    let content_range = (
+        header::CONTENT_RANGE,
+        HeaderValue::from_str(&content.content_range()).unwrap(),
+    );
+    match content {
+        RangedContent::Partial { content, .. } => (
+            StatusCode::PARTIAL_CONTENT,
+            headers,
+            [content_range, accept_ranges_header()],
+            content,
+        )
+            .into_response(),
+        RangedContent::Unsatisfiable { .. } => (StatusCode::RANGE_NOT_SATISFIABLE, [content_range]).into_response(),
+    }
 }
 
 fn get_content_type(name: &str) -> &'static str {
@@ -265,24 +634,60 @@ pub async fn api_v1_get_current_user(auth_data: AuthData, State(state): State<Ar
     response(state.application.get_current_user(&auth_data).await)
 }
 
+/// Gets the preferences of the current user for the notifications sent by email
+pub async fn api_v1_get_notification_settings(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> ApiResult<UserNotificationSettings> {
+    response(state.application.get_notification_settings(&auth_data).await)
+}
+
+/// Sets the preferences of the current user for the notifications sent by email
+pub async fn api_v1_set_notification_settings(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    settings: Json<UserNotificationSettings>,
+) -> ApiResult<UserNotificationSettings> {
+    response(state.application.set_notification_settings(&auth_data, &settings).await)
+}
+
+#[derive(Deserialize)]
+pub struct OAuthCallbackForm {
+    state: Option<String>,
+}
+
 /// Attempts to login using an OAuth code
 pub async fn api_v1_login_with_oauth_code(
     mut auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
+    form: Query<OAuthCallbackForm>,
     body: Bytes,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Json<RegistryUser>), (StatusCode, Json<ApiError>)> {
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Json<RegistryUser>), (StatusCode, Json<ApiError>)> {
     let code = String::from_utf8_lossy(&body);
-    let registry_user = state.application.login_with_oauth_code(&code).await.map_err(response_error)?;
+    let login_state = auth_data
+        .get_cookie(OAUTH_STATE_COOKIE, true)
+        .and_then(|value| serde_json::from_str::<OAuthLoginState>(&value).ok());
+    let registry_user = state
+        .application
+        .login_with_oauth_code(&code, form.state.as_deref(), login_state.as_ref())
+        .await
+        .map_err(response_error)?;
+    let state_cookie = auth_data.create_expired_cookie(OAUTH_STATE_COOKIE, true);
     let cookie = auth_data.create_id_cookie(&AuthenticatedUser {
         uid: registry_user.id,
         principal: registry_user.email.clone(),
         // when authenticated via cookies, can do everything
         can_write: true,
         can_admin: true,
+        token_id: None,
+        crates: Vec::new(),
     });
     Ok((
         StatusCode::OK,
-        [(SET_COOKIE, HeaderValue::from_str(&cookie.to_string()).unwrap())],
+        [
+            (SET_COOKIE, HeaderValue::from_str(&cookie.to_string()).unwrap()),
+            (SET_COOKIE, HeaderValue::from_str(&state_cookie.to_string()).unwrap()),
+        ],
         Json(registry_user),
     ))
 }
@@ -301,22 +706,63 @@ pub async fn api_v1_get_tokens(auth_data: AuthData, State(state): State<Arc<Axum
     response(state.application.get_tokens(&auth_data).await)
 }
 
+/// Gets the crates that the current token may publish to
+pub async fn api_v1_get_token_publish_scope(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> ApiResult<TokenPublishScope> {
+    response(state.application.get_token_publish_scope(&auth_data).await)
+}
+
 #[derive(Deserialize)]
 pub struct CreateTokenQuery {
     #[serde(rename = "canWrite")]
     can_write: bool,
     #[serde(rename = "canAdmin")]
     can_admin: bool,
+    #[serde(rename = "expiresAt", default)]
+    expires_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    crates: Option<String>,
+    #[serde(rename = "allowedIps", default)]
+    allowed_ips: Option<String>,
+}
+
+/// Splits a comma-separated list in a query parameter, ignoring the empty items
+fn split_query_list(list: Option<&str>) -> Vec<String> {
+    list.map(|list| {
+        list.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    })
+    .unwrap_or_default()
 }
 
 /// Creates a token for the current user
 pub async fn api_v1_create_token(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Query(CreateTokenQuery { can_write, can_admin }): Query<CreateTokenQuery>,
+    Query(CreateTokenQuery {
+        can_write,
+        can_admin,
+        expires_at,
+        crates,
+        allowed_ips,
+    }): Query<CreateTokenQuery>,
     name: String,
 ) -> ApiResult<RegistryUserTokenWithSecret> {
-    response(state.application.create_token(&auth_data, &name, can_write, can_admin).await)
+    let restrictions = TokenRestrictions {
+        crates: split_query_list(crates.as_deref()),
+        allowed_ips: split_query_list(allowed_ips.as_deref()),
+    };
+    response(
+        state
+            .application
+            .create_token(&auth_data, &name, can_write, can_admin, expires_at, &restrictions)
+            .await,
+    )
 }
 
 /// Revoke a previous token
@@ -328,11 +774,29 @@ pub async fn api_v1_revoke_token(
     response(state.application.revoke_token(&auth_data, token_id).await)
 }
 
+/// Revokes all the tokens matching criteria
+pub async fn api_v1_revoke_tokens_matching(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    input: Json<TokensRevocationCriteria>,
+) -> ApiResult<TokensRevocationResult> {
+    response(state.application.revoke_tokens_matching(&auth_data, &input).await)
+}
+
 /// Gets the known users
 pub async fn api_v1_get_users(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<Vec<RegistryUser>> {
     response(state.application.get_users(&auth_data).await)
 }
 
+/// Creates users in bulk, before their first login
+pub async fn api_v1_import_users(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    users: Json<Vec<RegistryUser>>,
+) -> ApiResult<Vec<UserImportResult>> {
+    response(state.application.import_users(&auth_data, &users).await)
+}
+
 /// Updates the information of a user
 pub async fn api_v1_update_user(
     auth_data: AuthData,
@@ -367,6 +831,47 @@ pub async fn api_v1_deactivate_user(
     response(state.application.deactivate_user(&auth_data, &email).await)
 }
 
+/// Reassigns all the crates owned by a user to a successor
+pub async fn api_v1_reassign_user_crates(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(Base64(email)): Path<Base64>,
+    input: Json<UserCratesReassignment>,
+) -> ApiResult<Vec<String>> {
+    response(state.application.reassign_user_crates(&auth_data, &email, &input).await)
+}
+
+/// Sends an email with a link to verify the email address of a user
+pub async fn api_v1_send_verification_email(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(Base64(email)): Path<Base64>,
+) -> ApiResult<()> {
+    response(state.application.send_verification_email(&auth_data, &email).await)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailForm {
+    token: String,
+}
+
+/// Confirms the email address of a user with the link sent by email
+/// Redirects to the web app on success
+pub async fn api_v1_verify_email(
+    State(state): State<Arc<AxumState>>,
+    form: Query<VerifyEmailForm>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2]), (StatusCode, Json<ApiError>)> {
+    state.application.verify_email(&form.token).await.map_err(response_error)?;
+    let target = format!("{}/webapp/index.html", state.application.configuration.web_public_uri);
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, HeaderValue::from_str(&target).unwrap()),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+    ))
+}
+
 /// Attempts to deactivate a user
 pub async fn api_v1_reactivate_user(
     auth_data: AuthData,
@@ -379,7 +884,11 @@ pub async fn api_v1_reactivate_user(
 #[derive(Deserialize)]
 pub struct SearchForm {
     q: String,
+    page: Option<usize>,
+    cursor: Option<String>,
     per_page: Option<usize>,
+    #[serde(default)]
+    search_readme: bool,
 }
 
 pub async fn api_v1_cargo_search(
@@ -387,116 +896,632 @@ pub async fn api_v1_cargo_search(
     State(state): State<Arc<AxumState>>,
     form: Query<SearchForm>,
 ) -> ApiResult<SearchResults> {
-    response(state.application.search_crates(&auth_data, &form.q, form.per_page).await)
+    response(
+        state
+            .application
+            .search_crates(
+                &auth_data,
+                &form.q,
+                form.page,
+                form.cursor.as_deref(),
+                form.per_page,
+                form.search_readme,
+            )
+            .await,
+    )
 }
 
-/// Gets the global statistics for the registry
-pub async fn api_v1_get_crates_stats(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<GlobalStats> {
-    response(state.application.get_crates_stats(&auth_data).await)
+#[derive(Deserialize)]
+pub struct ListingForm {
+    page: Option<i64>,
+    per_page: Option<i64>,
 }
 
-/// Gets all the packages that are outdated while also being the latest version
-pub async fn api_v1_get_crates_outdated_heads(
+/// Gets a page in the listing of all crates with their latest version
+pub async fn api_v1_get_crates_listing(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-) -> ApiResult<Vec<CrateAndVersion>> {
-    response(state.application.get_crates_outdated_heads(&auth_data).await)
+    form: Query<ListingForm>,
+) -> ApiResult<CratesListing> {
+    response(
+        state
+            .application
+            .get_crates_listing(&auth_data, form.page, form.per_page)
+            .await,
+    )
 }
 
-pub async fn api_v1_cargo_publish_crate_version(
-    auth_data: AuthData,
-    State(state): State<Arc<AxumState>>,
-    body: Bytes,
-) -> ApiResult<CrateUploadResult> {
-    response(state.application.publish_crate_version(&auth_data, &body).await)
+#[derive(Deserialize)]
+pub struct PublishedForm {
+    from: NaiveDate,
+    to: NaiveDate,
+    page: Option<i64>,
+    per_page: Option<i64>,
 }
 
-pub async fn api_v1_get_crate_info(
+/// Gets a page of the crate versions published between two dates
+pub async fn api_v1_get_crates_published(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
-) -> ApiResult<CrateInfo> {
-    response(state.application.get_crate_info(&auth_data, &package).await)
+    form: Query<PublishedForm>,
+) -> ApiResult<CratePublications> {
+    response(
+        state
+            .application
+            .get_crates_published_between(&auth_data, form.from, form.to, form.page, form.per_page)
+            .await,
+    )
 }
 
-pub async fn api_v1_get_crate_last_readme(
+/// Gets the sitemap for all the crates
+pub async fn get_sitemap(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
-    let data = state
-        .application
-        .get_crate_last_readme(&auth_data, &package)
-        .await
-        .map_err(response_error)?;
-
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], String), (StatusCode, Json<ApiError>)> {
+    let sitemap = state.application.get_sitemap(&auth_data).await.map_err(response_error)?;
     Ok((
         StatusCode::OK,
-        [(header::CONTENT_TYPE, HeaderValue::from_static("text/markdown"))],
-        data,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/xml")),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+        sitemap,
     ))
 }
 
-pub async fn api_v1_get_crate_readme(
+/// Gets the effective configuration, with the secrets redacted
+pub async fn api_v1_get_configuration(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<Configuration> {
+    response(state.application.get_configuration(&auth_data).await)
+}
+
+/// Rebuilds the index for all crates from the content in the storage
+pub async fn api_v1_reindex_from_storage(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
-    let data = state
-        .application
-        .get_crate_readme(&auth_data, &package, &version)
-        .await
-        .map_err(response_error)?;
+) -> ApiResult<ReindexSummary> {
+    response(state.application.reindex_from_storage(&auth_data).await)
+}
 
-    Ok((
-        StatusCode::OK,
-        [(header::CONTENT_TYPE, HeaderValue::from_static("text/markdown"))],
-        data,
-    ))
+#[derive(Deserialize)]
+pub struct StorageGcForm {
+    #[serde(rename = "dryRun", default)]
+    dry_run: bool,
 }
 
-pub async fn api_v1_download_crate(
+/// Collects the stored files of the crates and versions that were deleted
+pub async fn api_v1_collect_storage_garbage(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
-) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
-    match state.application.get_crate_content(&auth_data, &package, &version).await {
-        Ok(data) => Ok((
-            StatusCode::OK,
-            [(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"))],
-            data,
-        )),
-        Err(mut error) => {
-            if error.http == 401 {
-                // map to 403
-                error.http = 403;
-            }
-            Err(response_error(error))
-        }
-    }
+    form: Query<StorageGcForm>,
+) -> ApiResult<StorageGcSummary> {
+    response(state.application.collect_storage_garbage(&auth_data, form.dry_run).await)
 }
 
-pub async fn api_v1_cargo_yank(
-    auth_data: AuthData,
-    State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
-) -> ApiResult<YesNoResult> {
-    response(state.application.yank_crate_version(&auth_data, &package, &version).await)
+/// Gets the status of the background workers
+pub async fn api_v1_get_workers_status(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<WorkersStatus> {
+    response(state.application.get_workers_status(&auth_data).await)
 }
 
-pub async fn api_v1_cargo_unyank(
-    auth_data: AuthData,
-    State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
-) -> ApiResult<YesNoResult> {
-    response(state.application.unyank_crate_version(&auth_data, &package, &version).await)
+/// Gets the global statistics for the registry
+pub async fn api_v1_get_crates_stats(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<GlobalStats> {
+    response(state.application.get_crates_stats(&auth_data).await)
 }
 
-pub async fn api_v1_regen_crate_version_doc(
+#[derive(Deserialize)]
+pub struct DownloadsSeriesForm {
+    from: NaiveDate,
+    to: NaiveDate,
+    #[serde(default)]
+    granularity: DownloadsGranularity,
+}
+
+/// Gets the downloads across all crates over time
+pub async fn api_v1_get_crates_dl_stats(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
-    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
-) -> ApiResult<()> {
+    form: Query<DownloadsSeriesForm>,
+) -> ApiResult<GlobalDownloadsSeries> {
+    response(
+        state
+            .application
+            .get_global_dl_timeseries(&auth_data, form.from, form.to, form.granularity)
+            .await,
+    )
+}
+
+/// Gets the advisories found by the periodic scan against the last version of the crates
+pub async fn api_v1_get_affected_crates_advisories(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> ApiResult<Vec<AffectedCrateAdvisory>> {
+    response(state.application.get_affected_crates_advisories(&auth_data).await)
+}
+
+#[derive(Deserialize)]
+pub struct PathInfoAdvisory {
+    advisory: String,
+}
+
+/// Gets the crates affected by an advisory through their dependencies
+pub async fn api_v1_get_crates_affected_by_advisory(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoAdvisory { advisory }): Path<PathInfoAdvisory>,
+) -> ApiResult<Vec<AdvisoryAffectedCrate>> {
+    response(state.application.get_crates_affected_by_advisory(&auth_data, &advisory).await)
+}
+
+/// Gets all the packages that are outdated while also being the latest version
+pub async fn api_v1_get_crates_outdated_heads(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> ApiResult<Vec<CrateAndVersion>> {
+    response(state.application.get_crates_outdated_heads(&auth_data).await)
+}
+
+/// Gets whether the latest version of each of the given crates is outdated
+pub async fn api_v1_get_outdated_for_crates(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    input: Json<Vec<String>>,
+) -> ApiResult<Vec<CrateOutdatedStatus>> {
+    response(state.application.get_outdated_for_crates(&auth_data, input.0).await)
+}
+
+pub async fn api_v1_cargo_publish_crate_version(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<CrateUploadResult> {
+    response(
+        state
+            .application
+            .publish_crate_version(&auth_data, &body, get_user_agent(&headers))
+            .await,
+    )
+}
+
+/// Publishes a crate version from a multipart form with a `metadata` part (JSON) and a `crate` part (the .crate file)
+pub async fn api_v1_publish_crate_version_multipart(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> ApiResult<CrateUploadResult> {
+    let package = read_crate_upload_multipart(multipart).await.map_err(response_error)?;
+    response(
+        state
+            .application
+            .publish_crate_upload(&auth_data, package, get_user_agent(&headers))
+            .await,
+    )
+}
+
+/// Gets the value of the `User-Agent` header of a request, if any
+fn get_user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok())
+}
+
+/// Reads the upload data for a crate from a multipart form
+async fn read_crate_upload_multipart(mut multipart: Multipart) -> Result<CrateUploadData, ApiError> {
+    let mut metadata = None;
+    let mut content = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| specialize(error_invalid_request(), e.to_string()))?
+    {
+        let target = match field.name() {
+            Some("metadata") => &mut metadata,
+            Some("crate") => &mut content,
+            _ => continue,
+        };
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| specialize(error_invalid_request(), e.to_string()))?;
+        *target = Some(data);
+    }
+    let metadata = metadata.ok_or_else(|| specialize(error_invalid_request(), String::from("missing the metadata part")))?;
+    let content = content.ok_or_else(|| specialize(error_invalid_request(), String::from("missing the crate part")))?;
+    CrateUploadData::from_parts(&metadata, content.to_vec())
+}
+
+pub async fn api_v1_get_crate_info(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<CrateInfo> {
+    response(state.application.get_crate_info(&auth_data, &package).await)
+}
+
+/// Gets all the data about a crate in the shape of the crates.io API
+pub async fn cratesio_api_v1_get_crate_info(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<CratesIoCrateResponse> {
+    response(state.application.get_crate_info_cratesio(&auth_data, &package).await)
+}
+
+pub async fn api_v1_get_crate_last_readme(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
+    let data = state
+        .application
+        .get_crate_last_readme(&auth_data, &package)
+        .await
+        .map_err(response_error)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, HeaderValue::from_static(data.content_type))],
+        data.content,
+    ))
+}
+
+pub async fn api_v1_get_crate_readme(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Vec<u8>), (StatusCode, Json<ApiError>)> {
+    let data = state
+        .application
+        .get_crate_readme(&auth_data, &package, &version)
+        .await
+        .map_err(response_error)?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(data.content_type)),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_str(&state.application.configuration.web_content_cache_control).unwrap(),
+            ),
+        ],
+        data.content,
+    ))
+}
+
+/// Checks that the database, the storage and the index agree on the versions of a crate
+pub async fn api_v1_verify_crate_index(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<CrateConsistencyReport> {
+    response(state.application.verify_crate_index(&auth_data, &package).await)
+}
+
+/// Gets the status of the generation of the documentation for a crate version
+pub async fn api_v1_get_crate_doc_status(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<CrateDocsStatus> {
+    response(state.application.get_crate_doc_status(&auth_data, &package, &version).await)
+}
+
+#[derive(Deserialize)]
+pub struct PathInfoCrateVersionTarget {
+    package: String,
+    version: String,
+    target: String,
+}
+
+#[derive(Deserialize)]
+pub struct DocLogForm {
+    variant: Option<String>,
+}
+
+/// Gets the log of the last generation of the documentation of a crate version for a target, or a variant of it
+pub async fn api_v1_get_crate_doc_log(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersionTarget {
+        package,
+        version,
+        target,
+    }): Path<PathInfoCrateVersionTarget>,
+    form: Query<DocLogForm>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
+    let log = state
+        .application
+        .get_crate_doc_log(&auth_data, &package, &version, &target, form.variant.as_deref())
+        .await
+        .map_err(response_error)?;
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"))],
+        log,
+    ))
+}
+
+/// Gets the changelog for a crate version
+pub async fn api_v1_get_crate_changelog(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
+    let data = state
+        .application
+        .get_crate_changelog(&auth_data, &package, &version)
+        .await
+        .map_err(response_error)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("text/markdown"))],
+        data,
+    ))
+}
+
+/// Gets the `Cargo.lock` shipped with a crate version
+pub async fn api_v1_get_crate_lockfile(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Vec<u8>), (StatusCode, Json<ApiError>)> {
+    let data = state
+        .application
+        .get_crate_lockfile(&auth_data, &package, &version)
+        .await
+        .map_err(response_error)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"))],
+        data,
+    ))
+}
+
+/// Gets the headers for the content of a crate version
+/// A published version never changes so that it can be cached, the checksum of the crate is a strong `ETag`
+fn crate_content_headers(state: &AxumState, content: &CrateContent) -> [(HeaderName, HeaderValue); 4] {
+    [
+        (header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream")),
+        (header::CONTENT_LENGTH, HeaderValue::from(content.size)),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&state.application.configuration.web_crates_cache_control).unwrap(),
+        ),
+        (
+            header::ETAG,
+            HeaderValue::from_str(&format!("\"{}\"", content.checksum)).unwrap(),
+        ),
+    ]
+}
+
+/// Builds the body for the content of a crate version, streamed from the storage
+/// The download slot, if any, is held until the content is completely sent
+fn crate_content_body(content: CrateContent, permit: Option<OwnedSemaphorePermit>) -> Body {
+    Body::from_stream(content.stream.map_err(move |e| {
+        let _permit = &permit;
+        std::io::Error::other(e.to_string())
+    }))
+}
+
+/// Downloads a crate version
+/// A single range of bytes can be requested with the `Range` header, for example to resume a download
+pub async fn api_v1_download_crate(
+    auth_data: AuthData,
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    headers: HeaderMap,
+) -> Response {
+    if headers.contains_key(header::IF_NONE_MATCH) {
+        // the client has a cached copy, check it against the stored checksum without reading the crate
+        match state.application.get_crate_checksum(&auth_data, &package, &version).await {
+            Ok(Some(checksum)) => {
+                let etag = HeaderValue::from_str(&format!("\"{checksum}\"")).unwrap();
+                if is_not_modified(&headers, &etag) {
+                    let cache_control =
+                        HeaderValue::from_str(&state.application.configuration.web_crates_cache_control).unwrap();
+                    return not_modified_response(etag, cache_control);
+                }
+            }
+            Ok(None) => {}
+            Err(error) => return crate_download_error(error),
+        }
+    }
+    let permit = match state.acquire_download_permit(user.as_deref()).await {
+        Ok(permit) => permit,
+        Err(error) => return response_error(error).into_response(),
+    };
+    let result = match get_range_spec(&headers) {
+        None => state
+            .application
+            .get_crate_content(&auth_data, &package, &version)
+            .await
+            .map(|content| {
+                (
+                    StatusCode::OK,
+                    crate_content_headers(&state, &content),
+                    [accept_ranges_header()],
+                    crate_content_body(content, permit),
+                )
+                    .into_response()
+            }),
+        Some(spec) => state
+            .application
+            .get_crate_content_range(&auth_data, &package, &version, spec)
+            .await
+            .map(|content| {
+                let headers = [
+                    (header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream")),
+                    (
+                        header::CACHE_CONTROL,
+                        HeaderValue::from_str(&state.application.configuration.web_crates_cache_control).unwrap(),
+                    ),
+                ];
+                ranged_content_response(content, headers)
+            }),
+    };
+    result.unwrap_or_else(crate_download_error)
+}
+
+/// Builds the response for a failed download of a crate
+fn crate_download_error(mut error: ApiError) -> Response {
+    if error.http == 401 {
+        // map to 403
+        error.http = 403;
+    }
+    response_error(error).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct DownloadLinkForm {
+    ttl: Option<u64>,
+}
+
+/// Creates a signed link to download a crate version without authentication
+pub async fn api_v1_create_crate_download_link(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    form: Query<DownloadLinkForm>,
+) -> ApiResult<SignedDownloadLink> {
+    response(
+        state
+            .application
+            .create_download_token(&auth_data, &package, &version, form.ttl)
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SignedDownloadForm {
+    expires: i64,
+    signature: String,
+}
+
+/// Downloads a crate version with a signed link instead of the normal authentication
+pub async fn api_v1_download_crate_signed(
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    form: Query<SignedDownloadForm>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 4], Body), (StatusCode, Json<ApiError>)> {
+    let content = state
+        .application
+        .get_crate_content_signed(&package, &version, form.expires, &form.signature)
+        .await
+        .map_err(response_error)?;
+    let headers = crate_content_headers(&state, &content);
+    Ok((StatusCode::OK, headers, crate_content_body(content, None)))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteCrateForm {
+    confirm: String,
+}
+
+/// Deletes a crate with all its versions
+pub async fn api_v1_delete_crate(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+    form: Query<DeleteCrateForm>,
+) -> ApiResult<()> {
+    response(state.application.delete_crate(&auth_data, &package, &form.confirm).await)
+}
+
+/// Deletes a version of a crate
+pub async fn api_v1_delete_crate_version(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    form: Query<DeleteCrateForm>,
+) -> ApiResult<()> {
+    response(
+        state
+            .application
+            .delete_crate_version(&auth_data, &package, &version, &form.confirm)
+            .await,
+    )
+}
+
+/// Downloads a set of crate versions as a tar archive in the layout of a local registry
+pub async fn api_v1_download_crates_bundle(
+    auth_data: AuthData,
+    user: Option<Extension<AuthenticatedUser>>,
+    State(state): State<Arc<AxumState>>,
+    input: Json<Vec<CrateAndVersion>>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Body), (StatusCode, Json<ApiError>)> {
+    let permit = state.acquire_download_permit(user.as_deref()).await.map_err(response_error)?;
+    let stream = state
+        .application
+        .download_crates_bundle(&auth_data, &input)
+        .await
+        .map_err(response_error)?;
+    // the download slot is held until the archive is completely sent
+    let body = Body::from_stream(stream.map_err(move |e| {
+        let _permit = &permit;
+        std::io::Error::other(e.to_string())
+    }));
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/x-tar")),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_static("attachment; filename=\"crates.tar\""),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct YankForm {
+    #[serde(default)]
+    expire: bool,
+}
+
+pub async fn api_v1_cargo_yank(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    form: Query<YankForm>,
+) -> ApiResult<YesNoResult> {
+    response(
+        state
+            .application
+            .yank_crate_version(&auth_data, &package, &version, form.expire)
+            .await,
+    )
+}
+
+/// Confirms the yank of a crate version so that it is no longer automatically unyanked
+pub async fn api_v1_confirm_crate_version_yank(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<YesNoResult> {
+    response(
+        state
+            .application
+            .confirm_crate_version_yank(&auth_data, &package, &version)
+            .await,
+    )
+}
+
+pub async fn api_v1_cargo_unyank(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<YesNoResult> {
+    response(state.application.unyank_crate_version(&auth_data, &package, &version).await)
+}
+
+pub async fn api_v1_regen_crate_version_doc(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<()> {
     response(
         state
             .application
@@ -518,6 +1543,65 @@ pub async fn api_v1_check_crate_version(
     )
 }
 
+/// Gets the complete dependency graph of a crate version, as nodes and edges
+pub async fn api_v1_get_crate_dep_graph(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<DepsGraphExport> {
+    response(
+        state
+            .application
+            .get_crate_dependency_graph(&auth_data, &package, &version)
+            .await,
+    )
+}
+
+/// Checks the dependencies of a crate version against the supplied policy
+pub async fn api_v1_check_crate_version_policy(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    policy: Json<DepsPolicy>,
+) -> ApiResult<DepsPolicyReport> {
+    response(
+        state
+            .application
+            .check_crate_against_policy(&auth_data, &package, &version, &policy)
+            .await,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PathInfoCrateVersionPolicy {
+    package: String,
+    version: String,
+    policy: String,
+}
+
+/// Checks the dependencies of a crate version against a named policy
+pub async fn api_v1_check_crate_version_named_policy(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersionPolicy {
+        package,
+        version,
+        policy,
+    }): Path<PathInfoCrateVersionPolicy>,
+) -> ApiResult<DepsPolicyReport> {
+    let policy = state
+        .application
+        .get_deps_policy(&auth_data, &policy)
+        .await
+        .map_err(response_error)?;
+    response(
+        state
+            .application
+            .check_crate_against_policy(&auth_data, &package, &version, &policy)
+            .await,
+    )
+}
+
 /// Gets the download statistics for a crate
 pub async fn api_v1_get_crate_dl_stats(
     auth_data: AuthData,
@@ -527,6 +1611,25 @@ pub async fn api_v1_get_crate_dl_stats(
     response(state.application.get_crate_dl_stats(&auth_data, &package).await)
 }
 
+/// Gets the download counts for all the versions of a crate, in a stable format for external tools
+/// The counts change frequently, so they are only cached briefly
+pub async fn api_v1_get_crate_dl_counts(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], Json<CrateDownloadCounts>), (StatusCode, Json<ApiError>)> {
+    let counts = state
+        .application
+        .get_crate_dl_counts(&auth_data, &package)
+        .await
+        .map_err(response_error)?;
+    Ok((
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, HeaderValue::from_static("private, max-age=60"))],
+        Json(counts),
+    ))
+}
+
 pub async fn api_v1_cargo_get_crate_owners(
     auth_data: AuthData,
     State(state): State<Arc<AxumState>>,
@@ -558,6 +1661,55 @@ pub async fn api_v1_cargo_remove_crate_owners(
     )
 }
 
+/// Gets the crates that have no active owner
+pub async fn api_v1_get_orphaned_crates(auth_data: AuthData, State(state): State<Arc<AxumState>>) -> ApiResult<Vec<String>> {
+    response(state.application.get_orphaned_crates(&auth_data).await)
+}
+
+/// Claims the ownership of a crate that has no active owner
+pub async fn api_v1_claim_orphaned_crate(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<()> {
+    response(state.application.claim_orphaned_crate(&auth_data, &package).await)
+}
+
+/// Re-attests the ownership of a crate by the current user
+pub async fn api_v1_attest_crate_ownership(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<()> {
+    response(state.application.attest_crate_ownership(&auth_data, &package).await)
+}
+
+/// Gets the owners that did not re-attest their ownership in time
+pub async fn api_v1_get_stale_crate_owners(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> ApiResult<Vec<StaleCrateOwner>> {
+    response(state.application.get_stale_crate_owners(&auth_data).await)
+}
+
+/// Gets the information required to manage a crate, with the permissions of the current user on it
+pub async fn api_v1_get_crate_management_info(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<CrateManagementInfo> {
+    response(state.application.get_crate_management_info(&auth_data, &package).await)
+}
+
+/// Gets the versions of a crate, sorted according to semver from the greatest
+pub async fn api_v1_get_crate_versions(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<Vec<CrateVersionSummary>> {
+    response(state.application.get_crate_versions_sorted(&auth_data, &package).await)
+}
+
 /// Gets the targets for a crate
 pub async fn api_v1_get_crate_targets(
     auth_data: AuthData,
@@ -577,6 +1729,129 @@ pub async fn api_v1_set_crate_targets(
     response(state.application.set_crate_targets(&auth_data, &package, &input).await)
 }
 
+/// Gets the settings for the generation of the documentation of a crate
+pub async fn api_v1_get_crate_docs_build_settings(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<CrateDocsBuildSettings> {
+    response(state.application.get_crate_docs_build_settings(&auth_data, &package).await)
+}
+
+/// Sets the settings for the generation of the documentation of a crate
+pub async fn api_v1_set_crate_docs_build_settings(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+    input: Json<CrateDocsBuildSettings>,
+) -> ApiResult<()> {
+    response(
+        state
+            .application
+            .set_crate_docs_build_settings(&auth_data, &package, &input)
+            .await,
+    )
+}
+
+/// Gets the targets specifically configured for checking the dependencies of a crate
+pub async fn api_v1_get_crate_deps_targets(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<Option<Vec<String>>> {
+    response(state.application.get_crate_deps_targets(&auth_data, &package).await)
+}
+
+/// Sets the targets specifically used for checking the dependencies of a crate, `null` to use the build targets
+pub async fn api_v1_set_crate_deps_targets(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+    input: Json<Option<Vec<String>>>,
+) -> ApiResult<()> {
+    response(
+        state
+            .application
+            .set_crate_deps_targets(&auth_data, &package, input.as_deref())
+            .await,
+    )
+}
+
+/// Lists the files in the stored documentation of a crate version
+pub async fn api_v1_get_crate_doc_files(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+) -> ApiResult<Vec<CrateDocFile>> {
+    response(state.application.get_crate_doc_files(&auth_data, &package, &version).await)
+}
+
+#[derive(Deserialize)]
+pub struct DocsExportForm {
+    target: String,
+}
+
+/// Exports the documentation of a crate version to a folder, for publication on a static host
+pub async fn api_v1_export_crate_docs(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrateVersion { package, version }): Path<PathInfoCrateVersion>,
+    form: Query<DocsExportForm>,
+) -> ApiResult<DocsExport> {
+    response(
+        state
+            .application
+            .export_docs_to_dir(&auth_data, &package, &version, &form.target)
+            .await,
+    )
+}
+
+/// Sets whether the documentation is generated and served for a crate
+pub async fn api_v1_set_crate_docs_settings(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+    input: Json<CrateDocsSettings>,
+) -> ApiResult<()> {
+    response(state.application.set_crate_docs_settings(&auth_data, &package, &input).await)
+}
+
+/// Sets the URL of the documentation of a crate hosted elsewhere
+pub async fn api_v1_set_crate_docs_external(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+    input: Json<CrateDocsExternal>,
+) -> ApiResult<()> {
+    response(state.application.set_crate_docs_external(&auth_data, &package, &input).await)
+}
+
+/// Gets the results of the verification of the last version of a crate on each target
+pub async fn api_v1_get_crate_builds(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+    Path(PathInfoCrate { package }): Path<PathInfoCrate>,
+) -> ApiResult<Vec<CrateVersionBuild>> {
+    response(state.application.get_crate_builds(&auth_data, &package).await)
+}
+
+/// Exports the audit log as NDJSON
+pub async fn api_v1_export_audit_log(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 2], Body), (StatusCode, Json<ApiError>)> {
+    let stream = state.application.export_audit_log(&auth_data).await.map_err(response_error)?;
+    let body = Body::from_stream(stream.map_err(|e| std::io::Error::other(e.to_string())));
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson")),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+        body,
+    ))
+}
+
 pub async fn index_serve_inner(
     index: &Index,
     path: &str,
@@ -636,14 +1911,39 @@ pub async fn index_serve(
     }
     index_serve_check_auth(&state.application, &auth_data).await?;
     let index = state.application.index.lock().await;
-    let (stream, content_type) = index_serve_inner(&index, path).await.map_err(map_err)?;
+    let (stream, content_type) = match index_serve_inner(&index, path).await {
+        Ok(r) => r,
+        Err(e) => {
+            drop(index);
+            if e.http == 404 {
+                // the file for a crate is named after it
+                let package = path.rsplit('/').next().unwrap_or_default();
+                state.application.check_crate_not_deleted(package).await.map_err(map_err)?;
+                if let Some(content) = state.application.get_proxied_index_file(path).await.map_err(map_err)? {
+                    return Ok((
+                        StatusCode::OK,
+                        [
+                            (header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream")),
+                            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+                        ],
+                        Body::from(content),
+                    ));
+                }
+            }
+            return Err(map_err(e));
+        }
+    };
     let body = Body::from_stream(stream);
+    let max_age = state.application.configuration.index.config_cache_max_age;
+    let cache_control = if path == "/config.json" && max_age > 0 {
+        // the index requires authentication, shared caches must not keep it
+        HeaderValue::from_str(&format!("private, max-age={max_age}")).unwrap()
+    } else {
+        HeaderValue::from_static("no-cache")
+    };
     Ok((
         StatusCode::OK,
-        [
-            (header::CONTENT_TYPE, content_type),
-            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
-        ],
+        [(header::CONTENT_TYPE, content_type), (header::CACHE_CONTROL, cache_control)],
         body,
     ))
 }
@@ -705,6 +2005,32 @@ pub async fn index_serve_git_upload_pack(
     ))
 }
 
+/// Gets the health of the registry, including the readiness of the toolchain for the documentation
+pub async fn get_health(State(state): State<Arc<AxumState>>) -> ApiResult<HealthStatus> {
+    response(Ok(state.application.get_health()))
+}
+
+/// Gets the metrics for the registry in the Prometheus text format
+pub async fn get_metrics(
+    auth_data: AuthData,
+    State(state): State<Arc<AxumState>>,
+) -> Result<(StatusCode, [(HeaderName, HeaderValue); 1], String), (StatusCode, Json<ApiError>)> {
+    let metrics = state.application.get_metrics(&auth_data).await.map_err(response_error)?;
+    Ok((
+        StatusCode::OK,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        )],
+        metrics,
+    ))
+}
+
+/// Gets the protocols offered for the index, to configure cargo
+pub async fn api_v1_get_index_protocols(State(state): State<Arc<AxumState>>) -> ApiResult<IndexProtocols> {
+    response(Ok(IndexProtocols::new(&state.application.configuration)))
+}
+
 /// Gets the version data for the application
 ///
 /// # Errors
@@ -5,7 +5,7 @@ CREATE TABLE IF NOT EXISTS SchemaMetadata (
 
 CREATE INDEX IF NOT EXISTS SchemaMetadataIndex ON SchemaMetadata(name);
 
-INSERT INTO SchemaMetadata VALUES ('version', '1.5.0');
+INSERT INTO SchemaMetadata VALUES ('version', '1.33.0');
 
 CREATE TABLE RegistryUser (
     id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -13,7 +13,13 @@ CREATE TABLE RegistryUser (
     email TEXT NOT NULL,
     login TEXT NOT NULL,
     name TEXT NOT NULL,
-    roles TEXT NOT NULL
+    roles TEXT NOT NULL,
+    emailVerified BOOLEAN NOT NULL DEFAULT TRUE,
+    emailVerificationToken TEXT,
+    emailVerificationExpires TIMESTAMP,
+    publishNotifications TEXT NOT NULL DEFAULT 'none',
+    lastPublishDigest TIMESTAMP,
+    advisoryNotifications BOOLEAN NOT NULL DEFAULT TRUE
 );
 
 CREATE INDEX IndexRegistryUserByEmail ON RegistryUser (email);
@@ -26,7 +32,10 @@ CREATE TABLE RegistryUserToken (
     token TEXT NOT NULL,
     lastUsed TIMESTAMP NOT NULL,
     canWrite BOOLEAN NOT NULL,
-    canAdmin BOOLEAN NOT NULL
+    canAdmin BOOLEAN NOT NULL,
+    created TIMESTAMP NOT NULL,
+    expiresAt TIMESTAMP,
+    allowedIps TEXT
 );
 
 CREATE INDEX IndexRegistryUserToken ON RegistryUserToken (user);
@@ -34,7 +43,17 @@ CREATE INDEX IndexRegistryUserToken ON RegistryUserToken (user);
 CREATE TABLE Package (
     name TEXT NOT NULL PRIMARY KEY,
     lowercase TEXT NOT NULL,
-    targets TEXT NOT NULL
+    targets TEXT NOT NULL,
+    docsEnabled BOOLEAN NOT NULL,
+    depsTargets TEXT,
+    repoStars INTEGER,
+    repoLastActivity TIMESTAMP,
+    repoLastCheck TIMESTAMP,
+    docsFeatures TEXT,
+    docsNoDefaultFeatures BOOLEAN NOT NULL DEFAULT FALSE,
+    docsRustdocFlags TEXT NOT NULL DEFAULT '',
+    docsVariants TEXT NOT NULL DEFAULT '[]',
+    docsExternalUrl TEXT
 );
 
 CREATE INDEX IndexPackage ON Package (name);
@@ -42,7 +61,8 @@ CREATE INDEX IndexPackage ON Package (name);
 CREATE TABLE PackageOwner (
     id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
     package TEXT NOT NULL REFERENCES Package(name),
-    owner INTEGER NOT NULL REFERENCES RegistryUser(id)
+    owner INTEGER NOT NULL REFERENCES RegistryUser(id),
+    lastAttestation TIMESTAMP NOT NULL
 );
 
 CREATE INDEX IndexPackageOwner ON PackageOwner (package);
@@ -61,7 +81,119 @@ CREATE TABLE PackageVersion (
     downloads BLOB,
     depsLastCheck TIMESTAMP NOT NULL,
     depsHasOutdated BOOLEAN NOT NULL,
-    depsHasCVEs BOOLEAN NOT NULL
+    depsHasCVEs BOOLEAN NOT NULL,
+    yankExpiry TIMESTAMP,
+    docsStatus TEXT NOT NULL DEFAULT 'queued',
+    docsStatusUpdate TIMESTAMP,
+    uploadUserAgent TEXT
 );
 
 CREATE INDEX IndexPackageVersion ON PackageVersion(package);
+
+CREATE TABLE AuditLog (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    principal TEXT NOT NULL,
+    action TEXT NOT NULL,
+    package TEXT,
+    version TEXT,
+    details TEXT NOT NULL,
+    exported BOOLEAN NOT NULL
+);
+
+CREATE INDEX IndexAuditLogByTimestamp ON AuditLog(timestamp);
+
+CREATE TABLE PackageVersionBuild (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    target TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    message TEXT NOT NULL,
+    lastCheck TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IndexPackageVersionBuild ON PackageVersionBuild(package, version);
+
+CREATE TABLE DownloadDay (
+    day DATE NOT NULL PRIMARY KEY,
+    downloadCount INTEGER NOT NULL
+);
+
+CREATE VIRTUAL TABLE PackageReadmeSearch USING fts5(package UNINDEXED, content);
+
+CREATE TABLE PackageTombstone (
+    id INTEGER PRIMARY KEY,
+    package TEXT NOT NULL,
+    version TEXT,
+    deletedOn TIMESTAMP NOT NULL
+);
+
+CREATE INDEX PackageTombstoneIndex ON PackageTombstone(package);
+
+CREATE TABLE RegistryUserTokenScope (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    token INTEGER NOT NULL REFERENCES RegistryUserToken(id),
+    pattern TEXT NOT NULL
+);
+
+CREATE INDEX IndexRegistryUserTokenScope ON RegistryUserTokenScope (token);
+
+CREATE INDEX IndexRegistryUserTokenLastUsed ON RegistryUserToken (lastUsed);
+
+CREATE TABLE PackageVersionDocs (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    target TEXT NOT NULL,
+    success BOOLEAN NOT NULL,
+    lastUpdate TIMESTAMP NOT NULL,
+    variant TEXT
+);
+
+CREATE INDEX IndexPackageVersionDocs ON PackageVersionDocs(package, version);
+
+CREATE TABLE PackageAdvisoryScan (
+    package TEXT NOT NULL PRIMARY KEY REFERENCES Package(name),
+    version TEXT NOT NULL,
+    targets TEXT NOT NULL,
+    dependencies TEXT NOT NULL,
+    lastScan TIMESTAMP NOT NULL
+);
+
+CREATE TABLE PackageAdvisory (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    version TEXT NOT NULL,
+    advisory TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    dependency TEXT NOT NULL,
+    dependencyVersion TEXT NOT NULL,
+    detectedOn TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IndexPackageAdvisory ON PackageAdvisory(package);
+
+CREATE TABLE PackageAdvisoryNotification (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package TEXT NOT NULL REFERENCES Package(name),
+    advisory TEXT NOT NULL,
+    sentOn TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX IndexPackageAdvisoryNotification ON PackageAdvisoryNotification(package, advisory);
+
+CREATE VIRTUAL TABLE PackageSearch USING fts5(package UNINDEXED, name, description, keywords);
+
+CREATE TABLE ProxiedCrateVersion (
+    id INTEGER PRIMARY KEY,
+    package TEXT NOT NULL,
+    version TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    cachedOn TIMESTAMP NOT NULL,
+    downloadCount INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE UNIQUE INDEX ProxiedCrateVersionIndex ON ProxiedCrateVersion(package, version);
+
+CREATE UNIQUE INDEX IndexRegistryUserTokenSecret ON RegistryUserToken(token);
@@ -0,0 +1,174 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to periodically scan the last version of all crates against the `RustSec` advisories
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use log::{error, info};
+use sqlx::{Pool, Sqlite};
+
+use super::deps::{DepsChecker, DepsCheckerData};
+use super::rustsec::{RustSecChecker, RustSecData};
+use crate::model::config::Configuration;
+use crate::model::deps::{CrateAdvisoryScan, DepAdvisory};
+use crate::model::semver::SemverVersion;
+use crate::model::JobCrate;
+use crate::services::database::Database;
+use crate::services::emails::EmailSender;
+use crate::services::index::Index;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+
+/// Creates a worker for the periodic scan of the crates against the advisories
+pub fn create_advisories_worker(
+    configuration: Arc<Configuration>,
+    deps_data: Arc<Mutex<DepsCheckerData>>,
+    rustsec_data: Arc<Mutex<RustSecData>>,
+    index: Arc<Mutex<Index>>,
+    pool: Pool<Sqlite>,
+) {
+    if configuration.deps_advisories_scan_interval == 0 {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(configuration.deps_advisories_scan_interval * 60));
+        loop {
+            let _instant = interval.tick().await;
+            let checker = DepsChecker {
+                data: &deps_data,
+                configuration: &configuration,
+                index: &index,
+                rustsec: RustSecChecker {
+                    data: &rustsec_data,
+                    configuration: &configuration,
+                },
+            };
+            if let Err(e) = advisories_worker_job(&checker, &pool).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// A job for the worker
+async fn advisories_worker_job(checker: &DepsChecker<'_>, pool: &Pool<Sqlite>) -> Result<(), ApiError> {
+    checker.rustsec.refresh().await?;
+    let heads = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            let mut heads = Vec::new();
+            for head in database.get_crates_heads().await? {
+                let targets = database.get_crate_effective_deps_targets(&head.name).await?;
+                heads.push(JobCrate {
+                    name: head.name,
+                    version: head.version,
+                    targets,
+                });
+            }
+            Ok::<_, ApiError>(heads)
+        })
+        .await?
+    };
+    let mut newly_affected = 0;
+    for head in &heads {
+        // a failure for a crate does not prevent the scan of the others
+        match advisories_scan_crate(checker, pool, head).await {
+            Ok(0) => {}
+            Ok(count) => {
+                info!("advisories: {count} new advisories against {} {}", head.name, head.version);
+                newly_affected += 1;
+            }
+            Err(e) => error!("advisories: failed to scan {} {}: {e}", head.name, head.version),
+        }
+    }
+    info!("advisories: scanned {} crates, {newly_affected} newly affected", heads.len());
+    Ok(())
+}
+
+/// Scans the last version of a crate against the advisories
+/// The dependencies are only resolved again when the version or the targets changed since the last scan
+/// Returns the number of advisories that were not found by the previous scan
+async fn advisories_scan_crate(checker: &DepsChecker<'_>, pool: &Pool<Sqlite>, head: &JobCrate) -> Result<usize, ApiError> {
+    let previous = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database.get_crate_advisory_scan(&head.name).await
+        })
+        .await?
+    };
+    let scan = match previous {
+        Some(scan) if scan.version == head.version && scan.targets == head.targets => scan,
+        _ => {
+            let graph = checker.get_crate_graph(&head.name, &head.version, &head.targets).await?;
+            CrateAdvisoryScan {
+                version: head.version.clone(),
+                targets: head.targets.clone(),
+                dependencies: graph.get_resolved(),
+            }
+        }
+    };
+    let mut advisories = Vec::new();
+    for dependency in &scan.dependencies {
+        let version = dependency.version.parse::<SemverVersion>()?;
+        for content in checker.rustsec.check_crate(&dependency.name, &version.0).await? {
+            advisories.push(DepAdvisory {
+                package: dependency.name.clone(),
+                version: version.clone(),
+                content,
+            });
+        }
+    }
+    let count = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database.set_crate_advisory_scan(&head.name, &scan, &advisories).await
+        })
+        .await?
+    };
+    if checker.configuration.deps_notify_cves {
+        advisories_notify_owners(checker.configuration, pool, head).await?;
+    }
+    Ok(count)
+}
+
+/// Notifies the owners of a crate of the advisories against its last version they were not notified of yet
+/// A notification is sent only once for each advisory against a crate
+async fn advisories_notify_owners(configuration: &Configuration, pool: &Pool<Sqlite>, head: &JobCrate) -> Result<(), ApiError> {
+    let (advisories, owners) = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            let advisories = database.get_crate_advisories_not_notified(&head.name).await?;
+            let owners = database.get_crate_owners_advisory_notified(&head.name).await?;
+            Ok::<_, ApiError>((advisories, owners))
+        })
+        .await?
+    };
+    if advisories.is_empty() {
+        return Ok(());
+    }
+    if !owners.is_empty() {
+        EmailSender::new(configuration)
+            .send_advisory_notification(&owners, &head.name, &head.version, &advisories)
+            .await?;
+    }
+    let mut ids = advisories.into_iter().map(|advisory| advisory.advisory).collect::<Vec<_>>();
+    ids.dedup();
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.set_crate_advisories_notified(&head.name, &ids).await
+    })
+    .await
+}
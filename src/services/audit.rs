@@ -0,0 +1,60 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to enforce the retention of the audit log
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+use log::{error, info};
+use sqlx::{Pool, Sqlite};
+
+use crate::model::config::Configuration;
+use crate::services::database::Database;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+
+/// Creates a worker for the periodic purge of expired entries in the audit log
+pub fn create_audit_worker(configuration: Arc<Configuration>, pool: Pool<Sqlite>) {
+    if configuration.audit.retention_days == 0 {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        // every hour
+        let mut interval = tokio::time::interval(Duration::from_hours(1));
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(e) = audit_worker_job(&configuration, &pool).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// A job for the worker
+async fn audit_worker_job(configuration: &Configuration, pool: &Pool<Sqlite>) -> Result<(), ApiError> {
+    let days = i64::try_from(configuration.audit.retention_days).unwrap_or(i64::MAX);
+    let Some(before) = chrono::Duration::try_days(days).and_then(|delta| Local::now().naive_local().checked_sub_signed(delta))
+    else {
+        // nothing is that old
+        return Ok(());
+    };
+    // when archival is enabled, only the entries that have been exported can be deleted
+    let exported_only = configuration.audit.archive;
+    let mut connection = pool.acquire().await?;
+    let count = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.purge_audit_entries(before, exported_only).await
+    })
+    .await?;
+    if count > 0 {
+        info!("audit: purged {count} expired entries");
+    }
+    Ok(())
+}
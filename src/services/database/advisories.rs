@@ -0,0 +1,188 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service for persisting information in the database
+//! API related to the scans of crates against the advisories
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use chrono::Local;
+use futures::StreamExt;
+use semver::Version;
+
+use super::Database;
+use crate::model::deps::{AffectedCrateAdvisory, CrateAdvisoryScan, DepAdvisory};
+use crate::model::CrateAndVersion;
+use crate::utils::apierror::ApiError;
+
+impl Database<'_> {
+    /// Gets the last version of all the crates
+    pub async fn get_crates_heads(&self) -> Result<Vec<CrateAndVersion>, ApiError> {
+        let mut cache = HashMap::<String, (Version, String)>::new();
+        let transaction = &mut *self.transaction.borrow().await;
+        let mut stream = sqlx::query!("SELECT package, version FROM PackageVersion").fetch(transaction);
+        while let Some(row) = stream.next().await {
+            let row = row?;
+            let semver = row.version.parse::<Version>()?;
+            match cache.entry(row.package) {
+                Entry::Vacant(entry) => {
+                    entry.insert((semver, row.version));
+                }
+                Entry::Occupied(mut entry) => {
+                    if semver > entry.get().0 {
+                        entry.insert((semver, row.version));
+                    }
+                }
+            }
+        }
+        Ok(cache
+            .into_iter()
+            .map(|(name, (_, version))| CrateAndVersion { name, version })
+            .collect())
+    }
+
+    /// Gets the state of the last scan of a crate against the advisories
+    pub async fn get_crate_advisory_scan(&self, package: &str) -> Result<Option<CrateAdvisoryScan>, ApiError> {
+        let row = sqlx::query!(
+            "SELECT version, targets, dependencies FROM PackageAdvisoryScan WHERE package = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(CrateAdvisoryScan {
+            version: row.version,
+            targets: row.targets.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect(),
+            dependencies: serde_json::from_str(&row.dependencies)?,
+        }))
+    }
+
+    /// Records the scan of a crate against the advisories
+    /// Returns the number of advisories that were not found by the previous scan
+    pub async fn set_crate_advisory_scan(
+        &self,
+        package: &str,
+        scan: &CrateAdvisoryScan,
+        advisories: &[DepAdvisory],
+    ) -> Result<usize, ApiError> {
+        let now = Local::now().naive_local();
+        let targets = scan.targets.join(",");
+        let dependencies = serde_json::to_string(&scan.dependencies)?;
+        sqlx::query!(
+            "INSERT INTO PackageAdvisoryScan (package, version, targets, dependencies, lastScan) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(package) DO UPDATE SET version = $2, targets = $3, dependencies = $4, lastScan = $5",
+            package,
+            scan.version,
+            targets,
+            dependencies,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        let previous = sqlx::query!(
+            "SELECT id, version, advisory, dependency, dependencyVersion AS dependency_version FROM PackageAdvisory WHERE package = $1",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let is_same = |advisory: &DepAdvisory, version: &str, id: &str, dependency: &str, dependency_version: &str| {
+            version == scan.version
+                && id == advisory.content.id
+                && dependency == advisory.package
+                && dependency_version == advisory.version.to_string()
+        };
+        for row in &previous {
+            if !advisories.iter().any(|advisory| {
+                is_same(
+                    advisory,
+                    &row.version,
+                    &row.advisory,
+                    &row.dependency,
+                    &row.dependency_version,
+                )
+            }) {
+                // no longer affected
+                sqlx::query!("DELETE FROM PackageAdvisory WHERE id = $1", row.id)
+                    .execute(&mut *self.transaction.borrow().await)
+                    .await?;
+            }
+        }
+        let mut count = 0;
+        for advisory in advisories {
+            if previous.iter().any(|row| {
+                is_same(
+                    advisory,
+                    &row.version,
+                    &row.advisory,
+                    &row.dependency,
+                    &row.dependency_version,
+                )
+            }) {
+                continue;
+            }
+            let dependency_version = advisory.version.to_string();
+            sqlx::query!(
+                "INSERT INTO PackageAdvisory (package, version, advisory, summary, dependency, dependencyVersion, detectedOn) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                package,
+                scan.version,
+                advisory.content.id,
+                advisory.content.summary,
+                advisory.package,
+                dependency_version,
+                now
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Gets the advisories found by the periodic scan against the last version of the crates, the most recent first
+    pub async fn get_affected_crates_advisories(&self) -> Result<Vec<AffectedCrateAdvisory>, ApiError> {
+        let rows = sqlx::query_as!(
+            AffectedCrateAdvisory,
+            "SELECT package, version, advisory, summary, dependency, dependencyVersion AS dependency_version, detectedOn AS detected_on
+            FROM PackageAdvisory
+            ORDER BY detectedOn DESC, package"
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Gets the advisories against the last version of a crate for which its owners were not notified yet
+    pub async fn get_crate_advisories_not_notified(&self, package: &str) -> Result<Vec<AffectedCrateAdvisory>, ApiError> {
+        let rows = sqlx::query_as!(
+            AffectedCrateAdvisory,
+            "SELECT package, version, advisory, summary, dependency, dependencyVersion AS dependency_version, detectedOn AS detected_on
+            FROM PackageAdvisory
+            WHERE package = $1 AND advisory NOT IN (SELECT advisory FROM PackageAdvisoryNotification WHERE package = $1)
+            ORDER BY advisory",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Records that the owners of a crate were notified of advisories
+    pub async fn set_crate_advisories_notified(&self, package: &str, advisories: &[String]) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        for advisory in advisories {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO PackageAdvisoryNotification (package, advisory, sentOn) VALUES ($1, $2, $3)",
+                package,
+                advisory,
+                now
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service for persisting information in the database
+//! API related to the audit log
+
+use chrono::{Local, NaiveDateTime};
+
+use super::Database;
+use crate::model::audit::AuditLogEntry;
+use crate::model::auth::AuthenticatedUser;
+use crate::utils::apierror::{error_forbidden, specialize, ApiError};
+
+impl Database<'_> {
+    /// Records an entry in the audit log
+    pub async fn add_audit_entry(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        action: &str,
+        package: Option<&str>,
+        version: Option<&str>,
+        details: &str,
+    ) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            "INSERT INTO AuditLog (timestamp, principal, action, package, version, details, exported) VALUES ($1, $2, $3, $4, $5, $6, FALSE)",
+            now,
+            authenticated_user.principal,
+            action,
+            package,
+            version,
+            details
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets the identifier of the last entry in the audit log, as the upper bound for an export
+    pub async fn get_audit_export_bound(&self, authenticated_user: &AuthenticatedUser) -> Result<i64, ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        self.check_is_admin(authenticated_user.uid).await?;
+        let row = sqlx::query!("SELECT MAX(id) AS id FROM AuditLog")
+            .fetch_one(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(row.id.unwrap_or_default())
+    }
+
+    /// Gets a page of entries in the audit log, in the (after, upto] range of identifiers
+    pub async fn get_audit_entries(&self, after: i64, upto: i64, count: i64) -> Result<Vec<AuditLogEntry>, ApiError> {
+        let rows = sqlx::query_as!(
+            AuditLogEntry,
+            "SELECT id, timestamp, principal, action, package, version, details
+            FROM AuditLog WHERE id > $1 AND id <= $2 ORDER BY id LIMIT $3",
+            after,
+            upto,
+            count
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Marks all the entries in the audit log up to an identifier as exported
+    pub async fn set_audit_entries_exported(&self, upto: i64) -> Result<(), ApiError> {
+        sqlx::query!("UPDATE AuditLog SET exported = TRUE WHERE id <= $1", upto)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the entries in the audit log older than a timestamp
+    /// When `exported_only` is set, the entries that were not exported are kept
+    pub async fn purge_audit_entries(&self, before: NaiveDateTime, exported_only: bool) -> Result<u64, ApiError> {
+        let result = sqlx::query!(
+            "DELETE FROM AuditLog WHERE timestamp < $1 AND (exported = TRUE OR $2 = FALSE)",
+            before,
+            exported_only
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -4,11 +4,18 @@
 
 //! Service for persisting information in the database
 
+pub mod advisories;
+pub mod audit;
 pub mod packages;
+pub mod proxy;
 pub mod stats;
 pub mod users;
 
-use crate::utils::apierror::{error_forbidden, error_unauthorized, ApiError};
+#[cfg(test)]
+mod tests;
+
+use crate::model::auth::AuthenticatedUser;
+use crate::utils::apierror::{error_forbidden, error_unauthorized, specialize, ApiError};
 use crate::utils::db::AppTransaction;
 
 /// Represents the application
@@ -24,6 +31,7 @@ impl<'c> Database<'c> {
     }
 
     /// Checks the security for an operation and returns the identifier of the target user (login)
+    /// Deactivated users are rejected
     pub async fn check_is_user(&self, principal: &str) -> Result<i64, ApiError> {
         let maybe_row = sqlx::query!("SELECT id FROM RegistryUser WHERE isActive = TRUE AND email = $1", principal)
             .fetch_optional(&mut *self.transaction.borrow().await)
@@ -42,6 +50,17 @@ impl<'c> Database<'c> {
         Ok(roles.split(',').any(|role| role.trim() == "admin"))
     }
 
+    /// Checks that an authentication allows administration and that the user is an admin
+    pub async fn check_can_admin(&self, authenticated_user: &AuthenticatedUser) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        self.check_is_admin(authenticated_user.uid).await
+    }
+
     /// Checks that a user is an admin
     async fn check_is_admin(&self, uid: i64) -> Result<(), ApiError> {
         let is_admin = self.get_is_admin(uid).await?;
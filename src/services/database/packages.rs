@@ -6,7 +6,7 @@
 //! API related to the management of packages (crates)
 
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use byteorder::ByteOrder;
 use chrono::{Datelike, Duration, Local, NaiveDateTime};
@@ -14,50 +14,353 @@ use futures::StreamExt;
 use semver::Version;
 
 use super::Database;
-use crate::model::auth::AuthenticatedUser;
+use crate::model::auth::{AuthenticatedUser, SYSTEM_PRINCIPAL};
 use crate::model::cargo::{
-    CrateUploadData, CrateUploadResult, IndexCrateMetadata, OwnersQueryResult, RegistryUser, SearchResultCrate, SearchResults,
-    SearchResultsMeta, YesNoMsgResult, YesNoResult,
+    CrateUploadData, CrateUploadResult, IndexCrateMetadata, OwnersQueryResult, RegistryUser, SearchCursor, SearchResultCrate,
+    SearchResults, SearchResultsMeta, UserCratesReassignment, YesNoMsgResult, YesNoResult,
 };
-use crate::model::packages::CrateInfoVersion;
-use crate::model::stats::{DownloadStats, SERIES_LENGTH};
+use crate::model::packages::{
+    CrateDocsBuildSettings, CrateDocsStatus, CrateInfoVersion, CrateListingItem, CrateOutdatedStatus, CratePermissions,
+    CratePublication, CratePublications, CrateRepositoryInfo, CrateVersionBuild, CrateVersionDocs, CrateVersionSummary,
+    CratesListing, DocsGenerationStatus, StaleCrateOwner,
+};
+use crate::model::stats::{get_downloads_popularity, CrateDownloadCounts, DownloadStats, VersionDownloadCounts, SERIES_LENGTH};
 use crate::model::{CrateAndVersion, JobCrate};
-use crate::utils::apierror::{error_forbidden, error_invalid_request, error_not_found, specialize, ApiError};
+use crate::utils::apierror::{error_forbidden, error_gone, error_invalid_request, error_not_found, specialize, ApiError};
+use crate::utils::push_if_not_present;
+
+/// The weight of the popularity of a crate in its score for a search, relative to the text relevance
+const SEARCH_DOWNLOADS_WEIGHT: f64 = 0.1;
+
+/// Builds the query for the full-text index from the query of a user, if it has any term
+/// Only the alphanumeric parts of the input are kept so that the special characters of FTS5 cannot cause errors,
+/// all the terms must match, the last one as a prefix.
+fn get_search_text_query(query: &str) -> Option<String> {
+    let terms = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .collect::<Vec<_>>();
+    let (last, others) = terms.split_last()?;
+    let mut result = String::new();
+    for term in others {
+        result.push('"');
+        result.push_str(term);
+        result.push_str("\" ");
+    }
+    result.push('"');
+    result.push_str(last);
+    result.push_str("\"*");
+    Some(result)
+}
+
+/// A crate matching a search, accumulated from the rows for its versions
+#[derive(Debug, Default)]
+struct SearchCandidate {
+    /// The relevance of the crate for the text of the search, higher is more relevant
+    relevance: f64,
+    /// The popularity of the crate, from the downloads of its versions
+    popularity: f64,
+    /// Whether the crate has a version that is not yanked
+    available: bool,
+}
+
+impl SearchCandidate {
+    /// Adds the data for a version of the crate
+    fn add_version(&mut self, download_count: i64, downloads: Option<&[u8]>, yanked: bool, half_life: u64) {
+        self.popularity += get_downloads_popularity(download_count, downloads, half_life);
+        self.available |= !yanked;
+    }
+
+    /// Ranks the available candidates by descending score, ties being ordered by name
+    /// The score is the popularity, blended with the text relevance when requested
+    fn rank(candidates: HashMap<String, SearchCandidate>, with_relevance: bool) -> Vec<String> {
+        let mut scored = candidates
+            .into_iter()
+            .filter(|(_, candidate)| candidate.available)
+            .map(|(name, candidate)| {
+                let score = if with_relevance {
+                    candidate.relevance * (1.0 + candidate.popularity.ln_1p() * SEARCH_DOWNLOADS_WEIGHT)
+                } else {
+                    candidate.popularity
+                };
+                (name, score)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(name_1, score_1), (name_2, score_2)| score_2.total_cmp(score_1).then_with(|| name_1.cmp(name_2)));
+        scored.into_iter().map(|(name, _)| name).collect()
+    }
+}
 
 impl<'c> Database<'c> {
     /// Search for crates
-    pub async fn search_crates(&self, query: &str, per_page: Option<usize>) -> Result<SearchResults, ApiError> {
+    pub async fn search_crates(
+        &self,
+        query: &str,
+        page: Option<usize>,
+        cursor: Option<&SearchCursor>,
+        per_page: Option<usize>,
+        search_readme: bool,
+        popularity_half_life: u64,
+    ) -> Result<SearchResults, ApiError> {
         let per_page = match per_page {
             None => 10,
             Some(value) if value > 100 => 100,
-            Some(value) => value,
+            Some(value) => value.max(1),
         };
-        let pattern = format!("%{query}%");
-        let rows = sqlx::query!("SELECT name From Package WHERE name LIKE $1", pattern)
-            .fetch_all(&mut *self.transaction.borrow().await)
-            .await?;
+        let names = self.search_crates_names(query, search_readme, popularity_half_life).await?;
+        let total = names.len();
+        // the cursor takes precedence over the page
+        let skip = match cursor {
+            Some(cursor) => cursor.resolve(&names),
+            None => (page.unwrap_or(1).max(1) - 1).saturating_mul(per_page),
+        };
+        let page = skip / per_page + 1;
+        let next = names
+            .get(skip.saturating_add(per_page).saturating_sub(1))
+            .filter(|_| skip.saturating_add(per_page) < total)
+            .map(|last| {
+                SearchCursor {
+                    offset: skip + per_page,
+                    last: last.clone(),
+                }
+                .encode()
+            });
         let mut crates = Vec::new();
-        for row_name in rows {
-            let row = sqlx::query!("SELECT version, description FROM PackageVersion WHERE package = $1 AND yanked = FALSE ORDER BY id DESC LIMIT 1", row_name.name).fetch_optional(&mut *self.transaction.borrow().await).await?;
+        for name in names.into_iter().skip(skip).take(per_page) {
+            let row = sqlx::query!("SELECT version, description FROM PackageVersion WHERE package = $1 AND yanked = FALSE ORDER BY id DESC LIMIT 1", name).fetch_optional(&mut *self.transaction.borrow().await).await?;
             if let Some(row) = row {
                 crates.push(SearchResultCrate {
-                    name: row_name.name,
+                    name,
                     max_version: row.version,
                     description: row.description,
                 });
             }
         }
-        let total = crates.len();
+        let next_page = next.as_ref().map(|_| page + 1);
         Ok(SearchResults {
-            crates: if per_page > crates.len() {
-                crates.into_iter().take(per_page).collect()
-            } else {
-                crates
+            crates,
+            meta: SearchResultsMeta {
+                total,
+                page,
+                per_page,
+                next_page,
+                next,
             },
-            meta: SearchResultsMeta { total },
         })
     }
 
+    /// Collects the names of the crates matching a search, by order of relevance
+    /// All the collected crates have a version that is not yanked.
+    /// The crate with the exact name comes first, then the matches in the full-text index of the names, descriptions and keywords,
+    /// then the other crates with the query in their name by order of popularity and finally the matches in the READMEs, when requested.
+    async fn search_crates_names(&self, query: &str, search_readme: bool, half_life: u64) -> Result<Vec<String>, ApiError> {
+        let lowercase = query.trim().to_ascii_lowercase();
+        let mut names = sqlx::query!(
+            "SELECT name From Package
+            WHERE lowercase = $1 AND EXISTS (SELECT id FROM PackageVersion WHERE package = Package.name AND yanked = FALSE)",
+            lowercase
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?
+        .into_iter()
+        .map(|row| row.name)
+        .collect::<Vec<_>>();
+        if let Some(text_query) = get_search_text_query(query) {
+            for name in self.search_crates_text(&text_query, half_life).await? {
+                push_if_not_present(&mut names, name);
+            }
+        }
+        let pattern = format!("%{query}%");
+        let rows = sqlx::query!(
+            "SELECT Package.name, downloadCount AS download_count, downloads, yanked
+            FROM Package INNER JOIN PackageVersion ON PackageVersion.package = Package.name
+            WHERE Package.name LIKE $1",
+            pattern
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let mut candidates = HashMap::<String, SearchCandidate>::new();
+        for row in rows {
+            candidates.entry(row.name).or_default().add_version(
+                row.download_count,
+                row.downloads.as_deref(),
+                row.yanked,
+                half_life,
+            );
+        }
+        for name in SearchCandidate::rank(candidates, false) {
+            push_if_not_present(&mut names, name);
+        }
+        if search_readme {
+            // then matches in the README, ordered by relevance
+            let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+            let rows = sqlx::query!(
+                "SELECT package AS \"package!: String\" FROM PackageReadmeSearch
+                WHERE PackageReadmeSearch MATCH $1
+                AND EXISTS (SELECT id FROM PackageVersion WHERE PackageVersion.package = PackageReadmeSearch.package AND yanked = FALSE)
+                ORDER BY rank",
+                phrase
+            )
+            .fetch_all(&mut *self.transaction.borrow().await)
+            .await?;
+            for row in rows {
+                push_if_not_present(&mut names, row.package);
+            }
+        }
+        Ok(names)
+    }
+
+    /// Searches the full-text index of the names, descriptions and keywords of crates
+    /// The results are ordered by a blend of the text relevance and the popularity
+    async fn search_crates_text(&self, text_query: &str, half_life: u64) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT PackageSearch.package AS \"package!: String\",
+                bm25(PackageSearch, 0.0, 10.0, 2.0, 5.0) AS \"rank!: f64\",
+                downloadCount AS download_count, downloads, yanked
+            FROM PackageSearch INNER JOIN PackageVersion ON PackageVersion.package = PackageSearch.package
+            WHERE PackageSearch MATCH $1",
+            text_query
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let mut candidates = HashMap::<String, SearchCandidate>::new();
+        for row in rows {
+            let candidate = candidates.entry(row.package).or_default();
+            // bm25 is negative, lower is more relevant
+            candidate.relevance = -row.rank;
+            candidate.add_version(row.download_count, row.downloads.as_deref(), row.yanked, half_life);
+        }
+        Ok(SearchCandidate::rank(candidates, true))
+    }
+
+    /// Sets the name, description and keywords of a crate in the full-text index for the search
+    /// Only the metadata of the last published version are indexed
+    pub async fn set_crate_search(&self, package: &str, description: &str, keywords: &[String]) -> Result<(), ApiError> {
+        sqlx::query!("DELETE FROM PackageSearch WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        let keywords = keywords.join(" ");
+        sqlx::query!(
+            "INSERT INTO PackageSearch (package, name, description, keywords) VALUES ($1, $1, $2, $3)",
+            package,
+            description,
+            keywords
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Sets the README of a crate in the full-text index for the search
+    /// Only the README of the last published version is indexed
+    pub async fn set_crate_readme_search(&self, package: &str, readme: &str) -> Result<(), ApiError> {
+        sqlx::query!("DELETE FROM PackageReadmeSearch WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO PackageReadmeSearch (package, content) VALUES ($1, $2)",
+            package,
+            readme
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets a page in the listing of all crates with their latest version, ordered by name
+    pub async fn get_crates_listing(&self, page: i64, per_page: i64) -> Result<CratesListing, ApiError> {
+        let total = sqlx::query!(
+            "SELECT COUNT(name) AS total FROM Package
+            WHERE EXISTS (SELECT id FROM PackageVersion WHERE package = Package.name AND yanked = FALSE)"
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?
+        .total;
+        let offset = (page - 1).saturating_mul(per_page);
+        let crates = sqlx::query_as!(
+            CrateListingItem,
+            "SELECT Package.name, version, description, upload
+            FROM Package INNER JOIN PackageVersion ON PackageVersion.id = (
+                SELECT id FROM PackageVersion WHERE package = Package.name AND yanked = FALSE ORDER BY id DESC LIMIT 1
+            )
+            ORDER BY Package.name
+            LIMIT $1 OFFSET $2",
+            per_page,
+            offset
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(CratesListing {
+            crates,
+            total,
+            page,
+            per_page,
+        })
+    }
+
+    /// Gets a page of the crate versions published within a range of date times, `from` inclusive, `to` exclusive
+    pub async fn list_crates_published_between(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        page: i64,
+        per_page: i64,
+    ) -> Result<CratePublications, ApiError> {
+        let total = sqlx::query!(
+            "SELECT COUNT(id) AS total FROM PackageVersion WHERE upload >= $1 AND upload < $2",
+            from,
+            to
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?
+        .total;
+        let offset = (page - 1).saturating_mul(per_page);
+        let publications = sqlx::query_as!(
+            CratePublication,
+            "SELECT package, version, upload, RegistryUser.login AS publisher_login, RegistryUser.name AS publisher_name
+            FROM PackageVersion LEFT JOIN RegistryUser ON PackageVersion.uploadedBy = RegistryUser.id
+            WHERE upload >= $1 AND upload < $2
+            ORDER BY upload, PackageVersion.id
+            LIMIT $3 OFFSET $4",
+            from,
+            to,
+            per_page,
+            offset
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(CratePublications {
+            publications,
+            total,
+            page,
+            per_page,
+        })
+    }
+
+    /// Gets the crate versions published within a range of date times for the crates owned by a user, `from` inclusive, `to` exclusive
+    pub async fn get_crates_published_for_owner(
+        &self,
+        uid: i64,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Vec<CratePublication>, ApiError> {
+        let publications = sqlx::query_as!(
+            CratePublication,
+            "SELECT PackageVersion.package, version, upload, RegistryUser.login AS publisher_login, RegistryUser.name AS publisher_name
+            FROM PackageVersion
+            LEFT JOIN RegistryUser ON PackageVersion.uploadedBy = RegistryUser.id
+            INNER JOIN PackageOwner ON PackageOwner.package = PackageVersion.package
+            WHERE PackageOwner.owner = $1 AND upload >= $2 AND upload < $3
+            ORDER BY upload, PackageVersion.id",
+            uid,
+            from,
+            to
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(publications)
+    }
+
     /// Gets the last version number for a package
     pub async fn get_crate_last_version(&self, package: &str) -> Result<String, ApiError> {
         let row = sqlx::query!(
@@ -80,7 +383,8 @@ impl<'c> Database<'c> {
             "SELECT version, upload, uploadedBy AS uploaded_by,
                     hasDocs AS has_docs, docGenAttempted AS doc_gen_attempted,
                     downloadCount AS download_count,
-                    depsLastCheck AS deps_last_check, depsHasOutdated AS deps_has_outdated, depsHasCVEs AS deps_has_cves
+                    depsLastCheck AS deps_last_check, depsHasOutdated AS deps_has_outdated, depsHasCVEs AS deps_has_cves,
+                    yankExpiry AS yank_expiry, uploadUserAgent AS upload_user_agent
             FROM PackageVersion WHERE package = $1 ORDER BY id",
             package
         )
@@ -94,24 +398,54 @@ impl<'c> Database<'c> {
                     index: index_data,
                     upload: row.upload,
                     uploaded_by,
+                    upload_user_agent: row.upload_user_agent.clone(),
                     has_docs: row.has_docs,
                     doc_gen_attempted: row.doc_gen_attempted,
                     download_count: row.download_count,
                     deps_last_check: row.deps_last_check,
                     deps_has_outdated: row.deps_has_outdated,
                     deps_has_cves: row.deps_has_cves,
+                    yank_expiry: row.yank_expiry,
                 });
             }
         }
         Ok(result)
     }
 
+    /// Gets the versions of a crate, from the greatest to the lowest according to semver
+    pub async fn get_crate_versions_sorted(&self, package: &str) -> Result<Vec<CrateVersionSummary>, ApiError> {
+        let rows = sqlx::query!("SELECT version, yanked FROM PackageVersion WHERE package = $1", package)
+            .fetch_all(&mut *self.transaction.borrow().await)
+            .await?;
+        let mut versions = rows
+            .into_iter()
+            .map(|row| Ok((row.version.parse::<Version>()?, row.version, row.yanked)))
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        versions.sort_by(|(a, _, _), (b, _, _)| b.cmp(a));
+        let latest_stable = versions
+            .iter()
+            .position(|(semver, _, yanked)| !yanked && semver.pre.is_empty());
+        Ok(versions
+            .into_iter()
+            .enumerate()
+            .map(|(index, (semver, version, yanked))| CrateVersionSummary {
+                version,
+                yanked,
+                prerelease: !semver.pre.is_empty(),
+                latest_stable: latest_stable == Some(index),
+            })
+            .collect())
+    }
+
     /// Publish a crate
+    /// An existing version can be replaced within `mutable_window` minutes after its original publication, 0 makes versions immutable
     #[allow(clippy::similar_names)]
     pub async fn publish_crate_version(
         &self,
         authenticated_user: &AuthenticatedUser,
         package: &CrateUploadData,
+        mutable_window: i64,
+        user_agent: Option<&str>,
     ) -> Result<CrateUploadResult, ApiError> {
         if !authenticated_user.can_write {
             return Err(specialize(
@@ -119,6 +453,7 @@ impl<'c> Database<'c> {
                 String::from("writing is forbidden for this authentication"),
             ));
         }
+        authenticated_user.check_crate_in_scope(&package.metadata.name)?;
         let warnings = package.metadata.validate()?;
         let lowercase = package.metadata.name.to_ascii_lowercase();
         let row = sqlx::query!(
@@ -128,14 +463,18 @@ impl<'c> Database<'c> {
         )
         .fetch_optional(&mut *self.transaction.borrow().await)
         .await?;
-        if let Some(row) = row {
-            return Err(specialize(
-                error_invalid_request(),
-                format!(
-                    "Package {} already exists in version {}, uploaded on {}",
-                    &package.metadata.name, &package.metadata.vers, row.upload
-                ),
-            ));
+        let now = Local::now().naive_local();
+        let replaced = row.map(|row| row.upload);
+        if let Some(upload) = replaced {
+            if now - upload > Duration::minutes(mutable_window) {
+                return Err(specialize(
+                    error_invalid_request(),
+                    format!(
+                        "Package {} already exists in version {}, uploaded on {}",
+                        &package.metadata.name, &package.metadata.vers, upload
+                    ),
+                ));
+            }
         }
         // check whether the package already exists
         let row = sqlx::query!("SELECT name FROM Package WHERE lowercase = $1 LIMIT 1", lowercase)
@@ -143,7 +482,7 @@ impl<'c> Database<'c> {
             .await?;
         if let Some(row) = row {
             // check this is the same package
-            if row.name != lowercase {
+            if row.name != package.metadata.name {
                 return Err(specialize(
                     error_invalid_request(),
                     format!("A package named {} already exists", row.name),
@@ -162,7 +501,7 @@ impl<'c> Database<'c> {
         } else {
             // create the package
             sqlx::query!(
-                "INSERT INTO Package (name, lowercase, targets) VALUES ($1, $2, '')",
+                "INSERT INTO Package (name, lowercase, targets, docsEnabled) VALUES ($1, $2, '', TRUE)",
                 package.metadata.name,
                 lowercase
             )
@@ -170,44 +509,190 @@ impl<'c> Database<'c> {
             .await?;
             // add the principal as owner
             sqlx::query!(
-                "INSERT INTO PackageOwner (package, owner) VALUES ($1, $2)",
+                "INSERT INTO PackageOwner (package, owner, lastAttestation) VALUES ($1, $2, $3)",
                 package.metadata.name,
-                authenticated_user.uid
+                authenticated_user.uid,
+                now
             )
             .execute(&mut *self.transaction.borrow().await)
             .await?;
         }
-        let now = Local::now().naive_local();
-        // create the version
         let description = package.metadata.description.as_ref().map_or("", String::as_str);
+        self.set_crate_search(&package.metadata.name, description, &package.metadata.keywords)
+            .await?;
+        if replaced.is_some() {
+            self.replace_crate_version(authenticated_user, package, description, user_agent)
+                .await?;
+            return Ok(warnings);
+        }
+        // create the version
         sqlx::query!(
-            "INSERT INTO PackageVersion (package, version, description, upload, uploadedBy, yanked, hasDocs, docGenAttempted, downloadCount, downloads, depsLastCheck, depsHasOutdated, depsHasCVEs) VALUES ($1, $2, $3, $4, $5, false, false, false, 0, NULL, 0, false, false)",
+            "INSERT INTO PackageVersion (package, version, description, upload, uploadedBy, uploadUserAgent, yanked, hasDocs, docGenAttempted, downloadCount, downloads, depsLastCheck, depsHasOutdated, depsHasCVEs, docsStatus, docsStatusUpdate) VALUES ($1, $2, $3, $4, $5, $6, false, false, false, 0, NULL, 0, false, false, 'queued', $4)",
             package.metadata.name,
             package.metadata.vers,
             description,
             now,
-            authenticated_user.uid
+            authenticated_user.uid,
+            user_agent
         )
         .execute(&mut *self.transaction.borrow().await)
         .await?;
+        self.clear_crate_tombstones(&package.metadata.name, &package.metadata.vers)
+            .await?;
+        self.add_audit_entry(
+            authenticated_user,
+            "publish",
+            Some(&package.metadata.name),
+            Some(&package.metadata.vers),
+            user_agent.unwrap_or_default(),
+        )
+        .await?;
         Ok(warnings)
     }
 
+    /// Replaces a version that is published again within the allowed window
+    /// The time of the original publication is kept so that the window cannot be extended
+    async fn replace_crate_version(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &CrateUploadData,
+        description: &str,
+        user_agent: Option<&str>,
+    ) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            "UPDATE PackageVersion SET description = $3, uploadedBy = $4, uploadUserAgent = $6, yanked = FALSE, yankExpiry = NULL, hasDocs = FALSE, docGenAttempted = FALSE, docsStatus = 'queued', docsStatusUpdate = $5, depsLastCheck = 0, depsHasOutdated = FALSE, depsHasCVEs = FALSE WHERE package = $1 AND version = $2",
+            package.metadata.name,
+            package.metadata.vers,
+            description,
+            authenticated_user.uid,
+            now,
+            user_agent
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_audit_entry(
+            authenticated_user,
+            "republish",
+            Some(&package.metadata.name),
+            Some(&package.metadata.vers),
+            user_agent.unwrap_or_default(),
+        )
+        .await
+    }
+
     /// Checks that a package exists
     pub async fn check_crate_exists(&self, package: &str, version: &str) -> Result<(), ApiError> {
-        let _row = sqlx::query!(
+        let row = sqlx::query!(
             "SELECT id FROM PackageVersion WHERE package = $1 AND version = $2 LIMIT 1",
             package,
             version
         )
         .fetch_optional(&mut *self.transaction.borrow().await)
-        .await?
-        .ok_or_else(error_not_found)?;
+        .await?;
+        if row.is_none() {
+            self.check_crate_not_deleted(package, Some(version)).await?;
+            return Err(error_not_found());
+        }
+        Ok(())
+    }
+
+    /// Gets the name of a crate as it was published, looking it up without regard to the case
+    pub async fn get_crate_canonical_name(&self, package: &str) -> Result<Option<String>, ApiError> {
+        let lowercase = package.to_ascii_lowercase();
+        let row = sqlx::query!("SELECT name FROM Package WHERE lowercase = $1 LIMIT 1", lowercase)
+            .fetch_optional(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(row.map(|row| row.name))
+    }
+
+    /// Checks that a crate, or a version of it when specified, was not deliberately deleted
+    /// Yields a `410 Gone` error when it was
+    pub async fn check_crate_not_deleted(&self, package: &str, version: Option<&str>) -> Result<(), ApiError> {
+        let row = sqlx::query!(
+            "SELECT version, deletedOn AS deleted_on FROM PackageTombstone WHERE lower(package) = lower($1) AND (version IS NULL OR version = $2) ORDER BY id DESC LIMIT 1",
+            package,
+            version
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?;
+        match row {
+            None => Ok(()),
+            Some(row) => Err(specialize(
+                error_gone(),
+                match row.version {
+                    None => format!("crate {package} was deleted on {}", row.deleted_on),
+                    Some(version) => format!("version {version} of crate {package} was deleted on {}", row.deleted_on),
+                },
+            )),
+        }
+    }
+
+    /// Records that a crate, or a version of it when specified, was deliberately deleted
+    async fn add_crate_tombstone(&self, package: &str, version: Option<&str>) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            "INSERT INTO PackageTombstone (package, version, deletedOn) VALUES ($1, $2, $3)",
+            package,
+            version,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets the crates and versions that were deleted and that are not live again
+    /// A `None` version stands for all the versions of the crate
+    pub async fn get_deleted_crates(&self) -> Result<Vec<(String, Option<String>)>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT DISTINCT package, version FROM PackageTombstone
+            WHERE (version IS NULL AND NOT EXISTS (SELECT name FROM Package WHERE lower(name) = lower(PackageTombstone.package)))
+                OR (version IS NOT NULL AND NOT EXISTS (
+                    SELECT id FROM PackageVersion WHERE package = PackageTombstone.package AND version = PackageTombstone.version
+                ))
+            ORDER BY package, version"
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.package, row.version)).collect())
+    }
+
+    /// Gets whether a crate or a version that was deleted is still not live again
+    /// A `None` version stands for all the versions of the crate
+    pub async fn is_crate_still_deleted(&self, package: &str, version: Option<&str>) -> Result<bool, ApiError> {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM PackageTombstone
+            WHERE package = $1 AND version IS $2 AND (
+                (version IS NULL AND NOT EXISTS (SELECT name FROM Package WHERE lower(name) = lower(PackageTombstone.package)))
+                OR (version IS NOT NULL AND NOT EXISTS (
+                    SELECT id FROM PackageVersion WHERE package = PackageTombstone.package AND version = PackageTombstone.version
+                ))
+            )",
+            package,
+            version
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Removes the records of the deletion of a crate when a version of it is published again
+    async fn clear_crate_tombstones(&self, package: &str, version: &str) -> Result<(), ApiError> {
+        sqlx::query!(
+            "DELETE FROM PackageTombstone WHERE lower(package) = lower($1) AND (version IS NULL OR version = $2)",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
         Ok(())
     }
 
-    /// Checks the ownership of a package
-    async fn check_crate_ownership(&self, authenticated_user: &AuthenticatedUser, package: &str) -> Result<i64, ApiError> {
+    /// Checks the ownership of a package, administrators are deemed owners of all packages
+    /// A token limited to some crates is also checked to be allowed to act on the package, even for administrators
+    pub async fn check_crate_ownership(&self, authenticated_user: &AuthenticatedUser, package: &str) -> Result<i64, ApiError> {
+        authenticated_user.check_crate_in_scope(package)?;
         if self.check_is_admin(authenticated_user.uid).await.is_ok() {
             return Ok(authenticated_user.uid);
         }
@@ -227,12 +712,40 @@ impl<'c> Database<'c> {
         }
     }
 
+    /// Gets the effective permissions of an authenticated user on a package
+    /// They mirror the checks performed when acting on the package
+    pub async fn get_crate_permissions(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+    ) -> Result<CratePermissions, ApiError> {
+        let is_admin = self.get_is_admin(authenticated_user.uid).await?;
+        let is_owner = sqlx::query!(
+            "SELECT id from PackageOwner WHERE package = $1 AND owner = $2 LIMIT 1",
+            package,
+            authenticated_user.uid
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .is_some();
+        let is_manager = (is_owner || is_admin) && authenticated_user.is_crate_in_scope(package);
+        Ok(CratePermissions {
+            is_owner,
+            is_admin,
+            can_publish: is_manager && authenticated_user.can_write,
+            can_yank: is_manager && authenticated_user.can_write,
+            can_manage: is_manager && authenticated_user.can_admin,
+        })
+    }
+
     /// Yank a crate version
+    /// When an expiry is given, the version is automatically unyanked at that time, unless an admin confirms the yank
     pub async fn yank_crate_version(
         &self,
         authenticated_user: &AuthenticatedUser,
         package: &str,
         version: &str,
+        expiry: Option<NaiveDateTime>,
     ) -> Result<YesNoResult, ApiError> {
         if !authenticated_user.can_write {
             return Err(specialize(
@@ -261,18 +774,95 @@ impl<'c> Database<'c> {
                     ))
                 } else {
                     sqlx::query!(
-                        "UPDATE PackageVersion SET yanked = TRUE WHERE package = $1 AND version = $2",
+                        "UPDATE PackageVersion SET yanked = TRUE, yankExpiry = $3 WHERE package = $1 AND version = $2",
                         package,
-                        version
+                        version,
+                        expiry
                     )
                     .execute(&mut *self.transaction.borrow().await)
                     .await?;
+                    let details = expiry.map(|expiry| format!("expires at {expiry}")).unwrap_or_default();
+                    self.add_audit_entry(authenticated_user, "yank", Some(package), Some(version), &details)
+                        .await?;
                     Ok(YesNoResult::new())
                 }
             }
         }
     }
 
+    /// Confirms the yank of a crate version so that it is no longer automatically unyanked
+    pub async fn confirm_crate_version_yank(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        version: &str,
+    ) -> Result<YesNoResult, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        let row = sqlx::query!(
+            "SELECT yanked, yankExpiry AS yank_expiry FROM PackageVersion WHERE package = $1 AND version = $2 LIMIT 1",
+            package,
+            version
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(|| {
+            specialize(
+                error_invalid_request(),
+                format!("Version {version} of crate {package} does not exist"),
+            )
+        })?;
+        if !row.yanked || row.yank_expiry.is_none() {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("Version {version} of crate {package} has no pending automatic unyank"),
+            ));
+        }
+        sqlx::query!(
+            "UPDATE PackageVersion SET yankExpiry = NULL WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_audit_entry(authenticated_user, "yank-confirm", Some(package), Some(version), "")
+            .await?;
+        Ok(YesNoResult::new())
+    }
+
+    /// Unyanks the crate versions for which the yank expired
+    pub async fn unyank_expired_crate_versions(&self, now: NaiveDateTime) -> Result<Vec<CrateAndVersion>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT package, version FROM PackageVersion WHERE yanked = TRUE AND yankExpiry IS NOT NULL AND yankExpiry <= $1",
+            now
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        sqlx::query!(
+            "UPDATE PackageVersion SET yanked = FALSE, yankExpiry = NULL WHERE yanked = TRUE AND yankExpiry IS NOT NULL AND yankExpiry <= $1",
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        let system = AuthenticatedUser {
+            uid: -1,
+            principal: String::from(SYSTEM_PRINCIPAL),
+            can_write: true,
+            can_admin: false,
+            token_id: None,
+            crates: Vec::new(),
+        };
+        let mut unyanked = Vec::with_capacity(rows.len());
+        for row in rows {
+            self.add_audit_entry(&system, "unyank-expired", Some(&row.package), Some(&row.version), "")
+                .await?;
+            unyanked.push(CrateAndVersion {
+                name: row.package,
+                version: row.version,
+            });
+        }
+        Ok(unyanked)
+    }
+
     /// Unyank a crate version
     pub async fn unyank_crate_version(
         &self,
@@ -302,12 +892,14 @@ impl<'c> Database<'c> {
             Some(row) => {
                 if row.yanked {
                     sqlx::query!(
-                        "UPDATE PackageVersion SET yanked = FALSE WHERE package = $1 AND version = $2",
+                        "UPDATE PackageVersion SET yanked = FALSE, yankExpiry = NULL WHERE package = $1 AND version = $2",
                         package,
                         version
                     )
                     .execute(&mut *self.transaction.borrow().await)
                     .await?;
+                    self.add_audit_entry(authenticated_user, "unyank", Some(package), Some(version), "")
+                        .await?;
                     Ok(YesNoResult::new())
                 } else {
                     Err(specialize(
@@ -319,14 +911,142 @@ impl<'c> Database<'c> {
         }
     }
 
-    /// Gets the packages that need documentation generation
-    pub async fn get_undocumented_crates(&self) -> Result<Vec<JobCrate>, ApiError> {
-        let rows = sqlx::query!(
-            "SELECT package, version, targets
-            FROM PackageVersion
-            INNER JOIN Package ON PackageVersion.package = Package.name
-            WHERE hasDocs = FALSE AND docGenAttempted = FALSE ORDER BY id"
-        )
+    /// Deletes a crate with all its versions
+    pub async fn delete_crate(&self, authenticated_user: &AuthenticatedUser, package: &str) -> Result<(), ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        let exists = sqlx::query!("SELECT name FROM Package WHERE name = $1 LIMIT 1", package)
+            .fetch_optional(&mut *self.transaction.borrow().await)
+            .await?
+            .is_some();
+        if !exists {
+            return Err(specialize(
+                error_not_found(),
+                format!("package {package} is not in this registry"),
+            ));
+        }
+        sqlx::query!("DELETE FROM PackageVersionBuild WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageVersionDocs WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageAdvisoryScan WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageAdvisory WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageAdvisoryNotification WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageVersion WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageOwner WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageReadmeSearch WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM PackageSearch WHERE package = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        sqlx::query!("DELETE FROM Package WHERE name = $1", package)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        self.add_crate_tombstone(package, None).await?;
+        self.add_audit_entry(authenticated_user, "crate-delete", Some(package), None, "")
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a version of a crate
+    /// When this is the only version of the crate, the crate itself is deleted and `true` is returned
+    pub async fn delete_crate_version(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        version: &str,
+    ) -> Result<bool, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        self.check_crate_exists(package, version).await?;
+        let count = sqlx::query!("SELECT COUNT(id) AS count FROM PackageVersion WHERE package = $1", package)
+            .fetch_one(&mut *self.transaction.borrow().await)
+            .await?
+            .count;
+        if count <= 1 {
+            self.delete_crate(authenticated_user, package).await?;
+            return Ok(true);
+        }
+        sqlx::query!(
+            "DELETE FROM PackageVersionBuild WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM PackageVersionDocs WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        // the last version may change, the crate is scanned again
+        sqlx::query!(
+            "DELETE FROM PackageAdvisoryScan WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM PackageAdvisory WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM PackageVersion WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_crate_tombstone(package, Some(version)).await?;
+        self.add_audit_entry(authenticated_user, "version-delete", Some(package), Some(version), "")
+            .await?;
+        Ok(false)
+    }
+
+    /// Gets all the versions of all crates, with whether they are yanked, ordered by crate and then by publication
+    pub async fn get_all_crate_versions(&self) -> Result<Vec<(CrateAndVersion, bool)>, ApiError> {
+        let rows = sqlx::query!("SELECT package, version, yanked FROM PackageVersion ORDER BY package, id")
+            .fetch_all(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    CrateAndVersion {
+                        name: row.package,
+                        version: row.version,
+                    },
+                    row.yanked,
+                )
+            })
+            .collect())
+    }
+
+    /// Gets the packages that need documentation generation
+    pub async fn get_undocumented_crates(&self) -> Result<Vec<JobCrate>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT package, version, targets
+            FROM PackageVersion
+            INNER JOIN Package ON PackageVersion.package = Package.name
+            WHERE hasDocs = FALSE AND docGenAttempted = FALSE AND docsEnabled = TRUE AND docsExternalUrl IS NULL ORDER BY id"
+        )
         .fetch_all(&mut *self.transaction.borrow().await)
         .await?;
         Ok(rows
@@ -360,6 +1080,198 @@ impl<'c> Database<'c> {
         )
         .execute(&mut *self.transaction.borrow().await)
         .await?;
+        let status = if has_docs {
+            DocsGenerationStatus::Success
+        } else {
+            DocsGenerationStatus::Failed
+        };
+        self.set_crate_docs_status(package, version, status).await
+    }
+
+    /// Sets the status of the generation of the documentation for a crate version
+    pub async fn set_crate_docs_status(
+        &self,
+        package: &str,
+        version: &str,
+        status: DocsGenerationStatus,
+    ) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        let status = status.as_str();
+        sqlx::query!(
+            "UPDATE PackageVersion SET docsStatus = $3, docsStatusUpdate = $4 WHERE package = $1 AND version = $2",
+            package,
+            version,
+            status,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Gets the status of the generation of the documentation for a crate version
+    pub async fn get_crate_docs_status(&self, package: &str, version: &str) -> Result<CrateDocsStatus, ApiError> {
+        let row = sqlx::query!(
+            "SELECT docsStatus AS docs_status, COALESCE(docsStatusUpdate, upload) AS \"last_update!: NaiveDateTime\"
+            FROM PackageVersion WHERE package = $1 AND version = $2 LIMIT 1",
+            package,
+            version
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(|| {
+            specialize(
+                error_not_found(),
+                format!("Version {version} of crate {package} does not exist"),
+            )
+        })?;
+        Ok(CrateDocsStatus {
+            status: row.docs_status.parse().unwrap_or(DocsGenerationStatus::Queued),
+            last_update: row.last_update,
+            docs: self.get_crate_version_docs(package, version).await?,
+        })
+    }
+
+    /// Gets whether the documentation is generated and served for a crate
+    pub async fn get_crate_docs_enabled(&self, package: &str) -> Result<bool, ApiError> {
+        let row = sqlx::query!(
+            "SELECT docsEnabled AS docs_enabled FROM Package WHERE name = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(row.docs_enabled)
+    }
+
+    /// Gets the URL of the documentation of a crate hosted elsewhere, if any
+    pub async fn get_crate_docs_external_url(&self, package: &str) -> Result<Option<String>, ApiError> {
+        let row = sqlx::query!(
+            "SELECT docsExternalUrl AS docs_external_url FROM Package WHERE name = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(row.docs_external_url)
+    }
+
+    /// Sets the URL of the documentation of a crate hosted elsewhere
+    /// `None` reverts to serving the generated documentation
+    pub async fn set_crate_docs_external_url(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        url: Option<&str>,
+    ) -> Result<(), ApiError> {
+        if !authenticated_user.can_write {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("writing is forbidden for this authentication"),
+            ));
+        }
+        // check access
+        self.check_crate_ownership(authenticated_user, package).await?;
+        sqlx::query!("UPDATE Package SET docsExternalUrl = $2 WHERE name = $1", package, url)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        self.add_audit_entry(
+            authenticated_user,
+            if url.is_some() {
+                "docs-external-set"
+            } else {
+                "docs-external-unset"
+            },
+            Some(package),
+            None,
+            url.unwrap_or_default(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Gets the metadata fetched from the repository of a crate, if any
+    pub async fn get_crate_repository_info(&self, package: &str) -> Result<Option<CrateRepositoryInfo>, ApiError> {
+        let row = sqlx::query!(
+            "SELECT repoStars AS repo_stars, repoLastActivity AS repo_last_activity, repoLastCheck AS repo_last_check FROM Package WHERE name = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(row.repo_last_check.map(|last_check| CrateRepositoryInfo {
+            stars: row.repo_stars,
+            last_activity: row.repo_last_activity,
+            last_check,
+        }))
+    }
+
+    /// Gets the crates for which the metadata from the repository were never fetched or before a date
+    pub async fn get_crates_for_repository_check(&self, before: NaiveDateTime, limit: i64) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT name FROM Package WHERE repoLastCheck IS NULL OR repoLastCheck < $1 ORDER BY repoLastCheck LIMIT $2",
+            before,
+            limit
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.name).collect())
+    }
+
+    /// Sets the metadata fetched from the repository of a crate
+    /// When no metadata could be fetched, the previous ones are kept
+    pub async fn set_crate_repository_info(
+        &self,
+        package: &str,
+        stars: Option<i64>,
+        last_activity: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+    ) -> Result<(), ApiError> {
+        sqlx::query!(
+            "UPDATE Package SET repoStars = COALESCE($2, repoStars), repoLastActivity = COALESCE($3, repoLastActivity), repoLastCheck = $4 WHERE name = $1",
+            package,
+            stars,
+            last_activity,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Sets whether the documentation is generated and served for a crate
+    /// When the documentation is purged, all versions of the crate are marked as not having documentation
+    pub async fn set_crate_docs_enabled(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        enabled: bool,
+        purge: bool,
+    ) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        // check access
+        self.check_crate_ownership(authenticated_user, package).await?;
+        sqlx::query!("UPDATE Package SET docsEnabled = $2 WHERE name = $1", package, enabled)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        if purge {
+            sqlx::query!("UPDATE PackageVersion SET hasDocs = FALSE WHERE package = $1", package)
+                .execute(&mut *self.transaction.borrow().await)
+                .await?;
+        }
+        self.add_audit_entry(
+            authenticated_user,
+            if enabled { "docs-enable" } else { "docs-disable" },
+            Some(package),
+            None,
+            if purge { "purge" } else { "" },
+        )
+        .await?;
         Ok(())
     }
 
@@ -377,6 +1289,12 @@ impl<'c> Database<'c> {
             ));
         }
         self.check_crate_ownership(authenticated_user, package).await?;
+        if !self.get_crate_docs_enabled(package).await? {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("Documentation is disabled for crate {package}"),
+            ));
+        }
         let row = sqlx::query!(
             "SELECT yanked FROM PackageVersion WHERE package = $1 AND version = $2 LIMIT 1",
             package,
@@ -397,8 +1315,8 @@ impl<'c> Database<'c> {
                 )
                 .execute(&mut *self.transaction.borrow().await)
                 .await?;
-
-                Ok(())
+                self.set_crate_docs_status(package, version, DocsGenerationStatus::Queued)
+                    .await
             }
         }
     }
@@ -495,6 +1413,70 @@ impl<'c> Database<'c> {
             .collect())
     }
 
+    /// Gets whether the latest version of each of the given crates is outdated
+    /// The latest version is determined the same way as for `get_crates_outdated_heads`, unknown crates are reported without version
+    pub async fn get_outdated_for_crates(&self, packages: &[String]) -> Result<Vec<CrateOutdatedStatus>, ApiError> {
+        let mut results = Vec::with_capacity(packages.len());
+        for package in packages {
+            let rows = sqlx::query!(
+                "SELECT version, depsHasOutdated AS has_outdated
+                FROM PackageVersion WHERE package = $1",
+                package
+            )
+            .fetch_all(&mut *self.transaction.borrow().await)
+            .await?;
+            let mut head: Option<(Version, String, bool)> = None;
+            for row in rows {
+                let semver = row.version.parse::<Version>()?;
+                if head.as_ref().is_none_or(|(last, _, _)| semver > *last) {
+                    head = Some((semver, row.version, row.has_outdated));
+                }
+            }
+            results.push(CrateOutdatedStatus {
+                name: package.clone(),
+                outdated: head.as_ref().is_some_and(|(_, _, has_outdated)| *has_outdated),
+                version: head.map(|(_, version, _)| version),
+            });
+        }
+        Ok(results)
+    }
+
+    /// Gets the last version of the crates for which the last dependency analysis found advisories
+    pub async fn get_crates_vulnerable_heads(&self) -> Result<Vec<CrateAndVersion>, ApiError> {
+        let mut cache = HashMap::<String, (Version, String, bool)>::new();
+        let transaction = &mut *self.transaction.borrow().await;
+        let mut stream = sqlx::query!(
+            "SELECT package, version, depsHasCVEs AS has_cves
+            FROM PackageVersion"
+        )
+        .fetch(transaction);
+        while let Some(row) = stream.next().await {
+            let row = row?;
+            let name = row.package;
+            let semver = row.version.parse::<Version>()?;
+            match cache.entry(name.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert((semver, row.version, row.has_cves));
+                }
+                Entry::Occupied(mut entry) => {
+                    if semver > entry.get().0 {
+                        entry.insert((semver, row.version, row.has_cves));
+                    }
+                }
+            }
+        }
+        Ok(cache
+            .into_iter()
+            .filter_map(|(name, (_, version, has_cves))| {
+                if has_cves {
+                    Some(CrateAndVersion { name, version })
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+
     /// Saves the dependency analysis of a crate
     /// Returns the previous values
     pub async fn set_crate_deps_analysis(
@@ -531,6 +1513,98 @@ impl<'c> Database<'c> {
         Ok((deps_has_outdated, deps_has_cves))
     }
 
+    /// Gets the results of the verification of a crate version on each target
+    pub async fn get_crate_version_builds(&self, package: &str, version: &str) -> Result<Vec<CrateVersionBuild>, ApiError> {
+        let rows = sqlx::query_as!(
+            CrateVersionBuild,
+            "SELECT target, success, message, lastCheck AS last_check
+            FROM PackageVersionBuild
+            WHERE package = $1 AND version = $2
+            ORDER BY target",
+            package,
+            version
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Sets the results of the verification of a crate version on each target
+    pub async fn set_crate_version_builds(
+        &self,
+        package: &str,
+        version: &str,
+        builds: &[CrateVersionBuild],
+    ) -> Result<(), ApiError> {
+        sqlx::query!(
+            "DELETE FROM PackageVersionBuild WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        for build in builds {
+            sqlx::query!(
+                "INSERT INTO PackageVersionBuild (package, version, target, success, message, lastCheck) VALUES ($1, $2, $3, $4, $5, $6)",
+                package,
+                version,
+                build.target,
+                build.success,
+                build.message,
+                build.last_check
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Gets the results of the generation of the documentation of a crate version for each target
+    pub async fn get_crate_version_docs(&self, package: &str, version: &str) -> Result<Vec<CrateVersionDocs>, ApiError> {
+        let rows = sqlx::query_as!(
+            CrateVersionDocs,
+            "SELECT target, variant, success, lastUpdate AS last_update
+            FROM PackageVersionDocs
+            WHERE package = $1 AND version = $2
+            ORDER BY target, variant",
+            package,
+            version
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Sets the results of the generation of the documentation of a crate version for each target
+    pub async fn set_crate_version_docs(
+        &self,
+        package: &str,
+        version: &str,
+        docs: &[CrateVersionDocs],
+    ) -> Result<(), ApiError> {
+        sqlx::query!(
+            "DELETE FROM PackageVersionDocs WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        for doc in docs {
+            sqlx::query!(
+                "INSERT INTO PackageVersionDocs (package, version, target, variant, success, lastUpdate) VALUES ($1, $2, $3, $4, $5, $6)",
+                package,
+                version,
+                doc.target,
+                doc.variant,
+                doc.success,
+                doc.last_update
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        }
+        Ok(())
+    }
+
     /// Increments the counter of downloads for a crate version
     pub async fn increment_crate_version_dl_count(&self, package: &str, version: &str) -> Result<(), ApiError> {
         let row = sqlx::query!(
@@ -554,6 +1628,13 @@ impl<'c> Database<'c> {
         )
         .execute(&mut *self.transaction.borrow().await)
         .await?;
+        let today = Local::now().naive_local().date();
+        sqlx::query!(
+            "INSERT INTO DownloadDay (day, downloadCount) VALUES ($1, 1) ON CONFLICT(day) DO UPDATE SET downloadCount = downloadCount + 1",
+            today
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
         Ok(())
     }
 
@@ -570,6 +1651,45 @@ impl<'c> Database<'c> {
         Ok(stats)
     }
 
+    /// Gets the download counts for all the versions of a crate
+    pub async fn get_crate_dl_counts(&self, package: &str) -> Result<CrateDownloadCounts, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT version, downloadCount AS download_count, downloads FROM PackageVersion WHERE package = $1",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        if rows.is_empty() {
+            return Err(specialize(
+                error_not_found(),
+                format!("package {package} is not in this registry"),
+            ));
+        }
+        // the recent counts are not collapsed, contrary to the stats for the charts
+        let mut stats = DownloadStats::new();
+        let mut versions = BTreeMap::new();
+        for row in rows {
+            stats.add_version(row.version.clone(), row.downloads.as_deref());
+            versions.insert(
+                row.version,
+                VersionDownloadCounts {
+                    total: row.download_count,
+                    recent: 0,
+                },
+            );
+        }
+        for version in stats.versions {
+            if let Some(counts) = versions.get_mut(&version.version) {
+                counts.recent = version.total;
+            }
+        }
+        Ok(CrateDownloadCounts {
+            name: package.to_string(),
+            recent_days: SERIES_LENGTH,
+            versions,
+        })
+    }
+
     /// Gets the list of owners for a package
     pub async fn get_crate_owners(&self, package: &str) -> Result<OwnersQueryResult, ApiError> {
         let users = sqlx::query_as!(RegistryUser, "SELECT RegistryUser.id, isActive AS is_active, email, login, name, roles FROM RegistryUser INNER JOIN PackageOwner ON PackageOwner.owner = RegistryUser.id WHERE package = $1", package)
@@ -577,6 +1697,40 @@ impl<'c> Database<'c> {
         Ok(OwnersQueryResult { users })
     }
 
+    /// Gets the emails of the owners of a package that can receive notifications
+    /// Only active owners with a verified email are notified
+    pub async fn get_crate_owners_notified(&self, package: &str) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT email FROM RegistryUser INNER JOIN PackageOwner ON PackageOwner.owner = RegistryUser.id WHERE package = $1 AND isActive = TRUE AND emailVerified = TRUE",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.email).collect())
+    }
+
+    /// Gets the emails of the owners of a package that are notified immediately of publications
+    pub async fn get_crate_owners_publish_notified(&self, package: &str) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT email FROM RegistryUser INNER JOIN PackageOwner ON PackageOwner.owner = RegistryUser.id WHERE package = $1 AND isActive = TRUE AND emailVerified = TRUE AND publishNotifications = 'immediate'",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.email).collect())
+    }
+
+    /// Gets the emails of the owners of a package that are notified of new advisories against its dependencies
+    pub async fn get_crate_owners_advisory_notified(&self, package: &str) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT email FROM RegistryUser INNER JOIN PackageOwner ON PackageOwner.owner = RegistryUser.id WHERE package = $1 AND isActive = TRUE AND emailVerified = TRUE AND advisoryNotifications = TRUE",
+            package
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.email).collect())
+    }
+
     /// Add owners to a package
     pub async fn add_crate_owners(
         &self,
@@ -602,12 +1756,20 @@ impl<'c> Database<'c> {
             let new_uid = self.check_is_user(new_user).await?;
             if rows.iter().all(|r| r.owner != new_uid) {
                 // not already an owner
-                sqlx::query!("INSERT INTO PackageOwner (package, owner) VALUES ($1, $2)", package, new_uid)
-                    .execute(&mut *self.transaction.borrow().await)
-                    .await?;
+                let now = Local::now().naive_local();
+                sqlx::query!(
+                    "INSERT INTO PackageOwner (package, owner, lastAttestation) VALUES ($1, $2, $3)",
+                    package,
+                    new_uid,
+                    now
+                )
+                .execute(&mut *self.transaction.borrow().await)
+                .await?;
                 added.push(new_user.as_str());
             }
         }
+        self.add_audit_entry(authenticated_user, "owners-add", Some(package), None, &added.join(","))
+            .await?;
         let msg = format!(
             "User(s) {} has(-ve) been invited to be an owner of crate {}",
             added.join(", "),
@@ -636,6 +1798,7 @@ impl<'c> Database<'c> {
             .fetch_all(&mut *self.transaction.borrow().await)
             .await?;
         let mut current_owners: Vec<i64> = rows.into_iter().map(|r| r.owner).collect();
+        let mut removed = Vec::new();
         // remove old users
         for old_user in old_users {
             let old_uid = self.check_is_user(old_user).await?;
@@ -650,29 +1813,240 @@ impl<'c> Database<'c> {
                     .execute(&mut *self.transaction.borrow().await)
                     .await?;
                 current_owners.remove(index);
+                removed.push(old_user.as_str());
             }
         }
+        self.add_audit_entry(authenticated_user, "owners-remove", Some(package), None, &removed.join(","))
+            .await?;
         Ok(YesNoResult::new())
     }
 
+    /// Reassigns all the crates owned by a user to a successor, optionally removing the former owner
+    /// The successor is added as owner before the former one is removed so that no crate is left without owner
+    pub async fn reassign_user_crates(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        former: &str,
+        reassignment: &UserCratesReassignment,
+    ) -> Result<Vec<String>, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        // the former owner may already be deactivated
+        let former_uid = sqlx::query!("SELECT id FROM RegistryUser WHERE email = $1 LIMIT 1", former)
+            .fetch_optional(&mut *self.transaction.borrow().await)
+            .await?
+            .ok_or_else(|| specialize(error_not_found(), format!("unknown user {former}")))?
+            .id;
+        let successor_uid = sqlx::query!(
+            "SELECT id FROM RegistryUser WHERE email = $1 AND isActive = TRUE LIMIT 1",
+            reassignment.successor
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(|| {
+            specialize(
+                error_not_found(),
+                format!("unknown or inactive user {}", reassignment.successor),
+            )
+        })?
+        .id;
+        if former_uid == successor_uid {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the successor must be another user"),
+            ));
+        }
+        let packages = self.get_owned_crates(former_uid).await?;
+        let now = Local::now().naive_local();
+        let details = format!("{former} -> {}", reassignment.successor);
+        for package in &packages {
+            sqlx::query!(
+                "INSERT INTO PackageOwner (package, owner, lastAttestation)
+                SELECT $1, $2, $3 WHERE NOT EXISTS (SELECT id FROM PackageOwner WHERE package = $1 AND owner = $2)",
+                package,
+                successor_uid,
+                now
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+            if reassignment.remove_former {
+                sqlx::query!(
+                    "DELETE FROM PackageOwner WHERE package = $1 AND owner = $2",
+                    package,
+                    former_uid
+                )
+                .execute(&mut *self.transaction.borrow().await)
+                .await?;
+            }
+            self.add_audit_entry(authenticated_user, "owners-reassign", Some(package), None, &details)
+                .await?;
+        }
+        Ok(packages)
+    }
+
+    /// Gets the names of the crates owned by a user
+    pub async fn get_owned_crates(&self, uid: i64) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!("SELECT package FROM PackageOwner WHERE owner = $1 ORDER BY package", uid)
+            .fetch_all(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(rows.into_iter().map(|row| row.package).collect())
+    }
+
+    /// Gets the crates that have no active owner
+    pub async fn get_orphaned_crates(&self, authenticated_user: &AuthenticatedUser) -> Result<Vec<String>, ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        self.check_is_admin(authenticated_user.uid).await?;
+        let rows = sqlx::query!(
+            "SELECT name FROM Package
+            WHERE NOT EXISTS (
+                SELECT PackageOwner.id FROM PackageOwner
+                INNER JOIN RegistryUser ON PackageOwner.owner = RegistryUser.id
+                WHERE PackageOwner.package = Package.name AND RegistryUser.isActive = TRUE
+            )
+            ORDER BY name"
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.name).collect())
+    }
+
+    /// Claims the ownership of a crate that has no active owner
+    pub async fn claim_orphaned_crate(&self, authenticated_user: &AuthenticatedUser, package: &str) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        self.check_is_admin(authenticated_user.uid).await?;
+        let _row = sqlx::query!("SELECT name FROM Package WHERE name = $1 LIMIT 1", package)
+            .fetch_optional(&mut *self.transaction.borrow().await)
+            .await?
+            .ok_or_else(error_not_found)?;
+        let active_owners = sqlx::query!(
+            "SELECT COUNT(PackageOwner.id) AS count FROM PackageOwner
+            INNER JOIN RegistryUser ON PackageOwner.owner = RegistryUser.id
+            WHERE PackageOwner.package = $1 AND RegistryUser.isActive = TRUE",
+            package
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?
+        .count;
+        if active_owners != 0 {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("Crate {package} is not orphaned"),
+            ));
+        }
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            "INSERT INTO PackageOwner (package, owner, lastAttestation) VALUES ($1, $2, $3)",
+            package,
+            authenticated_user.uid,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_audit_entry(authenticated_user, "owners-claim", Some(package), None, "")
+            .await?;
+        Ok(())
+    }
+
+    /// Re-attests the ownership of a crate by the authenticated user
+    pub async fn attest_crate_ownership(&self, authenticated_user: &AuthenticatedUser, package: &str) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        let uid = authenticated_user.uid;
+        let now = Local::now().naive_local();
+        let result = sqlx::query!(
+            "UPDATE PackageOwner SET lastAttestation = $3 WHERE package = $1 AND owner = $2",
+            package,
+            uid,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("User is not an owner of this package"),
+            ));
+        }
+        self.add_audit_entry(authenticated_user, "owners-attest", Some(package), None, "")
+            .await?;
+        Ok(())
+    }
+
+    /// Gets the active owners that did not re-attest their ownership since a date time
+    pub async fn get_stale_crate_owners(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        before: NaiveDateTime,
+    ) -> Result<Vec<StaleCrateOwner>, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        let rows = sqlx::query!(
+            "SELECT package, lastAttestation AS last_attestation,
+                RegistryUser.id, isActive AS is_active, email, login, name, roles
+            FROM PackageOwner INNER JOIN RegistryUser ON PackageOwner.owner = RegistryUser.id
+            WHERE RegistryUser.isActive = TRUE AND lastAttestation < $1
+            ORDER BY lastAttestation, package",
+            before
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| StaleCrateOwner {
+                package: row.package,
+                owner: RegistryUser {
+                    id: row.id,
+                    is_active: row.is_active,
+                    email: row.email,
+                    login: row.login,
+                    name: row.name,
+                    roles: row.roles,
+                },
+                last_attestation: row.last_attestation,
+            })
+            .collect())
+    }
+
     /// Gets the targets for a crate
     pub async fn get_crate_targets(&self, package: &str) -> Result<Vec<String>, ApiError> {
         let row = sqlx::query!("SELECT targets FROM Package WHERE name = $1 LIMIT 1", package)
             .fetch_optional(&mut *self.transaction.borrow().await)
             .await?
             .ok_or_else(error_not_found)?;
-        Ok(row
-            .targets
-            .split(',')
-            .filter_map(|s| {
-                let s = s.trim();
-                if s.is_empty() {
-                    None
-                } else {
-                    Some(s.to_string())
-                }
-            })
-            .collect::<Vec<_>>())
+        Ok(split_targets(&row.targets))
+    }
+
+    /// Gets the targets specifically configured for checking the dependencies of a crate
+    /// `None` means that the build targets are used
+    pub async fn get_crate_deps_targets(&self, package: &str) -> Result<Option<Vec<String>>, ApiError> {
+        let row = sqlx::query!(
+            "SELECT depsTargets AS deps_targets FROM Package WHERE name = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(row.deps_targets.as_deref().map(split_targets))
+    }
+
+    /// Gets the targets to use for checking the dependencies of a crate
+    pub async fn get_crate_effective_deps_targets(&self, package: &str) -> Result<Vec<String>, ApiError> {
+        match self.get_crate_deps_targets(package).await? {
+            Some(targets) => Ok(targets),
+            None => self.get_crate_targets(package).await,
+        }
     }
 
     /// Sets the targets for a crate
@@ -694,6 +2068,112 @@ impl<'c> Database<'c> {
         sqlx::query!("UPDATE Package SET targets = $2 WHERE name = $1", package, targets)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
+        self.add_audit_entry(authenticated_user, "targets-set", Some(package), None, &targets)
+            .await?;
         Ok(())
     }
+
+    /// Gets the settings for the generation of the documentation of a crate
+    pub async fn get_crate_docs_build_settings(&self, package: &str) -> Result<CrateDocsBuildSettings, ApiError> {
+        let row = sqlx::query!(
+            "SELECT docsFeatures AS docs_features, docsNoDefaultFeatures AS docs_no_default_features, docsRustdocFlags AS docs_rustdoc_flags, docsVariants AS docs_variants
+            FROM Package WHERE name = $1 LIMIT 1",
+            package
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(CrateDocsBuildSettings {
+            features: row.docs_features.map(|features| {
+                features
+                    .split(',')
+                    .filter(|feature| !feature.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            no_default_features: row.docs_no_default_features,
+            rustdoc_flags: row.docs_rustdoc_flags.split_whitespace().map(str::to_string).collect(),
+            variants: serde_json::from_str(&row.docs_variants)?,
+        })
+    }
+
+    /// Sets the settings for the generation of the documentation of a crate
+    pub async fn set_crate_docs_build_settings(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        settings: &CrateDocsBuildSettings,
+    ) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        // check access
+        self.check_crate_ownership(authenticated_user, package).await?;
+        let features = settings.features.as_ref().map(|features| features.join(","));
+        let rustdoc_flags = settings.rustdoc_flags.join(" ");
+        let variants = serde_json::to_string(&settings.variants)?;
+        sqlx::query!(
+            "UPDATE Package SET docsFeatures = $2, docsNoDefaultFeatures = $3, docsRustdocFlags = $4, docsVariants = $5 WHERE name = $1",
+            package,
+            features,
+            settings.no_default_features,
+            rustdoc_flags,
+            variants
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_audit_entry(
+            authenticated_user,
+            "docs-build-set",
+            Some(package),
+            None,
+            &serde_json::to_string(settings)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Sets the targets specifically used for checking the dependencies of a crate
+    /// `None` resets to using the build targets
+    pub async fn set_crate_deps_targets(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        package: &str,
+        targets: Option<&[String]>,
+    ) -> Result<(), ApiError> {
+        if !authenticated_user.can_admin {
+            return Err(specialize(
+                error_forbidden(),
+                String::from("administration is forbidden for this authentication"),
+            ));
+        }
+        // check access
+        self.check_crate_ownership(authenticated_user, package).await?;
+        let targets = targets.map(|targets| targets.join(","));
+        sqlx::query!("UPDATE Package SET depsTargets = $2 WHERE name = $1", package, targets)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        self.add_audit_entry(
+            authenticated_user,
+            "deps-targets-set",
+            Some(package),
+            None,
+            targets.as_deref().unwrap_or_default(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Splits a comma-separated list of targets
+fn split_targets(targets: &str) -> Vec<String> {
+    targets
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
 }
@@ -0,0 +1,58 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service for persisting information in the database
+//! API related to the crates proxied from the upstream registry
+
+use chrono::Local;
+
+use super::Database;
+use crate::utils::apierror::ApiError;
+
+impl Database<'_> {
+    /// Gets whether a crate is hosted by this registry, looking it up without regard to the case
+    /// Crates hosted by this registry always take precedence over the upstream ones with the same name
+    pub async fn is_crate_local(&self, package: &str) -> Result<bool, ApiError> {
+        Ok(self.get_crate_canonical_name(package).await?.is_some())
+    }
+
+    /// Gets the checksum of a crate version proxied from the upstream registry, if it was already cached
+    pub async fn get_proxied_crate_version_checksum(&self, package: &str, version: &str) -> Result<Option<String>, ApiError> {
+        let row = sqlx::query!(
+            "SELECT checksum FROM ProxiedCrateVersion WHERE package = $1 AND version = $2 LIMIT 1",
+            package,
+            version
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(row.map(|row| row.checksum))
+    }
+
+    /// Records that a crate version proxied from the upstream registry was cached
+    pub async fn add_proxied_crate_version(&self, package: &str, version: &str, checksum: &str) -> Result<(), ApiError> {
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            "INSERT INTO ProxiedCrateVersion (package, version, checksum, cachedOn) VALUES ($1, $2, $3, $4) ON CONFLICT(package, version) DO NOTHING",
+            package,
+            version,
+            checksum,
+            now
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+
+    /// Increments the number of downloads of a crate version proxied from the upstream registry
+    pub async fn increment_proxied_crate_version_dl_count(&self, package: &str, version: &str) -> Result<(), ApiError> {
+        sqlx::query!(
+            "UPDATE ProxiedCrateVersion SET downloadCount = downloadCount + 1 WHERE package = $1 AND version = $2",
+            package,
+            version
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(())
+    }
+}
@@ -5,12 +5,31 @@
 //! Service for persisting information in the database
 //! API related to statistics
 
+use chrono::NaiveDate;
+
 use super::Database;
-use crate::model::stats::GlobalStats;
+use crate::model::stats::{DownloadsBucket, DownloadsGranularity, GlobalDownloadsSeries, GlobalStats, RegistryTotals};
 use crate::model::CrateAndVersion;
 use crate::utils::apierror::ApiError;
 
 impl<'c> Database<'c> {
+    /// Gets the totals for the content of the registry
+    pub async fn get_registry_totals(&self) -> Result<RegistryTotals, ApiError> {
+        let total_crates = sqlx::query!("SELECT COUNT(name) AS total_crates FROM Package")
+            .fetch_one(&mut *self.transaction.borrow().await)
+            .await?
+            .total_crates;
+        let row =
+            sqlx::query!("SELECT COUNT(version) AS total_versions, SUM(downloadCount) AS total_downloads FROM PackageVersion")
+                .fetch_one(&mut *self.transaction.borrow().await)
+                .await?;
+        Ok(RegistryTotals {
+            crates: total_crates,
+            versions: row.total_versions,
+            downloads: row.total_downloads.unwrap_or_default(),
+        })
+    }
+
     /// Gets the global statistics for the registry
     pub async fn get_crates_stats(&self) -> Result<GlobalStats, ApiError> {
         let total_crates = sqlx::query!("SELECT COUNT(name) AS total_crates FROM Package")
@@ -81,4 +100,40 @@ impl<'c> Database<'c> {
             crates_last_updated,
         })
     }
+
+    /// Gets the total downloads across all crates between two days (inclusive), bucketed by the granularity
+    pub async fn get_global_dl_timeseries(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: DownloadsGranularity,
+    ) -> Result<GlobalDownloadsSeries, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT day AS \"day!: NaiveDate\", downloadCount AS download_count FROM DownloadDay WHERE day >= $1 AND day <= $2 ORDER BY day",
+            from,
+            to
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let mut rows = rows.into_iter().peekable();
+        let mut buckets: Vec<DownloadsBucket> = Vec::new();
+        let mut total = 0;
+        for day in from.iter_days().take_while(|day| *day <= to) {
+            let start = granularity.bucket_start(day).max(from);
+            if buckets.last().is_none_or(|bucket| bucket.start != start) {
+                buckets.push(DownloadsBucket { start, count: 0 });
+            }
+            if let Some(row) = rows.next_if(|row| row.day == day) {
+                total += row.download_count;
+                if let Some(bucket) = buckets.last_mut() {
+                    bucket.count += row.download_count;
+                }
+            }
+        }
+        Ok(GlobalDownloadsSeries {
+            granularity,
+            buckets,
+            total,
+        })
+    }
 }
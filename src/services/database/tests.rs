@@ -0,0 +1,261 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Tests for the persistence of information in the database
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::{Connection, SqliteConnection};
+
+use super::Database;
+use crate::model::auth::{AuthenticatedUser, TokenRestrictions};
+use crate::model::generate_token;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+use crate::utils::hashes::sha256;
+
+/// Opens a connection to a new database in memory, with the current schema
+async fn test_connection() -> SqliteConnection {
+    let mut connection = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    sqlx::raw_sql(include_str!("../../schema.sql"))
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    connection
+}
+
+/// Inserts an active user and returns its identifier
+async fn insert_user(database: &Database<'_>, login: &str, roles: &str) -> i64 {
+    sqlx::query_scalar(
+        "INSERT INTO RegistryUser (isActive, email, login, name, roles) VALUES (TRUE, $1, $2, $2, $3) RETURNING id",
+    )
+    .bind(format!("{login}@example.com"))
+    .bind(login)
+    .bind(roles)
+    .fetch_one(&mut *database.transaction.borrow().await)
+    .await
+    .unwrap()
+}
+
+/// Inserts a token for a user, with the secret hashed as in the database
+async fn insert_token(database: &Database<'_>, uid: i64, secret: &str) {
+    sqlx::query(
+        "INSERT INTO RegistryUserToken (user, name, token, lastUsed, canWrite, canAdmin, created) VALUES ($1, 'token', $2, CURRENT_TIMESTAMP, TRUE, FALSE, CURRENT_TIMESTAMP)",
+    )
+    .bind(uid)
+    .bind(sha256(secret.as_bytes()))
+    .execute(&mut *database.transaction.borrow().await)
+    .await
+    .unwrap();
+}
+
+/// Inserts a crate owned by a user
+async fn insert_owned_crate(database: &Database<'_>, package: &str, owner: i64) {
+    sqlx::query("INSERT INTO Package (name, lowercase, targets, docsEnabled) VALUES ($1, $1, '', FALSE)")
+        .bind(package)
+        .execute(&mut *database.transaction.borrow().await)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO PackageOwner (package, owner, lastAttestation) VALUES ($1, $2, CURRENT_TIMESTAMP)")
+        .bind(package)
+        .bind(owner)
+        .execute(&mut *database.transaction.borrow().await)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn crate_scope_applies_to_owner_mutations() {
+    let mut connection = test_connection().await;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        let uid = insert_user(&database, "owner", "").await;
+        insert_owned_crate(&database, "crate-a", uid).await;
+        insert_owned_crate(&database, "crate-b", uid).await;
+        let scoped = AuthenticatedUser {
+            uid,
+            principal: String::from("owner@example.com"),
+            can_write: true,
+            can_admin: true,
+            token_id: Some(1),
+            crates: vec![String::from("crate-a")],
+        };
+        let targets = [String::from("x86_64-unknown-linux-gnu")];
+        database.set_crate_deps_targets(&scoped, "crate-a", Some(&targets)).await?;
+        let error = database
+            .set_crate_deps_targets(&scoped, "crate-b", Some(&targets))
+            .await
+            .unwrap_err();
+        assert_eq!(error.http, 403);
+        let error = database
+            .remove_crate_owners(&scoped, "crate-b", &[String::from("owner")])
+            .await
+            .unwrap_err();
+        assert_eq!(error.http, 403);
+        Ok::<_, ApiError>(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn deactivated_user_token_is_rejected() {
+    let mut connection = test_connection().await;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        let admin_uid = insert_user(&database, "admin", "admin").await;
+        let uid = insert_user(&database, "user", "").await;
+        insert_token(&database, uid, "secret").await;
+        database.check_token("user", "secret", None).await?;
+        let admin = AuthenticatedUser {
+            uid: admin_uid,
+            principal: String::from("admin@example.com"),
+            can_write: true,
+            can_admin: true,
+            token_id: None,
+            crates: Vec::new(),
+        };
+        database.deactivate_user(&admin, "user@example.com").await?;
+        let error = database.check_token("user", "secret", None).await.unwrap_err();
+        assert_eq!(error.http, 401);
+        Ok::<_, ApiError>(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn token_secret_collision_is_retried() {
+    let mut connection = test_connection().await;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        let uid = insert_user(&database, "user", "").await;
+        let user = AuthenticatedUser {
+            uid,
+            principal: String::from("user@example.com"),
+            can_write: true,
+            can_admin: true,
+            token_id: None,
+            crates: Vec::new(),
+        };
+        let restrictions = TokenRestrictions::default();
+        let first = database
+            .create_token(&user, generate_token(64), "first", true, false, None, &restrictions)
+            .await?
+            .unwrap();
+        // the same secret collides with the existing token
+        let collision = database
+            .create_token(&user, first.secret.clone(), "second", true, false, None, &restrictions)
+            .await?;
+        assert!(collision.is_none());
+        // the retry with a new secret succeeds
+        let second = database
+            .create_token(&user, generate_token(64), "second", true, false, None, &restrictions)
+            .await?
+            .unwrap();
+        assert_ne!(first.secret, second.secret);
+        assert_ne!(first.id, second.id);
+        let distinct: i64 = sqlx::query_scalar("SELECT COUNT(DISTINCT token) FROM RegistryUserToken WHERE user = $1")
+            .bind(uid)
+            .fetch_one(&mut *database.transaction.borrow().await)
+            .await?;
+        assert_eq!(distinct, 2);
+        Ok::<_, ApiError>(())
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_token_creations_are_distinct() {
+    const COUNT: u32 = 16;
+    // the connections of a pool share a database only when it is in a file
+    let path = std::env::temp_dir().join(format!("cratery-test-{}.db", uuid::Uuid::new_v4()));
+    let options = SqliteConnectOptions::new()
+        .filename(&path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(10));
+    let pool = SqlitePoolOptions::new()
+        .max_connections(COUNT)
+        .connect_with(options)
+        .await
+        .unwrap();
+    let mut connection = pool.acquire().await.unwrap();
+    sqlx::raw_sql(include_str!("../../schema.sql"))
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+    let uid = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        Ok::<_, ApiError>(insert_user(&database, "user", "").await)
+    })
+    .await
+    .unwrap();
+    drop(connection);
+    let user = AuthenticatedUser {
+        uid,
+        principal: String::from("user@example.com"),
+        can_write: true,
+        can_admin: true,
+        token_id: None,
+        crates: Vec::new(),
+    };
+
+    let creations = (0..COUNT).map(|index| {
+        let pool = pool.clone();
+        let user = user.clone();
+        tokio::spawn(async move {
+            let mut connection = pool.acquire().await?;
+            let restrictions = TokenRestrictions::default();
+            // retried with a new secret on a collision, as the application does
+            loop {
+                let user = &user;
+                let restrictions = &restrictions;
+                let created = in_transaction(&mut connection, |transaction| async move {
+                    Database::new(transaction)
+                        .create_token(
+                            user,
+                            generate_token(64),
+                            &format!("token-{index}"),
+                            true,
+                            false,
+                            None,
+                            restrictions,
+                        )
+                        .await
+                })
+                .await?;
+                if let Some(token) = created {
+                    return Ok::<_, ApiError>(token);
+                }
+            }
+        })
+    });
+    let tokens = futures::future::join_all(creations)
+        .await
+        .into_iter()
+        .map(|result| result.unwrap().unwrap())
+        .collect::<Vec<_>>();
+
+    let ids = tokens.iter().map(|token| token.id).collect::<HashSet<_>>();
+    assert_eq!(ids.len(), COUNT as usize);
+    let hashes = tokens
+        .iter()
+        .map(|token| sha256(token.secret.as_bytes()))
+        .collect::<HashSet<_>>();
+    assert_eq!(hashes.len(), COUNT as usize);
+    let stored: Vec<String> = sqlx::query_scalar("SELECT token FROM RegistryUserToken WHERE user = $1")
+        .bind(uid)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored.into_iter().collect::<HashSet<_>>(), hashes);
+    pool.close().await;
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
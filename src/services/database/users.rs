@@ -5,12 +5,18 @@
 //! Service for persisting information in the database
 //! API related to the management of users and authentication
 
-use chrono::Local;
+use std::net::IpAddr;
+
+use chrono::{Duration, Local, NaiveDateTime};
 use data_encoding::HEXLOWER;
 use ring::digest::{Context, SHA256};
 
 use super::Database;
-use crate::model::auth::{find_field_in_blob, AuthenticatedUser, OAuthToken, RegistryUserToken, RegistryUserTokenWithSecret};
+use crate::model::auth::{
+    find_field_in_blob, AuthenticatedUser, OAuthToken, PublishDigestRecipient, PublishNotifications, RegistryUserToken,
+    RegistryUserTokenWithSecret, RevokedToken, TokenRestrictions, TokensRevocationCriteria, TokensRevocationResult,
+    UserImportResult, UserImportStatus, UserNotificationSettings,
+};
 use crate::model::cargo::RegistryUser;
 use crate::model::config::Configuration;
 use crate::model::generate_token;
@@ -18,6 +24,18 @@ use crate::model::namegen::generate_name;
 use crate::utils::apierror::{
     error_conflict, error_forbidden, error_invalid_request, error_not_found, error_unauthorized, specialize, ApiError,
 };
+use crate::utils::cidr::{parse_cidr_list, IpCidr};
+
+/// The minimum number of seconds between two updates of the last use of a token
+/// This avoids writing to the database on every request authenticated with a busy token
+const TOKEN_LAST_USED_RESOLUTION: i64 = 60;
+
+/// Splits the comma-separated IP addresses or CIDR blocks a token is bound to
+fn split_allowed_ips(allowed_ips: Option<&str>) -> Vec<String> {
+    allowed_ips
+        .map(|allowed_ips| allowed_ips.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
 
 /// Computes the SHA256 digest of bytes
 fn sha256(buffer: &[u8]) -> String {
@@ -60,8 +78,83 @@ impl<'c> Database<'c> {
         maybe_row.ok_or_else(error_not_found)
     }
 
+    /// Gets the preferences of a user for the notifications sent by email
+    pub async fn get_notification_settings(&self, uid: i64) -> Result<UserNotificationSettings, ApiError> {
+        let row = sqlx::query!(
+            "SELECT publishNotifications, advisoryNotifications FROM RegistryUser WHERE id = $1",
+            uid
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        Ok(UserNotificationSettings {
+            publish: row.publishNotifications.parse().unwrap_or_default(),
+            advisories: row.advisoryNotifications,
+        })
+    }
+
+    /// Sets the preferences of a user for the notifications sent by email
+    /// A digest started now only covers the publications from now on
+    pub async fn set_notification_settings(
+        &self,
+        uid: i64,
+        settings: &UserNotificationSettings,
+    ) -> Result<UserNotificationSettings, ApiError> {
+        let current = self.get_notification_settings(uid).await?;
+        let publish = settings.publish.as_str();
+        sqlx::query!(
+            "UPDATE RegistryUser SET publishNotifications = $2, advisoryNotifications = $3 WHERE id = $1",
+            uid,
+            publish,
+            settings.advisories
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        if current.publish != PublishNotifications::Digest && settings.publish == PublishNotifications::Digest {
+            let now = Local::now().naive_local();
+            sqlx::query!("UPDATE RegistryUser SET lastPublishDigest = $2 WHERE id = $1", uid, now)
+                .execute(&mut *self.transaction.borrow().await)
+                .await?;
+        }
+        Ok(*settings)
+    }
+
+    /// Gets the users that chose a digest of the publications and whose last digest is older than `before`
+    pub async fn get_publish_digest_recipients(&self, before: NaiveDateTime) -> Result<Vec<PublishDigestRecipient>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT id, email, lastPublishDigest AS \"last_publish_digest!: NaiveDateTime\" FROM RegistryUser
+            WHERE publishNotifications = 'digest' AND isActive = TRUE AND emailVerified = TRUE
+            AND lastPublishDigest IS NOT NULL AND lastPublishDigest <= $1",
+            before
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| PublishDigestRecipient {
+                uid: row.id,
+                email: row.email,
+                since: row.last_publish_digest,
+            })
+            .collect())
+    }
+
+    /// Records that a digest of the publications was sent to a user, up to a date time
+    pub async fn set_publish_digest_sent(&self, uid: i64, upto: NaiveDateTime) -> Result<(), ApiError> {
+        sqlx::query!("UPDATE RegistryUser SET lastPublishDigest = $2 WHERE id = $1", uid, upto)
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        Ok(())
+    }
+
     /// Attempts to login using an OAuth code
-    pub async fn login_with_oauth_code(&self, configuration: &Configuration, code: &str) -> Result<RegistryUser, ApiError> {
+    /// When a nonce is expected, it must match the one within the identity token, if any
+    pub async fn login_with_oauth_code(
+        &self,
+        configuration: &Configuration,
+        code: &str,
+        nonce: Option<&str>,
+    ) -> Result<RegistryUser, ApiError> {
         let client = reqwest::Client::new();
         // retrieve the token
         let response = client
@@ -80,6 +173,11 @@ impl<'c> Database<'c> {
         }
         let body = response.bytes().await?;
         let token = serde_json::from_slice::<OAuthToken>(&body)?;
+        if let Some(nonce) = nonce {
+            if token.id_token.is_some() && token.get_id_token_nonce().as_deref() != Some(nonce) {
+                return Err(specialize(error_unauthorized(), String::from("invalid OAuth nonce")));
+            }
+        }
 
         // retrieve the user profile
         let response = client
@@ -105,6 +203,13 @@ impl<'c> Database<'c> {
             if !row.is_active {
                 return Err(specialize(error_unauthorized(), String::from("inactive user")));
             }
+            // the identity provider vouches for the email
+            sqlx::query!(
+                "UPDATE RegistryUser SET emailVerified = TRUE, emailVerificationToken = NULL, emailVerificationExpires = NULL WHERE id = $1 AND emailVerified = FALSE",
+                row.id
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
             // already exists
             return Ok(RegistryUser {
                 id: row.id,
@@ -151,6 +256,151 @@ impl<'c> Database<'c> {
         })
     }
 
+    /// Creates users in bulk, before their first login
+    /// Users whose email is already known are reported as duplicates and left untouched
+    pub async fn import_users(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        users: &[RegistryUser],
+    ) -> Result<Vec<UserImportResult>, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        let mut results = Vec::with_capacity(users.len());
+        for user in users {
+            results.push(self.import_user(authenticated_user, user).await?);
+        }
+        Ok(results)
+    }
+
+    /// Creates a single user as part of a bulk import
+    async fn import_user(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        user: &RegistryUser,
+    ) -> Result<UserImportResult, ApiError> {
+        let email = user.email.trim();
+        let failure = |status, message: &str| UserImportResult {
+            email: email.to_string(),
+            status,
+            user: None,
+            message: Some(message.to_string()),
+        };
+        let Some(at) = email.find('@').filter(|&at| at > 0 && at + 1 < email.len()) else {
+            return Ok(failure(UserImportStatus::Invalid, "invalid email"));
+        };
+        if sqlx::query!("SELECT COUNT(id) AS count FROM RegistryUser WHERE email = $1", email)
+            .fetch_one(&mut *self.transaction.borrow().await)
+            .await?
+            .count
+            != 0
+        {
+            return Ok(failure(UserImportStatus::Duplicate, "a user with this email already exists"));
+        }
+        let login = if user.login.trim().is_empty() {
+            email[..at].to_string()
+        } else {
+            user.login.trim().to_string()
+        };
+        if sqlx::query!("SELECT COUNT(id) AS count FROM RegistryUser WHERE login = $1", login)
+            .fetch_one(&mut *self.transaction.borrow().await)
+            .await?
+            .count
+            != 0
+        {
+            return Ok(failure(UserImportStatus::Invalid, "the login is not available"));
+        }
+        let name = if user.name.trim().is_empty() {
+            login.clone()
+        } else {
+            user.name.trim().to_string()
+        };
+        let id = sqlx::query!(
+            "INSERT INTO RegistryUser (isActive, email, login, name, roles, emailVerified) VALUES ($1, $2, $3, $4, $5, FALSE) RETURNING id",
+            user.is_active,
+            email,
+            login,
+            name,
+            user.roles
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?
+        .id;
+        self.add_audit_entry(authenticated_user, "user-import", None, None, email)
+            .await?;
+        Ok(UserImportResult {
+            email: email.to_string(),
+            status: UserImportStatus::Created,
+            user: Some(RegistryUser {
+                id,
+                is_active: user.is_active,
+                email: email.to_string(),
+                login,
+                name,
+                roles: user.roles.clone(),
+            }),
+            message: None,
+        })
+    }
+
+    /// Creates a new secret to verify the email of a user, valid for `validity` hours
+    /// An administrator can request it for any user, other users only for themselves
+    pub async fn create_email_verification(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        target: &str,
+        validity: i64,
+    ) -> Result<String, ApiError> {
+        if authenticated_user.principal != target {
+            self.check_can_admin(authenticated_user).await?;
+        }
+        let row = sqlx::query!(
+            "SELECT id, emailVerified AS email_verified FROM RegistryUser WHERE isActive = TRUE AND email = $1 LIMIT 1",
+            target
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(error_not_found)?;
+        if row.email_verified {
+            return Err(specialize(error_conflict(), String::from("the email is already verified")));
+        }
+        let secret = generate_token(64);
+        let secret_hashed = hash_token(&secret);
+        let expires = Local::now().naive_local() + chrono::Duration::hours(validity);
+        sqlx::query!(
+            "UPDATE RegistryUser SET emailVerificationToken = $2, emailVerificationExpires = $3 WHERE id = $1",
+            row.id,
+            secret_hashed,
+            expires
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        self.add_audit_entry(authenticated_user, "user-email-verification", None, None, target)
+            .await?;
+        Ok(secret)
+    }
+
+    /// Marks the email of a user as verified using the secret sent to this email
+    /// Returns the verified email
+    pub async fn verify_email(&self, secret: &str) -> Result<String, ApiError> {
+        let secret_hashed = hash_token(secret);
+        let row = sqlx::query!(
+            "SELECT id, email, emailVerificationExpires AS expires FROM RegistryUser WHERE emailVerificationToken = $1 LIMIT 1",
+            secret_hashed
+        )
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?
+        .ok_or_else(|| specialize(error_not_found(), String::from("invalid verification link")))?;
+        if row.expires.is_none_or(|expires| expires < Local::now().naive_local()) {
+            return Err(specialize(error_forbidden(), String::from("the verification link expired")));
+        }
+        sqlx::query!(
+            "UPDATE RegistryUser SET emailVerified = TRUE, emailVerificationToken = NULL, emailVerificationExpires = NULL WHERE id = $1",
+            row.id
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(row.email)
+    }
+
     /// Gets the known users
     pub async fn get_users(&self, authenticated_user: &AuthenticatedUser) -> Result<Vec<RegistryUser>, ApiError> {
         if !authenticated_user.can_admin {
@@ -227,6 +477,8 @@ impl<'c> Database<'c> {
         )
         .execute(&mut *self.transaction.borrow().await)
         .await?;
+        self.add_audit_entry(authenticated_user, "user-update", None, None, &target.email)
+            .await?;
         Ok(target.clone())
     }
 
@@ -248,6 +500,8 @@ impl<'c> Database<'c> {
         sqlx::query!("UPDATE RegistryUser SET isActive = FALSE WHERE id = $1", target_uid)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
+        self.add_audit_entry(authenticated_user, "user-deactivate", None, None, target)
+            .await?;
         Ok(())
     }
 
@@ -264,6 +518,8 @@ impl<'c> Database<'c> {
         sqlx::query!("UPDATE RegistryUser SET isActive = TRUE WHERE email = $1", target)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
+        self.add_audit_entry(authenticated_user, "user-reactivate", None, None, target)
+            .await?;
         Ok(())
     }
 
@@ -285,6 +541,12 @@ impl<'c> Database<'c> {
         if uid == target_uid {
             return Err(specialize(error_forbidden(), String::from("cannot delete self")));
         }
+        sqlx::query!(
+            "DELETE FROM RegistryUserTokenScope WHERE token IN (SELECT id FROM RegistryUserToken WHERE user = $1)",
+            target_uid
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
         sqlx::query!("DELETE FROM RegistryUserToken WHERE user = $1", target_uid)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
@@ -294,6 +556,8 @@ impl<'c> Database<'c> {
         sqlx::query!("DELETE FROM RegistryUser WHERE id = $1", target_uid)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
+        self.add_audit_entry(authenticated_user, "user-delete", None, None, target)
+            .await?;
         Ok(())
     }
 
@@ -307,31 +571,54 @@ impl<'c> Database<'c> {
         }
         let uid = authenticated_user.uid;
         let rows = sqlx::query!(
-            "SELECT id, name, lastUsed AS last_used, canWrite AS can_write, canAdmin AS can_admin FROM RegistryUserToken WHERE user = $1 ORDER BY id",
+            "SELECT id, name, lastUsed AS last_used, canWrite AS can_write, canAdmin AS can_admin, created, expiresAt AS expires_at, allowedIps AS allowed_ips FROM RegistryUserToken WHERE user = $1 ORDER BY id",
+            uid
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let scopes = sqlx::query!(
+            "SELECT token, pattern FROM RegistryUserTokenScope
+            WHERE token IN (SELECT id FROM RegistryUserToken WHERE user = $1)
+            ORDER BY id",
             uid
         )
         .fetch_all(&mut *self.transaction.borrow().await)
         .await?;
+        let now = Local::now().naive_local();
         Ok(rows
             .into_iter()
             .map(|row| RegistryUserToken {
+                crates: scopes
+                    .iter()
+                    .filter(|scope| scope.token == row.id)
+                    .map(|scope| scope.pattern.clone())
+                    .collect(),
+                allowed_ips: split_allowed_ips(row.allowed_ips.as_deref()),
                 id: row.id,
                 name: row.name,
                 last_used: row.last_used,
                 can_write: row.can_write,
                 can_admin: row.can_admin,
+                created: row.created,
+                expires_at: row.expires_at,
+                expired: row.expires_at.is_some_and(|expires_at| expires_at <= now),
             })
             .collect())
     }
 
-    /// Creates a token for the current user
+    /// Creates a token for the current user, with a secret generated by the caller
+    /// Returns `None` when the secret collides with the one of an existing token, so that the caller can try again with another one
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_token(
         &self,
         authenticated_user: &AuthenticatedUser,
+        token_secret: String,
         name: &str,
         can_write: bool,
         can_admin: bool,
-    ) -> Result<RegistryUserTokenWithSecret, ApiError> {
+        expires_at: Option<NaiveDateTime>,
+        restrictions: &TokenRestrictions,
+    ) -> Result<Option<RegistryUserTokenWithSecret>, ApiError> {
         if !authenticated_user.can_admin {
             return Err(specialize(
                 error_forbidden(),
@@ -339,29 +626,78 @@ impl<'c> Database<'c> {
             ));
         }
         let uid = authenticated_user.uid;
-        let token_secret = generate_token(64);
         let token_hash = hash_token(&token_secret);
         let now = Local::now().naive_local();
-        let id = sqlx::query!(
-            "INSERT INTO RegistryUserToken (user, name, token, lastUsed, canWrite, canAdmin) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("the expiration date of a token must be in the future"),
+            ));
+        }
+        let allowed_ips = if restrictions.allowed_ips.is_empty() {
+            None
+        } else {
+            Some(restrictions.allowed_ips.join(","))
+        };
+        let row = sqlx::query!(
+            "INSERT INTO RegistryUserToken (user, name, token, lastUsed, canWrite, canAdmin, created, expiresAt, allowedIps) VALUES ($1, $2, $3, $4, $5, $6, $4, $7, $8)
+            ON CONFLICT(token) DO NOTHING RETURNING id",
             uid,
             name,
             token_hash,
             now,
             can_write,
-            can_admin
+            can_admin,
+            expires_at,
+            allowed_ips
         )
-        .fetch_one(&mut *self.transaction.borrow().await)
-        .await?
-        .id;
-        Ok(RegistryUserTokenWithSecret {
+        .fetch_optional(&mut *self.transaction.borrow().await)
+        .await?;
+        let Some(id) = row.map(|row| row.id) else {
+            return Ok(None);
+        };
+        for pattern in &restrictions.crates {
+            sqlx::query!(
+                "INSERT INTO RegistryUserTokenScope (token, pattern) VALUES ($1, $2)",
+                id,
+                pattern
+            )
+            .execute(&mut *self.transaction.borrow().await)
+            .await?;
+        }
+        self.add_audit_entry(authenticated_user, "token-create", None, None, name)
+            .await?;
+        Ok(Some(RegistryUserTokenWithSecret {
             id,
             name: name.to_string(),
             secret: token_secret,
             last_used: now,
             can_write,
             can_admin,
-        })
+            expires_at,
+            crates: restrictions.crates.clone(),
+            allowed_ips: restrictions.allowed_ips.clone(),
+        }))
+    }
+
+    /// Checks that the user has no token with the same name
+    pub async fn check_token_name_unused(&self, authenticated_user: &AuthenticatedUser, name: &str) -> Result<(), ApiError> {
+        let uid = authenticated_user.uid;
+        let count = sqlx::query!(
+            "SELECT COUNT(id) AS count FROM RegistryUserToken WHERE user = $1 AND name = $2",
+            uid,
+            name
+        )
+        .fetch_one(&mut *self.transaction.borrow().await)
+        .await?
+        .count;
+        if count > 0 {
+            return Err(specialize(
+                error_invalid_request(),
+                format!("a token named {name} already exists"),
+            ));
+        }
+        Ok(())
     }
 
     /// Revoke a previous token
@@ -373,16 +709,120 @@ impl<'c> Database<'c> {
             ));
         }
         let uid = authenticated_user.uid;
+        sqlx::query!(
+            "DELETE FROM RegistryUserTokenScope WHERE token IN (SELECT id FROM RegistryUserToken WHERE user = $1 AND id = $2)",
+            uid,
+            token_id
+        )
+        .execute(&mut *self.transaction.borrow().await)
+        .await?;
         sqlx::query!("DELETE FROM RegistryUserToken WHERE user = $1 AND id = $2", uid, token_id)
             .execute(&mut *self.transaction.borrow().await)
             .await?;
+        self.add_audit_entry(authenticated_user, "token-revoke", None, None, &token_id.to_string())
+            .await?;
         Ok(())
     }
 
+    /// Revokes all the tokens, of all users, matching the criteria
+    /// In a dry run, the matching tokens are only listed
+    pub async fn revoke_tokens_matching(
+        &self,
+        authenticated_user: &AuthenticatedUser,
+        criteria: &TokensRevocationCriteria,
+    ) -> Result<TokensRevocationResult, ApiError> {
+        self.check_can_admin(authenticated_user).await?;
+        if criteria.is_empty() {
+            return Err(specialize(
+                error_invalid_request(),
+                String::from("at least one criteria is required to revoke tokens"),
+            ));
+        }
+        let rows = sqlx::query!(
+            "SELECT RegistryUserToken.id, RegistryUserToken.name, lastUsed AS last_used, canWrite AS can_write, canAdmin AS can_admin, created, expiresAt AS expires_at, allowedIps AS allowed_ips, login
+            FROM RegistryUserToken INNER JOIN RegistryUser ON RegistryUser.id = RegistryUserToken.user
+            WHERE ($1 IS NULL OR lastUsed < $1)
+                AND ($2 IS NULL OR created < $2)
+                AND ($3 IS NULL OR canWrite = $3)
+                AND ($4 IS NULL OR canAdmin = $4)
+            ORDER BY RegistryUserToken.id",
+            criteria.last_used_before,
+            criteria.created_before,
+            criteria.can_write,
+            criteria.can_admin
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        let now = Local::now().naive_local();
+        let mut tokens = Vec::with_capacity(rows.len());
+        for row in rows {
+            let crates = self.get_token_scope(row.id).await?;
+            tokens.push(RevokedToken {
+                owner: row.login,
+                token: RegistryUserToken {
+                    id: row.id,
+                    name: row.name,
+                    last_used: row.last_used,
+                    can_write: row.can_write,
+                    can_admin: row.can_admin,
+                    created: row.created,
+                    expires_at: row.expires_at,
+                    expired: row.expires_at.is_some_and(|expires_at| expires_at <= now),
+                    crates,
+                    allowed_ips: split_allowed_ips(row.allowed_ips.as_deref()),
+                },
+            });
+        }
+        if !criteria.dry_run {
+            for revoked in &tokens {
+                let token_id = revoked.token.id;
+                sqlx::query!("DELETE FROM RegistryUserTokenScope WHERE token = $1", token_id)
+                    .execute(&mut *self.transaction.borrow().await)
+                    .await?;
+                sqlx::query!("DELETE FROM RegistryUserToken WHERE id = $1", token_id)
+                    .execute(&mut *self.transaction.borrow().await)
+                    .await?;
+                self.add_audit_entry(
+                    authenticated_user,
+                    "token-revoke",
+                    None,
+                    None,
+                    &format!("{}/{token_id}", revoked.owner),
+                )
+                .await?;
+            }
+        }
+        Ok(TokensRevocationResult {
+            dry_run: criteria.dry_run,
+            count: tokens.len(),
+            tokens,
+        })
+    }
+
+    /// Gets the patterns for the names of the crates a token is limited to
+    async fn get_token_scope(&self, token_id: i64) -> Result<Vec<String>, ApiError> {
+        let rows = sqlx::query!(
+            "SELECT pattern FROM RegistryUserTokenScope WHERE token = $1 ORDER BY id",
+            token_id
+        )
+        .fetch_all(&mut *self.transaction.borrow().await)
+        .await?;
+        Ok(rows.into_iter().map(|row| row.pattern).collect())
+    }
+
     /// Checks an authentication request with a token
-    pub async fn check_token(&self, login: &str, token_secret: &str) -> Result<AuthenticatedUser, ApiError> {
+    /// The tokens of deactivated users are rejected, they are usable again if the user is reactivated
+    /// Expired tokens are rejected but kept so that their owner can see them
+    /// Tokens bound to IP addresses are rejected when the client is not within one of them, or is unknown
+    /// The last use of the token is only updated when the stored value is older than `TOKEN_LAST_USED_RESOLUTION` seconds
+    pub async fn check_token(
+        &self,
+        login: &str,
+        token_secret: &str,
+        client_ip: Option<IpAddr>,
+    ) -> Result<AuthenticatedUser, ApiError> {
         let rows = sqlx::query!(
-            "SELECT RegistryUser.id AS uid, email, RegistryUserToken.id, token, canWrite AS can_write, canAdmin AS can_admin
+            "SELECT RegistryUser.id AS uid, email, RegistryUserToken.id, token, lastUsed AS last_used, canWrite AS can_write, canAdmin AS can_admin, expiresAt AS expires_at, allowedIps AS allowed_ips
             FROM RegistryUser INNER JOIN RegistryUserToken ON RegistryUser.id = RegistryUserToken.user
             WHERE isActive = TRUE AND login = $1",
             login
@@ -392,14 +832,33 @@ impl<'c> Database<'c> {
         for row in rows {
             if check_hash(token_secret, &row.token).is_ok() {
                 let now = Local::now().naive_local();
-                sqlx::query!("UPDATE RegistryUserToken SET lastUsed = $2 WHERE id = $1", row.id, now)
-                    .execute(&mut *self.transaction.borrow().await)
-                    .await?;
+                if row.expires_at.is_some_and(|expires_at| expires_at <= now) {
+                    return Err(specialize(error_unauthorized(), String::from("the token has expired")));
+                }
+                if let Some(allowed_ips) = row.allowed_ips.as_deref() {
+                    let allowed = client_ip.is_some_and(|client_ip| {
+                        parse_cidr_list(allowed_ips).is_ok_and(|blocks| IpCidr::any_contains(&blocks, client_ip))
+                    });
+                    if !allowed {
+                        return Err(specialize(
+                            error_unauthorized(),
+                            String::from("the token cannot be used from this IP address"),
+                        ));
+                    }
+                }
+                if now - row.last_used >= Duration::seconds(TOKEN_LAST_USED_RESOLUTION) {
+                    sqlx::query!("UPDATE RegistryUserToken SET lastUsed = $2 WHERE id = $1", row.id, now)
+                        .execute(&mut *self.transaction.borrow().await)
+                        .await?;
+                }
+                let crates = self.get_token_scope(row.id).await?;
                 return Ok(AuthenticatedUser {
                     uid: row.uid,
                     principal: row.email,
                     can_write: row.can_write,
                     can_admin: row.can_admin,
+                    token_id: Some(row.id),
+                    crates,
                 });
             }
         }
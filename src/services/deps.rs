@@ -13,6 +13,7 @@ use std::time::{Duration, Instant};
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
+use chrono::Local;
 use futures::lock::Mutex;
 use log::{error, info};
 use sqlx::{Pool, Sqlite};
@@ -22,11 +23,16 @@ use tokio::io::AsyncBufReadExt;
 use super::rustsec::{RustSecChecker, RustSecData};
 use crate::model::cargo::{IndexCrateDependency, IndexCrateMetadata};
 use crate::model::config::{Configuration, ExternalRegistryProtocol};
-use crate::model::deps::{DepAdvisory, DepsAnalysis, DepsGraph, DepsGraphCrateOrigin, BUILTIN_CRATES_REGISTRY_URI};
+use crate::model::deps::{
+    DepAdvisory, DepsAnalysis, DepsGraph, DepsGraphCrateOrigin, DepsGraphExport, BUILTIN_CRATES_REGISTRY_URI,
+    CRATES_IO_REGISTRY_URI,
+};
+use crate::model::packages::CrateVersionBuild;
 use crate::model::JobCrate;
 use crate::services::database::Database;
 use crate::services::emails::EmailSender;
 use crate::services::index::Index;
+use crate::services::workers::WorkerState;
 use crate::utils::apierror::{error_backend_failure, error_not_found, specialize, ApiError};
 use crate::utils::db::in_transaction;
 use crate::utils::stale_instant;
@@ -38,6 +44,7 @@ pub fn create_deps_worker(
     rustsec_data: Arc<Mutex<RustSecData>>,
     index: Arc<Mutex<Index>>,
     pool: Pool<Sqlite>,
+    state: Arc<WorkerState>,
 ) {
     let _handle = tokio::spawn({
         let configuration = configuration.clone();
@@ -68,7 +75,15 @@ pub fn create_deps_worker(
         let mut interval = tokio::time::interval(Duration::from_secs(60));
         loop {
             let _instant = interval.tick().await;
-            if let Err(e) = deps_worker_job(&configuration, deps_data.clone(), rustsec_data.clone(), index.clone(), &pool).await
+            if let Err(e) = deps_worker_job(
+                &configuration,
+                deps_data.clone(),
+                rustsec_data.clone(),
+                index.clone(),
+                &pool,
+                &state,
+            )
+            .await
             {
                 error!("{e}");
                 if let Some(backtrace) = &e.backtrace {
@@ -86,6 +101,7 @@ async fn deps_worker_job(
     rustsec_data: Arc<Mutex<RustSecData>>,
     index: Arc<Mutex<Index>>,
     pool: &Pool<Sqlite>,
+    state: &WorkerState,
 ) -> Result<(), ApiError> {
     if configuration.deps_stale_analysis <= 0 {
         // deactivated
@@ -109,12 +125,44 @@ async fn deps_worker_job(
             configuration,
         },
     };
+    state.on_queued(jobs.len());
     for job in jobs {
-        deps_worker_job_on_crate_version(configuration, &checker, pool, &job).await?;
+        state.on_started();
+        let result = deps_worker_job_on_crate_version(configuration, &checker, pool, &job).await;
+        state.on_finished(result.is_ok());
+        if result.is_err() {
+            // the remaining jobs are fetched again on the next round
+            state.on_queue_dropped();
+        }
+        result?;
     }
     Ok(())
 }
 
+/// Checks and records whether a crate version builds on each of its targets
+async fn deps_worker_job_check_builds(checker: &DepsChecker<'_>, pool: &Pool<Sqlite>, job: &JobCrate) -> Result<(), ApiError> {
+    let builds = checker.check_crate_targets(&job.name, &job.version, &job.targets).await?;
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.set_crate_version_builds(&job.name, &job.version, &builds).await
+    })
+    .await
+}
+
+/// Gets the targets to use for checking the dependencies of a crate version
+async fn deps_worker_job_get_targets(pool: &Pool<Sqlite>, job: &JobCrate) -> Result<Vec<String>, ApiError> {
+    let mut connection = pool.acquire().await?;
+    let name = &job.name;
+    let targets = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.get_crate_deps_targets(name).await
+    })
+    .await?;
+    Ok(targets.unwrap_or_else(|| job.targets.clone()))
+}
+
+#[allow(clippy::too_many_lines)]
 async fn deps_worker_job_on_crate_version(
     configuration: &Configuration,
     checker: &DepsChecker<'_>,
@@ -122,7 +170,11 @@ async fn deps_worker_job_on_crate_version(
     job: &JobCrate,
 ) -> Result<(), ApiError> {
     info!("checking deps for {} {}", job.name, job.version);
-    let analysis = checker.check_crate(&job.name, &job.version, &job.targets).await?;
+    if let Err(e) = deps_worker_job_check_builds(checker, pool, job).await {
+        error!("failed to check the builds of {} {}: {e}", job.name, job.version);
+    }
+    let targets = deps_worker_job_get_targets(pool, job).await?;
+    let analysis = checker.check_crate(&job.name, &job.version, &targets).await?;
     let has_outdated = analysis.direct_dependencies.iter().any(|info| info.is_outdated);
     let has_cves = !analysis.advisories.is_empty();
     let (old_has_outdated, old_has_cves) = {
@@ -143,11 +195,14 @@ async fn deps_worker_job_on_crate_version(
             let mut connection = pool.acquire().await?;
             in_transaction(&mut connection, |transaction| async move {
                 let database = Database::new(transaction);
-                database.get_crate_owners(&job.name).await
+                database.get_crate_owners_notified(&job.name).await
             })
             .await?
         };
-        let owners = owners.users.into_iter().map(|owner| owner.email).collect::<Vec<_>>();
+        if owners.is_empty() {
+            // no owner with a verified email
+            return Ok(());
+        }
         if has_outdated != old_has_outdated {
             // new outdated dependencies ...
             let mut body = String::new();
@@ -224,6 +279,19 @@ async fn deps_worker_job_on_crate_version(
 pub struct DepsCheckerData {
     /// The last time a piece of data was touched
     last_touch: HashMap<String, Instant>,
+    /// The cached exports of dependency graphs, by crate and version
+    graph_exports: HashMap<String, CachedGraphExport>,
+}
+
+/// An exported dependency graph kept in the cache
+#[derive(Debug, Clone)]
+struct CachedGraphExport {
+    /// The targets for which the dependencies were resolved
+    targets: Vec<String>,
+    /// The time at which the graph was exported
+    created: Instant,
+    /// The exported graph
+    graph: DepsGraphExport,
 }
 
 /// Service to check the dependencies of a crate
@@ -238,10 +306,10 @@ pub struct DepsChecker<'a> {
     pub rustsec: RustSecChecker<'a>,
 }
 
-/// The URI identifying crates.io as the registry for a dependency
-const CRATES_IO_REGISTRY_URI: &str = "https://github.com/rust-lang/crates.io-index";
 /// The prefixes URI for the index for dependencies on crates.io
 const _CRATES_IO_INDEX_SPARSE_URI: &str = "https://index.crates.io/";
+/// The maximum number of exported dependency graphs kept in the cache
+const GRAPH_EXPORTS_CACHE_SIZE: usize = 256;
 /// Registry name for crates.io
 const CRATES_IO_NAME: &str = "crates.io";
 /// Name of the sub-directory to use within the data directory
@@ -257,13 +325,90 @@ impl<'a> DepsChecker<'a> {
 
     /// Checks the dependencies of a local crate
     pub async fn check_crate(&self, package: &str, version: &str, targets: &[String]) -> Result<DepsAnalysis, ApiError> {
+        let (_graph, analysis) = self.check_crate_graph(package, version, targets).await?;
+        Ok(analysis)
+    }
+
+    /// Checks the dependencies of a local crate, also returning the resolved dependency graph
+    pub async fn check_crate_graph(
+        &self,
+        package: &str,
+        version: &str,
+        targets: &[String],
+    ) -> Result<(DepsGraph, DepsAnalysis), ApiError> {
         let metadata = self.index.lock().await.get_crate_data(package).await?;
         let metadata = metadata
             .iter()
             .find(|meta| meta.vers == version)
             .ok_or_else(error_not_found)?;
+        self.check_dependencies(&metadata.deps, targets).await
+    }
 
+    /// Resolves the dependency graph of a local crate, without checking the advisories
+    pub async fn get_crate_graph(&self, package: &str, version: &str, targets: &[String]) -> Result<DepsGraph, ApiError> {
+        let metadata = self.index.lock().await.get_crate_data(package).await?;
+        let metadata = metadata
+            .iter()
+            .find(|meta| meta.vers == version)
+            .ok_or_else(error_not_found)?;
+        self.get_dependencies_closure(&metadata.deps, targets).await
+    }
+
+    /// Gets the complete dependency graph of a local crate version, as nodes and edges
+    /// Exported graphs are cached by crate and version until the data about the external registries is deemed stale
+    pub async fn export_crate_graph(
+        &self,
+        package: &str,
+        version: &str,
+        targets: &[String],
+    ) -> Result<DepsGraphExport, ApiError> {
+        let key = format!("{package}@{version}");
+        let stale = Duration::from_millis(self.configuration.deps_stale_registry);
+        if let Some(cached) = self.data.lock().await.graph_exports.get(&key) {
+            if cached.targets == targets && cached.created.elapsed() < stale {
+                return Ok(cached.graph.clone());
+            }
+        }
+        let metadata = self.index.lock().await.get_crate_data(package).await?;
+        let metadata = metadata
+            .iter()
+            .find(|meta| meta.vers == version)
+            .ok_or_else(error_not_found)?;
         let graph = self.get_dependencies_closure(&metadata.deps, targets).await?;
+        let export = graph.export(package, version, &metadata.deps);
+
+        let mut data = self.data.lock().await;
+        if data.graph_exports.len() >= GRAPH_EXPORTS_CACHE_SIZE && !data.graph_exports.contains_key(&key) {
+            data.graph_exports.retain(|_, cached| cached.created.elapsed() < stale);
+            if data.graph_exports.len() >= GRAPH_EXPORTS_CACHE_SIZE {
+                let oldest = data
+                    .graph_exports
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.created)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    data.graph_exports.remove(&oldest);
+                }
+            }
+        }
+        data.graph_exports.insert(
+            key,
+            CachedGraphExport {
+                targets: targets.to_vec(),
+                created: Instant::now(),
+                graph: export.clone(),
+            },
+        );
+        Ok(export)
+    }
+
+    /// Checks dependencies, for example those of a crate that is not yet published, also returning the resolved dependency graph
+    pub async fn check_dependencies(
+        &self,
+        deps: &[IndexCrateDependency],
+        targets: &[String],
+    ) -> Result<(DepsGraph, DepsAnalysis), ApiError> {
+        let graph = self.get_dependencies_closure(deps, targets).await?;
         let mut advisories = Vec::new();
         for dep in &graph.crates {
             for resolution in &dep.resolutions {
@@ -283,7 +428,42 @@ impl<'a> DepsChecker<'a> {
                 }
             }
         }
-        Ok(DepsAnalysis::new(&graph, &metadata.deps, advisories))
+        let analysis = DepsAnalysis::new(&graph, deps, advisories);
+        Ok((graph, analysis))
+    }
+
+    /// Verifies that the dependencies of a local crate can be resolved, for each target independently
+    /// When no target is specified, the host is used
+    pub async fn check_crate_targets(
+        &self,
+        package: &str,
+        version: &str,
+        targets: &[String],
+    ) -> Result<Vec<CrateVersionBuild>, ApiError> {
+        let metadata = self.index.lock().await.get_crate_data(package).await?;
+        let metadata = metadata
+            .iter()
+            .find(|meta| meta.vers == version)
+            .ok_or_else(error_not_found)?;
+
+        let targets = if targets.is_empty() {
+            vec![self.configuration.self_toolchain_host.clone()]
+        } else {
+            targets.to_vec()
+        };
+        let mut results = Vec::with_capacity(targets.len());
+        for target in targets {
+            let result = self
+                .get_dependencies_closure(&metadata.deps, std::slice::from_ref(&target))
+                .await;
+            results.push(CrateVersionBuild {
+                target,
+                success: result.is_ok(),
+                message: result.err().map(|e| e.to_string()).unwrap_or_default(),
+                last_check: Local::now().naive_local(),
+            });
+        }
+        Ok(results)
     }
 
     /// Gets the transitive closure of dependencies
@@ -292,19 +472,20 @@ impl<'a> DepsChecker<'a> {
         directs: &[IndexCrateDependency],
         targets: &[String],
     ) -> Result<DepsGraph, ApiError> {
-        let mut graph = if targets.is_empty() {
+        let graph = if targets.is_empty() {
             // use the host as default target
             DepsGraph::new(&[self.configuration.self_toolchain_host.clone()])
         } else {
             DepsGraph::new(targets)
         };
+        let mut graph = graph.with_limits(self.configuration.deps_max_depth, self.configuration.deps_max_nodes);
         let get_versions = |registry: Option<String>, name: String| async move {
             self.get_dependency_versions(registry.as_deref(), &name).await
         };
         for direct in directs {
             if direct.is_active_for(targets, &[]) {
                 graph
-                    .resolve(direct, &[], &[DepsGraphCrateOrigin::Direct(direct.kind)], &get_versions)
+                    .resolve(direct, &[], &[DepsGraphCrateOrigin::Direct(direct.kind)], 1, &get_versions)
                     .await?;
             }
         }
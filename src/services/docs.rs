@@ -9,72 +9,312 @@ use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 
+use chrono::Local;
 use flate2::bufread::GzDecoder;
 use futures::channel::mpsc::UnboundedSender;
 use futures::StreamExt;
-use log::{error, info};
+use log::{error, info, warn};
 use sqlx::{Pool, Sqlite};
 use tar::Archive;
 use tokio::process::Command;
 
 use crate::model::config::Configuration;
+use crate::model::packages::{CrateDocsBuildSettings, CrateVersionDocs, DocsGenerationStatus, DOCS_VARIANT_FOLDER_PREFIX};
+use crate::model::workers::ToolchainStatus;
 use crate::model::JobCrate;
 use crate::services::database::Database;
 use crate::services::storage;
+use crate::services::workers::WorkerState;
 use crate::utils::apierror::{error_backend_failure, specialize, ApiError};
 use crate::utils::concurrent::n_at_a_time;
 use crate::utils::db::in_transaction;
 
 /// Creates a worker for the generation of documentation
-pub fn create_docs_worker(configuration: Arc<Configuration>, pool: Pool<Sqlite>) -> UnboundedSender<JobCrate> {
-    let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+/// The worker reports its activity in `state`, the senders of jobs must record them as queued
+/// When the toolchain is not ready, jobs are skipped and left for a later launch
+pub fn create_docs_worker(
+    configuration: Arc<Configuration>,
+    pool: Pool<Sqlite>,
+    state: Arc<WorkerState>,
+    toolchain: &ToolchainStatus,
+) -> UnboundedSender<JobCrate> {
+    let (sender, mut receiver) = futures::channel::mpsc::unbounded::<JobCrate>();
+    let toolchain_ready = !toolchain.checked || toolchain.ready;
+    let requeue_sender = sender.clone();
     let _handle = tokio::spawn(async move {
         while let Some(job) = receiver.next().await {
-            if let Err(e) = docs_worker_job(configuration.clone(), &pool, job).await {
+            if !toolchain_ready {
+                info!("skipping doc for {} {}: toolchain missing", job.name, job.version);
+                state.on_skipped();
+                continue;
+            }
+            state.on_started();
+            let (name, version) = (job.name.clone(), job.version.clone());
+            let result = docs_worker_job(configuration.clone(), &pool, job).await;
+            state.on_finished(result.is_ok());
+            if let Err(e) = result {
                 error!("{e}");
                 if let Some(backtrace) = &e.backtrace {
                     error!("{backtrace}");
                 }
+                // the generation will be attempted again at the next launch
+                if let Err(e) = set_docs_status(&pool, &name, &version, DocsGenerationStatus::Failed).await {
+                    error!("{e}");
+                }
+            }
+            // the queue is drained, pick up the jobs that were deferred
+            if state.take_deferred() > 0 {
+                if let Err(e) = queue_undocumented_crates(&configuration, &pool, &state, &requeue_sender).await {
+                    error!("{e}");
+                }
             }
         }
     });
     sender
 }
 
+/// Queues the jobs for all the crate versions that still need their documentation, within the capacity of the queue
+pub async fn queue_undocumented_crates(
+    configuration: &Configuration,
+    pool: &Pool<Sqlite>,
+    state: &WorkerState,
+    sender: &UnboundedSender<JobCrate>,
+) -> Result<(), ApiError> {
+    let jobs = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database.get_undocumented_crates().await
+        })
+        .await?
+    };
+    for job in jobs {
+        send_docs_job(configuration, state, sender, job);
+    }
+    Ok(())
+}
+
+/// Sends a job to the worker for the generation of documentation
+/// This never fails: when the queue is at capacity or the job cannot be sent, the crate version is left undocumented
+/// and the job is deferred until the queue is drained, or the next launch.
+pub fn send_docs_job(configuration: &Configuration, state: &WorkerState, sender: &UnboundedSender<JobCrate>, job: JobCrate) {
+    if !state.on_queued_bounded(configuration.docs_queue_capacity) {
+        info!("deferring doc for {} {}: queue is full", job.name, job.version);
+        return;
+    }
+    let (name, version) = (job.name.clone(), job.version.clone());
+    if let Err(e) = sender.unbounded_send(job) {
+        error!("deferring doc for {name} {version}: {e}");
+        state.on_queue_failed();
+    }
+}
+
+/// Checks that the toolchain required for the generation of documentation is available
+/// The generation requires `cargo` and `rustdoc` on a nightly channel, as well as the host target.
+pub async fn check_docs_toolchain(configuration: &Configuration) -> ToolchainStatus {
+    if !configuration.docs_check_toolchain {
+        return ToolchainStatus::default();
+    }
+    let mut problems = Vec::new();
+    let cargo = get_tool_version("cargo", &["--version"], &mut problems).await;
+    // unstable options are only accepted on nightly
+    let rustdoc = get_tool_version("rustdoc", &["-Zunstable-options", "--version"], &mut problems).await;
+    if !configuration
+        .self_builtin_targets
+        .contains(&configuration.self_toolchain_host)
+    {
+        problems.push(format!(
+            "the host target {} is not available",
+            configuration.self_toolchain_host
+        ));
+    }
+    for problem in &problems {
+        warn!("documentation will not be generated: {problem}");
+    }
+    ToolchainStatus {
+        checked: true,
+        ready: problems.is_empty(),
+        cargo,
+        rustdoc,
+        problems,
+    }
+}
+
+/// Gets the version of a tool, recording a problem when it is not available
+async fn get_tool_version(tool: &str, args: &[&str], problems: &mut Vec<String>) -> Option<String> {
+    match Command::new(tool).args(args).stdin(Stdio::null()).output().await {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        Ok(output) => {
+            problems.push(format!(
+                "{tool} failed, a nightly toolchain may be required: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            None
+        }
+        Err(e) => {
+            problems.push(format!("{tool} is not available: {e}"));
+            None
+        }
+    }
+}
+
+/// Sets the status of the generation of the documentation for a crate version
+async fn set_docs_status(
+    pool: &Pool<Sqlite>,
+    package: &str,
+    version: &str,
+    status: DocsGenerationStatus,
+) -> Result<(), ApiError> {
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.set_crate_docs_status(package, version, status).await
+    })
+    .await
+}
+
 /// Executes a documentation generation job
 async fn docs_worker_job(configuration: Arc<Configuration>, pool: &Pool<Sqlite>, job: JobCrate) -> Result<(), ApiError> {
+    let (docs_enabled, docs_external_url, settings) = {
+        let name = &job.name;
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            let docs_enabled = database.get_crate_docs_enabled(name).await?;
+            let docs_external_url = database.get_crate_docs_external_url(name).await?;
+            let settings = database.get_crate_docs_build_settings(name).await?;
+            Ok::<_, ApiError>((docs_enabled, docs_external_url, settings))
+        })
+        .await?
+    };
+    if !docs_enabled || docs_external_url.is_some() {
+        let reason = if docs_enabled { "hosted externally" } else { "disabled" };
+        info!("skipping doc for {} {}: {reason}", job.name, job.version);
+        let mut connection = pool.acquire().await?;
+        return in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database.set_crate_documentation(&job.name, &job.version, false).await?;
+            database
+                .set_crate_docs_status(&job.name, &job.version, DocsGenerationStatus::Disabled)
+                .await
+        })
+        .await;
+    }
     info!("generating doc for {} {}", job.name, job.version);
-    let backend_storage = storage::Storage::from(&configuration.deref().clone());
+    set_docs_status(pool, &job.name, &job.version, DocsGenerationStatus::Building).await?;
+    let backend_storage = storage::get_storage(&configuration);
 
     let content = backend_storage.download_crate(&job.name, &job.version).await?;
 
     let temp_folder = extract_content(&job.name, &job.version, &content)?;
-    let gen_is_ok = match generate_doc(&configuration, &temp_folder).await {
-        Ok(mut project_folder) => {
-            project_folder.push("target");
-            project_folder.push("doc");
-            let doc_folder = project_folder;
-            upload_package(configuration, &job.name, &job.version, &doc_folder).await?;
-            true
-        }
-        Err(e) => {
-            // upload the log
-            let log = e.details.unwrap();
-            let path = format!("{}/{}/log.txt", job.name, job.version);
-            backend_storage.store_doc_data(&path, log.into_bytes()).await?;
-            false
+    let builds = get_docs_builds(&job.targets, &settings);
+    let results = n_at_a_time(
+        builds.into_iter().map(|build| {
+            let configuration = configuration.clone();
+            let temp_folder = temp_folder.clone();
+            let name = job.name.clone();
+            let version = job.version.clone();
+            Box::pin(async move {
+                let result = generate_and_upload_doc(configuration, &temp_folder, &name, &version, &build).await;
+                (build, result)
+            })
+        }),
+        configuration.docs_gen_concurrency,
+        |_| false,
+    )
+    .await;
+    let now = Local::now().naive_local();
+    let mut has_docs = false;
+    let mut docs = Vec::with_capacity(results.len());
+    for (build, result) in results {
+        let target = build.target.unwrap_or_else(|| configuration.self_toolchain_host.clone());
+        let success = result.is_ok();
+        // keep the log of the last generation for this target and variant
+        let log = result.unwrap_or_else(|e| e.details.clone().unwrap_or_else(|| e.to_string()));
+        backend_storage
+            .store_doc_log(&job.name, &job.version, &target, build.variant.as_deref(), log.into_bytes())
+            .await?;
+        if build.is_default {
+            has_docs = success;
         }
-    };
+        docs.push(CrateVersionDocs {
+            target,
+            variant: build.variant,
+            success,
+            last_update: now,
+        });
+    }
     let mut connection = pool.acquire().await?;
     in_transaction(&mut connection, |transaction| async move {
         let database = Database::new(transaction);
-        database.set_crate_documentation(&job.name, &job.version, gen_is_ok).await
+        database.set_crate_documentation(&job.name, &job.version, has_docs).await?;
+        database.set_crate_version_docs(&job.name, &job.version, &docs).await
     })
     .await?;
     tokio::fs::remove_dir_all(&temp_folder).await?;
     Ok(())
 }
 
+/// A build of the documentation of a crate version
+struct DocsBuild {
+    /// The target, `None` for the host
+    target: Option<String>,
+    /// The variant, `None` for the main documentation
+    variant: Option<String>,
+    /// Whether this is the main documentation for the default target
+    is_default: bool,
+    /// The settings for this build
+    settings: CrateDocsBuildSettings,
+}
+
+/// Gets the builds of the documentation for a crate version
+/// The first target is the default one, or the host when none is specified, the variants are only built for it
+fn get_docs_builds(targets: &[String], settings: &CrateDocsBuildSettings) -> Vec<DocsBuild> {
+    let default_target = targets.first().cloned();
+    let targets = if targets.is_empty() {
+        vec![None]
+    } else {
+        targets.iter().cloned().map(Some).collect::<Vec<_>>()
+    };
+    let mut builds = targets
+        .into_iter()
+        .enumerate()
+        .map(|(index, target)| DocsBuild {
+            target,
+            variant: None,
+            is_default: index == 0,
+            settings: settings.clone(),
+        })
+        .collect::<Vec<_>>();
+    builds.extend(settings.variants.iter().map(|variant| DocsBuild {
+        target: default_target.clone(),
+        variant: Some(variant.name.clone()),
+        is_default: false,
+        settings: settings.for_variant(variant),
+    }));
+    builds
+}
+
+/// Generates and uploads the documentation of a crate for a build, returning the output of the generation
+/// The documentation for the default target is served at the root of the version, the others under a sub-folder named after the target.
+/// The variants are served under a sub-folder named after them.
+async fn generate_and_upload_doc(
+    configuration: Arc<Configuration>,
+    temp_folder: &Path,
+    name: &str,
+    version: &str,
+    build: &DocsBuild,
+) -> Result<String, ApiError> {
+    let (doc_folder, log) = generate_doc(&configuration, temp_folder, build).await?;
+    let prefix = match (&build.variant, &build.target) {
+        (Some(variant), _) => format!("{name}/{version}/{DOCS_VARIANT_FOLDER_PREFIX}{variant}"),
+        (None, Some(target)) if !build.is_default => format!("{name}/{version}/{target}"),
+        _ => format!("{name}/{version}"),
+    };
+    upload_package(configuration, &prefix, &doc_folder).await?;
+    Ok(log)
+}
+
 /// Generates and upload the documentation for a crate
 fn extract_content(name: &str, version: &str, content: &[u8]) -> Result<PathBuf, ApiError> {
     let decoder = GzDecoder::new(content);
@@ -84,8 +324,15 @@ fn extract_content(name: &str, version: &str, content: &[u8]) -> Result<PathBuf,
     Ok(PathBuf::from(target))
 }
 
-/// Generate the documentation for the package in a specific folder
-async fn generate_doc(configuration: &Configuration, temp_folder: &Path) -> Result<PathBuf, ApiError> {
+/// Generate the documentation for the package in a specific folder, for a build
+/// Each target and variant uses its own build directory so that they can be generated concurrently
+/// Returns the folder containing the generated documentation and the output of the generation
+async fn generate_doc(
+    configuration: &Configuration,
+    temp_folder: &Path,
+    build: &DocsBuild,
+) -> Result<(PathBuf, String), ApiError> {
+    let settings = &build.settings;
     let mut path: PathBuf = temp_folder.to_path_buf();
     // get the first sub dir
     let mut dir = tokio::fs::read_dir(&path).await?;
@@ -97,10 +344,26 @@ async fn generate_doc(configuration: &Configuration, temp_folder: &Path) -> Resu
         .current_dir(&path)
         .arg("rustdoc")
         .arg("-Zunstable-options")
-        .arg("-Zrustdoc-map")
-        .arg("--all-features")
+        .arg("-Zrustdoc-map");
+    match &settings.features {
+        None => {
+            command.arg("--all-features");
+        }
+        Some(features) if !features.is_empty() => {
+            command.arg("--features").arg(features.join(","));
+        }
+        Some(_) => {}
+    }
+    if settings.no_default_features {
+        command.arg("--no-default-features");
+    }
+    let rustdoc_flags = ["-Zunstable-options", "--extern-html-root-takes-precedence"]
+        .into_iter()
+        .chain(settings.rustdoc_flags.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    command
         .arg("--config")
-        .arg("build.rustdocflags=[\"-Zunstable-options\",\"--extern-html-root-takes-precedence\"]")
+        .arg(format!("build.rustdocflags={}", serde_json::to_string(&rustdoc_flags)?))
         .arg("--config")
         .arg(format!(
             "doc.extern-map.registries.local=\"{}/docs\"",
@@ -112,6 +375,20 @@ async fn generate_doc(configuration: &Configuration, temp_folder: &Path) -> Resu
             external.name, external.docs_root
         ));
     }
+    let mut target_dir = path.join("target");
+    if let Some(variant) = &build.variant {
+        target_dir.push(format!("{DOCS_VARIANT_FOLDER_PREFIX}{variant}"));
+    }
+    if let Some(target) = &build.target {
+        target_dir.push(target);
+        command.arg("--target").arg(target);
+    }
+    command.arg("--target-dir").arg(&target_dir);
+    let mut doc_folder = target_dir;
+    if let Some(target) = &build.target {
+        doc_folder.push(target);
+    }
+    doc_folder.push("doc");
     let mut child = command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
@@ -120,27 +397,22 @@ async fn generate_doc(configuration: &Configuration, temp_folder: &Path) -> Resu
     drop(child.stdin.take()); // close stdin
     let output = child.wait_with_output().await?;
 
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let log = format!("-- stdout\n{stdout}\n\n-- stderr\n{stderr}");
     if !output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let error = format!("-- stdout\n{stdout}\n\n-- stderr\n{stderr}");
-        return Err(specialize(error_backend_failure(), error));
+        return Err(specialize(error_backend_failure(), log));
     }
-    Ok(path)
+    Ok((doc_folder, log))
 }
 
 /// Uploads the documentation for package
-async fn upload_package(
-    configuration: Arc<Configuration>,
-    name: &str,
-    version: &str,
-    doc_folder: &Path,
-) -> Result<(), ApiError> {
-    let files = upload_package_find_files(doc_folder, &format!("{name}/{version}")).await?;
+async fn upload_package(configuration: Arc<Configuration>, prefix: &str, doc_folder: &Path) -> Result<(), ApiError> {
+    let files = upload_package_find_files(doc_folder, prefix).await?;
     let results = n_at_a_time(
         files.into_iter().map(|(key, path)| {
             let configuration = configuration.deref().clone();
-            Box::pin(async move { storage::Storage::from(&configuration).store_doc_file(&key, &path).await })
+            Box::pin(async move { storage::get_storage(&configuration).store_doc_file(&key, &path).await })
         }),
         8,
         Result::is_err,
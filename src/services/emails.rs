@@ -4,6 +4,8 @@
 
 //! Service to send emails
 
+use std::fmt::Write;
+
 use lettre::message::header::ContentType;
 use lettre::message::{MessageBuilder, SinglePartBuilder};
 use lettre::transport::smtp::authentication::Credentials;
@@ -11,6 +13,7 @@ use lettre::transport::smtp::client::{Tls, TlsParametersBuilder};
 use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 
 use crate::model::config::Configuration;
+use crate::model::deps::AffectedCrateAdvisory;
 use crate::utils::apierror::ApiError;
 
 /// The service to send emails
@@ -44,6 +47,39 @@ impl<'a> EmailSender<'a> {
         self.send_built_message(email).await
     }
 
+    /// Sends the notification that a crate is affected by new advisories against its dependencies
+    pub async fn send_advisory_notification(
+        &self,
+        owners: &[String],
+        package: &str,
+        version: &str,
+        advisories: &[AffectedCrateAdvisory],
+    ) -> Result<(), ApiError> {
+        let mut body = String::new();
+        writeln!(
+            body,
+            "New advisories have been published against the dependencies of {package} {version}"
+        )
+        .unwrap();
+        writeln!(body, "See {}/crates/{package}/{version}", self.config.web_public_uri).unwrap();
+        writeln!(body).unwrap();
+        for advisory in advisories {
+            writeln!(
+                body,
+                "- {} resolved version {} is affected by https://rustsec.org/advisories/{}.html",
+                advisory.dependency, advisory.dependency_version, advisory.advisory
+            )
+            .unwrap();
+            writeln!(body, "  => {}", advisory.summary).unwrap();
+        }
+        self.send_email(
+            owners,
+            &format!("Cratery - new advisories against the dependencies of {package} {version}"),
+            body,
+        )
+        .await
+    }
+
     /// Generates a message id
     fn generate_msg_id(&self) -> String {
         format!("<{}@{}>", uuid::Uuid::new_v4(), self.config.web_domain)
@@ -0,0 +1,251 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to enrich crates with lightweight metadata fetched from their repository (stars, last activity)
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use log::{error, info, warn};
+use serde_derive::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::model::config::{Configuration, EnrichmentConfig};
+use crate::services::database::Database;
+use crate::services::storage::{get_storage, Storage};
+use crate::utils::apierror::{error_backend_failure, specialize, ApiError};
+use crate::utils::db::in_transaction;
+
+/// The maximum number of crates for which the repository is checked in one round, to respect the rate limits of hosts
+const ROUND_MAX_CRATES: i64 = 50;
+
+/// The user agent sent to the hosts, as required by some of them
+const USER_AGENT: &str = concat!("cratery/", env!("CARGO_PKG_VERSION"));
+
+/// Creates a worker for the periodic enrichment of crates with metadata from their repository
+pub fn create_enrich_worker(configuration: Arc<Configuration>, pool: Pool<Sqlite>) {
+    if !configuration.enrichment.enabled {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        // every hour
+        let mut interval = tokio::time::interval(Duration::from_hours(1));
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(e) = enrich_worker_job(&configuration, &pool).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// A job for the worker
+async fn enrich_worker_job(configuration: &Configuration, pool: &Pool<Sqlite>) -> Result<(), ApiError> {
+    let now = Local::now().naive_local();
+    let Some(before) =
+        chrono::Duration::try_hours(configuration.enrichment.stale_hours).and_then(|delta| now.checked_sub_signed(delta))
+    else {
+        return Ok(());
+    };
+    let mut connection = pool.acquire().await?;
+    let packages = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.get_crates_for_repository_check(before, ROUND_MAX_CRATES).await
+    })
+    .await?;
+    let storage = get_storage(configuration);
+    let client = reqwest::Client::new();
+    let mut count = 0;
+    for package in packages {
+        let fetched = match enrich_crate(&configuration.enrichment, storage.as_ref(), &client, pool, &package).await {
+            Ok(RepositoryFetch::RateLimited) => {
+                warn!("enrich: rate limited by the repository host, stopping for now");
+                break;
+            }
+            Ok(RepositoryFetch::Fetched(metadata)) => Some(metadata),
+            Ok(RepositoryFetch::Unrecognized) => None,
+            Err(e) => {
+                // the host is unreachable or replied with an error, keep the previous data
+                warn!("enrich: failed to fetch the repository metadata for {package}: {e}");
+                None
+            }
+        };
+        if fetched.is_some() {
+            count += 1;
+        }
+        let now = Local::now().naive_local();
+        let package = &package;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database
+                .set_crate_repository_info(
+                    package,
+                    fetched.as_ref().and_then(|metadata| metadata.stars),
+                    fetched.as_ref().and_then(|metadata| metadata.last_activity),
+                    now,
+                )
+                .await
+        })
+        .await?;
+    }
+    if count > 0 {
+        info!("enrich: fetched the repository metadata for {count} crates");
+    }
+    Ok(())
+}
+
+/// Fetches the metadata for the repository of a crate
+async fn enrich_crate(
+    config: &EnrichmentConfig,
+    storage: &dyn Storage,
+    client: &reqwest::Client,
+    pool: &Pool<Sqlite>,
+    package: &str,
+) -> Result<RepositoryFetch, ApiError> {
+    let mut connection = pool.acquire().await?;
+    let version = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.get_crate_last_version(package).await
+    })
+    .await?;
+    let repository = storage
+        .download_crate_metadata(package, &version)
+        .await?
+        .and_then(|metadata| metadata.repository);
+    let Some(host) = repository.as_deref().and_then(RepositoryHost::parse) else {
+        return Ok(RepositoryFetch::Unrecognized);
+    };
+    host.fetch(config, client).await
+}
+
+/// The outcome of fetching the metadata for a repository
+enum RepositoryFetch {
+    /// The metadata were fetched
+    Fetched(RepositoryMetadata),
+    /// The repository is not on a recognized host
+    Unrecognized,
+    /// The host rejected the request because of its rate limit
+    RateLimited,
+}
+
+/// The metadata for a repository
+struct RepositoryMetadata {
+    /// The number of stars
+    stars: Option<i64>,
+    /// The last activity on the repository
+    last_activity: Option<NaiveDateTime>,
+}
+
+/// A repository on a recognized host
+enum RepositoryHost {
+    /// A repository on github.com, with the owner and name
+    GitHub(String, String),
+    /// A project on gitlab.com, with its full path
+    GitLab(String),
+}
+
+/// The data for a repository in the GitHub API
+#[derive(Deserialize)]
+struct GitHubRepository {
+    stargazers_count: Option<i64>,
+    pushed_at: Option<String>,
+}
+
+/// The data for a project in the GitLab API
+#[derive(Deserialize)]
+struct GitLabProject {
+    star_count: Option<i64>,
+    last_activity_at: Option<String>,
+}
+
+impl RepositoryHost {
+    /// Recognizes the host for the URL to a repository
+    fn parse(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+        let (host, path) = rest.split_once('/')?;
+        let path = path.trim_end_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        match host.to_ascii_lowercase().as_str() {
+            "github.com" | "www.github.com" => {
+                let mut segments = path.split('/');
+                let owner = segments.next().filter(|s| !s.is_empty())?;
+                let name = segments.next().filter(|s| !s.is_empty())?;
+                Some(Self::GitHub(owner.to_string(), name.to_string()))
+            }
+            "gitlab.com" | "www.gitlab.com" => {
+                // links to a sub-page of the project are separated with /-/
+                let path = path.split("/-/").next().unwrap_or(path);
+                if path.split('/').count() < 2 {
+                    return None;
+                }
+                Some(Self::GitLab(path.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fetches the metadata for the repository
+    async fn fetch(&self, config: &EnrichmentConfig, client: &reqwest::Client) -> Result<RepositoryFetch, ApiError> {
+        let request = match self {
+            Self::GitHub(owner, name) => {
+                let request = client
+                    .get(format!("https://api.github.com/repos/{owner}/{name}"))
+                    .header("Accept", "application/vnd.github+json");
+                match &config.github_token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            Self::GitLab(path) => {
+                let request = client.get(format!("https://gitlab.com/api/v4/projects/{}", urlencoding::encode(path)));
+                match &config.gitlab_token {
+                    Some(token) => request.header("PRIVATE-TOKEN", token),
+                    None => request,
+                }
+            }
+        };
+        let response = request
+            .header("User-Agent", USER_AGENT)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        if status == 403 || status == 429 {
+            return Ok(RepositoryFetch::RateLimited);
+        }
+        if !response.status().is_success() {
+            return Err(specialize(error_backend_failure(), format!("error code {status}")));
+        }
+        let body = response.bytes().await?;
+        let metadata = match self {
+            Self::GitHub(_, _) => {
+                let data = serde_json::from_slice::<GitHubRepository>(&body)?;
+                RepositoryMetadata {
+                    stars: data.stargazers_count,
+                    last_activity: data.pushed_at.as_deref().and_then(parse_date_time),
+                }
+            }
+            Self::GitLab(_) => {
+                let data = serde_json::from_slice::<GitLabProject>(&body)?;
+                RepositoryMetadata {
+                    stars: data.star_count,
+                    last_activity: data.last_activity_at.as_deref().and_then(parse_date_time),
+                }
+            }
+        };
+        Ok(RepositoryFetch::Fetched(metadata))
+    }
+}
+
+/// Parses a RFC 3339 date time into the local time
+fn parse_date_time(input: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(input)
+        .ok()
+        .map(|date_time| date_time.with_timezone(&Local).naive_local())
+}
@@ -0,0 +1,106 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to collect the stored files of deleted crates and versions
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use log::{error, info};
+use sqlx::{Pool, Sqlite};
+
+use crate::model::config::Configuration;
+use crate::model::packages::StorageGcSummary;
+use crate::services::database::Database;
+use crate::services::index::Index;
+use crate::services::storage::get_storage;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+
+/// Creates a worker for the periodic collection of the stored files of deleted crates and versions
+pub fn create_gc_worker(configuration: Arc<Configuration>, pool: Pool<Sqlite>, index: Arc<Mutex<Index>>) {
+    if configuration.storage_gc_interval_hours == 0 {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_hours(configuration.storage_gc_interval_hours));
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(e) = collect_storage_garbage(&configuration, &pool, &index, false).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// Gets the crates and versions that were deleted and are not live again
+async fn get_deleted_crates(pool: &Pool<Sqlite>) -> Result<Vec<(String, Option<String>)>, ApiError> {
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.get_deleted_crates().await
+    })
+    .await
+}
+
+/// Gets whether a crate or a version that was deleted is still not live again
+async fn is_crate_still_deleted(pool: &Pool<Sqlite>, package: &str, version: Option<&str>) -> Result<bool, ApiError> {
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.is_crate_still_deleted(package, version).await
+    })
+    .await
+}
+
+/// Collects the stored files of the crates and versions recorded as deleted in the database
+/// Versions that are merely yanked are never collected.
+/// In a dry run, the files are only listed.
+pub async fn collect_storage_garbage(
+    configuration: &Configuration,
+    pool: &Pool<Sqlite>,
+    index: &Mutex<Index>,
+    dry_run: bool,
+) -> Result<StorageGcSummary, ApiError> {
+    let storage = get_storage(configuration);
+    let mut summary = StorageGcSummary {
+        dry_run,
+        ..Default::default()
+    };
+    for (package, version) in get_deleted_crates(pool).await? {
+        let files = storage.list_crate_files(&package, version.as_deref()).await?;
+        if files.is_empty() {
+            continue;
+        }
+        if !dry_run {
+            // cross-check that the crate or version was not published again in the meantime,
+            // the publications hold the index lock until they are committed
+            let _index = index.lock().await;
+            if !is_crate_still_deleted(pool, &package, version.as_deref()).await? {
+                continue;
+            }
+            match &version {
+                None => storage.delete_crate_files(&package).await?,
+                Some(version) => storage.delete_crate_version_files(&package, version).await?,
+            }
+        }
+        summary.count += files.len();
+        summary.size += files.iter().map(|file| file.size).sum::<u64>();
+        summary.files.extend(files);
+    }
+    if summary.count > 0 {
+        info!(
+            "storage gc: {} {} files ({} bytes) of deleted crates",
+            if dry_run { "found" } else { "collected" },
+            summary.count,
+            summary.size
+        );
+    }
+    Ok(summary)
+}
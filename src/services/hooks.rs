@@ -0,0 +1,135 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to execute the hooks configured around the publication of crates
+
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::model::cargo::CrateMetadata;
+use crate::model::config::Configuration;
+use crate::utils::apierror::{error_backend_failure, error_invalid_request, specialize, ApiError};
+
+/// The service to execute hooks
+#[derive(Debug, Clone)]
+pub struct HooksExecutor<'a> {
+    /// The configuration
+    config: &'a Configuration,
+}
+
+impl<'a> HooksExecutor<'a> {
+    /// Creates the service
+    #[must_use]
+    pub fn new(config: &'a Configuration) -> Self {
+        Self { config }
+    }
+
+    /// Executes the pre-publish hook, if any
+    /// A failure of the hook rejects the publication, using the hook's stderr as the error message
+    pub async fn run_pre_publish(&self, principal: &str, metadata: &CrateMetadata) -> Result<(), ApiError> {
+        let Some(command) = &self.config.hooks.pre_publish else {
+            return Ok(());
+        };
+        self.run_hook("pre-publish", command, principal, metadata).await
+    }
+
+    /// Executes the post-publish hook, if any
+    /// A failure of the hook is only logged because the crate is already published
+    pub async fn run_post_publish(&self, principal: &str, metadata: &CrateMetadata) {
+        let Some(command) = &self.config.hooks.post_publish else {
+            return;
+        };
+        if let Err(e) = self.run_hook("post-publish", command, principal, metadata).await {
+            error!("{e}");
+        }
+    }
+
+    /// Executes a hook within a sandboxed working directory
+    async fn run_hook(&self, hook: &str, command: &str, principal: &str, metadata: &CrateMetadata) -> Result<(), ApiError> {
+        info!("hooks: running {hook} for {} {}", metadata.name, metadata.vers);
+        let input = serde_json::to_vec(metadata)?;
+        let work_dir = std::env::temp_dir().join(format!("cratery-hook-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&work_dir).await?;
+        let result = self.run_hook_in(hook, command, principal, metadata, &input, &work_dir).await;
+        // the verdict of the hook prevails over a failure to clean up
+        if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+            error!("hooks: failed to remove {}: {e}", work_dir.display());
+        }
+        result
+    }
+
+    /// Executes a hook in a specific working directory
+    async fn run_hook_in(
+        &self,
+        hook: &str,
+        command: &str,
+        principal: &str,
+        metadata: &CrateMetadata,
+        input: &[u8],
+        work_dir: &Path,
+    ) -> Result<(), ApiError> {
+        let mut child = Command::new(command)
+            .current_dir(work_dir)
+            .env_clear()
+            .env("PATH", std::env::var("PATH").unwrap_or_default())
+            .env("HOME", work_dir)
+            .env("CRATERY_HOOK", hook)
+            .env("CRATERY_CRATE_NAME", &metadata.name)
+            .env("CRATERY_CRATE_VERSION", &metadata.vers)
+            .env("CRATERY_PUBLISHER", principal)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdin = child.stdin.take().ok_or_else(|| {
+            specialize(
+                error_backend_failure(),
+                format!("failed to open the standard input of the {hook} hook"),
+            )
+        })?;
+        // the input is written while the output is read, both within the timeout,
+        // so that a hook that does not read its input cannot block the publication
+        let write_input = async move {
+            let result = stdin.write_all(input).await;
+            drop(stdin); // close stdin
+            result
+        };
+        let execution = async move { tokio::join!(write_input, child.wait_with_output()) };
+        let Ok((written, output)) = tokio::time::timeout(Duration::from_millis(self.config.hooks.timeout), execution).await
+        else {
+            return Err(specialize(
+                error_backend_failure(),
+                format!("the {hook} hook did not complete in time"),
+            ));
+        };
+        // a hook may exit without reading all its input
+        if let Err(e) = written {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(e.into());
+            }
+        }
+        let output = output?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim();
+            Err(specialize(
+                error_invalid_request(),
+                if stderr.is_empty() {
+                    format!("the {hook} hook rejected the crate")
+                } else {
+                    stderr.to_string()
+                },
+            ))
+        }
+    }
+}
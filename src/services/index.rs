@@ -164,6 +164,88 @@ impl Index {
         Ok(())
     }
 
+    /// Replaces the metadata for a version already in the index, when it is published again
+    pub async fn replace_crate_version(&self, metadata: &IndexCrateMetadata) -> Result<(), ApiError> {
+        let mut versions = self.get_crate_data(&metadata.name).await?;
+        let Some(existing) = versions.iter_mut().find(|version| version.vers == metadata.vers) else {
+            return self.publish_crate_version(metadata).await;
+        };
+        *existing = metadata.clone();
+        self.rewrite_crate(&metadata.name, &versions).await?;
+        self.commit_changes(&format!("Republish {}:{}", &metadata.name, &metadata.vers))
+            .await
+    }
+
+    /// Removes a crate from the index, so that cargo no longer sees it
+    pub async fn remove_crate(&self, package: &str) -> Result<(), ApiError> {
+        let file_name = build_package_file_path(PathBuf::from(&self.config.location), package);
+        if !file_name.exists() {
+            return Err(specialize(
+                error_not_found(),
+                format!("package {package} is not in this registry"),
+            ));
+        }
+        tokio::fs::remove_file(&file_name).await?;
+        // commit and update
+        let location = PathBuf::from(&self.config.location);
+        let message = format!("Remove {package}");
+        execute_git(&location, &["add", "--all", "."]).await?;
+        execute_git(&location, &["commit", "-m", &message]).await?;
+        execute_git(&location, &["update-server-info"]).await?;
+        if let (Some(_), true) = (self.config.remote_origin.as_ref(), self.config.remote_push_changes) {
+            execute_git(&location, &["push", "origin", "master"]).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes a version of a crate from the index, so that cargo no longer sees it
+    /// The crate itself is removed when this is its only version
+    pub async fn remove_crate_version(&self, package: &str, version: &str) -> Result<(), ApiError> {
+        let mut versions = self.get_crate_data(package).await?;
+        let count = versions.len();
+        versions.retain(|metadata| metadata.vers != version);
+        if versions.len() == count {
+            return Err(specialize(
+                error_not_found(),
+                format!("version {version} of package {package} is not in this registry"),
+            ));
+        }
+        if versions.is_empty() {
+            return self.remove_crate(package).await;
+        }
+        self.rewrite_crate(package, &versions).await?;
+        self.commit_changes(&format!("Remove {package}:{version}")).await
+    }
+
+    /// Rewrites the file for a crate in the index with the specified versions
+    /// The changes are only committed with `commit_changes`
+    pub async fn rewrite_crate(&self, package: &str, versions: &[IndexCrateMetadata]) -> Result<(), ApiError> {
+        let file_name = build_package_file_path(PathBuf::from(&self.config.location), package);
+        create_dir_all(file_name.parent().unwrap()).await?;
+        let mut buffer = Vec::new();
+        for metadata in versions {
+            serde_json::to_writer(&mut buffer, metadata)?;
+            buffer.push(0x0A); // add line end
+        }
+        let mut file = File::create(file_name).await?;
+        file.write_all(&buffer).await?;
+        file.flush().await?;
+        file.sync_all().await?;
+        Ok(())
+    }
+
+    /// Commits all the pending changes to the index
+    pub async fn commit_changes(&self, message: &str) -> Result<(), ApiError> {
+        let location = PathBuf::from(&self.config.location);
+        execute_git(&location, &["add", "--all", "."]).await?;
+        execute_git(&location, &["commit", "--allow-empty", "-m", message]).await?;
+        execute_git(&location, &["update-server-info"]).await?;
+        if let (Some(_), true) = (self.config.remote_origin.as_ref(), self.config.remote_push_changes) {
+            execute_git(&location, &["push", "origin", "master"]).await?;
+        }
+        Ok(())
+    }
+
     ///  Gets the data for a crate
     pub async fn get_crate_data(&self, package: &str) -> Result<Vec<IndexCrateMetadata>, ApiError> {
         let file_name = build_package_file_path(PathBuf::from(&self.config.location), package);
@@ -4,10 +4,20 @@
 
 //! Service implementations
 
+pub mod advisories;
+pub mod audit;
 pub mod database;
 pub mod deps;
 pub mod docs;
 pub mod emails;
+pub mod enrich;
+pub mod gc;
+pub mod hooks;
 pub mod index;
+pub mod notifications;
+pub mod proxy;
 pub mod rustsec;
 pub mod storage;
+pub mod webhooks;
+pub mod workers;
+pub mod yanks;
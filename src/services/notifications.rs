@@ -0,0 +1,159 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to notify the owners of crates of the publication of new versions
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+use log::{error, info};
+use sqlx::{Pool, Sqlite};
+
+use crate::model::auth::PublishDigestRecipient;
+use crate::model::config::Configuration;
+use crate::services::database::Database;
+use crate::services::emails::EmailSender;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+
+/// The number of hours between two digests of the publications for a user
+const PUBLISH_DIGEST_PERIOD_HOURS: i64 = 24;
+
+/// Sends a notification for the publication of a crate version to the owners that want to be notified immediately
+pub async fn notify_publication(
+    configuration: &Configuration,
+    pool: &Pool<Sqlite>,
+    package: &str,
+    version: &str,
+    publisher: &str,
+) -> Result<(), ApiError> {
+    if !configuration.publish_notifications {
+        return Ok(());
+    }
+    let owners = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database.get_crate_owners_publish_notified(package).await
+        })
+        .await?
+    };
+    if owners.is_empty() {
+        return Ok(());
+    }
+    let body = format!(
+        "{package} {version} has been published by {publisher}\nSee {}/crates/{package}/{version}\n",
+        configuration.web_public_uri
+    );
+    EmailSender::new(configuration)
+        .send_email(&owners, &format!("Cratery - {package} {version} published"), body)
+        .await
+}
+
+/// Creates a worker for sending the digests of the publications to the owners that chose them
+pub fn create_publish_digest_worker(configuration: Arc<Configuration>, pool: Pool<Sqlite>) {
+    if !configuration.publish_notifications {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        // every hour, the digests are sent when due
+        let mut interval = tokio::time::interval(Duration::from_hours(1));
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(e) = publish_digest_worker_job(&configuration, &pool).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// A job for the worker
+async fn publish_digest_worker_job(configuration: &Configuration, pool: &Pool<Sqlite>) -> Result<(), ApiError> {
+    let now = Local::now().naive_local();
+    let recipients = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database
+                .get_publish_digest_recipients(now - chrono::Duration::hours(PUBLISH_DIGEST_PERIOD_HOURS))
+                .await
+        })
+        .await?
+    };
+    for recipient in recipients {
+        // a failure for a recipient does not prevent the others from getting theirs
+        if let Err(e) = send_publish_digest(configuration, pool, &recipient).await {
+            error!("failed to send the digest of publications to {}: {e}", recipient.email);
+        }
+    }
+    Ok(())
+}
+
+/// Sends the digest of the publications since the last one to a user
+/// Nothing is sent when there was no publication, but the period is still closed
+async fn send_publish_digest(
+    configuration: &Configuration,
+    pool: &Pool<Sqlite>,
+    recipient: &PublishDigestRecipient,
+) -> Result<(), ApiError> {
+    let now = Local::now().naive_local();
+    let publications = {
+        let mut connection = pool.acquire().await?;
+        in_transaction(&mut connection, |transaction| async move {
+            let database = Database::new(transaction);
+            database
+                .get_crates_published_for_owner(recipient.uid, recipient.since, now)
+                .await
+        })
+        .await?
+    };
+    if !publications.is_empty() {
+        let mut body = String::new();
+        writeln!(
+            body,
+            "The following versions were published for your crates since {}",
+            recipient.since
+        )
+        .unwrap();
+        writeln!(body).unwrap();
+        for publication in &publications {
+            writeln!(
+                body,
+                "- {} {} by {} on {}: {}/crates/{}/{}",
+                publication.package,
+                publication.version,
+                publication.publisher_name.as_deref().unwrap_or("a deleted user"),
+                publication.upload,
+                configuration.web_public_uri,
+                publication.package,
+                publication.version
+            )
+            .unwrap();
+        }
+        EmailSender::new(configuration)
+            .send_email(
+                std::slice::from_ref(&recipient.email),
+                &format!("Cratery - {} new publications for your crates", publications.len()),
+                body,
+            )
+            .await?;
+        info!(
+            "notifications: sent a digest of {} publications to {}",
+            publications.len(),
+            recipient.email
+        );
+    }
+    let mut connection = pool.acquire().await?;
+    in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.set_publish_digest_sent(recipient.uid, now).await
+    })
+    .await
+}
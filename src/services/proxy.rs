@@ -0,0 +1,141 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to proxy the crates of an upstream registry, crates.io by default, that are not hosted by this registry
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{error, warn};
+use reqwest::StatusCode;
+
+use super::index::{build_package_file_path, package_file_path};
+use crate::model::cargo::IndexCrateMetadata;
+use crate::model::config::Configuration;
+use crate::utils::apierror::{error_backend_failure, error_not_found, specialize, ApiError};
+use crate::utils::hashes::sha256;
+
+/// Name of the sub-directory to use within the data directory for the cached files of the upstream index
+const DATA_SUB_DIR: &str = "proxy";
+
+/// Gets whether a name is a valid name for a crate, before looking it up upstream
+pub fn is_valid_crate_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Gets the file of the upstream sparse index for a crate, if the crate exists upstream
+/// The file is cached in the data directory and fetched again once older than the configured maximum age.
+/// When the upstream registry is unreachable, the cached file is used regardless of its age.
+pub async fn get_index_file(configuration: &Configuration, package: &str) -> Result<Option<Vec<u8>>, ApiError> {
+    let lowercase = package.to_ascii_lowercase();
+    let mut root = PathBuf::from(&configuration.data_dir);
+    root.push(DATA_SUB_DIR);
+    root.push("index");
+    let file_path = build_package_file_path(root, &lowercase);
+    let is_fresh = tokio::fs::metadata(&file_path)
+        .await
+        .ok()
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < Duration::from_secs(configuration.proxy.index_max_age));
+    if is_fresh {
+        return Ok(Some(tokio::fs::read(&file_path).await?));
+    }
+
+    let (first, second) = package_file_path(&lowercase);
+    let target_uri = match second {
+        Some(second) => format!("{}/{first}/{second}/{lowercase}", configuration.proxy.upstream_index),
+        None => format!("{}/{first}/{lowercase}", configuration.proxy.upstream_index),
+    };
+    match fetch(configuration, &target_uri).await {
+        Ok(Some(content)) => {
+            if let Some(parent) = file_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&file_path, &content).await?;
+            Ok(Some(content))
+        }
+        Ok(None) => {
+            if tokio::fs::try_exists(&file_path).await? {
+                tokio::fs::remove_file(&file_path).await?;
+            }
+            Ok(None)
+        }
+        Err(e) => match tokio::fs::read(&file_path).await {
+            Ok(content) => {
+                warn!("proxy: failed to refresh the index for {lowercase}, using the cached one: {e}");
+                Ok(Some(content))
+            }
+            Err(_) => Err(e),
+        },
+    }
+}
+
+/// Gets the metadata of a crate version in the upstream index
+pub async fn get_crate_metadata(
+    configuration: &Configuration,
+    package: &str,
+    version: &str,
+) -> Result<IndexCrateMetadata, ApiError> {
+    let content = get_index_file(configuration, package).await?.ok_or_else(error_not_found)?;
+    for line in content.split(|&c| c == b'\n').filter(|line| !line.is_empty()) {
+        let metadata: IndexCrateMetadata = serde_json::from_slice(line)?;
+        if metadata.vers == version {
+            return Ok(metadata);
+        }
+    }
+    Err(error_not_found())
+}
+
+/// Fetches the content of a crate version from the upstream registry
+/// The content is checked against the checksum in the upstream index
+pub async fn fetch_crate(configuration: &Configuration, metadata: &IndexCrateMetadata) -> Result<Vec<u8>, ApiError> {
+    let target_uri = format!(
+        "{}/{}/{}/download",
+        configuration.proxy.upstream_dl, metadata.name, metadata.vers
+    );
+    let content = fetch(configuration, &target_uri).await?.ok_or_else(error_not_found)?;
+    let checksum = sha256(&content);
+    if checksum != metadata.cksum {
+        // a mismatch is either a corrupted transfer or a tampered upstream, it must be visible to the operators
+        error!(
+            "checksum mismatch for {} {} from {target_uri}: expected {}, got {checksum}",
+            metadata.name, metadata.vers, metadata.cksum
+        );
+        return Err(specialize(
+            error_backend_failure(),
+            format!(
+                "checksum mismatch for {} {} from the upstream registry: expected {}, got {checksum}",
+                metadata.name, metadata.vers, metadata.cksum
+            ),
+        ));
+    }
+    Ok(content)
+}
+
+/// Fetches a resource from the upstream registry, `None` when it does not exist
+async fn fetch(configuration: &Configuration, target_uri: &str) -> Result<Option<Vec<u8>>, ApiError> {
+    let response = reqwest::Client::new()
+        .get(target_uri)
+        .timeout(Duration::from_millis(configuration.proxy.timeout))
+        .send()
+        .await?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(specialize(
+            error_backend_failure(),
+            format!(
+                "failed to get {target_uri} from the upstream registry: error code {}",
+                status.as_u16()
+            ),
+        ));
+    }
+    Ok(Some(response.bytes().await?.to_vec()))
+}
@@ -47,6 +47,11 @@ impl Default for RustSecData {
 }
 
 impl RustSecData {
+    /// Gets the number of known advisories
+    pub fn get_advisories_count(&self) -> usize {
+        self.db.lock().unwrap().values().map(Vec::len).sum()
+    }
+
     /// Updates the data
     async fn update_data(&mut self, config: &Configuration) -> Result<(), ApiError> {
         let now = Instant::now();
@@ -104,6 +109,12 @@ pub struct RustSecChecker<'a> {
 }
 
 impl<'a> RustSecChecker<'a> {
+    /// Refreshes the advisories when they are stale
+    pub async fn refresh(&self) -> Result<(), ApiError> {
+        let mut data = self.data.lock().await;
+        data.update_data(self.configuration).await
+    }
+
     /// Gets the advisories against a crate
     pub async fn check_crate(&self, package: &str, version: &semver::Version) -> Result<Vec<SimpleAdvisory>, ApiError> {
         let mut data = self.data.lock().await;
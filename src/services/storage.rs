@@ -5,74 +5,281 @@
 //! Storage implementations for crates data and documentation
 
 use crate::model::cargo::CrateMetadata;
-use crate::model::config::{Configuration, StorageConfig};
-use crate::utils::apierror::ApiError;
+use crate::model::config::{Configuration, S3Params, StorageConfig};
+use crate::model::packages::{CrateDocFile, StoredFile};
+use crate::utils::apierror::{error_backend_failure, specialize, ApiError};
+use crate::utils::hashes::sha256;
+use crate::utils::range::{ByteRangeSpec, RangedContent};
+use axum::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use flate2::bufread::GzDecoder;
-use opendal::{layers::LoggingLayer, Operator};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use log::error;
+use opendal::{layers::LoggingLayer, Metakey, Operator, Writer};
 use std::io::Read;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Component, Path};
 use tar::Archive;
+use tokio::io::AsyncReadExt;
 
-/// Backing storage
-pub struct Storage {
-    opendal_operator: Operator,
+/// The size of the chunks written to the storage when streaming a file
+/// This is above the minimum size of the parts of multipart uploads on S3
+const WRITE_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The size of the buffer used to read a local file when streaming it to the storage
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A stream of the content of a stored file
+pub type ContentStream = BoxStream<'static, Result<Bytes, ApiError>>;
+
+/// The metadata of a stored file
+#[derive(Debug, Clone)]
+pub struct FileMetadata {
+    /// The size of the file, in bytes
+    pub size: u64,
+    /// The entity tag maintained by the backend, if any
+    pub etag: Option<String>,
+    /// The date time of the last modification, if known
+    pub last_modified: Option<DateTime<Utc>>,
 }
 
-impl From<&Configuration> for Storage {
-    fn from(config: &Configuration) -> Self {
-        let opendal_operator = match &config.storage {
-            StorageConfig::FileSystem => {
-                let builder = opendal::services::Fs::default().root(&config.data_dir);
+/// The content of a crate version, streamed from the storage
+pub struct CrateContent {
+    /// The checksum of the crate, used as its entity tag
+    pub checksum: String,
+    /// The size of the crate, in bytes
+    pub size: u64,
+    /// The stream of the content
+    pub stream: ContentStream,
+}
 
-                opendal::Operator::new(builder)
-                    .unwrap()
-                    .layer(LoggingLayer::default())
-                    .finish()
-            }
-            StorageConfig::S3 { params, bucket } => {
-                let builder = opendal::services::S3::default()
-                    .bucket(bucket)
-                    .region(&params.region)
-                    .endpoint(&params.uri)
-                    .access_key_id(&params.access_key)
-                    .secret_access_key(&params.secret_key);
-
-                opendal::Operator::new(builder)
-                    .unwrap()
-                    .layer(LoggingLayer::default())
-                    .finish()
-            }
-        };
+impl CrateContent {
+    /// Wraps a content already loaded in memory
+    #[must_use]
+    pub fn from_bytes(content: Vec<u8>) -> CrateContent {
+        CrateContent {
+            checksum: sha256(&content),
+            size: content.len() as u64,
+            stream: futures::stream::once(async move { Ok(Bytes::from(content)) }).boxed(),
+        }
+    }
+}
 
-        Storage { opendal_operator }
+/// Gets the storage backend selected by the configuration
+#[must_use]
+pub fn get_storage(config: &Configuration) -> Box<dyn Storage> {
+    match &config.storage {
+        StorageConfig::FileSystem => Box::new(FsStorage::new(config)),
+        StorageConfig::S3 { params, bucket } => Box::new(S3Storage::new(config, params, bucket)),
     }
 }
 
-impl Storage {
+/// Backing storage for the crates data and documentation
+/// A backend provides the operations on the stored files, the operations on crates and documentation are built upon them.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Gets whether identical READMEs are stored once and shared across versions
+    fn dedup_readme(&self) -> bool;
+
+    /// Gets whether a file exists
+    async fn exists(&self, path: &str) -> Result<bool, ApiError>;
+
+    /// Gets the metadata of a file
+    async fn stat(&self, path: &str) -> Result<FileMetadata, ApiError>;
+
+    /// Reads from a file
+    async fn read_from_file(&self, path: &str) -> Result<Vec<u8>, ApiError>;
+
+    /// Reads a range of the bytes in a file
+    async fn read_range(&self, path: &str, range: Range<u64>) -> Result<Vec<u8>, ApiError>;
+
+    /// Streams the content of a file, without loading it in memory at once
+    async fn read_stream(&self, path: &str) -> Result<ContentStream, ApiError>;
+
+    /// Write to a file
+    async fn write_to_file(&self, path: &str, content: Vec<u8>) -> Result<(), ApiError>;
+
+    /// Writes a file with the content of a local file, streamed so that it is not loaded in memory at once
+    async fn write_from_local_file(&self, path: &str, file: &Path) -> Result<(), ApiError>;
+
+    /// Moves a file, using the most efficient operation supported by the backend
+    async fn move_file(&self, from: &str, to: &str) -> Result<(), ApiError>;
+
+    /// Deletes a file, if it exists
+    async fn delete_file(&self, path: &str) -> Result<(), ApiError>;
+
+    /// Deletes all the files under a prefix
+    async fn delete_all(&self, prefix: &str) -> Result<(), ApiError>;
+
+    /// Lists all the files under a prefix, recursively
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, ApiError>;
+
     /// Stores the data for a crate
-    pub async fn store_crate(&self, metadata: &CrateMetadata, content: Vec<u8>) -> Result<(), ApiError> {
+    /// All parts are first written to temporary files that are committed only when all writes succeeded,
+    /// so that a failure never leaves a partially stored crate behind
+    async fn store_crate(&self, metadata: &CrateMetadata, content: Vec<u8>) -> Result<(), ApiError> {
         let readme = extract_readme(&content)?;
+        let changelog = extract_changelog(&content)?;
         let metadata_json = serde_json::to_vec(metadata)?;
         let name = &metadata.name;
         let version = &metadata.vers;
+        let checksum = sha256(&content);
+        let mut parts = vec![
+            (data_path(name, version), content),
+            (metadata_path(name, version), metadata_json),
+            (checksum_path(name, version), checksum.into_bytes()),
+            // an empty file records the absence of a changelog
+            (changelog_path(name, version), changelog.unwrap_or_default()),
+        ];
+        if self.dedup_readme() {
+            // the README is stored once by content hash, the version only keeps a reference to it
+            let hash = sha256(&readme);
+            let blob_path = readme_blob_path(name, &hash);
+            if !self.exists(&blob_path).await? {
+                parts.push((blob_path, readme));
+            }
+            parts.push((readme_ref_path(name, version), hash.into_bytes()));
+        } else {
+            parts.push((readme_path(name, version), readme));
+        }
 
-        self.write_to_file(&Self::data_path(name, version), content).await?;
+        // stage all the parts
+        let suffix = uuid::Uuid::new_v4();
+        let mut staged: Vec<(String, String)> = Vec::with_capacity(parts.len());
+        for (path, data) in parts {
+            let temp_path = format!("{path}.{suffix}.tmp");
+            if let Err(e) = self.write_to_file(&temp_path, data).await {
+                let remaining = staged.iter().map(|(_, temp_path)| temp_path.as_str()).collect::<Vec<_>>();
+                self.delete_files(&remaining).await;
+                return Err(specialize(
+                    error_backend_failure(),
+                    format!("failed to store crate {name} {version}: {e}"),
+                ));
+            }
+            staged.push((path, temp_path));
+        }
 
-        self.write_to_file(&Self::metadata_path(name, version), metadata_json).await?;
+        // commit, the previous files of a replaced version are kept aside until all the parts are committed
+        let mut committed: Vec<(&str, Option<String>)> = Vec::with_capacity(staged.len());
+        for (index, (path, temp_path)) in staged.iter().enumerate() {
+            let result = async {
+                let backup_path = if self.exists(path).await? {
+                    let backup_path = format!("{path}.{suffix}.bak");
+                    self.move_file(path, &backup_path).await?;
+                    Some(backup_path)
+                } else {
+                    None
+                };
+                // recorded before the move so that a partial move is also undone
+                committed.push((path, backup_path));
+                self.move_file(temp_path, path).await
+            }
+            .await;
+            if let Err(e) = result {
+                self.restore_files(&committed).await;
+                let remaining = staged[index..].iter().map(|(_, temp_path)| temp_path.as_str());
+                self.delete_files(&remaining.collect::<Vec<_>>()).await;
+                return Err(specialize(
+                    error_backend_failure(),
+                    format!("failed to store crate {name} {version}: {e}"),
+                ));
+            }
+        }
+        let backups = committed.iter().filter_map(|(_, backup_path)| backup_path.as_deref());
+        self.delete_files(&backups.collect::<Vec<_>>()).await;
+        // when the version is replaced, the README stored in the other form and the files extracted on first access are stale
+        let stale_readme = if self.dedup_readme() {
+            readme_path(name, version)
+        } else {
+            readme_ref_path(name, version)
+        };
+        for path in [stale_readme, lockfile_path(name, version)] {
+            self.delete_file(&path).await?;
+        }
+        Ok(())
+    }
 
-        self.write_to_file(&Self::readme_path(name, version), readme).await?;
+    /// Restores the files replaced by a failed commit from their backups, the files without a previous version are deleted
+    /// Failures are only logged
+    async fn restore_files(&self, committed: &[(&str, Option<String>)]) {
+        for (path, backup_path) in committed {
+            let result = match backup_path {
+                Some(backup_path) => self.move_file(backup_path, path).await,
+                None => self.delete_file(path).await,
+            };
+            if let Err(e) = result {
+                error!("failed to restore {path}: {e}");
+            }
+        }
+    }
 
-        Ok(())
+    /// Deletes files as part of a clean-up, failures are only logged
+    async fn delete_files(&self, paths: &[&str]) {
+        for path in paths {
+            if let Err(e) = self.delete_file(path).await {
+                error!("failed to clean up {path}: {e}");
+            }
+        }
     }
 
     /// Downloads a crate
-    pub async fn download_crate(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
-        self.read_from_file(&Self::data_path(name, version)).await
+    async fn download_crate(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        self.read_from_file(&data_path(name, version)).await
+    }
+
+    /// Streams the content of a crate, without loading it in memory at once
+    async fn download_crate_stream(&self, name: &str, version: &str) -> Result<ContentStream, ApiError> {
+        self.read_stream(&data_path(name, version)).await
+    }
+
+    /// Gets the content of a crate with its checksum and size, the content is streamed from the storage
+    async fn download_crate_content(&self, name: &str, version: &str) -> Result<CrateContent, ApiError> {
+        let checksum = self.get_crate_checksum(name, version).await?;
+        let size = self.get_crate_size(name, version).await?;
+        let stream = self.download_crate_stream(name, version).await?;
+        Ok(CrateContent { checksum, size, stream })
+    }
+
+    /// Gets the size of a crate, in bytes, without downloading it
+    async fn get_crate_size(&self, name: &str, version: &str) -> Result<u64, ApiError> {
+        Ok(self.stat(&data_path(name, version)).await?.size)
+    }
+
+    /// Gets the checksum of a crate, used as its entity tag
+    /// The checksum is stored along the crate on publication,
+    /// for the crates published before, it is computed once on first access and stored.
+    async fn get_crate_checksum(&self, name: &str, version: &str) -> Result<String, ApiError> {
+        let path = checksum_path(name, version);
+        if self.exists(&path).await? {
+            let content = self.read_from_file(&path).await?;
+            return Ok(String::from_utf8(content)?);
+        }
+        let checksum = sha256(&self.download_crate(name, version).await?);
+        self.write_to_file(&path, checksum.clone().into_bytes()).await?;
+        Ok(checksum)
+    }
+
+    /// Downloads a range of the bytes of a crate
+    async fn download_crate_range(&self, name: &str, version: &str, spec: ByteRangeSpec) -> Result<RangedContent, ApiError> {
+        self.read_range_from_file(&data_path(name, version), spec).await
+    }
+
+    /// Downloads a crate version proxied from the upstream registry
+    async fn download_proxied_crate(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        self.read_from_file(&proxied_data_path(name, version)).await
+    }
+
+    /// Stores a crate version proxied from the upstream registry
+    /// Proxied crates are kept apart from the crates published to this registry
+    async fn store_proxied_crate(&self, name: &str, version: &str, content: Vec<u8>) -> Result<(), ApiError> {
+        self.write_to_file(&proxied_data_path(name, version), content).await
     }
 
     /// Downloads the last metadata for a crate
-    pub async fn download_crate_metadata(&self, name: &str, version: &str) -> Result<Option<CrateMetadata>, ApiError> {
-        if let Ok(data) = self.read_from_file(&Self::metadata_path(name, version)).await {
+    async fn download_crate_metadata(&self, name: &str, version: &str) -> Result<Option<CrateMetadata>, ApiError> {
+        if let Ok(data) = self.read_from_file(&metadata_path(name, version)).await {
             Ok(Some(serde_json::from_slice(&data)?))
         } else {
             Ok(None)
@@ -80,58 +287,463 @@ impl Storage {
     }
 
     /// Downloads the last README for a crate
-    pub async fn download_crate_readme(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
-        self.read_from_file(&Self::readme_path(name, version)).await
+    /// The README may have been stored as a reference to a shared blob when de-duplication is active,
+    /// the form used by the current configuration is looked for first.
+    async fn download_crate_readme(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        if self.dedup_readme() {
+            match self.download_crate_readme_blob(name, version).await {
+                Ok(content) => Ok(content),
+                Err(e) => self.read_from_file(&readme_path(name, version)).await.map_err(|_| e),
+            }
+        } else {
+            match self.read_from_file(&readme_path(name, version)).await {
+                Ok(content) => Ok(content),
+                Err(e) => self.download_crate_readme_blob(name, version).await.map_err(|_| e),
+            }
+        }
     }
 
-    /// Stores a documentation file
-    pub async fn store_doc_file(&self, path: &str, file: &Path) -> Result<(), ApiError> {
-        let content = tokio::fs::read(file).await?;
-        self.write_to_file(&format!("/docs/{path}"), content).await?;
-        Ok(())
+    /// Downloads the README for a crate stored as a reference to a shared blob
+    async fn download_crate_readme_blob(&self, name: &str, version: &str) -> Result<Vec<u8>, ApiError> {
+        let hash = self.read_from_file(&readme_ref_path(name, version)).await?;
+        let hash = String::from_utf8_lossy(&hash);
+        self.read_from_file(&readme_blob_path(name, hash.trim())).await
+    }
+
+    /// Downloads the changelog for a crate, if any
+    /// The changelog is extracted from the crate on publication,
+    /// for the crates published before, it is extracted on first access and cached afterwards
+    async fn download_crate_changelog(&self, name: &str, version: &str) -> Result<Option<Vec<u8>>, ApiError> {
+        let path = changelog_path(name, version);
+        if self.exists(&path).await? {
+            let content = self.read_from_file(&path).await?;
+            // an empty file records the absence of a changelog
+            return Ok(if content.is_empty() { None } else { Some(content) });
+        }
+        let content = self.download_crate(name, version).await?;
+        let changelog = extract_changelog(&content)?;
+        self.write_to_file(&path, changelog.clone().unwrap_or_default()).await?;
+        Ok(changelog)
+    }
+
+    /// Downloads the `Cargo.lock` for a crate, if any
+    /// The lockfile is extracted from the crate on first access and cached afterwards
+    async fn download_crate_lockfile(&self, name: &str, version: &str) -> Result<Option<Vec<u8>>, ApiError> {
+        let path = lockfile_path(name, version);
+        if self.exists(&path).await? {
+            let content = self.read_from_file(&path).await?;
+            // an empty file records the absence of a lockfile
+            return Ok(if content.is_empty() { None } else { Some(content) });
+        }
+        let content = self.download_crate(name, version).await?;
+        let lockfile = extract_lockfile(&content)?;
+        self.write_to_file(&path, lockfile.clone().unwrap_or_default()).await?;
+        Ok(lockfile)
+    }
+
+    /// Stores the log of the generation of the documentation for a crate version and a target, or a variant of it
+    /// The log of a previous generation for the same target and variant is replaced
+    async fn store_doc_log(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        variant: Option<&str>,
+        log: Vec<u8>,
+    ) -> Result<(), ApiError> {
+        self.write_to_file(&doc_log_path(name, version, target, variant), log).await
+    }
+
+    /// Downloads the log of the last generation of the documentation for a crate version and a target, or a variant of it, if any
+    async fn download_doc_log(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        variant: Option<&str>,
+    ) -> Result<Option<Vec<u8>>, ApiError> {
+        let path = doc_log_path(name, version, target, variant);
+        if !self.exists(&path).await? {
+            return Ok(None);
+        }
+        self.read_from_file(&path).await.map(Some)
     }
 
     /// Stores a documentation file
-    pub async fn store_doc_data(&self, path: &str, content: Vec<u8>) -> Result<(), ApiError> {
-        self.write_to_file(&format!("docs/{path}"), content).await?;
-        Ok(())
+    /// The file is streamed to the storage so that large files are not loaded in memory at once
+    async fn store_doc_file(&self, path: &str, file: &Path) -> Result<(), ApiError> {
+        self.write_from_local_file(&format!("docs/{path}"), file).await
+    }
+
+    /// Deletes all the stored files for a crate, i.e. its versions, READMEs and documentation
+    async fn delete_crate_files(&self, name: &str) -> Result<(), ApiError> {
+        self.delete_all(&format!("crates/{name}/")).await?;
+        self.delete_doc_files(name).await
+    }
+
+    /// Deletes all the documentation files for a crate
+    async fn delete_doc_files(&self, name: &str) -> Result<(), ApiError> {
+        self.delete_all(&format!("docs/{name}/")).await
+    }
+
+    /// Lists the versions of a crate that have an archive in the storage
+    async fn list_crate_versions(&self, name: &str) -> Result<Vec<String>, ApiError> {
+        let prefix = format!("crates/{name}/");
+        let files = self.list_files(&prefix).await?;
+        Ok(files
+            .into_iter()
+            .filter_map(|file| {
+                file.path
+                    .strip_prefix(&prefix)
+                    .and_then(|path| path.strip_suffix("/data"))
+                    .filter(|version| !version.contains('/'))
+                    .map(str::to_string)
+            })
+            .collect())
+    }
+
+    /// Lists all the stored files for a crate, or only for one of its versions
+    /// When all versions are selected, the files shared across versions, such as READMEs, are included
+    async fn list_crate_files(&self, name: &str, version: Option<&str>) -> Result<Vec<StoredFile>, ApiError> {
+        let prefixes = match version {
+            None => [format!("crates/{name}/"), format!("docs/{name}/")],
+            Some(version) => [format!("crates/{name}/{version}/"), format!("docs/{name}/{version}/")],
+        };
+        let mut files = Vec::new();
+        for prefix in prefixes {
+            files.extend(self.list_files(&prefix).await?);
+        }
+        Ok(files)
+    }
+
+    /// Deletes the stored files for a crate version, i.e. its data, metadata, README and documentation
+    async fn delete_crate_version_files(&self, name: &str, version: &str) -> Result<(), ApiError> {
+        self.delete_all(&format!("crates/{name}/{version}/")).await?;
+        self.delete_all(&format!("docs/{name}/{version}/")).await
+    }
+
+    /// Lists the files in the documentation of a crate version
+    async fn list_doc_files(&self, name: &str, version: &str) -> Result<Vec<CrateDocFile>, ApiError> {
+        let prefix = format!("docs/{name}/{version}/");
+        let mut files = self
+            .list_files(&prefix)
+            .await?
+            .into_iter()
+            .map(|file| CrateDocFile {
+                path: file.path.strip_prefix(&prefix).unwrap_or(&file.path).to_string(),
+                size: file.size,
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
     }
 
     /// Gets the content of a documentation file
-    pub async fn download_doc_file(&self, path: &str) -> Result<Vec<u8>, ApiError> {
+    async fn download_doc_file(&self, path: &str) -> Result<Vec<u8>, ApiError> {
         self.read_from_file(&format!("docs/{path}")).await
     }
 
-    /// Write to a file
-    pub async fn write_to_file(&self, path: &str, content: Vec<u8>) -> Result<(), ApiError> {
+    /// Gets the entity tag of a documentation file, without reading the file
+    /// This is the tag maintained by the backend when it has one, like S3,
+    /// otherwise it is derived from the size and the last modification of the file.
+    async fn get_doc_file_etag(&self, path: &str) -> Result<String, ApiError> {
+        let metadata = self.stat(&format!("docs/{path}")).await?;
+        if let Some(etag) = metadata.etag {
+            return Ok(etag.trim_matches('"').to_string());
+        }
+        let modified = metadata.last_modified.map_or(0, |modified| modified.timestamp_millis());
+        Ok(format!("{:x}-{modified:x}", metadata.size))
+    }
+
+    /// Gets a range of the bytes of a documentation file
+    async fn download_doc_file_range(&self, path: &str, spec: ByteRangeSpec) -> Result<RangedContent, ApiError> {
+        self.read_range_from_file(&format!("docs/{path}"), spec).await
+    }
+
+    /// Reads a range of the bytes in a file
+    /// Only the requested range is fetched from the storage, not the complete file
+    async fn read_range_from_file(&self, path: &str, spec: ByteRangeSpec) -> Result<RangedContent, ApiError> {
+        let total = self.stat(path).await?.size;
+        let Some(range) = spec.resolve(total) else {
+            return Ok(RangedContent::Unsatisfiable { total });
+        };
+        let content = self.read_range(path, range.clone()).await?;
+        Ok(RangedContent::Partial { content, range, total })
+    }
+}
+
+/// Storage on the local file system, under the data directory
+pub struct FsStorage {
+    /// The operator for the file system
+    opendal_operator: Operator,
+    /// Whether identical READMEs are stored once and shared across versions
+    dedup_readme: bool,
+}
+
+impl FsStorage {
+    /// Creates the storage on the file system
+    #[must_use]
+    pub fn new(config: &Configuration) -> FsStorage {
+        let builder = opendal::services::Fs::default().root(&config.data_dir);
+        let opendal_operator = opendal::Operator::new(builder)
+            .unwrap()
+            .layer(LoggingLayer::default())
+            .finish();
+        FsStorage {
+            opendal_operator,
+            dedup_readme: config.storage_dedup_readme,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for FsStorage {
+    fn dedup_readme(&self) -> bool {
+        self.dedup_readme
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, ApiError> {
+        Ok(self.opendal_operator.is_exist(path).await?)
+    }
+
+    async fn stat(&self, path: &str) -> Result<FileMetadata, ApiError> {
+        stat_file(&self.opendal_operator, path).await
+    }
+
+    async fn read_from_file(&self, path: &str) -> Result<Vec<u8>, ApiError> {
+        Ok(self.opendal_operator.read(path).await?.to_vec())
+    }
+
+    async fn read_range(&self, path: &str, range: Range<u64>) -> Result<Vec<u8>, ApiError> {
+        Ok(self.opendal_operator.read_with(path).range(range).await?.to_vec())
+    }
+
+    async fn read_stream(&self, path: &str) -> Result<ContentStream, ApiError> {
+        stream_file(&self.opendal_operator, path).await
+    }
+
+    async fn write_to_file(&self, path: &str, content: Vec<u8>) -> Result<(), ApiError> {
         self.opendal_operator.write(path, content).await?;
         Ok(())
     }
 
-    /// Reads from a file
+    async fn write_from_local_file(&self, path: &str, file: &Path) -> Result<(), ApiError> {
+        let writer = self.opendal_operator.writer(path).await?;
+        write_local_file(writer, file).await
+    }
+
+    async fn move_file(&self, from: &str, to: &str) -> Result<(), ApiError> {
+        self.opendal_operator.rename(from, to).await?;
+        Ok(())
+    }
+
+    async fn delete_file(&self, path: &str) -> Result<(), ApiError> {
+        self.opendal_operator.delete(path).await?;
+        Ok(())
+    }
+
+    async fn delete_all(&self, prefix: &str) -> Result<(), ApiError> {
+        self.opendal_operator.remove_all(prefix).await?;
+        Ok(())
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, ApiError> {
+        list_files(&self.opendal_operator, prefix).await
+    }
+}
+
+/// Storage in a bucket of an S3-compatible service
+pub struct S3Storage {
+    /// The operator for the bucket
+    opendal_operator: Operator,
+    /// Whether identical READMEs are stored once and shared across versions
+    dedup_readme: bool,
+}
+
+impl S3Storage {
+    /// Creates the storage in an S3 bucket
+    #[must_use]
+    pub fn new(config: &Configuration, params: &S3Params, bucket: &str) -> S3Storage {
+        let builder = opendal::services::S3::default()
+            .bucket(bucket)
+            .region(&params.region)
+            .endpoint(&params.uri)
+            .access_key_id(&params.access_key)
+            .secret_access_key(&params.secret_key);
+        let opendal_operator = opendal::Operator::new(builder)
+            .unwrap()
+            .layer(LoggingLayer::default())
+            .finish();
+        S3Storage {
+            opendal_operator,
+            dedup_readme: config.storage_dedup_readme,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    fn dedup_readme(&self) -> bool {
+        self.dedup_readme
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, ApiError> {
+        Ok(self.opendal_operator.is_exist(path).await?)
+    }
+
+    async fn stat(&self, path: &str) -> Result<FileMetadata, ApiError> {
+        stat_file(&self.opendal_operator, path).await
+    }
+
     async fn read_from_file(&self, path: &str) -> Result<Vec<u8>, ApiError> {
-        let buffer = self.opendal_operator.read(path).await?;
+        Ok(self.opendal_operator.read(path).await?.to_vec())
+    }
+
+    async fn read_range(&self, path: &str, range: Range<u64>) -> Result<Vec<u8>, ApiError> {
+        Ok(self.opendal_operator.read_with(path).range(range).await?.to_vec())
+    }
+
+    async fn read_stream(&self, path: &str) -> Result<ContentStream, ApiError> {
+        stream_file(&self.opendal_operator, path).await
+    }
+
+    async fn write_to_file(&self, path: &str, content: Vec<u8>) -> Result<(), ApiError> {
+        self.opendal_operator.write(path, content).await?;
+        Ok(())
+    }
 
-        Ok(buffer.to_vec())
+    async fn write_from_local_file(&self, path: &str, file: &Path) -> Result<(), ApiError> {
+        // large files are sent as multipart uploads
+        let writer = self.opendal_operator.writer_with(path).chunk(WRITE_CHUNK_SIZE).await?;
+        write_local_file(writer, file).await
     }
 
-    fn crate_file_key(name: &str, version: &str, filename: &str) -> String {
-        format!("crates/{name}/{version}/{filename}")
+    async fn move_file(&self, from: &str, to: &str) -> Result<(), ApiError> {
+        // objects cannot be renamed, they are copied within the bucket
+        self.opendal_operator.copy(from, to).await?;
+        self.opendal_operator.delete(from).await?;
+        Ok(())
     }
 
-    fn data_path(name: &str, version: &str) -> String {
-        Self::crate_file_key(name, version, "data")
+    async fn delete_file(&self, path: &str) -> Result<(), ApiError> {
+        self.opendal_operator.delete(path).await?;
+        Ok(())
     }
 
-    fn metadata_path(name: &str, version: &str) -> String {
-        Self::crate_file_key(name, version, "metadata")
+    async fn delete_all(&self, prefix: &str) -> Result<(), ApiError> {
+        self.opendal_operator.remove_all(prefix).await?;
+        Ok(())
     }
 
-    fn readme_path(name: &str, version: &str) -> String {
-        Self::crate_file_key(name, version, "readme")
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, ApiError> {
+        list_files(&self.opendal_operator, prefix).await
     }
 }
 
+/// Gets the metadata of a file through an operator
+async fn stat_file(operator: &Operator, path: &str) -> Result<FileMetadata, ApiError> {
+    let metadata = operator.stat(path).await?;
+    Ok(FileMetadata {
+        size: metadata.content_length(),
+        etag: metadata.etag().map(str::to_string),
+        last_modified: metadata.last_modified(),
+    })
+}
+
+/// Streams the content of a file through an operator
+async fn stream_file(operator: &Operator, path: &str) -> Result<ContentStream, ApiError> {
+    let stream = operator.reader(path).await?.into_bytes_stream(..).await?;
+    Ok(stream.map_err(ApiError::from).boxed())
+}
+
+/// Lists all the files under a prefix through an operator, recursively
+async fn list_files(operator: &Operator, prefix: &str) -> Result<Vec<StoredFile>, ApiError> {
+    let entries = operator
+        .list_with(prefix)
+        .recursive(true)
+        .metakey(Metakey::Mode | Metakey::ContentLength)
+        .await?;
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.metadata().is_file())
+        .map(|entry| StoredFile {
+            path: entry.path().to_string(),
+            size: entry.metadata().content_length(),
+        })
+        .collect())
+}
+
+/// Writes the content of a local file with a writer of the storage, aborting the write on failure
+async fn write_local_file(mut writer: Writer, file: &Path) -> Result<(), ApiError> {
+    let mut source = tokio::fs::File::open(file).await?;
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        let read = match source.read(&mut buffer).await {
+            Ok(read) => read,
+            Err(e) => {
+                writer.abort().await?;
+                return Err(e.into());
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        if let Err(e) = writer.write(buffer[..read].to_vec()).await {
+            writer.abort().await?;
+            return Err(e.into());
+        }
+    }
+    writer.close().await?;
+    Ok(())
+}
+
+fn crate_file_key(name: &str, version: &str, filename: &str) -> String {
+    format!("crates/{name}/{version}/{filename}")
+}
+
+fn data_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "data")
+}
+
+fn checksum_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "checksum")
+}
+
+fn metadata_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "metadata")
+}
+
+fn readme_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "readme")
+}
+
+fn changelog_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "changelog")
+}
+
+fn lockfile_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "lockfile")
+}
+
+fn doc_log_path(name: &str, version: &str, target: &str, variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => crate_file_key(name, version, &format!("docs-{target}+{variant}.log")),
+        None => crate_file_key(name, version, &format!("docs-{target}.log")),
+    }
+}
+
+fn readme_ref_path(name: &str, version: &str) -> String {
+    crate_file_key(name, version, "readme.ref")
+}
+
+fn readme_blob_path(name: &str, hash: &str) -> String {
+    format!("crates/{name}/readmes/{hash}")
+}
+
+fn proxied_data_path(name: &str, version: &str) -> String {
+    format!("proxy/{name}/{version}/data")
+}
+
 /// Extract the content of the README from the
 pub fn extract_readme(crate_content: &[u8]) -> Result<Vec<u8>, ApiError> {
     let decoder = GzDecoder::new(crate_content);
@@ -154,3 +766,89 @@ pub fn extract_readme(crate_content: &[u8]) -> Result<Vec<u8>, ApiError> {
 
     Ok(buffer)
 }
+
+/// Checks that the content of a crate is a readable archive
+pub fn check_crate_archive(crate_content: &[u8]) -> Result<(), ApiError> {
+    let decoder = GzDecoder::new(crate_content);
+    let mut archive = Archive::new(decoder);
+    for entry in archive.entries()? {
+        std::io::copy(&mut entry?, &mut std::io::sink())?;
+    }
+    Ok(())
+}
+
+/// The file names recognized as changelogs, compared case-insensitively
+const CHANGELOG_FILE_NAMES: &[&str] = &["CHANGELOG.md", "CHANGELOG"];
+
+/// The directories, relative to the root of a crate, where changelogs are looked for, by order of preference
+const CHANGELOG_LOCATIONS: &[&str] = &["", "doc", "docs"];
+
+/// Extracts the content of the changelog from the crate, if any
+pub fn extract_changelog(crate_content: &[u8]) -> Result<Option<Vec<u8>>, ApiError> {
+    let decoder = GzDecoder::new(crate_content);
+    let mut archive = Archive::new(decoder);
+    let mut result: Option<(usize, Vec<u8>)> = None;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let Some(location) = get_changelog_location(&entry.path()?) else {
+            continue;
+        };
+        if result.as_ref().is_some_and(|(best, _)| *best <= location) {
+            continue;
+        }
+        let mut buffer = Vec::new();
+        entry.read_to_end(&mut buffer)?;
+        result = Some((location, buffer));
+    }
+    Ok(result.map(|(_, buffer)| buffer))
+}
+
+/// Gets the index of the location of a changelog in a crate, if the path is one
+/// Paths within a crate all start with the `{name}-{version}` directory.
+/// Paths that are absolute or that traverse to parent directories are rejected.
+fn get_changelog_location(path: &Path) -> Option<usize> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_string_lossy()),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    let [_root, directories @ .., file_name] = components.as_slice() else {
+        return None;
+    };
+    if !CHANGELOG_FILE_NAMES.iter().any(|name| file_name.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+    let directory = directories.join("/");
+    CHANGELOG_LOCATIONS
+        .iter()
+        .position(|location| directory.eq_ignore_ascii_case(location))
+}
+
+/// Extracts the content of the `Cargo.lock` at the root of the crate, if any
+pub fn extract_lockfile(crate_content: &[u8]) -> Result<Option<Vec<u8>>, ApiError> {
+    let decoder = GzDecoder::new(crate_content);
+    let mut archive = Archive::new(decoder);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !is_lockfile_path(&entry.path()?) {
+            continue;
+        }
+        let mut buffer = Vec::new();
+        entry.read_to_end(&mut buffer)?;
+        return Ok(Some(buffer));
+    }
+    Ok(None)
+}
+
+/// Gets whether a path within a crate is the one of the `Cargo.lock` at its root
+/// Paths within a crate all start with the `{name}-{version}` directory.
+fn is_lockfile_path(path: &Path) -> bool {
+    let mut components = path.components().filter(|component| !matches!(component, Component::CurDir));
+    matches!(
+        (components.next(), components.next(), components.next()),
+        (Some(Component::Normal(_)), Some(Component::Normal(file_name)), None) if file_name == "Cargo.lock"
+    )
+}
@@ -0,0 +1,116 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to notify webhooks of the changes to crates
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::channel::mpsc::UnboundedSender;
+use futures::StreamExt;
+use log::{error, info};
+use reqwest::header::CONTENT_TYPE;
+
+use crate::model::config::Configuration;
+use crate::model::webhooks::WebhookEvent;
+use crate::utils::apierror::{error_backend_failure, specialize, ApiError};
+use crate::utils::hashes::hmac_sha256_sign;
+
+/// The header holding the kind of event
+const HEADER_EVENT: &str = "X-Cratery-Event";
+/// The header holding the signature of the payload
+const HEADER_SIGNATURE: &str = "X-Cratery-Signature";
+
+/// Creates the worker delivering the notifications to the webhooks
+/// Each delivery to each endpoint is retried independently, so that a slow endpoint does not hold up the others
+pub fn create_webhooks_worker(configuration: Arc<Configuration>) -> UnboundedSender<WebhookEvent> {
+    let (sender, mut receiver) = futures::channel::mpsc::unbounded::<WebhookEvent>();
+    let _handle = tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        while let Some(event) = receiver.next().await {
+            let body = match serde_json::to_vec(&event) {
+                Ok(body) => Arc::new(body),
+                Err(e) => {
+                    error!(
+                        "webhooks: failed to serialize the {} event for {}: {e}",
+                        event.event.name(),
+                        event.package
+                    );
+                    continue;
+                }
+            };
+            let signature = Arc::new(format!(
+                "sha256={}",
+                hmac_sha256_sign(configuration.webhooks.secret.as_bytes(), &body)
+            ));
+            for endpoint in &configuration.webhooks.endpoints {
+                let configuration = configuration.clone();
+                let client = client.clone();
+                let endpoint = endpoint.clone();
+                let body = body.clone();
+                let signature = signature.clone();
+                let kind = event.event.name();
+                let _handle = tokio::spawn(async move {
+                    deliver_with_retries(&configuration, &client, &endpoint, kind, &body, &signature).await;
+                });
+            }
+        }
+    });
+    sender
+}
+
+/// Delivers a notification to an endpoint, retrying with an exponential backoff
+async fn deliver_with_retries(
+    configuration: &Configuration,
+    client: &reqwest::Client,
+    endpoint: &str,
+    kind: &str,
+    body: &[u8],
+    signature: &str,
+) {
+    let attempts = configuration.webhooks.attempts.max(1);
+    for attempt in 1..=attempts {
+        match deliver(configuration, client, endpoint, kind, body, signature).await {
+            Ok(()) => {
+                info!("webhooks: delivered {kind} to {endpoint}");
+                return;
+            }
+            Err(e) => {
+                error!("webhooks: attempt {attempt}/{attempts} to deliver {kind} to {endpoint} failed: {e}");
+                if attempt < attempts {
+                    tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(10))).await;
+                }
+            }
+        }
+    }
+}
+
+/// Attempts to deliver a notification to an endpoint
+async fn deliver(
+    configuration: &Configuration,
+    client: &reqwest::Client,
+    endpoint: &str,
+    kind: &str,
+    body: &[u8],
+    signature: &str,
+) -> Result<(), ApiError> {
+    let response = client
+        .post(endpoint)
+        .header(CONTENT_TYPE, "application/json")
+        .header(HEADER_EVENT, kind)
+        .header(HEADER_SIGNATURE, signature)
+        .timeout(Duration::from_millis(configuration.webhooks.timeout))
+        .body(body.to_vec())
+        .send()
+        .await?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(specialize(
+            error_backend_failure(),
+            format!("the endpoint responded with {status}"),
+        ))
+    }
+}
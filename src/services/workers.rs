@@ -0,0 +1,93 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Shared state for the background workers to report their activity
+
+use std::sync::Mutex;
+
+use chrono::Local;
+
+use crate::model::workers::WorkerStatus;
+
+/// The activity of a background worker, updated by the worker and read for monitoring
+#[derive(Debug, Default)]
+pub struct WorkerState {
+    /// The current status
+    status: Mutex<WorkerStatus>,
+}
+
+impl WorkerState {
+    /// Records that jobs were added to the queue
+    pub fn on_queued(&self, count: usize) {
+        let mut status = self.status.lock().unwrap();
+        status.queued += count;
+    }
+
+    /// Records that a job is added to the queue, unless the queue is at capacity, 0 meaning an unbounded queue
+    /// Returns whether the job can be queued, otherwise it is recorded as deferred
+    pub fn on_queued_bounded(&self, capacity: usize) -> bool {
+        let mut status = self.status.lock().unwrap();
+        if capacity > 0 && status.queued >= capacity {
+            status.deferred += 1;
+            return false;
+        }
+        status.queued += 1;
+        true
+    }
+
+    /// Records that a job recorded as queued could not be sent to the worker and is deferred
+    pub fn on_queue_failed(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.queued = status.queued.saturating_sub(1);
+        status.deferred += 1;
+    }
+
+    /// Takes the deferred jobs once the queue is empty, so that they can be queued again
+    /// Returns the number of deferred jobs, 0 while the queue is not empty
+    pub fn take_deferred(&self) -> usize {
+        let mut status = self.status.lock().unwrap();
+        if status.queued > 0 {
+            return 0;
+        }
+        std::mem::take(&mut status.deferred)
+    }
+
+    /// Records that a job was taken from the queue and is being processed
+    pub fn on_started(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.queued = status.queued.saturating_sub(1);
+        status.in_flight += 1;
+    }
+
+    /// Records that a job finished
+    pub fn on_finished(&self, success: bool) {
+        let mut status = self.status.lock().unwrap();
+        status.in_flight = status.in_flight.saturating_sub(1);
+        if success {
+            status.completed += 1;
+        } else {
+            status.failed += 1;
+        }
+        status.last_finished = Some(Local::now().naive_local());
+    }
+
+    /// Records that a job was taken from the queue but skipped
+    pub fn on_skipped(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.queued = status.queued.saturating_sub(1);
+        status.skipped += 1;
+        status.last_finished = Some(Local::now().naive_local());
+    }
+
+    /// Records that the remaining jobs in the queue were abandoned
+    pub fn on_queue_dropped(&self) {
+        let mut status = self.status.lock().unwrap();
+        status.queued = 0;
+    }
+
+    /// Gets the current status
+    pub fn get_status(&self) -> WorkerStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,55 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! Service to automatically unyank the crate versions yanked with an expiry
+
+use std::time::Duration;
+
+use chrono::Local;
+use log::{error, info};
+use sqlx::{Pool, Sqlite};
+
+use crate::model::config::Configuration;
+use crate::services::database::Database;
+use crate::utils::apierror::ApiError;
+use crate::utils::db::in_transaction;
+
+/// Creates a worker for the periodic unyank of crate versions for which the yank expired
+pub fn create_yanks_worker(configuration: &Configuration, pool: Pool<Sqlite>) {
+    if configuration.yank_expiry_hours <= 0 {
+        // deactivated
+        return;
+    }
+    let _handle = tokio::spawn(async move {
+        // every minute
+        let mut interval = tokio::time::interval(Duration::from_mins(1));
+        loop {
+            let _instant = interval.tick().await;
+            if let Err(e) = yanks_worker_job(&pool).await {
+                error!("{e}");
+                if let Some(backtrace) = &e.backtrace {
+                    error!("{backtrace}");
+                }
+            }
+        }
+    });
+}
+
+/// A job for the worker
+async fn yanks_worker_job(pool: &Pool<Sqlite>) -> Result<(), ApiError> {
+    let now = Local::now().naive_local();
+    let mut connection = pool.acquire().await?;
+    let unyanked = in_transaction(&mut connection, |transaction| async move {
+        let database = Database::new(transaction);
+        database.unyank_expired_crate_versions(now).await
+    })
+    .await?;
+    for crate_version in unyanked {
+        info!(
+            "yanks: unyanked {} {} after expiry",
+            crate_version.name, crate_version.version
+        );
+    }
+    Ok(())
+}
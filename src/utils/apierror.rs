@@ -18,9 +18,13 @@ pub struct ApiError {
     pub message: String,
     /// Optional details for the error
     pub details: Option<String>,
+    /// The individual errors, when multiple errors are aggregated
+    /// This is compatible with the format expected by cargo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Box<[ApiErrorItem]>>,
     /// The backtrace when the error was produced
     #[serde(skip_serializing, skip_deserializing)]
-    pub backtrace: Option<Backtrace>,
+    pub backtrace: Option<Box<Backtrace>>,
 }
 
 impl ApiError {
@@ -32,7 +36,28 @@ impl ApiError {
             http,
             message: message.to_string(),
             details,
-            backtrace: Some(Backtrace::capture()),
+            errors: None,
+            backtrace: Some(Box::new(Backtrace::capture())),
+        }
+    }
+}
+
+/// An individual error within an aggregated API error
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiErrorItem {
+    /// A stable code identifying the kind of error
+    pub code: String,
+    /// The human readable message for the error
+    pub detail: String,
+}
+
+impl ApiErrorItem {
+    /// Creates a new item
+    #[must_use]
+    pub fn new(code: &str, detail: String) -> Self {
+        Self {
+            code: code.to_string(),
+            detail,
         }
     }
 }
@@ -50,6 +75,7 @@ impl Clone for ApiError {
             http: self.http,
             message: self.message.clone(),
             details: self.details.clone(),
+            errors: self.errors.clone(),
             backtrace: None,
         }
     }
@@ -102,6 +128,12 @@ pub fn error_not_found() -> ApiError {
     ApiError::new(404, "The requested resource cannot be found.", None)
 }
 
+/// Error when the requested resource existed but was deliberately deleted
+#[must_use]
+pub fn error_gone() -> ApiError {
+    ApiError::new(410, "The requested resource was deleted.", None)
+}
+
 /// Error when the request has a conflicts
 #[must_use]
 pub fn error_conflict() -> ApiError {
@@ -111,3 +143,19 @@ pub fn error_conflict() -> ApiError {
         None,
     )
 }
+
+/// Error when too many requests have been received
+#[must_use]
+pub fn error_too_many_requests() -> ApiError {
+    ApiError::new(429, "Too many requests, try again later.", None)
+}
+
+/// Error when the request failed validation, aggregating all the individual errors
+#[must_use]
+pub fn error_validation(errors: Vec<ApiErrorItem>) -> ApiError {
+    let details = errors.iter().map(|e| e.detail.as_str()).collect::<Vec<_>>().join("; ");
+    ApiError {
+        errors: Some(errors.into_boxed_slice()),
+        ..specialize(error_invalid_request(), details)
+    }
+}
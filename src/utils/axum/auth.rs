@@ -5,6 +5,7 @@
 //! Authentication management
 
 use std::borrow::Cow;
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use axum::extract::FromRequestParts;
@@ -15,9 +16,10 @@ use base64::Engine;
 use cookie::time::OffsetDateTime;
 use cookie::{Cookie, CookieJar, Expiration, Key, SameSite};
 
-use super::extractors::Cookies;
+use super::extractors::{resolve_client_ip, Cookies};
 use crate::model::auth::AuthenticatedUser;
 use crate::utils::apierror::ApiError;
+use crate::utils::cidr::IpCidr;
 
 /// An authentication token
 #[derive(Debug, Clone)]
@@ -66,6 +68,11 @@ pub trait AxumStateForCookies {
 
     /// Gets the cookie key
     fn get_cookie_key(&self) -> &Key;
+
+    /// Gets the blocks of IP addresses of the reverse proxies trusted for the `X-Forwarded-For` header
+    fn get_trusted_proxies(&self) -> &[IpCidr] {
+        &[]
+    }
 }
 
 /// Authentication data for a request
@@ -80,6 +87,8 @@ pub struct AuthData {
     pub cookie_jar: CookieJar,
     /// The authentication token, if any
     pub token: Option<Token>,
+    /// The IP address of the client, resolved through the trusted proxies, if known
+    pub client_ip: Option<IpAddr>,
 }
 
 #[async_trait]
@@ -97,12 +106,14 @@ where
         } else {
             None
         };
+        let client_ip = resolve_client_ip(parts, state.get_trusted_proxies()).await;
         Ok(AuthData {
             cookie_domain: state.get_domain(),
             cookie_id_name: state.get_id_cookie_name(),
             cookie_key,
             cookie_jar,
             token,
+            client_ip,
         })
     }
 }
@@ -155,6 +166,16 @@ impl AuthData {
         }
     }
 
+    /// Gets the value of a cookie on the HTTP request
+    pub fn get_cookie(&self, name: &str, is_private: bool) -> Option<String> {
+        if is_private {
+            self.cookie_jar.private(&self.cookie_key).get(name)
+        } else {
+            self.cookie_jar.get(name).cloned()
+        }
+        .map(|cookie| cookie.value().to_string())
+    }
+
     /// Creates an identification cookie to be returned on the HTTP response
     ///
     /// # Panics
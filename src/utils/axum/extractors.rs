@@ -7,6 +7,7 @@
 use std::fmt;
 use std::net::{IpAddr, SocketAddr};
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 
 use axum::extract::{ConnectInfo, FromRequestParts};
 use axum::http::request::Parts;
@@ -17,30 +18,52 @@ use cookie::{Cookie, CookieJar};
 use serde::de::Visitor;
 use serde::Deserialize;
 
+use super::auth::AxumStateForCookies;
+use crate::utils::cidr::IpCidr;
+
 /// The client for the request, if any
 #[derive(Debug, Clone)]
 pub struct ClientIp(pub Option<IpAddr>);
 
 #[async_trait]
-impl<S> FromRequestParts<S> for ClientIp
+impl<S> FromRequestParts<Arc<S>> for ClientIp
 where
-    S: Send + Sync,
+    S: AxumStateForCookies + Send + Sync,
 {
     type Rejection = ();
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        if let Some(forwarded) = parts.headers.get("x-forwarded-for") {
-            if let Ok(forwarded) = forwarded.to_str() {
-                if let Some(Ok(client_ip)) = forwarded.split(',').next().map(str::trim).map(str::parse) {
-                    return Ok(ClientIp(Some(client_ip)));
-                }
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<S>) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(resolve_client_ip(parts, state.get_trusted_proxies()).await))
+    }
+}
+
+/// Resolves the IP address of the client for a request
+/// The `X-Forwarded-For` header is only used when the peer is a trusted proxy.
+/// The header is walked from the right, skipping the trusted proxies, so that a client cannot spoof its address by prepending to the header.
+pub async fn resolve_client_ip(parts: &mut Parts, trusted_proxies: &[IpCidr]) -> Option<IpAddr> {
+    let peer = match parts.extract::<ConnectInfo<SocketAddr>>().await {
+        Ok(ConnectInfo(addr)) => addr.ip().to_canonical(),
+        Err(_) => return None,
+    };
+    if !IpCidr::any_contains(trusted_proxies, peer) {
+        return Some(peer);
+    }
+    let mut client_ip = peer;
+    for forwarded in parts.headers.get_all("x-forwarded-for").iter().rev() {
+        let Ok(forwarded) = forwarded.to_str() else {
+            return Some(client_ip);
+        };
+        for hop in forwarded.rsplit(',').map(str::trim) {
+            let Ok(hop) = hop.parse::<IpAddr>() else {
+                return Some(client_ip);
+            };
+            client_ip = hop.to_canonical();
+            if !IpCidr::any_contains(trusted_proxies, client_ip) {
+                return Some(client_ip);
             }
         }
-        match parts.extract::<ConnectInfo<SocketAddr>>().await {
-            Ok(ConnectInfo(addr)) => Ok(ClientIp(Some(addr.ip()))),
-            Err(_) => Ok(ClientIp(None)),
-        }
     }
+    Some(client_ip)
 }
 
 impl fmt::Display for ClientIp {
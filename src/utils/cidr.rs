@@ -0,0 +1,92 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+******************************************************************************/
+
+//! API for blocks of IP addresses in the CIDR notation
+
+use std::fmt::{self, Display};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+/// A block of IP addresses, such as `10.0.0.0/8` or `fd00::/8`
+/// A single address is a block with the full prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpCidr {
+    /// The base address of the block
+    pub address: IpAddr,
+    /// The length of the prefix, in bits
+    pub prefix: u8,
+}
+
+impl IpCidr {
+    /// Gets whether an IP address is within this block
+    /// IPv4 addresses mapped in IPv6 are matched as IPv4 addresses
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(base), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(base) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(base), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(base) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets whether an IP address is within any of the blocks
+    #[must_use]
+    pub fn any_contains(blocks: &[IpCidr], ip: IpAddr) -> bool {
+        blocks.iter().any(|block| block.contains(ip))
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let address = address
+            .parse::<IpAddr>()
+            .map_err(|_| format!("invalid IP address in {s}"))?
+            .to_canonical();
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            None => max,
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|&prefix| prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length in {s}"))?,
+        };
+        Ok(IpCidr { address, prefix })
+    }
+}
+
+impl Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// Parses a comma-separated list of blocks of IP addresses
+///
+/// # Errors
+///
+/// Returns the description of the first invalid block
+pub fn parse_cidr_list(input: &str) -> Result<Vec<IpCidr>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .map(str::parse)
+        .collect()
+}
@@ -8,10 +8,13 @@ use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 
 use futures::Future;
+use log::warn;
 use serde_derive::{Deserialize, Serialize};
-use sqlx::{Acquire, Sqlite, SqliteConnection, Transaction};
+use sqlx::pool::PoolConnection;
+use sqlx::{Acquire, Pool, Sqlite, SqliteConnection, Transaction};
 
 use crate::utils::shared::{ResourceLock, SharedResource, StillSharedError};
 
@@ -83,6 +86,56 @@ where
     }
 }
 
+/// Acquires a connection from a pool, retrying with an exponential backoff on transient failures
+/// After the first attempt, at most `retries` attempts are made.
+/// The first retry waits for `backoff` milliseconds and the wait doubles each time,
+/// without waiting for more than `max_wait` milliseconds in total.
+///
+/// # Errors
+///
+/// Returns the last `sqlx::Error` when no connection could be acquired
+pub async fn acquire_with_retry(
+    pool: &Pool<Sqlite>,
+    retries: u32,
+    backoff: u64,
+    max_wait: u64,
+) -> Result<PoolConnection<Sqlite>, sqlx::Error> {
+    let mut attempt = 0;
+    let mut delay = backoff;
+    let mut waited = 0;
+    loop {
+        match pool.acquire().await {
+            Ok(connection) => return Ok(connection),
+            Err(error) if attempt < retries && is_transient(&error) && waited + delay <= max_wait => {
+                warn!("failed to acquire a database connection, retrying in {delay}ms: {error}");
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+                waited += delay;
+                delay = delay.saturating_mul(2);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// The primary result code of `SQLite` when the database file is locked
+const SQLITE_BUSY: i32 = 5;
+/// The primary result code of `SQLite` when a table is locked
+const SQLITE_LOCKED: i32 = 6;
+
+/// Gets whether an error when acquiring a connection may disappear by itself
+/// Errors from the database are transient only when the database is busy or locked, extended result codes are reduced to their primary code.
+fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) => true,
+        sqlx::Error::Database(error) => error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED)),
+        _ => false,
+    }
+}
+
 /// Represents a migration
 pub struct Migration<'a> {
     /// The target version
@@ -1,5 +1,6 @@
 use data_encoding::HEXLOWER;
 use ring::digest::{Context, SHA256};
+use ring::hmac;
 
 /// Computes the SHA256 digest of bytes
 pub fn sha256(buffer: &[u8]) -> String {
@@ -8,3 +9,18 @@ pub fn sha256(buffer: &[u8]) -> String {
     let digest = context.finish();
     HEXLOWER.encode(digest.as_ref())
 }
+
+/// Computes the HMAC-SHA256 signature of a message
+pub fn hmac_sha256_sign(key: &[u8], message: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    HEXLOWER.encode(hmac::sign(&key, message).as_ref())
+}
+
+/// Verifies the HMAC-SHA256 signature of a message, in constant time
+pub fn hmac_sha256_verify(key: &[u8], message: &[u8], signature: &str) -> bool {
+    let Ok(signature) = HEXLOWER.decode(signature.as_bytes()) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::verify(&key, message, &signature).is_ok()
+}
@@ -8,9 +8,12 @@ use std::time::{Duration, Instant};
 
 pub mod apierror;
 pub mod axum;
+pub mod cidr;
 pub mod concurrent;
 pub mod db;
 pub mod hashes;
+pub mod range;
+pub mod ratelimit;
 pub mod shared;
 pub mod sigterm;
 
@@ -0,0 +1,101 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+******************************************************************************/
+
+//! API for the HTTP range requests
+
+use std::ops::Range;
+
+/// A range of bytes requested with the `Range` header, before it is resolved against the size of the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRangeSpec {
+    /// From an offset to the end, as in `bytes=100-`
+    From(u64),
+    /// Between two offsets, both inclusive, as in `bytes=100-199`
+    Between(u64, u64),
+    /// The last bytes, as in `bytes=-100`
+    Suffix(u64),
+}
+
+impl ByteRangeSpec {
+    /// Parses the value of a `Range` header
+    /// Only single ranges of bytes are supported, `None` is returned for anything else so that the full content is served instead.
+    #[must_use]
+    pub fn parse(value: &str) -> Option<ByteRangeSpec> {
+        let spec = value.trim().strip_prefix("bytes=")?.trim();
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+        match (start.is_empty(), end.is_empty()) {
+            (true, true) => None,
+            (true, false) => end.parse().ok().map(ByteRangeSpec::Suffix),
+            (false, true) => start.parse().ok().map(ByteRangeSpec::From),
+            (false, false) => {
+                let start = start.parse().ok()?;
+                let end = end.parse().ok()?;
+                (start <= end).then_some(ByteRangeSpec::Between(start, end))
+            }
+        }
+    }
+
+    /// Resolves the range against the size of the content
+    /// Returns `None` when the range cannot be satisfied
+    #[must_use]
+    pub fn resolve(self, total: u64) -> Option<Range<u64>> {
+        match self {
+            ByteRangeSpec::From(start) => (start < total).then_some(start..total),
+            ByteRangeSpec::Between(start, end) => (start < total).then(|| start..(end.saturating_add(1)).min(total)),
+            ByteRangeSpec::Suffix(length) => (length > 0 && total > 0).then(|| total.saturating_sub(length)..total),
+        }
+    }
+}
+
+/// The result of reading a range of a content
+#[derive(Debug, Clone)]
+pub enum RangedContent {
+    /// The part of the content within the range
+    Partial {
+        /// The bytes within the range
+        content: Vec<u8>,
+        /// The range of the bytes in the complete content
+        range: Range<u64>,
+        /// The total size of the content
+        total: u64,
+    },
+    /// The range is beyond the end of the content
+    Unsatisfiable {
+        /// The total size of the content
+        total: u64,
+    },
+}
+
+impl RangedContent {
+    /// Extracts a range from a complete content already in memory
+    #[must_use]
+    pub fn from_content(content: &[u8], spec: ByteRangeSpec) -> RangedContent {
+        let total = content.len() as u64;
+        match spec.resolve(total) {
+            None => RangedContent::Unsatisfiable { total },
+            Some(range) => {
+                #[allow(clippy::cast_possible_truncation)]
+                let slice = &content[range.start as usize..range.end as usize];
+                RangedContent::Partial {
+                    content: slice.to_vec(),
+                    range,
+                    total,
+                }
+            }
+        }
+    }
+
+    /// Gets the value for the `Content-Range` header
+    #[must_use]
+    pub fn content_range(&self) -> String {
+        match self {
+            RangedContent::Partial { range, total, .. } => format!("bytes {}-{}/{total}", range.start, range.end - 1),
+            RangedContent::Unsatisfiable { total } => format!("bytes */{total}"),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/*******************************************************************************
+ * Copyright (c) 2024 Cénotélie Opérations SAS (cenotelie.fr)
+ ******************************************************************************/
+
+//! In-memory rate limiter with a sliding window
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of tracked keys above which the keys without recent requests are purged
+const RATE_LIMITER_PURGE_THRESHOLD: usize = 10_000;
+
+/// A rate limiter allowing a maximum number of requests for each key within a sliding window
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// The maximum number of requests in a window, 0 for no limit
+    limit: usize,
+    /// The duration of the window
+    window: Duration,
+    /// The instants of the requests within the window, for each key
+    requests: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter
+    #[must_use]
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a request for a key
+    ///
+    /// # Errors
+    ///
+    /// Returns the duration after which a request will be accepted again when the limit is reached
+    ///
+    /// # Panics
+    ///
+    /// Panics when the lock on the requests is poisoned
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut requests = self.requests.lock().unwrap();
+        if requests.len() > RATE_LIMITER_PURGE_THRESHOLD {
+            requests.retain(|_, instants| instants.back().is_some_and(|last| now.duration_since(*last) < self.window));
+        }
+        let instants = requests.entry(key.to_string()).or_default();
+        while instants
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            instants.pop_front();
+        }
+        if instants.len() >= self.limit {
+            let oldest = instants.front().copied().unwrap_or(now);
+            return Err(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+        instants.push_back(now);
+        Ok(())
+    }
+}
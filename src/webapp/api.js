@@ -18,8 +18,11 @@ function apiMe() {
   });
 }
 
-function apiOAuthLoginWithCode(code) {
-  return fetch("/api/v1/oauth/code", { method: "POST", body: code }).then(
+function apiOAuthLoginWithCode(code, state) {
+  return fetch("/api/v1/oauth/code?state=" + encodeURIComponent(state || ""), {
+    method: "POST",
+    body: code,
+  }).then(
     (response) => {
       if (response.status !== 200) {
         throw response.text();
@@ -36,6 +39,30 @@ function apiLogout() {
   }).then((r) => r.text());
 }
 
+function apiGetNotificationSettings() {
+  return fetch("/api/v1/me/notifications").then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiSetNotificationSettings(settings) {
+  return fetch("/api/v1/me/notifications", {
+    method: "PUT",
+    body: JSON.stringify(settings),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiGetTokens() {
   return fetch("/api/v1/tokens").then((response) => {
     if (response.status !== 200) {
@@ -46,8 +73,11 @@ function apiGetTokens() {
   });
 }
 
-function apiCreateToken(name, canWrite, canAdmin) {
-  return fetch(`/api/v1/tokens?canWrite=${canWrite}&canAdmin=${canAdmin}`, {
+function apiCreateToken(name, canWrite, canAdmin, expiresAt, crates, allowedIps) {
+  const expiry = expiresAt ? `&expiresAt=${encodeURIComponent(expiresAt)}` : "";
+  const scope = crates ? `&crates=${encodeURIComponent(crates)}` : "";
+  const binding = allowedIps ? `&allowedIps=${encodeURIComponent(allowedIps)}` : "";
+  return fetch(`/api/v1/tokens?canWrite=${canWrite}&canAdmin=${canAdmin}${expiry}${scope}${binding}`, {
     method: "PUT",
     body: name,
   }).then((response) => {
@@ -71,6 +101,20 @@ function apiRevokeToken(token_id) {
   );
 }
 
+function apiRevokeTokensMatching(criteria) {
+  return fetch("/api/v1/tokens/revoke", {
+    method: "POST",
+    body: JSON.stringify(criteria),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiGetUsers() {
   return fetch("/api/v1/users").then((response) => {
     if (response.status !== 200) {
@@ -95,6 +139,20 @@ function apiUpdateUser(user) {
   });
 }
 
+function apiImportUsers(users) {
+  return fetch("/api/v1/users/import", {
+    method: "POST",
+    body: JSON.stringify(users),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiDeleteUser(email) {
   return fetch(`/api/v1/users/${btoa(email)}`, {
     method: "DELETE",
@@ -151,16 +209,15 @@ function apiGetCratesOutdatedHeads() {
   });
 }
 
-function apiLookupCrates(input) {
-  return fetch("/api/v1/crates?q=" + encodeURIComponent(input))
-    .then((response) => {
-      if (response.status !== 200) {
-        throw response.text();
-      } else {
-        return response.json();
-      }
-    })
-    .then((response) => response.crates);
+function apiLookupCrates(input, cursor) {
+  const query = "/api/v1/crates?q=" + encodeURIComponent(input) + "&per_page=20";
+  return fetch(cursor === undefined ? query : query + "&cursor=" + encodeURIComponent(cursor)).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
 }
 
 function apiGetCrate(crate) {
@@ -178,13 +235,25 @@ function apiGetCrateLastReadme(crate) {
     if (response.status !== 200) {
       throw response.text();
     } else {
-      return response.text();
+      const contentType = response.headers.get("content-type");
+      return response.text().then((content) => ({ contentType, content }));
     }
   });
 }
 
 function apiGetCrateReadmeAt(crate, version) {
   return fetch(`/api/v1/crates/${crate}/${version}/readme`).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      const contentType = response.headers.get("content-type");
+      return response.text().then((content) => ({ contentType, content }));
+    }
+  });
+}
+
+function apiGetCrateChangelog(crate, version) {
+  return fetch(`/api/v1/crates/${crate}/${version}/changelog`).then((response) => {
     if (response.status !== 200) {
       throw response.text();
     } else {
@@ -231,6 +300,26 @@ function apiRemoveCrateOwners(crate, email) {
   });
 }
 
+function apiGetCrateVersions(crate) {
+  return fetch(`/api/v1/crates/${crate}/versions`).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetCrateDocStatus(crate, version) {
+  return fetch(`/api/v1/crates/${crate}/${version}/docsstatus`).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiGetCrateTargets(crate) {
   return fetch(`/api/v1/crates/${crate}/targets`).then((response) => {
     if (response.status !== 200) {
@@ -255,6 +344,136 @@ function apiSetCrateTargets(crate, targets) {
   });
 }
 
+function apiGetCrateDepsTargets(crate) {
+  return fetch(`/api/v1/crates/${crate}/depstargets`).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiSetCrateDepsTargets(crate, targets) {
+  return fetch(`/api/v1/crates/${crate}/depstargets`, {
+    method: "PATCH",
+    body: JSON.stringify(targets),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetOrphanedCrates() {
+  return fetch("/api/v1/crates/orphans").then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiClaimOrphanedCrate(crate) {
+  return fetch(`/api/v1/crates/${crate}/claim`, {
+    method: "POST",
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiAttestCrateOwnership(crate) {
+  return fetch(`/api/v1/crates/${crate}/attest`, {
+    method: "POST",
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetStaleCrateOwners() {
+  return fetch("/api/v1/crates/staleowners").then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetCratesPublished(from, to, page) {
+  return fetch(
+    `/api/v1/crates/published?from=${encodeURIComponent(from)}&to=${encodeURIComponent(to)}&page=${page}`,
+  ).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetCratesDlStats(from, to, granularity) {
+  return fetch(
+    `/api/v1/crates/dlstats?from=${encodeURIComponent(from)}&to=${encodeURIComponent(to)}&granularity=${granularity}`,
+  ).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiGetCrateBuilds(crate) {
+  return fetch(`/api/v1/crates/${crate}/builds`).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiSetCrateDocsSettings(crate, enabled, purge) {
+  return fetch(`/api/v1/crates/${crate}/docs`, {
+    method: "PATCH",
+    body: JSON.stringify({ enabled, purge }),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
+function apiSetCrateDocsExternal(crate, url) {
+  return fetch(`/api/v1/crates/${crate}/docsexternal`, {
+    method: "PATCH",
+    body: JSON.stringify({ url }),
+    headers: [["content-type", "application/json"]],
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiRegenCrateDoc(crate, version) {
   return fetch(`/api/v1/crates/${crate}/${version}/docsregen`, {
     method: "POST",
@@ -279,6 +498,18 @@ function apiCheckCrateDeps(crate, version) {
   });
 }
 
+function apiGetCrateDepsGraph(crate, version) {
+  return fetch(`/api/v1/crates/${crate}/${version}/depsgraph`, {
+    method: "GET",
+  }).then((response) => {
+    if (response.status !== 200) {
+      throw response.text();
+    } else {
+      return response.json();
+    }
+  });
+}
+
 function apiGetCrateDlStats(crate) {
   return fetch(`/api/v1/crates/${crate}/dlstats`, {
     method: "GET",
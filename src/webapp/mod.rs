@@ -21,13 +21,15 @@ macro_rules! add {
 
 /// Gets the resources to serve for the web application
 pub fn get_resources() -> Resources {
-    let mut resources = Resources::with_fallback("index.html");
+    let mut resources = Resources::with_fallback("404.html");
     // HTML
     add!(resources, "index.html");
     add!(resources, "account.html");
     add!(resources, "admin.html");
     add!(resources, "crate.html");
     add!(resources, "oauthcallback.html");
+    add!(resources, "error.html");
+    add!(resources, "404.html");
     // CSS
     add!(resources, "index.css");
     // JS